@@ -1,7 +1,13 @@
-use fast_down::{Merge, ProgressEntry, Proxy};
+use fast_down::{
+    CappedRetry, Digest, ExponentialRetry, FixedRetry, Merge, PieceHashes, ProgressEntry, Proxy,
+    RetryPolicy, SpeedFloor, Verbosity,
+    fast_puller::ConnectionPool,
+    resolver::{AddressFamily, ResolveOverride, Resolver},
+};
 use inherit_config::InheritConfig;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, net::IpAddr, path::PathBuf, time::Duration};
+use std::{collections::HashMap, net::IpAddr, path::PathBuf, sync::Arc, time::Duration};
+use url::Url;
 
 /// File write method for downloaded data.
 ///
@@ -14,6 +20,18 @@ pub enum WriteMethod {
     Std,
 }
 
+/// How the retry interval grows while a download keeps failing.
+///
+/// - `Fixed`: always wait `retry_gap` (default)
+/// - `Exponential`: double the wait after every consecutive error, starting at
+///   `retry_gap` and capped at `max_retry_gap`, with random jitter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RetryBackoff {
+    #[default]
+    Fixed,
+    Exponential,
+}
+
 /// Configuration for a download task.
 ///
 /// All fields have sensible defaults; see [`Config::default`] for values.
@@ -33,9 +51,21 @@ pub struct Config {
     pub gid: String,
 
     /// Number of threads. Recommended: `32` / `16` / `8`. More threads does not always mean faster.
+    ///
+    /// `"auto"` (stored as `0`) starts with a few threads and adds more while
+    /// the total speed keeps rising, up to `max_auto_threads`; a `429` / `503`
+    /// halves them and a burst of errors cuts them by a quarter. Each change
+    /// is reported as [`crate::Event::Concurrency`].
     #[config(default = 32)]
+    #[config(partial_attr(serde(with = "thread_count::option")))]
+    #[config(partial_attr(serde(default)))]
+    #[serde(with = "thread_count")]
     pub threads: usize,
 
+    /// Upper bound of `threads = "auto"`. Recommended: `64`
+    #[config(default = 64)]
+    pub max_auto_threads: usize,
+
     /// Proxy setting. Supports https, http, and socks5 proxies.
     pub proxy: Proxy<String>,
 
@@ -96,6 +126,32 @@ pub struct Config {
     #[config(partial_attr(serde(default)))]
     pub retry_gap: Duration,
 
+    /// Retry backoff strategy during download. Recommended: [`RetryBackoff::Fixed`]
+    pub retry_backoff: RetryBackoff,
+
+    /// Upper bound of the retry interval. Recommended: `30s`
+    ///
+    /// Only effective for [`RetryBackoff::Exponential`].
+    #[config(default = Duration::from_secs(30))]
+    #[config(partial_attr(serde(with = "humantime_serde::option")))]
+    #[config(partial_attr(serde(default)))]
+    pub max_retry_gap: Duration,
+
+    /// Number of consecutive errors after which the download fails. Recommended: `0` (never give up)
+    ///
+    /// Counted per thread across pull errors, pull timeouts and write errors, and
+    /// reset as soon as that thread makes progress again. Once exceeded, the
+    /// download stops with [`crate::Event::PullFailed`],
+    /// [`crate::Event::PullTimeoutFailed`] or [`crate::Event::PushFailed`].
+    pub max_consecutive_errors: usize,
+
+    /// Number of consecutive errors across all threads after which the download fails. Recommended: `0` (never give up)
+    ///
+    /// Counted over the whole session and reset as soon as any thread makes
+    /// progress, so it bounds a download whose threads keep failing in turn
+    /// without one of them reaching `max_consecutive_errors`.
+    pub max_session_errors: usize,
+
     /// Pull timeout. Recommended: `5s`
     ///
     /// If no bytes are received within `pull_timeout` after sending the request,
@@ -106,6 +162,42 @@ pub struct Config {
     #[config(partial_attr(serde(default)))]
     pub pull_timeout: Duration,
 
+    /// Minimum speed of each thread in bytes per second. Recommended: `0` (off)
+    ///
+    /// A thread pulling slower than this over `slow_thread_window` drops its
+    /// connection and reconnects, emitting [`crate::Event::PullSlow`], while
+    /// idle threads take over part of its range. Only effective for
+    /// multi-threaded downloads. Time spent waiting on `speed_limit` or on the
+    /// disk is not counted against the thread.
+    pub slow_thread_speed: u64,
+
+    /// Window over which `slow_thread_speed` is measured. Recommended: `10s`
+    #[config(default = Duration::from_secs(10))]
+    #[config(partial_attr(serde(with = "humantime_serde::option")))]
+    #[config(partial_attr(serde(default)))]
+    pub slow_thread_window: Duration,
+
+    /// Minimum speed of the whole download in bytes per second. Recommended: `0` (off)
+    ///
+    /// Like curl's `--speed-limit`: a download pulling slower than this over
+    /// `low_speed_time` fails with [`crate::Event::Failed`]. Paused time and
+    /// the cooldown after a `429` / `503` ([`crate::Event::Throttled`]) are
+    /// not counted.
+    pub low_speed_limit: u64,
+
+    /// Window over which `low_speed_limit` is measured, like curl's
+    /// `--speed-time`. Recommended: `30s`
+    #[config(default = Duration::from_secs(30))]
+    #[config(partial_attr(serde(with = "humantime_serde::option")))]
+    #[config(partial_attr(serde(default)))]
+    pub low_speed_time: Duration,
+
+    /// Download speed limit in bytes per second. Recommended: `0` (unlimited)
+    ///
+    /// Shared by all threads of the download, so it caps the total throughput
+    /// rather than the throughput of each connection.
+    pub speed_limit: u64,
+
     /// Minimum interval between [`crate::Event::Progress`] emissions. Recommended: `500ms`
     ///
     /// The progress reporter runs on its own timer, so this cadence is driven
@@ -119,6 +211,21 @@ pub struct Config {
     #[config(partial_attr(serde(default)))]
     pub progress_emit_gap: Duration,
 
+    /// Which events reach the channel. Recommended: `trace` (everything)
+    ///
+    /// Events above the level are never built nor sent; see
+    /// [`crate::Event::verbosity`] for the level of each. `progress` forwards
+    /// the [`crate::Event::Progress`] samples but not the per-range
+    /// [`crate::Event::PushProgress`], which is `trace` here. The `.fd` state
+    /// and [`crate::DownloadHandle::progress`] are kept up to date at any level.
+    pub verbosity: Verbosity,
+
+    /// Number of cells in [`crate::ProgressSample::chunks`]. Recommended: `0` (off)
+    ///
+    /// A UI drawing a segmented bar sets it to the bar width and reads the
+    /// per-cell fill and in-flight flags instead of the raw ranges.
+    pub chunk_map_cells: usize,
+
     /// Whether to accept invalid certificates (dangerous). Recommended: `false`
     pub accept_invalid_certs: bool,
 
@@ -151,6 +258,18 @@ pub struct Config {
     /// address in the list is used. This may not always improve speed.
     pub local_address: Vec<IpAddr>,
 
+    /// Mirror URLs serving the same file as the download URL. Recommended: `Vec::new()`
+    ///
+    /// Each mirror is prefetched before the download starts and used only if it
+    /// reports the same size and `ETag` / `Last-Modified` identity and supports
+    /// range requests (the identity may differ when [`Self::expected_digest`] or
+    /// [`Self::expected_pieces`] is set); rejected mirrors are reported as
+    /// [`crate::Event::MirrorRejected`]. Workers then spread their ranges over
+    /// the download URL and the accepted mirrors by observed throughput and
+    /// error rate, moving to another mirror when one fails. Ignored when the
+    /// server does not support range requests.
+    pub mirrors: Vec<Url>,
+
     /// Maximum number of speculative workers. Recommended: `3`
     ///
     /// When the remaining chunk is smaller than `min_chunk_size` and cannot be split,
@@ -184,12 +303,161 @@ pub struct Config {
     /// subsequent requests (including across redirects).
     pub cookie_store: bool,
 
+    /// Whether workers share their connections. Recommended: [`ConnectionPool::PerClone`]
+    ///
+    /// - [`ConnectionPool::PerClone`] gives every worker its own client, so every
+    ///   worker and every reconnect pays a new TCP and TLS handshake. In return a
+    ///   reconnect after `pull_timeout` or `slow_thread_speed` always opens a new
+    ///   connection, which is how a worker gets off a bad route.
+    /// - [`ConnectionPool::Shared`] keeps one client per `local_address`, whose idle
+    ///   connections are handed from worker to worker. It speaks HTTP/1.1 only, and
+    ///   a reconnecting worker may be handed another worker's idle connection
+    ///   instead of a new one. Worth it for many short ranges over TLS.
+    /// - [`ConnectionPool::Multiplexed`] also lets HTTP/2 servers carry every range
+    ///   request of a `local_address` over one connection. Fewer connections, but
+    ///   one slow connection then slows every worker, and it needs the `http2`
+    ///   feature and a TLS server offering HTTP/2; otherwise it acts like `Shared`.
+    pub connection_pool: ConnectionPool,
+
+    /// Static DNS answers, like curl's `--resolve`. Recommended: `Vec::new()`
+    ///
+    /// Written `"host:port:addr[,addr]"`, e.g. `"example.com:443:10.0.0.7"` to
+    /// test against a staging server. An override applies only to URLs on its
+    /// port, named or the default of the scheme; `*` matches every port.
+    pub resolve: Vec<ResolveOverride>,
+
+    /// Which IP versions to connect over. Recommended: [`AddressFamily::Any`]
+    pub address_family: AddressFamily,
+
+    /// Spread connections over every address of the host. Recommended: `false`
+    ///
+    /// Each new connection starts at the next A/AAAA record, so the workers of a
+    /// download hitting a CDN name land on different servers. Combine with
+    /// [`ConnectionPool::PerClone`] or [`ConnectionPool::Shared`]; a multiplexed
+    /// pool opens only one connection per `local_address`.
+    pub dns_round_robin: bool,
+
     /// 是否尝试断点续传，推荐值: `true`
     #[config(default = true)]
     pub resume: bool,
 
     /// 是否覆盖已存在的文件，推荐值: `false`
     pub overwrite: bool,
+
+    /// Expected digest of the whole file, e.g. `"sha256:e3b0c4…"`. Recommended: `None`
+    ///
+    /// Supports `sha256`, `sha1`, `md5` and `blake3`. The written bytes are
+    /// hashed while they stream in, so only the part that could not be hashed
+    /// in order (a resumed prefix, or ranges that outran
+    /// `cache_high_watermark` bytes of buffering) is read back from the `.part`
+    /// file afterwards. A match emits [`crate::Event::Verified`]; a mismatch
+    /// emits [`crate::Event::DigestMismatch`] and the `.part` file is not
+    /// renamed.
+    pub expected_digest: Option<Digest>,
+
+    /// Piece checksums the downloaded file must match. Recommended: `None`
+    ///
+    /// Checked after [`Self::expected_digest`] by reading the `.part` file back;
+    /// bad pieces are reported as [`crate::Event::PieceMismatch`] and the file is
+    /// not renamed. Metalink jobs set this when the document lists no usable
+    /// whole-file hash.
+    pub expected_pieces: Option<PieceHashes>,
+
+    /// Whether to check the file against the digest the server advertised
+    /// (`Repr-Digest`, `Digest`, `Content-MD5`, `x-goog-hash`). Recommended: `true`
+    ///
+    /// The strongest advertised digest is computed while the file streams in,
+    /// or the one matching the algorithm of [`Self::expected_digest`] when that
+    /// is set; other advertised algorithms are not checked. A mismatch emits
+    /// [`crate::Event::IntegrityError`] and the `.part` file is not renamed.
+    #[config(default = true)]
+    pub verify_server_digest: bool,
+
+    /// Size the file must have, in bytes. Recommended: `None`
+    ///
+    /// Compared with the size the server reports before anything is written;
+    /// a mismatch emits [`crate::Event::SizeMismatch`] and ends the run.
+    /// Ignored when the server reports no size. Metalink jobs set it from the
+    /// document's `<size>`.
+    pub expected_size: Option<u64>,
+
+    /// Download the files a metalink lists instead of the metalink itself. Recommended: `true`
+    ///
+    /// A URL served as `application/metalink4+xml` or named `*.meta4` is then
+    /// not saved: its files come back as [`crate::MetalinkJob`]s in
+    /// [`crate::Event::Metalink`], and the run ends there.
+    #[config(default = true)]
+    pub follow_metalink: bool,
+
+    /// Country to prefer the metalink sources of, as an ISO 3166-1 alpha-2
+    /// code like `"de"`. Recommended: `None`
+    ///
+    /// Used by [`Self::follow_metalink`] to pick among sources of equal
+    /// priority; see [`crate::MetalinkJob::new`].
+    pub metalink_location: Option<String>,
+}
+
+impl Config {
+    /// Build the [`RetryPolicy`] described by `retry_gap`, `retry_backoff`,
+    /// `max_retry_gap`, `max_consecutive_errors` and `max_session_errors`.
+    #[must_use]
+    pub fn retry_policy(&self) -> Arc<dyn RetryPolicy> {
+        match self.retry_backoff {
+            RetryBackoff::Fixed => self.cap_retries(FixedRetry::new(self.retry_gap)),
+            RetryBackoff::Exponential => {
+                self.cap_retries(ExponentialRetry::new(self.retry_gap, self.max_retry_gap))
+            }
+        }
+    }
+
+    fn cap_retries<P: RetryPolicy + 'static>(&self, policy: P) -> Arc<dyn RetryPolicy> {
+        if self.max_consecutive_errors == 0 && self.max_session_errors == 0 {
+            Arc::new(policy)
+        } else {
+            Arc::new(
+                CappedRetry::new(policy, self.max_consecutive_errors)
+                    .with_session_limit(self.max_session_errors),
+            )
+        }
+    }
+
+    /// The [`Resolver`] described by `resolve`, `address_family` and
+    /// `dns_round_robin`, `None` when they leave the system resolver alone.
+    #[must_use]
+    pub fn resolver(&self) -> Option<Resolver> {
+        let resolver = Resolver::new(
+            self.resolve.clone(),
+            self.address_family,
+            self.dns_round_robin,
+        );
+        (!resolver.is_system()).then_some(resolver)
+    }
+
+    /// Whether `threads` is `"auto"`.
+    #[must_use]
+    pub const fn is_auto_threads(&self) -> bool {
+        self.threads == 0
+    }
+
+    /// The per-thread [`SpeedFloor`] of `slow_thread_speed`, `None` when off.
+    #[must_use]
+    pub const fn slow_thread_floor(&self) -> Option<SpeedFloor> {
+        floor(self.slow_thread_speed, self.slow_thread_window)
+    }
+
+    /// The session [`SpeedFloor`] of `low_speed_limit`, `None` when off.
+    #[must_use]
+    pub const fn low_speed_floor(&self) -> Option<SpeedFloor> {
+        floor(self.low_speed_limit, self.low_speed_time)
+    }
+}
+
+const fn floor(bps: u64, window: Duration) -> Option<SpeedFloor> {
+    if bps == 0 || window.is_zero() {
+        None
+    } else {
+        Some(SpeedFloor::new(bps, window))
+    }
 }
 
 impl PartialConfig {
@@ -211,6 +479,89 @@ impl PartialConfig {
 #[allow(clippy::single_range_in_vec_init)]
 mod tests {
     use super::*;
+    use fast_down::{RetryContext, RetryKind};
+
+    const fn retry_ctx(attempt: usize) -> RetryContext {
+        RetryContext {
+            kind: RetryKind::Pull,
+            attempt,
+            session_attempt: attempt,
+            retry_after: None,
+        }
+    }
+
+    #[test]
+    fn retry_policy_follows_config() {
+        let config = Config {
+            retry_gap: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let policy = config.retry_policy();
+        assert_eq!(
+            policy.next_delay(&retry_ctx(1000)),
+            Some(Duration::from_millis(100))
+        );
+
+        let config = Config {
+            retry_gap: Duration::from_millis(100),
+            retry_backoff: RetryBackoff::Exponential,
+            max_retry_gap: Duration::from_millis(300),
+            max_consecutive_errors: 3,
+            ..Default::default()
+        };
+        let policy = config.retry_policy();
+        assert!(policy.next_delay(&retry_ctx(3)).unwrap() <= Duration::from_millis(300));
+        assert_eq!(policy.next_delay(&retry_ctx(4)), None);
+
+        let config = Config {
+            max_session_errors: 5,
+            ..Default::default()
+        };
+        let policy = config.retry_policy();
+        let ctx = |session_attempt| RetryContext {
+            session_attempt,
+            ..retry_ctx(1)
+        };
+        assert!(policy.next_delay(&ctx(5)).is_some());
+        assert_eq!(policy.next_delay(&ctx(6)), None);
+    }
+
+    #[test]
+    fn speed_floors_follow_config() {
+        let config = Config::default();
+        assert_eq!(config.slow_thread_floor(), None);
+        assert_eq!(config.low_speed_floor(), None);
+
+        let config = Config {
+            slow_thread_speed: 1024,
+            low_speed_limit: 10,
+            low_speed_time: Duration::ZERO,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.slow_thread_floor(),
+            Some(SpeedFloor::new(1024, Duration::from_secs(10)))
+        );
+        assert_eq!(config.low_speed_floor(), None);
+    }
+
+    #[test]
+    fn resolver_follows_config() {
+        assert!(Config::default().resolver().is_none());
+        let pc: PartialConfig = toml::from_str(
+            r#"
+            resolve = ["example.com:443:10.0.0.7,[fd00::7]"]
+            address_family = "PreferV6"
+            "#,
+        )
+        .unwrap();
+        let config = inherit_config::ConfigLayer::build(pc);
+        assert_eq!(config.resolve[0].host, "example.com");
+        assert_eq!(config.resolve[0].port, Some(443));
+        assert_eq!(config.resolve[0].addrs.len(), 2);
+        assert_eq!(config.address_family, AddressFamily::PreferV6);
+        assert!(config.resolver().is_some());
+    }
 
     #[test]
     fn merge_progress_none_to_some() {
@@ -331,6 +682,65 @@ mod range_list {
     }
 }
 
+/// (De)serialization of [`Config::threads`]: a count, or `"auto"` for `0`,
+/// which is also accepted as a number.
+mod thread_count {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Count(usize),
+        Name(String),
+    }
+
+    fn parse<E: serde::de::Error>(repr: Repr) -> Result<usize, E> {
+        match repr {
+            Repr::Count(n) => Ok(n),
+            Repr::Name(name) if name.eq_ignore_ascii_case("auto") => Ok(0),
+            Repr::Name(name) => Err(E::custom(format!(
+                "invalid threads {name:?}, expected a number or \"auto\""
+            ))),
+        }
+    }
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn serialize<S: Serializer>(threads: &usize, serializer: S) -> Result<S::Ok, S::Error> {
+        match threads {
+            0 => serializer.serialize_str("auto"),
+            n => n.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+        parse(Repr::deserialize(deserializer)?)
+    }
+
+    pub mod option {
+        use super::{Repr, parse};
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        #[allow(clippy::ref_option)]
+        pub fn serialize<S: Serializer>(
+            threads: &Option<usize>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match threads {
+                None => serializer.serialize_none(),
+                Some(threads) => super::serialize(threads, serializer),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<usize>, D::Error> {
+            Option::<Repr>::deserialize(deserializer)?
+                .map(parse)
+                .transpose()
+        }
+    }
+}
+
 #[cfg(test)]
 mod range_list_tests {
     use super::*;
@@ -350,6 +760,19 @@ mod range_list_tests {
         assert_eq!(back.downloaded_chunk, Some(vec![1..4, 5..10, 100..101]));
     }
 
+    #[test]
+    fn threads_accept_auto() {
+        let pc: PartialConfig = toml::from_str("threads = \"auto\"").unwrap();
+        assert_eq!(pc.threads, Some(0));
+        assert!(inherit_config::ConfigLayer::build(pc.clone()).is_auto_threads());
+        assert_eq!(toml::to_string(&pc).unwrap().trim(), "threads = \"auto\"");
+        let pc: PartialConfig = toml::from_str("threads = 8").unwrap();
+        assert_eq!(pc.threads, Some(8));
+        assert_eq!(toml::to_string(&pc).unwrap().trim(), "threads = 8");
+        assert!(toml::from_str::<PartialConfig>("threads = \"many\"").is_err());
+        assert_eq!(PartialConfig::default().threads, None);
+    }
+
     #[test]
     fn downloaded_chunk_absent_when_none() {
         let pc = PartialConfig::default();
@@ -0,0 +1,178 @@
+//! Automatic worker count for `threads = "auto"`.
+//!
+//! [`AutoThreads`] is an AIMD controller: every [`AutoThreads::TICK`] it adds
+//! [`AutoThreads::STEP`] workers while the summed worker speed keeps rising,
+//! halves them when the server rate limits (`429` / `503`), and drops a
+//! quarter of them when errors pile up. The engine's own
+//! [`Event::Throttled`] cooldown comes first; the halving keeps the count
+//! down once it is over. The engine loop feeds it through
+//! [`Signals`]; [`AutoThreads::spawn`] applies its decisions with
+//! [`DownloadResult::set_threads`] and reports them as
+//! [`Event::Concurrency`].
+use crate::{DownloadHandle, Event, Tx};
+use fast_down::{DownloadResult, Puller, Verbosity, multi::TokioExecutor};
+use std::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+
+/// Errors and rate limits seen since the last tick.
+#[derive(Debug, Default)]
+pub(super) struct Signals {
+    errors: AtomicUsize,
+    throttled: AtomicBool,
+}
+
+impl Signals {
+    /// Count `event` if it is a pull error, timeout or rate limit.
+    pub fn observe(&self, event: &Event) {
+        match event {
+            Event::PullError(..) | Event::PullTimeout(_) | Event::PullSlow(_) => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+            }
+            Event::Throttled(_) => self.throttled.store(true, Ordering::Relaxed),
+            _ => {}
+        }
+    }
+
+    /// The errors and whether a rate limit was hit since the last call.
+    fn take(&self) -> (usize, bool) {
+        (
+            self.errors.swap(0, Ordering::Relaxed),
+            self.throttled.swap(false, Ordering::Relaxed),
+        )
+    }
+}
+
+/// The AIMD state: the current worker count and the best speed it reached.
+#[derive(Debug)]
+pub(super) struct AutoThreads {
+    threads: usize,
+    best_bps: u64,
+    last_errors: usize,
+}
+
+impl AutoThreads {
+    /// How often the worker count is reconsidered.
+    pub const TICK: Duration = Duration::from_secs(2);
+    /// Workers to start with.
+    pub const START: usize = 4;
+    /// Workers added per tick while the speed improves.
+    pub const STEP: usize = 2;
+
+    pub fn new(max: usize) -> Self {
+        Self {
+            threads: Self::START.min(max).max(1),
+            best_bps: 0,
+            last_errors: 0,
+        }
+    }
+
+    pub const fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Feed one tick: the summed worker speed, the errors since the last tick
+    /// and whether the server rate limited. Returns the new worker count if
+    /// it changed.
+    pub fn tick(&mut self, bps: u64, errors: usize, throttled: bool, max: usize) -> Option<usize> {
+        let before = self.threads;
+        if throttled {
+            self.threads /= 2;
+            self.best_bps = bps;
+        } else if errors > self.last_errors && errors * 4 >= self.threads {
+            self.threads -= self.threads / 4;
+            self.best_bps = bps;
+        } else if bps > self.best_bps + self.best_bps / 10 {
+            self.threads += Self::STEP;
+            self.best_bps = bps;
+        }
+        self.last_errors = errors;
+        self.threads = self.threads.min(max).max(1);
+        (self.threads != before).then_some(self.threads)
+    }
+
+    /// Retune `res` every [`TICK`](Self::TICK) until the task is aborted,
+    /// within [`DownloadHandle::set_max_threads`] or else `max`. Stops once
+    /// [`DownloadHandle::set_threads`] fixes the count, and skips ticks while
+    /// paused.
+    pub fn spawn<R: Puller, PushError: Send + Unpin + 'static>(
+        mut self,
+        res: DownloadResult<TokioExecutor<R, PushError>, R::Error, PushError>,
+        handle: DownloadHandle,
+        signals: std::sync::Arc<Signals>,
+        tx: Tx,
+        max: usize,
+        verbosity: Verbosity,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Self::TICK).await;
+                let (errors, throttled) = signals.take();
+                if handle.tuning().0.is_some() {
+                    return;
+                }
+                if handle.is_paused() {
+                    continue;
+                }
+                let max = handle.max_threads().unwrap_or(max);
+                let bps = res.workers().iter().map(|w| w.bps).sum();
+                if let Some(threads) = self.tick(bps, errors, throttled, max) {
+                    res.set_threads(threads, res.min_chunk_size());
+                    if verbosity.includes(Verbosity::Progress) {
+                        let _ = tx.send(Event::Concurrency(threads));
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AutoThreads;
+
+    #[test]
+    fn grows_while_speed_improves() {
+        let mut auto = AutoThreads::new(64);
+        assert_eq!(auto.threads(), AutoThreads::START);
+        assert_eq!(auto.tick(1000, 0, false, 64), Some(6));
+        assert_eq!(auto.tick(2000, 0, false, 64), Some(8));
+        // Less than 10% better: hold.
+        assert_eq!(auto.tick(2100, 0, false, 64), None);
+        assert_eq!(auto.tick(1500, 0, false, 64), None);
+        assert_eq!(auto.tick(3000, 0, false, 64), Some(10));
+    }
+
+    #[test]
+    fn stays_within_max() {
+        let mut auto = AutoThreads::new(5);
+        assert_eq!(auto.threads(), 4);
+        assert_eq!(auto.tick(1000, 0, false, 5), Some(5));
+        assert_eq!(auto.tick(2000, 0, false, 5), None);
+        assert_eq!(auto.tick(3000, 0, false, 2), Some(2), "the cap was lowered");
+        assert_eq!(AutoThreads::new(0).threads(), 1);
+    }
+
+    #[test]
+    fn backs_off_on_rate_limits_and_errors() {
+        let mut auto = AutoThreads::new(64);
+        for bps in [1000, 2000, 3000, 4000, 5000, 6000] {
+            auto.tick(bps, 0, false, 64);
+        }
+        assert_eq!(auto.threads(), 16);
+        assert_eq!(auto.tick(6000, 0, true, 64), Some(8), "429 halves");
+        // The speed it reached after backing off is the new bar to beat.
+        assert_eq!(auto.tick(6000, 0, false, 64), None);
+        assert_eq!(
+            auto.tick(6000, 2, false, 64),
+            Some(6),
+            "errors cut a quarter"
+        );
+        assert_eq!(auto.tick(6000, 2, false, 64), None, "steady errors hold");
+        assert_eq!(auto.tick(6000, 0, true, 64), Some(3));
+        assert_eq!(auto.tick(0, 0, true, 64), Some(1));
+        assert_eq!(auto.tick(0, 0, true, 64), None, "never below one");
+    }
+}
@@ -0,0 +1,276 @@
+//! Control handle of a running [`crate::download`] or [`crate::resume`].
+
+use crate::ProgressSample;
+use fast_down::{DownloadOutcome, PauseToken, WorkerSnapshot};
+use parking_lot::Mutex;
+use std::{
+    fmt,
+    sync::{Arc, OnceLock},
+};
+use tokio_util::sync::CancellationToken;
+
+/// The running engine as seen by a [`DownloadHandle`], installed by
+/// [`overwrite`](super::overwrite::overwrite) once the engine has started.
+pub trait Session: Send + Sync {
+    fn set_threads(&self, threads: usize);
+    fn set_min_chunk_size(&self, min_chunk_size: u64);
+    fn set_max_speculative(&self, max_speculative: usize);
+    fn set_speed_limit(&self, bytes_per_sec: u64);
+    fn progress(&self) -> ProgressSample;
+    fn workers(&self) -> Vec<WorkerSnapshot>;
+}
+
+/// Controls a download spawned by [`download`](crate::download) or
+/// [`resume`](crate::resume).
+///
+/// The handle is cheap to clone and valid for the whole run, including the
+/// prefetch before the engine starts: a pause or tuning set that early is
+/// applied as soon as the engine is up. Dropping it does not affect the
+/// download.
+#[derive(Clone)]
+pub struct DownloadHandle {
+    inner: Arc<HandleInner>,
+}
+
+struct HandleInner {
+    token: CancellationToken,
+    pause_token: PauseToken,
+    control: Mutex<Control>,
+    /// Cancelled once the spawned task has returned.
+    done: CancellationToken,
+    outcome: OnceLock<Option<DownloadOutcome>>,
+}
+
+/// Tuning requested through the handle, plus the engine it applies to.
+#[derive(Default)]
+struct Control {
+    threads: Option<usize>,
+    max_threads: Option<usize>,
+    min_chunk_size: Option<u64>,
+    max_speculative: Option<usize>,
+    speed_limit: Option<u64>,
+    /// Present only while the engine runs.
+    session: Option<Box<dyn Session>>,
+    /// The final sample of an engine that has stopped.
+    last_progress: Option<ProgressSample>,
+}
+
+impl fmt::Debug for DownloadHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let control = self.inner.control.lock();
+        f.debug_struct("DownloadHandle")
+            .field("is_cancelled", &self.is_cancelled())
+            .field("is_paused", &self.is_paused())
+            .field("is_finished", &self.is_finished())
+            .field("threads", &control.threads)
+            .field("max_threads", &control.max_threads)
+            .field("min_chunk_size", &control.min_chunk_size)
+            .field("max_speculative", &control.max_speculative)
+            .field("speed_limit", &control.speed_limit)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DownloadHandle {
+    pub(crate) fn new(token: CancellationToken) -> Self {
+        Self {
+            inner: Arc::new(HandleInner {
+                token,
+                pause_token: PauseToken::new(),
+                control: Mutex::default(),
+                done: CancellationToken::new(),
+                outcome: OnceLock::new(),
+            }),
+        }
+    }
+
+    /// Suspend the download without tearing it down.
+    ///
+    /// Workers stop and release their connections, writes stop, and the
+    /// progress and `.fd` state are kept; [`unpause`](Self::unpause) continues
+    /// from there without another prefetch. Pausing during the prefetch starts
+    /// the engine paused.
+    pub fn pause(&self) {
+        self.inner.pause_token.pause();
+    }
+
+    pub fn unpause(&self) {
+        self.inner.pause_token.unpause();
+    }
+
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.inner.pause_token.is_paused()
+    }
+
+    /// Change the number of workers, overriding `threads` from the config.
+    ///
+    /// Growing splits ranges off the busiest workers, shrinking hands the
+    /// ranges of the stopped workers to the others. Takes effect immediately on
+    /// a running multi-threaded download, or when the engine starts.
+    /// Single-stream downloads ignore it. Clamped to at least one worker.
+    pub fn set_threads(&self, threads: usize) {
+        let threads = threads.max(1);
+        let mut control = self.inner.control.lock();
+        control.threads = Some(threads);
+        if let Some(session) = &control.session {
+            session.set_threads(threads);
+        }
+    }
+
+    /// Cap the workers of a `threads = "auto"` download, overriding
+    /// `max_auto_threads` from the config. The tuner shrinks to the cap on its
+    /// next step and stays within it. Downloads with a fixed worker count
+    /// ignore it. Clamped to at least one worker.
+    pub fn set_max_threads(&self, max_threads: usize) {
+        self.inner.control.lock().max_threads = Some(max_threads.max(1));
+    }
+
+    /// Change the smallest range split off for a worker, overriding
+    /// `min_chunk_size` from the config. Ranges already handed out are not
+    /// re-cut.
+    pub fn set_min_chunk_size(&self, min_chunk_size: u64) {
+        let mut control = self.inner.control.lock();
+        control.min_chunk_size = Some(min_chunk_size);
+        if let Some(session) = &control.session {
+            session.set_min_chunk_size(min_chunk_size);
+        }
+    }
+
+    /// Change how many workers may race on the last unfinished ranges,
+    /// overriding `max_speculative` from the config.
+    pub fn set_max_speculative(&self, max_speculative: usize) {
+        let mut control = self.inner.control.lock();
+        control.max_speculative = Some(max_speculative);
+        if let Some(session) = &control.session {
+            session.set_max_speculative(max_speculative);
+        }
+    }
+
+    /// Change the bandwidth cap in bytes per second, overriding `speed_limit`
+    /// from the config; `0` lifts it. Takes effect immediately, including for
+    /// workers waiting on the old cap.
+    pub fn set_speed_limit(&self, bytes_per_sec: u64) {
+        let mut control = self.inner.control.lock();
+        control.speed_limit = Some(bytes_per_sec);
+        if let Some(session) = &control.session {
+            session.set_speed_limit(bytes_per_sec);
+        }
+    }
+
+    /// The progress right now, computed the same way as
+    /// [`Event::Progress`](crate::Event::Progress).
+    ///
+    /// `None` until the engine has started. Once it has stopped, the final
+    /// sample of the run.
+    #[must_use]
+    pub fn progress(&self) -> Option<ProgressSample> {
+        let control = self.inner.control.lock();
+        control.session.as_ref().map_or_else(
+            || control.last_progress.clone(),
+            |session| Some(session.progress()),
+        )
+    }
+
+    /// The workers running right now, computed the same way as
+    /// [`Event::Workers`](crate::Event::Workers). Empty while the engine is
+    /// not running.
+    #[must_use]
+    pub fn workers(&self) -> Vec<WorkerSnapshot> {
+        let control = self.inner.control.lock();
+        control
+            .session
+            .as_ref()
+            .map_or_else(Vec::new, |session| session.workers())
+    }
+
+    /// Cancel the download, leaving the `.part` and `.fd` files for a later
+    /// [`resume`](crate::resume). Same as cancelling the token passed in.
+    pub fn cancel(&self) {
+        self.inner.token.cancel();
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.token.is_cancelled()
+    }
+
+    /// The cancellation token the download was started with.
+    #[must_use]
+    pub fn token(&self) -> &CancellationToken {
+        &self.inner.token
+    }
+
+    /// Whether the run is over and every event has been sent.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.inner.done.is_cancelled()
+    }
+
+    /// Wait for the run to end.
+    ///
+    /// Returns the outcome carried by the final
+    /// [`Event::Completed`](crate::Event::Completed) or
+    /// [`Event::Failed`](crate::Event::Failed), or `None` if the run stopped
+    /// before the engine started, e.g. because the prefetch failed or the
+    /// token was cancelled during it. Any number of clones may join, and
+    /// joining consumes no events.
+    pub async fn join(&self) -> Option<DownloadOutcome> {
+        self.inner.done.cancelled().await;
+        self.inner.outcome.get().cloned().flatten()
+    }
+
+    pub(crate) fn pause_token(&self) -> PauseToken {
+        self.inner.pause_token.clone()
+    }
+
+    /// The tuning requested so far: threads, minimum chunk size and
+    /// speculation limit.
+    pub(crate) fn tuning(&self) -> (Option<usize>, Option<u64>, Option<usize>) {
+        let control = self.inner.control.lock();
+        (
+            control.threads,
+            control.min_chunk_size,
+            control.max_speculative,
+        )
+    }
+
+    /// The cap set by [`set_max_threads`](Self::set_max_threads), if any.
+    pub(crate) fn max_threads(&self) -> Option<usize> {
+        self.inner.control.lock().max_threads
+    }
+
+    /// Route the setters to the running engine until
+    /// [`detach`](Self::detach). Tuning requested before this call is applied
+    /// right away.
+    pub(crate) fn attach(&self, session: impl Session + 'static) {
+        let mut control = self.inner.control.lock();
+        if let Some(threads) = control.threads {
+            session.set_threads(threads);
+        }
+        if let Some(min_chunk_size) = control.min_chunk_size {
+            session.set_min_chunk_size(min_chunk_size);
+        }
+        if let Some(max_speculative) = control.max_speculative {
+            session.set_max_speculative(max_speculative);
+        }
+        if let Some(speed_limit) = control.speed_limit {
+            session.set_speed_limit(speed_limit);
+        }
+        control.session = Some(Box::new(session));
+    }
+
+    /// Drop the engine hook so the handle no longer keeps the session alive,
+    /// keeping `last_progress` for [`progress`](Self::progress).
+    pub(crate) fn detach(&self, last_progress: ProgressSample) {
+        let mut control = self.inner.control.lock();
+        control.session = None;
+        control.last_progress = Some(last_progress);
+    }
+
+    /// Record the end of the run and wake every [`join`](Self::join)er.
+    pub(crate) fn finish(&self, outcome: Option<DownloadOutcome>) {
+        let _ = self.inner.outcome.set(outcome);
+        self.inner.done.cancel();
+    }
+}
@@ -1,7 +1,8 @@
 use crate::core::download::overwrite::OverwriteOption;
 use crate::utils::ForceSendExt;
-use crate::{DownloadState, Event, StateError};
+use crate::{Config, DownloadState, Event, StateError};
 use crate::{PartialConfig, Tx, prefetch, tx_err, utils::gen_path};
+use crate::{expand_response, is_metalink};
 use fast_down::UrlInfo;
 use inherit_config::ConfigLayer;
 use overwrite::overwrite;
@@ -11,10 +12,14 @@ use tokio::fs::{self, OpenOptions};
 use tokio_util::sync::CancellationToken;
 use url::Url;
 
+mod auto_threads;
+mod handle;
 mod overwrite;
 mod pipeline;
 mod progress_reporter;
 
+pub use handle::DownloadHandle;
+
 fn open_existing() -> OpenOptions {
     let mut o = OpenOptions::new();
     o.read(true).write(true).truncate(false).create(false);
@@ -86,22 +91,49 @@ async fn try_load_resume_state(
     Ok(Some(state))
 }
 
+/// Check the size the server reports against [`Config::expected_size`],
+/// emitting [`Event::SizeMismatch`] and returning `None` when they differ.
+fn check_size(config: &Config, info: &UrlInfo, tx: &Tx) -> Option<()> {
+    match config.expected_size {
+        Some(expected) if info.size != 0 && info.size != expected => {
+            let _ = tx.send(Event::SizeMismatch {
+                expected,
+                actual: info.size,
+            });
+            None
+        }
+        _ => Some(()),
+    }
+}
+
 /// Spawn a detached background download task that resumes automatically when
 /// possible.
 ///
 /// The task first `prefetch`es metadata, then either resumes from a valid
 /// `.fd`/`.part` state or starts a fresh download (falling back silently when
 /// resume is impossible). Progress and lifecycle events are delivered through
-/// `tx`.
+/// `tx`. A metalink document is not saved when
+/// [`Config::follow_metalink`] is set: the run ends with
+/// [`Event::Metalink`], carrying the jobs of the files it lists.
 ///
 /// Completion is observed through the [`Rx`](crate::Rx) you created alongside
 /// `tx`: the spawned task holds the only `Tx` clones, so the receiver
 /// disconnects once the task has fully finished — including the final
-/// `overwrite`. Drain `rx` until it disconnects to await completion; keep the
-/// [`CancellationToken`](crate::create_cancellation_token) you passed in if you
-/// need to cancel.
-pub fn download(url: Url, partial_config: PartialConfig, tx: Tx, token: CancellationToken) {
-    tokio::spawn(
+/// `overwrite`. Drain `rx` until it disconnects to await completion.
+///
+/// The returned [`DownloadHandle`] pauses, resumes, resizes and cancels the
+/// running download; cancelling the `token` passed in works as well. Dropping
+/// it leaves the download running.
+#[allow(clippy::must_use_candidate)]
+pub fn download(
+    url: Url,
+    partial_config: PartialConfig,
+    tx: Tx,
+    token: CancellationToken,
+) -> DownloadHandle {
+    let handle = DownloadHandle::new(token.clone());
+    tokio::spawn({
+        let handle = handle.clone();
         async move {
             let token2 = token.clone();
             let opt = token
@@ -110,12 +142,15 @@ pub fn download(url: Url, partial_config: PartialConfig, tx: Tx, token: Cancella
                 )
                 .await
                 .flatten();
-            if let Some(opt) = opt {
-                overwrite(opt).await;
-            }
+            let outcome = match opt {
+                Some(opt) => overwrite(opt, &handle).await,
+                None => None,
+            };
+            handle.finish(outcome);
         }
-        .force_send(),
-    );
+        .force_send()
+    });
+    handle
 }
 
 /// Spawn a detached task that resumes a previously interrupted download from its
@@ -138,25 +173,29 @@ pub fn download(url: Url, partial_config: PartialConfig, tx: Tx, token: Cancella
 /// `.fd` carries no resolvable URL, the call reports `StateError::NoUrl`.
 ///
 /// Completion is observed the same way as [`download`](crate::download): drain the `Rx` paired
-/// with `tx` until it disconnects.
+/// with `tx` until it disconnects. The returned [`DownloadHandle`] controls the
+/// run like the one of [`download`](crate::download).
 pub fn resume(
     tmp_path: impl AsRef<Path>,
     url: Option<Url>,
     partial_config: PartialConfig,
     tx: Tx,
     token: CancellationToken,
-) {
+) -> DownloadHandle {
+    let handle = DownloadHandle::new(token.clone());
     let tmp_path = tmp_path.as_ref();
     if tmp_path.extension() != Some(std::ffi::OsStr::new("part")) {
         let _ = tx.send(Event::ResumeError(StateError::Open(std::io::Error::new(
             std::io::ErrorKind::InvalidInput,
             "tmp_path must end with .part extension",
         ))));
-        return;
+        handle.finish(None);
+        return handle;
     }
     let tmp_path = tmp_path.to_path_buf();
 
-    tokio::spawn(
+    tokio::spawn({
+        let handle = handle.clone();
         async move {
             let token2 = token.clone();
             let opt = Box::pin(token.run_until_cancelled(async move {
@@ -164,12 +203,15 @@ pub fn resume(
             }))
             .await
             .flatten();
-            if let Some(opt) = opt {
-                overwrite(opt).await;
-            }
+            let outcome = match opt {
+                Some(opt) => overwrite(opt, &handle).await,
+                None => None,
+            };
+            handle.finish(outcome);
         }
-        .force_send(),
-    );
+        .force_send()
+    });
+    handle
 }
 
 async fn run_download(
@@ -180,6 +222,14 @@ async fn run_download(
 ) -> Option<OverwriteOption> {
     let config = partial_config.clone().build();
     let (info, resp) = prefetch(&url, &config, &tx).await?;
+    if config.follow_metalink && is_metalink(&info) {
+        let location = config.metalink_location.as_deref();
+        let jobs = expand_response(resp, &partial_config, location).await;
+        let jobs = tx_err!(jobs, tx, MetalinkError, None);
+        let _ = tx.send(Event::Metalink(jobs));
+        return None;
+    }
+    check_size(&config, &info, &tx)?;
     let can_resume = config.resume && info.fast_download;
 
     let origin_path = tx_err!(gen_path(&url, &info, &config).await, tx, GenPathError, None);
@@ -297,6 +347,7 @@ async fn run_resume(
     partial_config.resume = Some(true);
     let config = partial_config.clone().build();
     let (info, resp) = prefetch(&url, &config, &tx).await?;
+    check_size(&config, &info, &tx)?;
     if !info.fast_download {
         let _ = tx.send(Event::ResumeError(StateError::NotResumable(info, resp)));
         return None;
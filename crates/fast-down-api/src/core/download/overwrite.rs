@@ -8,17 +8,31 @@
 //! [`fast_down::single::download_single`], forwards engine events to the public
 //! [`crate::Event`] stream, periodically saves progress, and on success renames
 //! the `.part` file to its final destination (or a unique variant when
-//! `overwrite` is disabled).
-use super::progress_reporter::ProgressReporter;
+//! `overwrite` is disabled), after checking it against
+//! [`crate::Config::expected_digest`], [`crate::Config::expected_pieces`] and the
+//! digest the server advertised, if set.
+use super::{
+    auto_threads::{AutoThreads, Signals},
+    handle::Session,
+    progress_reporter::ProgressReporter,
+};
 use crate::{
-    DownloadState, Event, PartialConfig, Tx, core::download::pipeline::build_pipeline, tx_err,
+    DownloadHandle, DownloadState, Event, PartialConfig, ProgressSample, Tx,
+    core::download::pipeline::build_pipeline, tx_err,
+};
+use fast_down::{
+    Digest, DigestHandle, DownloadOutcome, DownloadResult, DownloadStatus, ProgressEntry, Puller,
+    SpeedLimiter, UrlInfo, Verbosity, WorkerSnapshot, invert,
+    multi::{TokioExecutor, download_multi},
+    single::download_single,
 };
-use fast_down::{UrlInfo, invert, multi::download_multi, single::download_single};
 use inherit_config::ConfigLayer;
 use path_helper::tokio::gen_unique_path;
 use reqwest::Response;
 use std::{
+    io::{Seek, SeekFrom},
     path::PathBuf,
+    sync::Arc,
     time::{Duration, Instant},
 };
 use tokio::fs;
@@ -50,17 +64,27 @@ pub struct OverwriteOption {
 /// 1. Saves the `.fd` state up front.
 /// 2. Builds the pull/push pipeline for the `.part` file.
 /// 3. Emits [`crate::Event::Start`] and runs `download_multi` (fast downloads)
-///    or `download_single` (single-stream) according to `info.fast_download`.
+///    or `download_single` (single-stream) according to `info.fast_download`,
+///    wired to `handle` for pausing and resizing.
 /// 4. Forwards every engine event to the public [`crate::Event`] channel and
 ///    merges `PushProgress` ranges into the state.
 /// 5. Periodically (≈1s) re-saves the `.fd` so progress survives interruption.
-/// 6. On success, renames `.part` to `final_path` (or a unique variant when
-///    `overwrite` is disabled) and emits [`crate::Event::Renamed`].
+/// 6. On success, checks the digest and pieces when `expected_digest`,
+///    `expected_pieces` or a server-advertised digest is set, then renames
+///    `.part` to `final_path` (or a unique variant when `overwrite` is
+///    disabled) and emits [`crate::Event::Renamed`].
 ///
 /// If the token is cancelled or the download did not complete, the `.part` and
 /// `.fd` files are left in place so a later resume can continue.
+///
+/// Every run ends with exactly one [`crate::Event::Completed`] or
+/// [`crate::Event::Failed`], sent after all other events. Returns the outcome
+/// it carried.
 #[allow(clippy::too_many_lines)]
-pub async fn overwrite(option: OverwriteOption) {
+pub async fn overwrite(
+    option: OverwriteOption,
+    handle: &DownloadHandle,
+) -> Option<DownloadOutcome> {
     let OverwriteOption {
         state,
         final_path,
@@ -69,7 +93,16 @@ pub async fn overwrite(option: OverwriteOption) {
         tx,
         token,
     } = option;
-    tx_err!(state.store().await, tx, StateSaveError);
+    tx_err!(
+        state.store().await,
+        tx,
+        StateSaveError,
+        fail(
+            &tx,
+            anyhow::anyhow!("failed to save the download state"),
+            None
+        )
+    );
     let _ = state.take_dirty();
 
     let inner_state = state.lock_inner().clone();
@@ -78,10 +111,42 @@ pub async fn overwrite(option: OverwriteOption) {
     let tmp_path = state.tmp_path();
     let config = &inner_state.config;
 
-    let pipeline =
-        build_pipeline(&info.final_url, config, &info, resp, &tmp_path, &tx, &token).await;
-    let Some((puller, pusher)) = pipeline else {
-        return;
+    let advertised = if config.verify_server_digest {
+        match &config.expected_digest {
+            Some(expected) => inner_state
+                .digests
+                .iter()
+                .find(|d| d.algorithm == expected.algorithm),
+            None => inner_state
+                .digests
+                .iter()
+                .max_by_key(|d| d.algorithm.strength()),
+        }
+    } else {
+        None
+    };
+    let hash = config
+        .expected_digest
+        .as_ref()
+        .or(advertised)
+        .map(|d| d.algorithm);
+    let pipeline = build_pipeline(
+        &info.final_url,
+        config,
+        &info,
+        resp,
+        &tmp_path,
+        hash,
+        &tx,
+        &token,
+    )
+    .await;
+    let Some((puller, pusher, digest)) = pipeline else {
+        return fail(
+            &tx,
+            anyhow::anyhow!("failed to build the download pipeline"),
+            None,
+        );
     };
 
     let _ = tx.send(Event::Start {
@@ -90,6 +155,12 @@ pub async fn overwrite(option: OverwriteOption) {
         parsed_config,
     });
 
+    let (threads, min_chunk_size, max_speculative) = handle.tuning();
+    // The state needs every written range, whatever reaches the channel.
+    let engine_verbosity = config.verbosity.max(Verbosity::Progress);
+    // An explicit `set_threads` before the start fixes the count.
+    let auto = (threads.is_none() && config.is_auto_threads() && info.fast_download)
+        .then(|| AutoThreads::new(handle.max_threads().unwrap_or(config.max_auto_threads)));
     let res = if info.fast_download {
         download_multi(
             puller,
@@ -100,12 +171,20 @@ pub async fn overwrite(option: OverwriteOption) {
                     info.size,
                     config.chunk_window,
                 ),
-                concurrent: config.threads,
+                concurrent: auto
+                    .as_ref()
+                    .map_or_else(|| threads.unwrap_or(config.threads), AutoThreads::threads),
                 retry_gap: config.retry_gap,
                 pull_timeout: config.pull_timeout,
                 push_queue_cap: config.write_queue_cap,
-                min_chunk_size: config.min_chunk_size,
-                max_speculative: config.max_speculative,
+                min_chunk_size: min_chunk_size.unwrap_or(config.min_chunk_size),
+                max_speculative: max_speculative.unwrap_or(config.max_speculative),
+                speed_limiter: SpeedLimiter::new(config.speed_limit),
+                retry_policy: Some(config.retry_policy()),
+                pause_token: handle.pause_token(),
+                verbosity: engine_verbosity,
+                slow_worker: config.slow_thread_floor(),
+                low_speed_abort: config.low_speed_floor(),
             },
         )
     } else {
@@ -115,9 +194,23 @@ pub async fn overwrite(option: OverwriteOption) {
             fast_down::single::DownloadOptions {
                 retry_gap: config.retry_gap,
                 push_queue_cap: config.write_queue_cap,
+                speed_limiter: SpeedLimiter::new(config.speed_limit),
+                retry_policy: Some(config.retry_policy()),
+                pause_token: handle.pause_token(),
+                verbosity: engine_verbosity,
+                low_speed_abort: config.low_speed_floor(),
             },
         )
     };
+    let reporter = ProgressReporter::new(inner_state.elapsed, info.size, state.share_inner())
+        .with_chunk_map(config.chunk_map_cells, {
+            let res = res.clone();
+            move || res.workers().into_iter().filter_map(|w| w.range).collect()
+        });
+    handle.attach(Engine {
+        res: res.clone(),
+        reporter: reporter.clone(),
+    });
 
     let abort_handle = {
         let token = token.clone();
@@ -129,6 +222,7 @@ pub async fn overwrite(option: OverwriteOption) {
     };
 
     let persist_token = token.child_token();
+    let verbosity = config.verbosity;
     let persist_task = {
         let st = state.clone();
         let tx = tx.clone();
@@ -138,7 +232,9 @@ pub async fn overwrite(option: OverwriteOption) {
                 tokio::select! {
                     () = tokio::time::sleep(Duration::from_secs(1)) => {
                         if st.take_dirty() && let Err(e) = st.store().await {
-                            let _ = tx.send(Event::StateSaveError(e));
+                            if verbosity.includes(Verbosity::Errors) {
+                                let _ = tx.send(Event::StateSaveError(e));
+                            }
                             st.mark_dirty(); // retry on the next cadence tick
                         }
                     }
@@ -148,61 +244,282 @@ pub async fn overwrite(option: OverwriteOption) {
         })
     };
 
-    let reporter = ProgressReporter::new(inner_state.elapsed, info.size, state.share_inner());
-    let progress_task = reporter.clone().spawn(&tx, config.progress_emit_gap);
+    let signals = Arc::new(Signals::default());
+    let auto_task = auto.map(|auto| {
+        if verbosity.includes(Verbosity::Progress) {
+            let _ = tx.send(Event::Concurrency(auto.threads()));
+        }
+        auto.spawn(
+            res.clone(),
+            handle.clone(),
+            signals.clone(),
+            tx.clone(),
+            config.max_auto_threads,
+            verbosity,
+        )
+    });
+    let progress_task = verbosity
+        .includes(Verbosity::Progress)
+        .then(|| reporter.clone().spawn(&tx, config.progress_emit_gap));
+    let workers_task = verbosity.includes(Verbosity::Progress).then(|| {
+        let res = res.clone();
+        let tx = tx.clone();
+        let gap = config.progress_emit_gap;
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(gap).await;
+                let _ = tx.send(Event::Workers(res.workers()));
+            }
+        })
+    });
 
     while let Ok(e) = res.event_chain().recv().await {
         if let fast_down::Event::PushProgress(range) = &e {
             state.merge_progress(range.clone());
         }
-        let _ = match e {
-            fast_down::Event::Pulling(id) => tx.send(Event::Pulling(id)),
-            fast_down::Event::PullError(id, e) => tx.send(Event::PullError(id, anyhow::anyhow!(e))),
-            fast_down::Event::PullTimeout(id) => tx.send(Event::PullTimeout(id)),
-            fast_down::Event::PullProgress(id, range) => tx.send(Event::PullProgress(id, range)),
-            fast_down::Event::Pushing(id, range) => tx.send(Event::Pushing(id, range)),
+        let e = match e {
+            fast_down::Event::Pulling(id) => Event::Pulling(id),
+            fast_down::Event::PullError(id, e) => Event::PullError(id, anyhow::anyhow!(e)),
+            fast_down::Event::PullTimeout(id) => Event::PullTimeout(id),
+            fast_down::Event::PullSlow(id) => Event::PullSlow(id),
+            fast_down::Event::Throttled(cooldown) => Event::Throttled(cooldown),
+            fast_down::Event::PullProgress(id, range) => Event::PullProgress(id, range),
+            fast_down::Event::Pushing(id, range) => Event::Pushing(id, range),
             fast_down::Event::PushError(id, range, e) => {
-                tx.send(Event::PushError(id, range, anyhow::anyhow!(e)))
+                Event::PushError(id, range, anyhow::anyhow!(e))
             }
-            fast_down::Event::PushProgress(range) => tx.send(Event::PushProgress(range)),
-            fast_down::Event::Flushing => tx.send(Event::Flushing),
-            fast_down::Event::FlushError(e) => tx.send(Event::FlushError(anyhow::anyhow!(e))),
-            fast_down::Event::Finished(id) => tx.send(Event::Finished(id)),
+            fast_down::Event::PushProgress(range) => Event::PushProgress(range),
+            fast_down::Event::Flushing => Event::Flushing,
+            fast_down::Event::FlushError(e) => Event::FlushError(anyhow::anyhow!(e)),
+            fast_down::Event::PullFailed(id, e) => Event::PullFailed(id, anyhow::anyhow!(e)),
+            fast_down::Event::PullTimeoutFailed(id) => Event::PullTimeoutFailed(id),
+            fast_down::Event::PushFailed(id, range, e) => {
+                Event::PushFailed(id, range, anyhow::anyhow!(e))
+            }
+            fast_down::Event::Finished(id) => Event::Finished(id),
         };
+        if auto_task.is_some() {
+            signals.observe(&e);
+        }
+        if verbosity.includes(e.verbosity()) {
+            let _ = tx.send(e);
+        }
     }
 
-    progress_task.abort();
-    let _ = progress_task.await;
+    for task in [progress_task, workers_task, auto_task]
+        .into_iter()
+        .flatten()
+    {
+        task.abort();
+        let _ = task.await;
+    }
     let sample = reporter.compute(Instant::now(), None);
     let elapsed = sample.elapsed;
-    let _ = tx.send(Event::Progress(sample));
+    handle.detach(sample.clone());
+    if verbosity.includes(Verbosity::Progress) {
+        let _ = tx.send(Event::Progress(sample));
+    }
     persist_token.cancel();
     let _ = persist_task.await;
 
     abort_handle.abort();
+    let mut outcome = res.join().await;
 
     let download_complete = info.size == 0
         || matches!(&state.lock_inner().config, Some(PartialConfig { downloaded_chunk: Some(x), .. }) if x.len() == 1 && x[0] == (0..info.size));
     if token.is_cancelled() || !download_complete {
         state.set_elapsed(elapsed);
-        if let Err(e) = state.store().await {
+        if let Err(e) = state.store().await
+            && verbosity.includes(Verbosity::Errors)
+        {
             let _ = tx.send(Event::StateSaveError(e));
         }
-        return;
+        let error = match outcome.status {
+            DownloadStatus::Failed(ref e) => anyhow::anyhow!("{e}"),
+            DownloadStatus::Aborted => anyhow::anyhow!("download cancelled"),
+            DownloadStatus::Completed if token.is_cancelled() => {
+                outcome.status = DownloadStatus::Aborted;
+                anyhow::anyhow!("download cancelled")
+            }
+            DownloadStatus::Completed => {
+                anyhow::anyhow!("download ended before every byte was written")
+            }
+        };
+        return fail(&tx, error, Some(outcome));
+    }
+
+    if let Some(digest) = digest {
+        let verified = tokio::task::spawn_blocking({
+            let tmp_path = tmp_path.clone();
+            move || finish_digest(&digest, &tmp_path)
+        })
+        .await
+        .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+        let actual = match verified {
+            Ok(actual) => actual,
+            Err(e) => {
+                let error = anyhow::anyhow!("failed to verify the downloaded file: {e}");
+                let _ = tx.send(Event::VerifyError(e));
+                return fail(&tx, error, Some(outcome));
+            }
+        };
+        if let Some(expected) = &config.expected_digest
+            && actual != *expected
+        {
+            forget_progress(&state, std::slice::from_ref(&(0..u64::MAX)), &tx, verbosity).await;
+            let error = anyhow::anyhow!("digest mismatch: expected {expected}, got {actual}");
+            let _ = tx.send(Event::DigestMismatch {
+                expected: expected.clone(),
+                actual,
+            });
+            return fail(&tx, error, Some(outcome));
+        }
+        if let Some(advertised) = advertised
+            && actual != *advertised
+        {
+            forget_progress(&state, std::slice::from_ref(&(0..u64::MAX)), &tx, verbosity).await;
+            let error = anyhow::anyhow!(
+                "file does not match the digest advertised by the server: expected {advertised}, got {actual}"
+            );
+            let _ = tx.send(Event::IntegrityError {
+                advertised: advertised.clone(),
+                actual,
+            });
+            return fail(&tx, error, Some(outcome));
+        }
+        let _ = tx.send(Event::Verified(actual));
+    }
+
+    if let Some(pieces) = &config.expected_pieces {
+        let checked = tokio::task::spawn_blocking({
+            let (pieces, tmp_path) = (pieces.clone(), tmp_path.clone());
+            move || pieces.verify(std::fs::File::open(tmp_path)?)
+        })
+        .await
+        .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+        match checked {
+            Ok(bad) if bad.is_empty() => {}
+            Ok(bad) => {
+                let ranges: Vec<_> = bad
+                    .iter()
+                    .map(|&i| {
+                        let start = i as u64 * pieces.length;
+                        start..start + pieces.length
+                    })
+                    .collect();
+                forget_progress(&state, &ranges, &tx, verbosity).await;
+                let error = anyhow::anyhow!("{} of the listed pieces do not match", bad.len());
+                let _ = tx.send(Event::PieceMismatch(bad));
+                return fail(&tx, error, Some(outcome));
+            }
+            Err(e) => {
+                let error = anyhow::anyhow!("failed to verify the downloaded file: {e}");
+                let _ = tx.send(Event::VerifyError(e));
+                return fail(&tx, error, Some(outcome));
+            }
+        }
     }
 
     let final_path = if config.overwrite {
         final_path
     } else {
-        tx_err!(gen_unique_path(final_path).await, tx, GenPathError)
+        match gen_unique_path(final_path).await {
+            Ok(path) => path,
+            Err(e) => {
+                let error = anyhow::anyhow!("failed to generate a unique path: {e}");
+                let _ = tx.send(Event::GenPathError(e));
+                return fail(&tx, error, Some(outcome));
+            }
+        }
     };
     if let Err(e) = fs::rename(tmp_path, &final_path).await {
         if !config.overwrite {
             let _ = fs::remove_file(&final_path).await;
         }
+        let error = anyhow::anyhow!("failed to rename the downloaded file: {e}");
         let _ = tx.send(Event::RenameFailed(e));
-        return;
+        return fail(&tx, error, Some(outcome));
     }
     let _ = fs::remove_file(&state.config_path).await;
     let _ = tx.send(Event::Renamed(final_path));
+    let _ = tx.send(Event::Completed(outcome.clone()));
+    Some(outcome)
+}
+
+/// Drop `ranges` from the progress in the `.fd`, so that a rerun pulls them
+/// again instead of resuming onto bytes that failed verification.
+async fn forget_progress(
+    state: &DownloadState,
+    ranges: &[ProgressEntry],
+    tx: &Tx,
+    verbosity: Verbosity,
+) {
+    state.forget_progress(ranges);
+    if let Err(e) = state.store().await
+        && verbosity.includes(Verbosity::Errors)
+    {
+        let _ = tx.send(Event::StateSaveError(e));
+    }
+}
+
+/// Complete `digest` with the part of the `.part` file it could not hash while
+/// streaming, then return it.
+fn finish_digest(digest: &DigestHandle, path: &std::path::Path) -> std::io::Result<Digest> {
+    let mut file = std::fs::File::open(path)?;
+    file.seek(SeekFrom::Start(digest.hashed_len()))?;
+    digest.update_from(file)?;
+    Ok(digest.digest())
+}
+
+/// Emit the final [`Event::Failed`] of a run and return its outcome.
+///
+/// The engine may have completed while a step after it failed; the status is
+/// then replaced by `error`, since no file was produced.
+fn fail(
+    tx: &Tx,
+    error: anyhow::Error,
+    mut outcome: Option<DownloadOutcome>,
+) -> Option<DownloadOutcome> {
+    if let Some(outcome) = &mut outcome
+        && outcome.is_completed()
+    {
+        outcome.status = DownloadStatus::Failed(error.to_string());
+    }
+    let _ = tx.send(Event::Failed {
+        error,
+        outcome: outcome.clone(),
+    });
+    outcome
+}
+
+/// The running engine behind a [`DownloadHandle`].
+struct Engine<R: Puller, PushError: Send + Unpin + 'static> {
+    res: DownloadResult<TokioExecutor<R, PushError>, R::Error, PushError>,
+    reporter: ProgressReporter,
+}
+
+impl<R: Puller, PushError: Send + Unpin + 'static> Session for Engine<R, PushError> {
+    fn set_threads(&self, threads: usize) {
+        self.res.set_threads(threads, self.res.min_chunk_size());
+    }
+
+    fn set_min_chunk_size(&self, min_chunk_size: u64) {
+        self.res.set_min_chunk_size(min_chunk_size);
+    }
+
+    fn set_max_speculative(&self, max_speculative: usize) {
+        self.res.set_max_speculative(max_speculative);
+    }
+
+    fn set_speed_limit(&self, bytes_per_sec: u64) {
+        self.res.set_speed_limit(bytes_per_sec);
+    }
+
+    fn progress(&self) -> ProgressSample {
+        self.reporter.sample()
+    }
+
+    fn workers(&self) -> Vec<WorkerSnapshot> {
+        self.res.workers()
+    }
 }
@@ -4,11 +4,18 @@
 //! [`BoxPusher`] (file side) for the `.part` file, choosing the memory-mapped
 //! writer on 64-bit targets when the server supports fast (resumable) downloads
 //! and `Mmap` writing is configured, and the buffered/cache writer otherwise.
-use crate::{Config, Event, Tx, WriteMethod, core::download::open_existing, utils::build_header};
+//! When the file is to be verified, the writer is wrapped in a
+//! [`HashingPusher`]. The puller spreads its ranges over the download URL and
+//! the mirrors accepted by [`prefetch_mirrors`].
+use crate::{
+    Config, Event, Tx, WriteMethod, core::download::open_existing, prefetch_mirrors,
+    utils::build_header,
+};
 use fast_down::{
-    BoxPusher, UrlInfo,
+    BoxPusher, DigestHandle, HashAlgorithm, HashingPusher, UrlInfo,
     fast_puller::{FastDownPuller, FastDownPullerOptions},
     file::{CacheFilePusher, MmapFilePusher},
+    http::MirrorPuller,
 };
 use parking_lot::Mutex;
 use reqwest::Response;
@@ -23,7 +30,8 @@ use url::Url;
 /// or if `token` is cancelled before construction finishes.
 ///
 /// * `url` / `config` drive the puller (headers, proxy, cert handling, range
-///   identity, local bind address, redirect limit).
+///   identity, local bind address, redirect limit, connection pool, DNS
+///   resolver, mirrors).
 /// * `info` supplies the file identity used for range validation and selects
 ///   the writer: on 64-bit targets a resumable `info.fast_download` download
 ///   with [`WriteMethod::Mmap`] uses [`MmapFilePusher`]; otherwise
@@ -32,31 +40,53 @@ use url::Url;
 ///   without an extra round-trip.
 /// * `path` is the `.part` file; `tx` receives error events; `token` makes
 ///   construction cancellable.
+///
+/// With `hash` set, the pusher is wrapped in a [`HashingPusher`] computing it,
+/// whose [`DigestHandle`] is returned alongside.
+#[allow(clippy::too_many_arguments)]
 pub async fn build_pipeline(
     url: &Url,
     config: &Config,
     info: &UrlInfo,
     resp: Response,
     path: &Path,
+    hash: Option<HashAlgorithm>,
     tx: &Tx,
     token: &CancellationToken,
-) -> Option<(FastDownPuller, BoxPusher)> {
+) -> Option<(
+    MirrorPuller<FastDownPuller>,
+    BoxPusher,
+    Option<DigestHandle>,
+)> {
     let resp = Some(Arc::new(Mutex::new(Some(resp))));
     let built = token
         .run_until_cancelled(async move {
-            let puller = FastDownPuller::new(FastDownPullerOptions {
-                url: url.clone(),
-                headers: build_header(&config.headers).into(),
-                proxy: config.proxy.as_deref(),
-                accept_invalid_certs: config.accept_invalid_certs,
-                accept_invalid_hostnames: config.accept_invalid_hostnames,
-                cookie_store: config.cookie_store,
-                file_id: info.file_id.clone(),
-                resp,
-                available_ips: config.local_address.clone().into(),
-                max_redirects: config.max_redirects,
-            })
-            .map_err(Event::BuildClientError)?;
+            let headers = Arc::new(build_header(&config.headers));
+            let available_ips: Arc<[_]> = config.local_address.clone().into();
+            let resolver = config.resolver();
+            let mirrors = prefetch_mirrors(info, config, tx).await;
+            let mut pullers = Vec::with_capacity(mirrors.len() + 1);
+            for (url, file_id, resp) in std::iter::once((url.clone(), info.file_id.clone(), resp))
+                .chain(mirrors.into_iter().map(|(url, id)| (url, id, None)))
+            {
+                let puller = FastDownPuller::new(FastDownPullerOptions {
+                    url,
+                    headers: headers.clone(),
+                    proxy: config.proxy.as_deref(),
+                    accept_invalid_certs: config.accept_invalid_certs,
+                    accept_invalid_hostnames: config.accept_invalid_hostnames,
+                    cookie_store: config.cookie_store,
+                    file_id,
+                    resp,
+                    available_ips: available_ips.clone(),
+                    max_redirects: config.max_redirects,
+                    resolver: resolver.clone(),
+                    pool: config.connection_pool,
+                })
+                .map_err(Event::BuildClientError)?;
+                pullers.push(puller);
+            }
+            let puller = MirrorPuller::new(pullers);
 
             let file = open_existing()
                 .open(path)
@@ -82,7 +112,15 @@ pub async fn build_pipeline(
                 .map(BoxPusher::new)
             }
             .map_err(Event::BuildPusherError)?;
-            Ok::<_, Event>((puller, pusher))
+            let (pusher, digest) = match hash {
+                Some(algorithm) => {
+                    let pusher = HashingPusher::new(pusher, algorithm, config.cache_high_watermark);
+                    let digest = pusher.digest_handle();
+                    (BoxPusher::new(pusher), Some(digest))
+                }
+                None => (pusher, None),
+            };
+            Ok::<_, Event>((puller, pusher, digest))
         })
         .await;
     match built {
@@ -12,7 +12,7 @@
 //! driven purely by `Config::progress_emit_gap` and is never delayed by flushing,
 //! state saving, event forwarding, or a slow consumer (the channel is unbounded).
 use crate::{Event, ProgressSample, Tx, core::state::PartialDownloadStateInner};
-use fast_down::Total;
+use fast_down::{ChunkMap, ProgressEntry, Total};
 use parking_lot::Mutex;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -62,8 +62,8 @@ impl RateEstimator {
 
 /// Drives [`crate::Event::Progress`] emission on a fixed cadence.
 ///
-/// Cheap to clone: `state` is an `Arc`, and every other field is a small
-/// read-only `Copy` value captured at construction time.
+/// Cheap to clone: `state` and `rate` are `Arc`s, and every other field is a
+/// small read-only `Copy` value captured at construction time.
 #[derive(Clone)]
 pub(super) struct ProgressReporter {
     /// The single source of truth: already-written byte ranges from `DownloadState`.
@@ -74,8 +74,17 @@ pub(super) struct ProgressReporter {
     start: Instant,
     /// Active time already spent on this download in prior resume runs.
     loaded_elapsed: Duration,
+    /// Smoothing state of the recent rate, shared by the cadence task and
+    /// on-demand [`sample`](Self::sample)s so both report the same `bps`.
+    rate: Arc<Mutex<RateEstimator>>,
+    /// Cell count of [`ProgressSample::chunks`] and the source of the ranges
+    /// being pulled; `None` leaves `chunks` empty.
+    chunk_map: Option<(usize, ActiveRanges)>,
 }
 
+/// Lists the ranges the workers are pulling right now.
+type ActiveRanges = Arc<dyn Fn() -> Vec<ProgressEntry> + Send + Sync>;
+
 impl ProgressReporter {
     /// Capture the starting point of this run.
     ///
@@ -94,9 +103,23 @@ impl ProgressReporter {
             total,
             start: Instant::now(),
             loaded_elapsed,
+            rate: Arc::default(),
+            chunk_map: None,
         }
     }
 
+    /// Render [`ProgressSample::chunks`] into `cells` cells, marking the
+    /// ranges returned by `active` as in flight. `cells == 0` disables it.
+    #[must_use]
+    pub fn with_chunk_map(
+        mut self,
+        cells: usize,
+        active: impl Fn() -> Vec<ProgressEntry> + Send + Sync + 'static,
+    ) -> Self {
+        self.chunk_map = (cells > 0).then(|| (cells, Arc::new(active) as ActiveRanges));
+        self
+    }
+
     /// Total active time so far: prior runs plus this run's wall-clock.
     #[must_use]
     pub fn elapsed_now(&self, now: Instant) -> Duration {
@@ -158,6 +181,11 @@ impl ProgressReporter {
             }
         };
 
+        let chunks = self
+            .chunk_map
+            .as_ref()
+            .map(|(cells, active)| ChunkMap::new(&progress, &active(), total, *cells));
+
         ProgressSample {
             progress,
             bps,
@@ -167,9 +195,16 @@ impl ProgressReporter {
             total,
             elapsed,
             eta,
+            chunks,
         }
     }
 
+    /// Compute a live sample now, advancing the shared rate estimate.
+    #[must_use]
+    pub fn sample(&self) -> ProgressSample {
+        self.compute(Instant::now(), Some(&mut self.rate.lock()))
+    }
+
     /// Spawn the cadence-driven reporter task.
     ///
     /// The task emits [`crate::Event::Progress`] every `gap` until the caller
@@ -185,10 +220,8 @@ impl ProgressReporter {
     pub fn spawn(self, tx: &Tx, gap: Duration) -> tokio::task::JoinHandle<()> {
         let tx = tx.clone();
         spawn(async move {
-            let mut rate = RateEstimator::default();
             loop {
-                let sample = self.compute(Instant::now(), Some(&mut rate));
-                let _ = tx.send(Event::Progress(sample));
+                let _ = tx.send(Event::Progress(self.sample()));
                 sleep(gap).await;
             }
         })
@@ -219,6 +252,7 @@ mod tests {
             final_url: url.clone(),
             file_id: fast_down::FileId::new(None, None),
             content_type: None,
+            digests: Vec::new(),
         };
         let state = DownloadState::new(
             &url,
@@ -253,6 +287,7 @@ mod tests {
             final_url: url.clone(),
             file_id: fast_down::FileId::new(None, None),
             content_type: None,
+            digests: Vec::new(),
         };
         let state = DownloadState::new(
             &url,
@@ -285,6 +320,39 @@ mod tests {
         );
     }
 
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn compute_renders_chunk_map_when_enabled() {
+        let url = Url::parse("https://example.com/x").unwrap();
+        let info = UrlInfo {
+            size: 1000,
+            raw_name: "x".to_string(),
+            supports_range: true,
+            fast_download: true,
+            final_url: url.clone(),
+            file_id: fast_down::FileId::new(None, None),
+            content_type: None,
+            digests: Vec::new(),
+        };
+        let state = DownloadState::new(
+            &url,
+            &info,
+            &PartialConfig::default(),
+            Path::new("/tmp/_pr_chunks.fd"),
+        );
+        state.update(|inner| {
+            inner.config.get_or_insert_default().downloaded_chunk = Some(vec![0u64..500]);
+        });
+
+        let reporter = ProgressReporter::new(Duration::ZERO, 1000, state.share_inner());
+        assert!(reporter.compute(Instant::now(), None).chunks.is_none());
+        let reporter = reporter.with_chunk_map(4, || vec![500..700]);
+        let chunks = reporter.compute(Instant::now(), None).chunks.unwrap();
+        assert_eq!(chunks.cells(), 4);
+        assert!(chunks.is_done(0) && chunks.is_done(1) && !chunks.is_done(2));
+        assert!(chunks.is_active(2) && !chunks.is_active(3));
+    }
+
     #[test]
     fn rate_estimator_smooths_and_lags() {
         // RateEstimator::observe (progress_reporter.rs lines 45-60): the first
@@ -384,6 +452,7 @@ mod tests {
             final_url: url.clone(),
             file_id: fast_down::FileId::new(None, None),
             content_type: None,
+            digests: Vec::new(),
         };
         let state = DownloadState::new(
             &url,
@@ -0,0 +1,724 @@
+//! A queue of downloads sharing one connection budget.
+
+use crate::{
+    Config, DownloadHandle, Event, JobStatus, PartialConfig, Session, SessionJob, StateError,
+    create_channel, download, resume,
+};
+use fast_down::DownloadOutcome;
+use inherit_config::ConfigLayer;
+use parking_lot::Mutex;
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    path::PathBuf,
+    pin::pin,
+    sync::{Arc, Weak},
+};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+/// Identifies a job of a [`DownloadManager`], unique per manager.
+pub type JobId = u64;
+
+/// Sender half of the channel a [`DownloadManager`] reports through.
+pub type ManagerTx = crossfire::MTx<crossfire::mpmc::List<ManagerEvent>>;
+/// Receiver half of the channel a [`DownloadManager`] reports through.
+pub type ManagerRx = crossfire::MAsyncRx<crossfire::mpmc::List<ManagerEvent>>;
+
+/// Create a new unbounded channel for a [`DownloadManager`]'s events.
+#[must_use]
+pub fn create_manager_channel() -> (ManagerTx, ManagerRx) {
+    crossfire::mpmc::unbounded_async()
+}
+
+/// A download waiting in a [`DownloadManager`].
+#[derive(Debug, Clone)]
+pub struct DownloadJob {
+    pub url: Url,
+    pub config: PartialConfig,
+    /// Jobs with a higher priority start first; equal priorities start in the
+    /// order they were added.
+    pub priority: i32,
+}
+
+/// What a [`DownloadManager`] reports, each tagged with the job it is about.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum ManagerEvent {
+    /// Job `id` left the queue and its download was spawned.
+    Started(JobId),
+    /// An [`Event`] of job `id`'s download, forwarded in order.
+    Job(JobId, Event),
+    /// Job `id` turned out to be a metalink document (see
+    /// [`Event::Metalink`]); its files were queued as the listed jobs, with
+    /// its priority. Sent right before its [`ManagerEvent::Ended`], and the
+    /// job counts as [`JobStatus::Completed`].
+    Expanded(JobId, Vec<JobId>),
+    /// Job `id` is over and every one of its events was forwarded. Carries
+    /// the outcome of [`DownloadHandle::join`]; `None` also when the job was
+    /// cancelled while queued or expanded into metalink jobs.
+    Ended(JobId, Option<DownloadOutcome>),
+    /// Writing the session file failed. Retried on the next change.
+    SessionSaveError(StateError),
+}
+
+/// Runs [`download`]s from a priority queue, capping how many are active at
+/// once and how many workers they use together.
+///
+/// Each active download is given the `threads` of its config, scaled down so
+/// the total stays within `max_workers`: the budget is split evenly, and what
+/// a download does not need goes to the others. The split is recomputed with
+/// [`DownloadHandle::set_threads`] whenever a download starts or ends. A job
+/// starts only when at least one worker is left for it. A `threads = "auto"`
+/// download asks for its `max_auto_threads`, and its share caps the tuner
+/// through [`DownloadHandle::set_max_threads`] instead.
+///
+/// A manager made by [`DownloadManager::restore`] keeps every job, finished
+/// ones included, in a [`Session`] file rewritten on each change, and picks
+/// them up again when the process restarts.
+///
+/// The manager is cheap to clone; clones share the queue. Dropping every
+/// clone leaves the queued jobs unstarted and the active ones running.
+#[derive(Clone)]
+pub struct DownloadManager {
+    inner: Arc<ManagerInner>,
+}
+
+struct ManagerInner {
+    tx: ManagerTx,
+    token: CancellationToken,
+    state: Mutex<ManagerState>,
+    /// Notified whenever the manager becomes idle.
+    idle: Notify,
+    /// The session file, and the signal to rewrite it.
+    session: Option<(PathBuf, Arc<Notify>)>,
+}
+
+struct ManagerState {
+    max_active: usize,
+    max_workers: usize,
+    next_id: JobId,
+    jobs: BTreeMap<JobId, Record>,
+}
+
+struct Record {
+    job: DownloadJob,
+    status: JobStatus,
+    tmp_path: Option<PathBuf>,
+    /// Present while the download runs.
+    run: Option<Run>,
+}
+
+struct Run {
+    handle: DownloadHandle,
+    /// The `threads` of the job's config, or its `max_auto_threads` when
+    /// `auto`.
+    threads: usize,
+    auto: bool,
+}
+
+impl Run {
+    fn new(handle: DownloadHandle, config: &Config) -> Self {
+        let auto = config.is_auto_threads();
+        let threads = if auto {
+            config.max_auto_threads
+        } else {
+            config.threads
+        };
+        Self {
+            handle,
+            threads: threads.max(1),
+            auto,
+        }
+    }
+}
+
+impl DownloadManager {
+    /// A manager running at most `max_active` downloads with at most
+    /// `max_workers` workers in total, reporting through `tx`. `0` lifts
+    /// either limit.
+    ///
+    /// Cancelling `token` cancels every active download and keeps queued jobs
+    /// from starting; with a session file, both stay recorded as they were so
+    /// a later [`restore`](Self::restore) continues them.
+    #[must_use]
+    pub fn new(
+        max_active: usize,
+        max_workers: usize,
+        tx: ManagerTx,
+        token: CancellationToken,
+    ) -> Self {
+        Self::build(max_active, max_workers, tx, token, None)
+    }
+
+    /// Like [`new`](Self::new), persisting the jobs to the session file at
+    /// `path` and restoring those it already lists: queued jobs are queued
+    /// again, interrupted ones are [`resume`]d from their `.part` file (and
+    /// paused again if they were paused) ahead of the queue, and finished
+    /// ones are kept as they are. A missing file starts an empty session.
+    ///
+    /// The file is rewritten in the background after every change; failures
+    /// are reported as [`ManagerEvent::SessionSaveError`]. Call
+    /// [`save_session`](Self::save_session) before exiting to be sure the last
+    /// change is on disk.
+    ///
+    /// # Errors
+    /// Returns an error if the session file exists but cannot be read or
+    /// decoded.
+    #[allow(clippy::result_large_err)]
+    pub async fn restore(
+        path: impl Into<PathBuf>,
+        max_active: usize,
+        max_workers: usize,
+        tx: ManagerTx,
+        token: CancellationToken,
+    ) -> Result<Self, StateError> {
+        let path = path.into();
+        let session = Session::load(&path).await?;
+        let manager = Self::build(max_active, max_workers, tx, token, Some(path));
+        let mut state = manager.inner.state.lock();
+        for job in session.jobs {
+            state.next_id = state.next_id.max(job.id + 1);
+            let record = Record {
+                job: DownloadJob {
+                    url: job.url,
+                    config: job.config,
+                    priority: job.priority,
+                },
+                status: job.status,
+                tmp_path: job.tmp_path,
+                run: None,
+            };
+            state.jobs.insert(job.id, record);
+        }
+        drop(state);
+        manager.inner.schedule();
+        Ok(manager)
+    }
+
+    fn build(
+        max_active: usize,
+        max_workers: usize,
+        tx: ManagerTx,
+        token: CancellationToken,
+        session_path: Option<PathBuf>,
+    ) -> Self {
+        let session = session_path.map(|path| (path, Arc::new(Notify::new())));
+        let inner = Arc::new(ManagerInner {
+            tx,
+            token,
+            state: Mutex::new(ManagerState {
+                max_active,
+                max_workers,
+                next_id: 0,
+                jobs: BTreeMap::new(),
+            }),
+            idle: Notify::new(),
+            session,
+        });
+        if let Some((path, changed)) = &inner.session {
+            tokio::spawn(persist(
+                Arc::downgrade(&inner),
+                path.clone(),
+                changed.clone(),
+            ));
+        }
+        Self { inner }
+    }
+
+    /// Queue `job`, starting it right away if the limits allow.
+    ///
+    /// Must be called within a Tokio runtime, like [`download`].
+    #[allow(clippy::must_use_candidate)]
+    pub fn add(&self, job: DownloadJob) -> JobId {
+        let id = self.inner.queue(job);
+        self.inner.changed();
+        self.inner.schedule();
+        id
+    }
+
+    /// Change the priority of a job waiting to start. Returns `false` if `id`
+    /// is not waiting.
+    #[allow(clippy::must_use_candidate)]
+    pub fn set_priority(&self, id: JobId, priority: i32) -> bool {
+        let updated = self
+            .inner
+            .state
+            .lock()
+            .jobs
+            .get_mut(&id)
+            .filter(|r| r.is_pending())
+            .map(|r| r.job.priority = priority)
+            .is_some();
+        if updated {
+            self.inner.changed();
+        }
+        updated
+    }
+
+    /// Layer `patch` over the config of job `id`: its set fields replace the
+    /// job's. A waiting job starts with the new config; an active one takes
+    /// `threads` (within the worker budget), `min_chunk_size`,
+    /// `max_speculative` and `speed_limit` right away, and the rest when it is
+    /// next resumed.
+    /// Returns `false` if `id` is not known or already over.
+    #[allow(clippy::must_use_candidate)]
+    pub fn reconfigure(&self, id: JobId, mut patch: PartialConfig) -> bool {
+        let mut state = self.inner.state.lock();
+        let Some(record) = state.jobs.get_mut(&id).filter(|r| !r.status.is_finished()) else {
+            return false;
+        };
+        patch.inherit_from(&record.job.config);
+        record.job.config = patch;
+        if let Some(run) = &mut record.run {
+            let config = &record.job.config;
+            *run = Run::new(run.handle.clone(), &config.clone().build());
+            if let Some(min_chunk_size) = config.min_chunk_size {
+                run.handle.set_min_chunk_size(min_chunk_size);
+            }
+            if let Some(max_speculative) = config.max_speculative {
+                run.handle.set_max_speculative(max_speculative);
+            }
+            if let Some(speed_limit) = config.speed_limit {
+                run.handle.set_speed_limit(speed_limit);
+            }
+            state.rebalance();
+        }
+        drop(state);
+        self.inner.changed();
+        true
+    }
+
+    /// Cancel job `id`: a queued job is dropped and reported as
+    /// [`ManagerEvent::Ended`], an active one is cancelled like
+    /// [`DownloadHandle::cancel`]. Either way it ends as
+    /// [`JobStatus::Cancelled`]. Returns `false` if `id` is not known or
+    /// already over.
+    #[allow(clippy::must_use_candidate)]
+    pub fn cancel(&self, id: JobId) -> bool {
+        let mut state = self.inner.state.lock();
+        let Some(record) = state.jobs.get_mut(&id) else {
+            return false;
+        };
+        if let Some(run) = &record.run {
+            run.handle.cancel();
+            return true;
+        }
+        if record.status.is_finished() {
+            return false;
+        }
+        record.status = JobStatus::Cancelled;
+        drop(state);
+        let _ = self.inner.tx.send(ManagerEvent::Ended(id, None));
+        self.inner.changed();
+        self.inner.notify_if_idle();
+        true
+    }
+
+    /// Pause active job `id` like [`DownloadHandle::pause`]; it keeps its
+    /// slot. Returns `false` if `id` is not active.
+    #[allow(clippy::must_use_candidate)]
+    pub fn pause(&self, id: JobId) -> bool {
+        self.set_paused(id, true)
+    }
+
+    /// Continue paused job `id`. Returns `false` if `id` is not active.
+    #[allow(clippy::must_use_candidate)]
+    pub fn unpause(&self, id: JobId) -> bool {
+        self.set_paused(id, false)
+    }
+
+    fn set_paused(&self, id: JobId, paused: bool) -> bool {
+        let mut state = self.inner.state.lock();
+        let Some(record) = state.jobs.get_mut(&id).filter(|r| r.run.is_some()) else {
+            return false;
+        };
+        if let Some(run) = &record.run {
+            if paused {
+                run.handle.pause();
+            } else {
+                run.handle.unpause();
+            }
+        }
+        record.status = if paused {
+            JobStatus::Paused
+        } else {
+            JobStatus::Running
+        };
+        drop(state);
+        self.inner.changed();
+        true
+    }
+
+    /// Where job `id` stands, `None` if it is not known.
+    #[must_use]
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        self.inner.state.lock().jobs.get(&id).map(|r| r.status)
+    }
+
+    /// Job `id` as the session file records it, `None` if it is not known.
+    #[must_use]
+    pub fn job(&self, id: JobId) -> Option<SessionJob> {
+        let state = self.inner.state.lock();
+        let job = state.jobs.get(&id).map(|r| r.to_session_job(id));
+        drop(state);
+        job
+    }
+
+    /// The handle of active job `id`.
+    #[must_use]
+    pub fn handle(&self, id: JobId) -> Option<DownloadHandle> {
+        let state = self.inner.state.lock();
+        let handle = state.jobs.get(&id)?.run.as_ref()?.handle.clone();
+        drop(state);
+        Some(handle)
+    }
+
+    /// The jobs waiting to start, in the order they will.
+    #[must_use]
+    pub fn queued(&self) -> Vec<JobId> {
+        let state = self.inner.state.lock();
+        let mut queued: Vec<_> = state
+            .jobs
+            .iter()
+            .filter(|(_, r)| r.is_pending())
+            .map(|(&id, r)| (Reverse(r.start_order(id)), id))
+            .collect();
+        drop(state);
+        queued.sort_unstable();
+        queued.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// The jobs currently downloading.
+    #[must_use]
+    pub fn active(&self) -> Vec<JobId> {
+        let state = self.inner.state.lock();
+        state
+            .jobs
+            .iter()
+            .filter(|(_, r)| r.run.is_some())
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Every job, finished ones included, as the session file records them.
+    #[must_use]
+    pub fn session(&self) -> Session {
+        self.inner.snapshot()
+    }
+
+    /// Write the session file now. Does nothing for a manager made by
+    /// [`new`](Self::new).
+    ///
+    /// # Errors
+    /// Returns an error if serializing or writing the session fails.
+    #[allow(clippy::result_large_err)]
+    pub async fn save_session(&self) -> Result<(), StateError> {
+        match &self.inner.session {
+            Some((path, _)) => self.inner.snapshot().store(path).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Forget the finished jobs, dropping them from the session file.
+    pub fn clear_finished(&self) {
+        self.inner
+            .state
+            .lock()
+            .jobs
+            .retain(|_, r| !r.status.is_finished());
+        self.inner.changed();
+    }
+
+    /// Change the number of downloads allowed at once; `0` lifts the limit.
+    /// Active downloads above a lowered limit are left to finish.
+    pub fn set_max_active(&self, max_active: usize) {
+        self.inner.state.lock().max_active = max_active;
+        self.inner.schedule();
+    }
+
+    /// Change the total number of workers; `0` lifts the limit. Active
+    /// downloads are resized right away.
+    pub fn set_max_workers(&self, max_workers: usize) {
+        let mut state = self.inner.state.lock();
+        state.max_workers = max_workers;
+        state.rebalance();
+        drop(state);
+        self.inner.schedule();
+    }
+
+    /// Wait until no job is active and none is queued, or none can start
+    /// because the manager was cancelled.
+    pub async fn join(&self) {
+        loop {
+            let mut notified = pin!(self.inner.idle.notified());
+            notified.as_mut().enable();
+            if self.inner.is_idle() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl ManagerInner {
+    /// Start waiting jobs while the limits allow.
+    fn schedule(self: &Arc<Self>) {
+        if self.token.is_cancelled() {
+            return;
+        }
+        let mut state = self.state.lock();
+        let mut started = false;
+        while state.has_room() {
+            let Some((&id, record)) = state
+                .jobs
+                .iter_mut()
+                .filter(|(_, r)| r.is_pending())
+                .max_by_key(|(id, r)| r.start_order(**id))
+            else {
+                break;
+            };
+            let DownloadJob {
+                url,
+                config,
+                priority,
+            } = record.job.clone();
+            let built = config.clone().build();
+            let (tx, rx) = create_channel();
+            let token = self.token.child_token();
+            let handle = match &record.tmp_path {
+                Some(tmp_path) => resume(tmp_path, Some(url), config, tx, token),
+                None => download(url, config, tx, token),
+            };
+            if record.status == JobStatus::Paused {
+                handle.pause();
+            } else {
+                record.status = JobStatus::Running;
+            }
+            record.run = Some(Run::new(handle.clone(), &built));
+            let _ = self.tx.send(ManagerEvent::Started(id));
+            started = true;
+            let inner = self.clone();
+            tokio::spawn(async move {
+                let mut expanded = None;
+                while let Ok(event) = rx.recv().await {
+                    match &event {
+                        Event::Start { tmp_path, .. } => inner.set_tmp_path(id, tmp_path.clone()),
+                        Event::Metalink(jobs) => {
+                            let ids = jobs.iter().map(|job| {
+                                inner.queue(DownloadJob {
+                                    url: job.url.clone(),
+                                    config: job.config.clone(),
+                                    priority,
+                                })
+                            });
+                            expanded = Some(ids.collect());
+                        }
+                        _ => {}
+                    }
+                    let _ = inner.tx.send(ManagerEvent::Job(id, event));
+                }
+                let outcome = handle.join().await;
+                let is_expanded = expanded.is_some();
+                if let Some(ids) = expanded {
+                    let _ = inner.tx.send(ManagerEvent::Expanded(id, ids));
+                }
+                inner.end(id, outcome, is_expanded);
+            });
+        }
+        if started {
+            state.rebalance();
+            drop(state);
+            self.changed();
+        }
+    }
+
+    /// Queue `job` without starting anything.
+    fn queue(&self, job: DownloadJob) -> JobId {
+        let mut state = self.state.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.jobs.insert(
+            id,
+            Record {
+                job,
+                status: JobStatus::Queued,
+                tmp_path: None,
+                run: None,
+            },
+        );
+        id
+    }
+
+    fn set_tmp_path(&self, id: JobId, tmp_path: PathBuf) {
+        if let Some(record) = self.state.lock().jobs.get_mut(&id) {
+            record.tmp_path = Some(tmp_path);
+        }
+        self.changed();
+    }
+
+    /// Record how job `id` ended; an `expanded` metalink counts as completed.
+    fn end(self: &Arc<Self>, id: JobId, outcome: Option<DownloadOutcome>, expanded: bool) {
+        let mut state = self.state.lock();
+        if let Some(record) = state.jobs.get_mut(&id) {
+            let run = record.run.take();
+            if expanded || outcome.as_ref().is_some_and(DownloadOutcome::is_completed) {
+                record.status = JobStatus::Completed;
+            } else if !self.token.is_cancelled() {
+                // Left as running or paused when the whole manager was
+                // cancelled, so a restore resumes it.
+                record.status = if run.is_some_and(|r| r.handle.is_cancelled()) {
+                    JobStatus::Cancelled
+                } else {
+                    JobStatus::Failed
+                };
+            }
+        }
+        state.rebalance();
+        drop(state);
+        let _ = self.tx.send(ManagerEvent::Ended(id, outcome));
+        self.changed();
+        self.schedule();
+        self.notify_if_idle();
+    }
+
+    fn is_idle(&self) -> bool {
+        let cancelled = self.token.is_cancelled();
+        self.state
+            .lock()
+            .jobs
+            .values()
+            .all(|r| r.run.is_none() && (cancelled || !r.is_pending()))
+    }
+
+    fn notify_if_idle(&self) {
+        if self.is_idle() {
+            self.idle.notify_waiters();
+        }
+    }
+
+    /// Schedule a rewrite of the session file, if there is one.
+    fn changed(&self) {
+        if let Some((_, changed)) = &self.session {
+            changed.notify_one();
+        }
+    }
+
+    fn snapshot(&self) -> Session {
+        let jobs = self
+            .state
+            .lock()
+            .jobs
+            .iter()
+            .map(|(&id, r)| r.to_session_job(id))
+            .collect();
+        Session { jobs }
+    }
+}
+
+impl Drop for ManagerInner {
+    fn drop(&mut self) {
+        // Wake the persist task so it sees the manager is gone.
+        self.changed();
+    }
+}
+
+/// Rewrite the session file after every change until the manager is dropped.
+async fn persist(inner: Weak<ManagerInner>, path: PathBuf, changed: Arc<Notify>) {
+    loop {
+        changed.notified().await;
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        let session = inner.snapshot();
+        let tx = inner.tx.clone();
+        drop(inner);
+        if let Err(e) = session.store(&path).await {
+            let _ = tx.send(ManagerEvent::SessionSaveError(e));
+        }
+    }
+}
+
+impl ManagerState {
+    fn has_room(&self) -> bool {
+        let active = self.jobs.values().filter(|r| r.run.is_some()).count();
+        self.jobs.values().any(Record::is_pending)
+            && (self.max_active == 0 || active < self.max_active)
+            && (self.max_workers == 0 || active < self.max_workers)
+    }
+
+    fn rebalance(&self) {
+        let runs: Vec<_> = self.jobs.values().filter_map(|r| r.run.as_ref()).collect();
+        let wanted: Vec<_> = runs.iter().map(|r| r.threads).collect();
+        for (run, threads) in runs.iter().zip(share_workers(&wanted, self.max_workers)) {
+            if run.auto {
+                run.handle.set_max_threads(threads);
+            } else {
+                run.handle.set_threads(threads);
+            }
+        }
+    }
+}
+
+impl Record {
+    /// Waiting to start: queued, or interrupted by a restart.
+    const fn is_pending(&self) -> bool {
+        self.run.is_none() && !self.status.is_finished()
+    }
+
+    fn to_session_job(&self, id: JobId) -> SessionJob {
+        SessionJob {
+            id,
+            status: self.status,
+            priority: self.job.priority,
+            url: self.job.url.clone(),
+            tmp_path: self.tmp_path.clone(),
+            config: self.job.config.clone(),
+        }
+    }
+
+    /// The pending job with the greatest key starts next: interrupted jobs
+    /// first, then by priority, then the oldest.
+    fn start_order(&self, id: JobId) -> (bool, i32, Reverse<JobId>) {
+        (
+            self.status != JobStatus::Queued,
+            self.job.priority,
+            Reverse(id),
+        )
+    }
+}
+
+/// Split `budget` workers over downloads wanting `wanted` workers each: evenly,
+/// with what a download does not want handed to the others, and at least one
+/// each. A `budget` of `0` gives every download what it wants.
+fn share_workers(wanted: &[usize], budget: usize) -> Vec<usize> {
+    if budget == 0 {
+        return wanted.to_vec();
+    }
+    let mut shares = vec![0; wanted.len()];
+    let mut order: Vec<_> = (0..wanted.len()).collect();
+    order.sort_by_key(|&i| wanted[i]);
+    let mut left = budget;
+    for (n, &i) in order.iter().enumerate() {
+        let fair = left / (order.len() - n);
+        shares[i] = wanted[i].min(fair).max(1);
+        left = left.saturating_sub(shares[i]);
+    }
+    shares
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_are_capped_and_redistributed() {
+        assert_eq!(share_workers(&[32, 32], 0), [32, 32], "no limit");
+        assert_eq!(share_workers(&[32, 32], 16), [8, 8]);
+        assert_eq!(share_workers(&[2, 32, 32], 16), [2, 7, 7]);
+        assert_eq!(share_workers(&[32, 4, 1], 64), [32, 4, 1], "all fit");
+        assert_eq!(share_workers(&[8, 8, 8], 2), [1, 1, 1], "at least one");
+        assert!(share_workers(&[], 8).is_empty());
+    }
+}
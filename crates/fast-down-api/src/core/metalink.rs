@@ -0,0 +1,194 @@
+use crate::{PartialConfig, utils::build_header};
+use fast_down::{
+    UrlInfo,
+    fast_puller::build_client,
+    http::{HttpClient, HttpRequestBuilder},
+    metalink::{Metalink, MetalinkError, MetalinkFile},
+};
+use inherit_config::ConfigLayer;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Where to read a `.meta4` document from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetalinkSource {
+    /// Fetched with the headers, proxy and TLS settings of the base config.
+    Url(Url),
+    File(PathBuf),
+}
+
+/// Why [`load_metalink`] produced no jobs.
+#[derive(Debug, thiserror::Error)]
+pub enum MetalinkLoadError {
+    #[error("failed to build HTTP client: {0}")]
+    BuildClient(#[from] reqwest::Error),
+    #[error("failed to fetch metalink: {0}")]
+    Fetch(String),
+    #[error("failed to read metalink file: {0}")]
+    Read(#[from] std::io::Error),
+    #[error("invalid metalink: {0}")]
+    Parse(#[from] MetalinkError),
+}
+
+/// A download prepared from one `<file>` of a metalink: pass `url` and
+/// `config` to [`download`](crate::download).
+#[derive(Debug, Clone)]
+pub struct MetalinkJob {
+    /// The most preferred source.
+    pub url: Url,
+    /// The base config with the other sources as
+    /// [`mirrors`](crate::Config::mirrors), the file's name, and its hash as
+    /// [`expected_digest`](crate::Config::expected_digest) — or its piece hashes
+    /// as [`expected_pieces`](crate::Config::expected_pieces) when it lists no
+    /// usable whole-file hash — and its size as
+    /// [`expected_size`](crate::Config::expected_size). Values set in the base
+    /// config win.
+    pub config: PartialConfig,
+    pub file: MetalinkFile,
+}
+
+impl MetalinkJob {
+    /// Prepare the job of `file` on top of `base`. Sources in `location` (an
+    /// ISO 3166-1 alpha-2 country code) are preferred among equal priorities.
+    ///
+    /// # Panics
+    /// Panics if `file` has no URL, which [`Metalink::parse`] never returns.
+    #[must_use]
+    pub fn new(file: MetalinkFile, base: &PartialConfig, location: Option<&str>) -> Self {
+        let location = location.map(str::to_ascii_lowercase);
+        let mut urls = file
+            .sorted_urls(location.as_deref())
+            .into_iter()
+            .map(|u| u.url.clone());
+        let url = urls.next().expect("parsed metalink files have a URL");
+        let mut config = base.clone();
+        let mut mirrors: Vec<_> = urls.collect();
+        mirrors.extend(config.mirrors.take().unwrap_or_default());
+        config.mirrors = Some(mirrors);
+        let name = Path::new(&file.name);
+        if config.filename.is_none()
+            && let Some(filename) = name.file_name()
+        {
+            config.filename = Some(filename.to_string_lossy().into_owned());
+            config.parse_filename = Some(false);
+            if let Some(parent) = name.parent().filter(|p| !p.as_os_str().is_empty()) {
+                let save_dir = config.save_dir.take().unwrap_or_else(|| PathBuf::from("."));
+                config.save_dir = Some(save_dir.join(parent));
+            }
+        }
+        if !matches!(config.expected_size, Some(Some(_))) {
+            config.expected_size = Some(file.size);
+        }
+        // The listed files are downloaded as they are, even if one is itself
+        // named `.meta4`.
+        config.follow_metalink = Some(false);
+        if !matches!(config.expected_digest, Some(Some(_))) {
+            match file.best_hash() {
+                Some(digest) => config.expected_digest = Some(Some(digest.clone())),
+                None if !matches!(config.expected_pieces, Some(Some(_))) => {
+                    config.expected_pieces = Some(file.pieces.clone());
+                }
+                None => {}
+            }
+        }
+        Self { url, config, file }
+    }
+}
+
+/// Whether the prefetched `info` is a `.meta4` document: served as
+/// `application/metalink4+xml`, or named `*.meta4`.
+#[must_use]
+pub fn is_metalink(info: &UrlInfo) -> bool {
+    let content_type = info
+        .content_type
+        .as_deref()
+        .and_then(|t| t.split(';').next())
+        .map(str::trim);
+    content_type.is_some_and(|t| t.eq_ignore_ascii_case("application/metalink4+xml"))
+        || has_meta4_extension(info.final_url.path())
+        || has_meta4_extension(&info.raw_name)
+}
+
+fn has_meta4_extension(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("meta4"))
+}
+
+/// Read a `.meta4` document and prepare one [`MetalinkJob`] per file it lists,
+/// in document order. Sources in `location` are preferred, as in
+/// [`MetalinkJob::new`].
+///
+/// # Errors
+/// Returns [`MetalinkLoadError`] when the document cannot be fetched, read or
+/// parsed.
+pub async fn load_metalink(
+    source: MetalinkSource,
+    base: &PartialConfig,
+    location: Option<&str>,
+) -> Result<Vec<MetalinkJob>, MetalinkLoadError> {
+    let xml = match source {
+        MetalinkSource::File(path) => tokio::fs::read_to_string(path).await?,
+        MetalinkSource::Url(url) => {
+            let config = base.clone().build();
+            let client = build_client(
+                build_header(&config.headers),
+                config.proxy.as_deref(),
+                config.accept_invalid_certs,
+                config.accept_invalid_hostnames,
+                config.cookie_store,
+                config.local_address.first().copied(),
+                config.max_redirects,
+                config.resolver().as_ref(),
+            )?;
+            let resp = client
+                .get(url, None)
+                .send()
+                .await
+                .map_err(|(e, _)| MetalinkLoadError::Fetch(e.to_string()))?;
+            read_response(resp).await?
+        }
+    };
+    parse_jobs(&xml, base, location)
+}
+
+/// Prepare the jobs of the metalink document in the body of `resp`, e.g. the
+/// prefetch response of a URL [`is_metalink`] accepts.
+///
+/// # Errors
+/// Returns [`MetalinkLoadError`] when the body cannot be read or parsed.
+pub async fn expand_response(
+    resp: reqwest::Response,
+    base: &PartialConfig,
+    location: Option<&str>,
+) -> Result<Vec<MetalinkJob>, MetalinkLoadError> {
+    let xml = read_response(resp).await?;
+    parse_jobs(&xml, base, location)
+}
+
+/// The body of `resp`; an error page is a failed fetch, not a bad document.
+async fn read_response(resp: reqwest::Response) -> Result<String, MetalinkLoadError> {
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(MetalinkLoadError::Fetch(format!(
+            "HTTP {status} from {}",
+            resp.url()
+        )));
+    }
+    resp.text()
+        .await
+        .map_err(|e| MetalinkLoadError::Fetch(e.to_string()))
+}
+
+fn parse_jobs(
+    xml: &str,
+    base: &PartialConfig,
+    location: Option<&str>,
+) -> Result<Vec<MetalinkJob>, MetalinkLoadError> {
+    let metalink = Metalink::parse(xml)?;
+    Ok(metalink
+        .files
+        .into_iter()
+        .map(|file| MetalinkJob::new(file, base, location))
+        .collect())
+}
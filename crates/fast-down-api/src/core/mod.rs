@@ -1,7 +1,13 @@
 mod download;
+mod manager;
+mod metalink;
 mod prefetch;
+mod session;
 mod state;
 
 pub use download::*;
+pub use manager::*;
+pub use metalink::*;
 pub use prefetch::*;
+pub use session::*;
 pub use state::*;
@@ -1,6 +1,11 @@
 use crate::{Config, Event, Tx, tx_err, utils::build_header};
-use fast_down::{UrlInfo, fast_puller::build_client, http::Prefetch};
+use fast_down::{
+    FileId, UrlInfo, Verbosity,
+    fast_puller::build_client,
+    http::{MirrorMismatch, Prefetch, StatusClass, StatusCodeError, check_mirror},
+};
 use reqwest::Response;
+use tokio::task::JoinSet;
 use url::Url;
 
 pub async fn prefetch(url: &Url, config: &Config, tx: &Tx) -> Option<(UrlInfo, Response)> {
@@ -12,6 +17,7 @@ pub async fn prefetch(url: &Url, config: &Config, tx: &Tx) -> Option<(UrlInfo, R
         config.cookie_store,
         config.local_address.first().copied(),
         config.max_redirects,
+        config.resolver().as_ref(),
     );
     let client = tx_err!(client, tx, BuildClientError, None);
     let mut retry_count = 0;
@@ -22,9 +28,10 @@ pub async fn prefetch(url: &Url, config: &Config, tx: &Tx) -> Option<(UrlInfo, R
                 break Some(t);
             }
             Err((e, t)) => {
+                let permanent = e.status_class() == Some(StatusClass::Permanent);
                 let _ = tx.send(Event::PrefetchError(e));
                 retry_count += 1;
-                if retry_count >= config.retry_times {
+                if permanent || retry_count >= config.retry_times {
                     return None;
                 }
                 tokio::time::sleep(t.unwrap_or(config.retry_gap)).await;
@@ -33,6 +40,66 @@ pub async fn prefetch(url: &Url, config: &Config, tx: &Tx) -> Option<(UrlInfo, R
     }
 }
 
+/// Prefetch every URL of `config.mirrors` once and keep the ones serving the
+/// same file as `info`, in their configured order.
+///
+/// Each mirror gets one attempt of at most `pull_timeout`; a mirror that fails
+/// it or does not pass [`check_mirror`] is reported as
+/// [`Event::MirrorRejected`] and left out. A differing [`FileId`] is tolerated
+/// when the file is verified against `expected_digest` or `expected_pieces`,
+/// since independent servers rarely agree on `ETag`s. Returns the final URL and
+/// [`FileId`] of every accepted mirror.
+pub async fn prefetch_mirrors(info: &UrlInfo, config: &Config, tx: &Tx) -> Vec<(Url, FileId)> {
+    if config.mirrors.is_empty() || !info.fast_download {
+        return Vec::new();
+    }
+    let client = build_client(
+        build_header(&config.headers),
+        config.proxy.as_deref(),
+        config.accept_invalid_certs,
+        config.accept_invalid_hostnames,
+        config.cookie_store,
+        config.local_address.first().copied(),
+        config.max_redirects,
+        config.resolver().as_ref(),
+    );
+    let client = tx_err!(client, tx, BuildClientError, Vec::new());
+    let mut probes = JoinSet::new();
+    for (i, url) in config.mirrors.iter().cloned().enumerate() {
+        let client = client.clone();
+        let pull_timeout = config.pull_timeout;
+        probes.spawn(async move {
+            let probe = tokio::time::timeout(pull_timeout, client.prefetch(url.clone())).await;
+            (i, url, probe)
+        });
+    }
+    let hash_checked = config.expected_digest.is_some() || config.expected_pieces.is_some();
+    let mut accepted = Vec::new();
+    while let Some(Ok((i, url, probe))) = probes.join_next().await {
+        let checked = match probe {
+            Ok(Ok((mirror, _))) => match check_mirror(info, &mirror) {
+                Ok(()) => Ok((mirror.final_url, mirror.file_id)),
+                Err(MirrorMismatch::FileId { .. }) if hash_checked => {
+                    Ok((mirror.final_url, mirror.file_id))
+                }
+                Err(e) => Err(e.into()),
+            },
+            Ok(Err((e, _))) => Err(anyhow::anyhow!("prefetch failed: {e}")),
+            Err(_) => Err(anyhow::anyhow!("prefetch timed out")),
+        };
+        match checked {
+            Ok(mirror) => accepted.push((i, mirror)),
+            Err(e) => {
+                if config.verbosity.includes(Verbosity::Errors) {
+                    let _ = tx.send(Event::MirrorRejected(url, e));
+                }
+            }
+        }
+    }
+    accepted.sort_by_key(|(i, _)| *i);
+    accepted.into_iter().map(|(_, mirror)| mirror).collect()
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::unwrap_used)]
@@ -178,4 +245,47 @@ mod tests {
             "prefetch must emit Event::Prefetch on the success path"
         );
     }
+
+    #[tokio::test]
+    async fn prefetch_does_not_retry_permanent_status() {
+        // A 404 will not go away by asking again, so `prefetch` must give up after
+        // the first attempt regardless of `retry_times`.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let io = TokioIo::new(stream);
+                tokio::spawn(async move {
+                    let service = service_fn(|_req: Request<Incoming>| async {
+                        let resp = Response::builder()
+                            .status(StatusCode::NOT_FOUND)
+                            .body(Full::new(Bytes::new()))
+                            .unwrap();
+                        Ok::<_, Infallible>(resp)
+                    });
+                    let _ = http1::Builder::new().serve_connection(io, service).await;
+                });
+            }
+        });
+
+        let url = Url::parse(&format!("http://{addr}/missing.bin")).unwrap();
+        let config = Config {
+            retry_times: 5,
+            retry_gap: Duration::ZERO,
+            ..Default::default()
+        };
+        let (tx, rx) = create_channel();
+        let result = prefetch(&url, &config, &tx).await;
+        assert!(result.is_none());
+        drop(tx);
+        let mut errors = 0;
+        while let Ok(e) = rx.recv().await {
+            if let Event::PrefetchError(e) = e {
+                assert_eq!(e.status_code(), Some(404));
+                errors += 1;
+            }
+        }
+        assert_eq!(errors, 1, "a permanent status must not be retried");
+    }
 }
@@ -0,0 +1,145 @@
+//! The session file a [`DownloadManager`](crate::DownloadManager) persists.
+
+use crate::{JobId, PartialConfig, StateError};
+use path_helper::tokio::safe_replace;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use url::Url;
+
+/// Where a job of a [`DownloadManager`](crate::DownloadManager) stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// Waiting for a free slot.
+    Queued,
+    /// Downloading. Read back from a session file, the download was
+    /// interrupted and is resumed on restore.
+    Running,
+    /// Started, then paused. Restored like [`JobStatus::Running`], and paused
+    /// again.
+    Paused,
+    Completed,
+    Failed,
+    /// Cancelled through [`DownloadManager::cancel`](crate::DownloadManager::cancel).
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Whether the job is over and is not run again on restore.
+    #[must_use]
+    pub const fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// One job of a [`Session`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionJob {
+    pub id: JobId,
+    pub status: JobStatus,
+    pub priority: i32,
+    pub url: Url,
+    /// The `.part` file, once the download got that far; an interrupted job
+    /// is [`resume`](crate::resume)d from it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmp_path: Option<PathBuf>,
+    #[serde(default)]
+    pub config: PartialConfig,
+}
+
+/// Every job of a [`DownloadManager`](crate::DownloadManager), as written to
+/// its session file.
+///
+/// The file is a TOML list of [`SessionJob`]s. Together with the `.fd` state
+/// of each download it lets a restarted process pick every job up again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default)]
+    pub jobs: Vec<SessionJob>,
+}
+
+impl Session {
+    /// Read a session file. A missing file is an empty session.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or deserialized.
+    #[allow(clippy::result_large_err)]
+    pub async fn load(path: &Path) -> Result<Self, StateError> {
+        match fs::read(path).await {
+            Ok(bytes) => Ok(toml::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(StateError::Open(e)),
+        }
+    }
+
+    /// Atomically replace the session file with this session.
+    ///
+    /// # Errors
+    /// Returns an error if serializing or writing the session fails.
+    #[allow(clippy::result_large_err)]
+    pub async fn store(&self, path: &Path) -> Result<(), StateError> {
+        let serialized = toml::to_string_pretty(self)?;
+        safe_replace(path, serialized.as_bytes())
+            .await
+            .map_err(StateError::Save)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn session_round_trips_through_toml() {
+        let dir = std::env::temp_dir().join(format!("fast_down_session_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("session.toml");
+        assert!(Session::load(&path).await.unwrap().jobs.is_empty());
+
+        let session = Session {
+            jobs: vec![
+                SessionJob {
+                    id: 3,
+                    status: JobStatus::Paused,
+                    priority: -1,
+                    url: Url::parse("https://example.com/a.bin").unwrap(),
+                    tmp_path: Some(dir.join("a.bin.part")),
+                    config: PartialConfig {
+                        threads: Some(4),
+                        downloaded_chunk: Some(vec![0..10, 20..30]),
+                        ..Default::default()
+                    },
+                },
+                SessionJob {
+                    id: 4,
+                    status: JobStatus::Queued,
+                    priority: 0,
+                    url: Url::parse("https://example.com/b.bin").unwrap(),
+                    tmp_path: None,
+                    config: PartialConfig::default(),
+                },
+            ],
+        };
+        session.store(&path).await.unwrap();
+        let loaded = Session::load(&path).await.unwrap();
+        assert_eq!(loaded.jobs.len(), 2);
+        let [a, b] = &loaded.jobs[..] else {
+            unreachable!()
+        };
+        assert_eq!((a.id, a.status, a.priority), (3, JobStatus::Paused, -1));
+        assert_eq!(a.tmp_path, session.jobs[0].tmp_path);
+        assert_eq!(a.config.threads, Some(4));
+        assert_eq!(a.config.downloaded_chunk, Some(vec![0..10, 20..30]));
+        assert_eq!((b.status, b.tmp_path.as_ref()), (JobStatus::Queued, None));
+        assert!(!JobStatus::Paused.is_finished() && JobStatus::Cancelled.is_finished());
+
+        std::fs::write(&path, "jobs = 1").unwrap();
+        assert!(matches!(
+            Session::load(&path).await,
+            Err(StateError::Decode(_))
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{Config, PartialConfig};
-use fast_down::{FileId, ProgressEntry, UrlInfo};
+use fast_down::{Digest, FileId, ProgressEntry, UrlInfo};
 use inherit_config::{ConfigLayer, InheritConfig};
 use parking_lot::Mutex;
 use path_helper::tokio::safe_replace;
@@ -110,6 +110,12 @@ pub struct DownloadStateInner {
     #[config(partial_attr(serde(with = "humantime_serde::option")))]
     #[config(partial_attr(serde(default)))]
     pub elapsed: Duration,
+    /// Whole-file digests the server advertised (`UrlInfo.digests`), checked
+    /// against the finished `.part` file before it is renamed.
+    ///
+    /// Kept across resumes whose prefetch advertises none; an older `.fd`
+    /// without this field reads back as `None` and skips the check.
+    pub digests: Vec<Digest>,
 }
 
 /// On-disk state for an in-progress download, backing resume support.
@@ -171,6 +177,7 @@ impl DownloadState {
                 config: Some(config.clone()),
                 size: Some(url_info.size),
                 elapsed: Some(Duration::ZERO),
+                digests: Some(url_info.digests.clone()),
             })),
             is_dirty: Arc::new(AtomicBool::new(true)),
             config_path: config_path.to_path_buf(),
@@ -309,6 +316,36 @@ impl DownloadState {
         self.update(|inner| inner.config.get_or_insert_default().merge_progress(range));
     }
 
+    /// Drop `ranges` from the recorded progress, so the next run pulls them
+    /// again, e.g. after the bytes on disk failed verification. Marks the
+    /// state dirty.
+    pub fn forget_progress(&self, ranges: &[ProgressEntry]) {
+        self.update(|inner| {
+            let Some(chunks) = inner
+                .config
+                .as_mut()
+                .and_then(|c| c.downloaded_chunk.as_mut())
+            else {
+                return;
+            };
+            for cut in ranges {
+                *chunks = chunks
+                    .iter()
+                    .flat_map(|c| {
+                        if c.end <= cut.start || cut.end <= c.start {
+                            return [Some(c.clone()), None];
+                        }
+                        [
+                            (c.start < cut.start).then_some(c.start..cut.start),
+                            (cut.end < c.end).then_some(cut.end..c.end),
+                        ]
+                    })
+                    .flatten()
+                    .collect();
+            }
+        });
+    }
+
     /// Fold a freshly-built [`PartialConfig`] into this loaded state for resume.
     ///
     /// This is the key bridge that preserves download progress across a resume:
@@ -345,7 +382,8 @@ impl DownloadState {
     ///    itself read back from the `.fd` (a `resume(None)`), this assignment is a
     ///    no-op.
     /// 2. **The content identity headers** (etag/last-modified/size), refreshed to
-    ///    the fresh server values from the current prefetch.
+    ///    the fresh server values from the current prefetch, along with the
+    ///    advertised digests when the server sent any.
     ///
     /// The redirect target (`info.final_url`) is deliberately **never** persisted:
     /// such links are usually temporary signed/CDN URLs that expire, so writing one
@@ -358,6 +396,9 @@ impl DownloadState {
             inner.etag = Some(info.file_id.etag.clone());
             inner.last_modified = Some(info.file_id.last_modified.clone());
             inner.size = Some(info.size);
+            if !info.digests.is_empty() {
+                inner.digests = Some(info.digests.clone());
+            }
         });
     }
 
@@ -402,10 +443,26 @@ mod tests {
             final_url: url.clone(),
             file_id: FileId::new(Some("etag-1"), None),
             content_type: Some("application/octet-stream".to_string()),
+            digests: Vec::new(),
         };
         DownloadState::new(&url, &url_info, &PartialConfig::default(), path)
     }
 
+    #[test]
+    fn forget_progress_cuts_ranges_out() {
+        let state = make_state(Path::new("unused.fd"));
+        state.forget_progress(std::slice::from_ref(&(0..10)));
+        assert!(state.get_progress().is_empty(), "nothing recorded yet");
+        state.merge_progress(0..100);
+        state.merge_progress(200..300);
+        let _ = state.take_dirty();
+        state.forget_progress(&[50..60, 250..400]);
+        assert_eq!(state.get_progress(), [0..50, 60..100, 200..250]);
+        assert!(state.take_dirty());
+        state.forget_progress(std::slice::from_ref(&(0..1024)));
+        assert!(state.get_progress().is_empty());
+    }
+
     #[tokio::test]
     async fn elapsed_stored_as_human_readable_string_and_round_trips() {
         let path = std::env::temp_dir().join(format!(
@@ -561,6 +618,7 @@ mod tests {
             final_url: Url::parse("https://example.com/file.bin").unwrap(),
             file_id: FileId::new(etag, None),
             content_type: Some("application/octet-stream".to_string()),
+            digests: Vec::new(),
         }
     }
 
@@ -632,6 +690,7 @@ mod tests {
             final_url: Url::parse("https://cdn.example.com/signed/token-abc/file.bin").unwrap(),
             file_id: FileId::new(Some("etag-2"), Some("Mon, 02 Aug 2026 00:00:00 GMT")),
             content_type: Some("application/octet-stream".to_string()),
+            digests: Vec::new(),
         };
 
         // Caller passes the new initial URL: it must take priority over the stored one.
@@ -658,5 +717,6 @@ mod tests {
             "refresh must update last-modified"
         );
         assert_eq!(inner.size, Some(2048), "refresh must update size");
+        drop(inner);
     }
 }
@@ -1,6 +1,11 @@
-use crate::{PartialConfig, StateError};
-use fast_down::{ProgressEntry, UrlInfo, WorkerId, reqwest::ReqwestResponseError};
+use crate::{MetalinkJob, MetalinkLoadError, PartialConfig, StateError};
+use fast_down::{
+    ChunkMap, Digest, DownloadOutcome, ProgressEntry, UrlInfo, Verbosity, WorkerId, WorkerSnapshot,
+    reqwest::ReqwestResponseError,
+};
+use serde::{Deserialize, Serialize};
 use std::{path::PathBuf, time::Duration};
+use url::Url;
 
 /// Events emitted by a download run, consumed through the crossfire channel
 /// returned by [`crate::create_channel`].
@@ -11,7 +16,8 @@ use std::{path::PathBuf, time::Duration};
 /// ([`Event::Progress`]), resume ([`Event::Resumed`], [`Event::ResumeError`]),
 /// and completion ([`Event::Renamed`]). Error variants (`*Error`) report failures
 /// without aborting the stream, so a consumer can decide whether to retry,
-/// cancel, or surface them in a UI.
+/// cancel, or surface them in a UI. Once the engine stage is reached, the run
+/// ends with exactly one [`Event::Completed`] or [`Event::Failed`].
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum Event {
@@ -24,6 +30,23 @@ pub enum Event {
     ///
     /// Fatal for the download: without [`UrlInfo`] the engine cannot plan ranges.
     PrefetchError(ReqwestResponseError),
+    /// A URL of [`crate::Config::mirrors`] could not be prefetched or does not
+    /// serve the same file (size, identity, range support) as the primary URL.
+    ///
+    /// Not fatal: the download continues without that mirror.
+    MirrorRejected(Url, anyhow::Error),
+    /// The server reports a size other than [`crate::Config::expected_size`].
+    ///
+    /// Fatal for the download: nothing is written.
+    SizeMismatch { expected: u64, actual: u64 },
+    /// The URL is a metalink document and [`crate::Config::follow_metalink`] is
+    /// set. Carries one job per file it lists, in document order; the run ends
+    /// without saving anything, so download the jobs instead.
+    Metalink(Vec<MetalinkJob>),
+    /// The metalink document could not be read or parsed.
+    ///
+    /// Fatal for the download, like [`Event::PrefetchError`].
+    MetalinkError(MetalinkLoadError),
     /// Failed to compute the output / `.part` / `.fd` paths.
     ///
     /// For example the target directory is not writable or the file name is
@@ -43,6 +66,29 @@ pub enum Event {
     /// The success counterpart is [`Event::Renamed`]. The bytes are already on
     /// disk under the `.part` name, so they can still be resumed or retried.
     RenameFailed(std::io::Error),
+    /// The `.part` file matches [`crate::Config::expected_digest`] and the
+    /// digest the server advertised, whichever are set. Sent right before the
+    /// rename; carries the computed digest.
+    Verified(Digest),
+    /// The `.part` file does not match [`crate::Config::expected_digest`].
+    ///
+    /// Fatal for the download: the file is not renamed. The `.part` and `.fd`
+    /// files are left in place for inspection, but the progress in the `.fd`
+    /// is dropped, so a rerun pulls the whole file again.
+    DigestMismatch { expected: Digest, actual: Digest },
+    /// The `.part` file does not match the digest the server advertised for it
+    /// (see [`crate::Config::verify_server_digest`]).
+    ///
+    /// Fatal, like [`Event::DigestMismatch`]: the file is not renamed.
+    IntegrityError { advertised: Digest, actual: Digest },
+    /// The listed pieces of the `.part` file do not match
+    /// [`crate::Config::expected_pieces`]. Fatal, like [`Event::DigestMismatch`],
+    /// but only the bad pieces are dropped from the progress in the `.fd`, so a
+    /// rerun pulls just them again.
+    PieceMismatch(Vec<usize>),
+    /// Reading the `.part` file back to finish its digest or check its pieces
+    /// failed. Fatal, like [`Event::DigestMismatch`].
+    VerifyError(std::io::Error),
     /// Emitted after the `.part` file is successfully renamed to its final
     /// destination. Carries the actual landing path, which in unique mode may
     /// differ from the originally-planned name (e.g. `xxx (1).mp4`) when the
@@ -77,6 +123,14 @@ pub enum Event {
     PullError(WorkerId, anyhow::Error),
     /// Worker `id`'s fetch exceeded its time budget and was aborted.
     PullTimeout(WorkerId),
+    /// Worker `id` pulled slower than `Config::slow_thread_speed` and
+    /// reconnects from where it stopped.
+    PullSlow(WorkerId),
+    /// The server answered `429` / `503`: no worker connects for this long,
+    /// and for as long again afterwards only half as many reconnect. Sent
+    /// once per cooldown, so a UI can show the download as throttled until it
+    /// ends.
+    Throttled(Duration),
     /// Worker `id` pulled some bytes into memory.
     ///
     /// `ProgressEntry` describes the contiguous range that just arrived from the
@@ -109,17 +163,90 @@ pub enum Event {
     /// forwarding, or a slow consumer (the channel is unbounded). One final
     /// `Progress` is sent when the run ends (success, cancellation, or error).
     Progress(ProgressSample),
+    /// The workers running right now: the range each has left, its speed,
+    /// retries and whether it races another worker. Emitted on the
+    /// [`crate::Config::progress_emit_gap`] cadence while the engine runs, so
+    /// a "connections" view need not rebuild it from the per-worker events.
+    Workers(Vec<WorkerSnapshot>),
+    /// The worker count picked for a `threads = "auto"` download: sent once
+    /// when the engine starts and again whenever the tuner grows or shrinks
+    /// it. See [`crate::Config::threads`].
+    Concurrency(usize),
     /// The sink is being flushed and synced to the `.part` file.
     Flushing,
     /// Flushing / syncing the sink failed.
     FlushError(anyhow::Error),
+    /// Worker `id` hit an error that retrying cannot fix, such as an HTTP `404`
+    /// or `403`, or failed more than
+    /// [`max_consecutive_errors`](crate::Config::max_consecutive_errors) times
+    /// in a row.
+    ///
+    /// Fatal for the download: the engine stops every worker, and the `.part`
+    /// and `.fd` files are left in place without renaming.
+    PullFailed(WorkerId, anyhow::Error),
+    /// Worker `id` timed out more than
+    /// [`max_consecutive_errors`](crate::Config::max_consecutive_errors) times
+    /// in a row. Fatal, like [`Event::PullFailed`].
+    PullTimeoutFailed(WorkerId),
+    /// Writing `ProgressEntry` failed more than
+    /// [`max_consecutive_errors`](crate::Config::max_consecutive_errors) times
+    /// in a row. Fatal, like [`Event::PullFailed`].
+    PushFailed(WorkerId, ProgressEntry, anyhow::Error),
     /// Worker `id` completed its assigned range and exited.
     Finished(WorkerId),
+    /// The file was downloaded and renamed into place. Always the last event of
+    /// a successful run, right after [`Event::Renamed`].
+    Completed(DownloadOutcome),
+    /// The run ended without producing the file: the engine failed or was
+    /// cancelled, or a step right before or after it failed. Always the last
+    /// event of a run that got past prefetch but did not complete.
+    ///
+    /// `error` summarizes why; the detailed `*Error` / `*Failed` event was sent
+    /// before. `outcome` is `None` when the run stopped before the engine
+    /// started; when the engine completed but a later step failed, its status
+    /// is [`DownloadStatus::Failed`](fast_down::DownloadStatus::Failed) with
+    /// the message of `error`. The `.part` and `.fd` files are left in place
+    /// for a resume.
+    Failed {
+        error: anyhow::Error,
+        outcome: Option<DownloadOutcome>,
+    },
+}
+
+impl Event {
+    /// The lowest [`crate::Config::verbosity`] a download must run at to emit
+    /// this event.
+    ///
+    /// Prefetch, setup, verification and the end of the run are lifecycle
+    /// events, like the fatal errors. [`Event::MirrorRejected`] and
+    /// [`Event::StateSaveError`] are errors, [`Event::Progress`],
+    /// [`Event::Workers`] and [`Event::Concurrency`] are progress,
+    /// and the per-chunk [`Event::PullProgress`], [`Event::Pushing`] and
+    /// [`Event::PushProgress`] are trace. Engine events keep the level of
+    /// [`fast_down::Event::verbosity`] otherwise.
+    #[must_use]
+    pub const fn verbosity(&self) -> Verbosity {
+        match self {
+            Self::MirrorRejected(..)
+            | Self::StateSaveError(_)
+            | Self::PullError(..)
+            | Self::PullTimeout(_)
+            | Self::PullSlow(_)
+            | Self::Throttled(_)
+            | Self::PushError(..)
+            | Self::FlushError(_) => Verbosity::Errors,
+            Self::Progress(_) | Self::Workers(_) | Self::Concurrency(_) => Verbosity::Progress,
+            Self::PullProgress(..) | Self::Pushing(..) | Self::PushProgress(_) => Verbosity::Trace,
+            _ => Verbosity::Lifecycle,
+        }
+    }
 }
 
 /// Computed aggregate view of the current download progress, carried by
 /// [`Event::Progress`].
-#[derive(Debug, Clone)]
+///
+/// Serializes with its durations as human-readable strings, like the config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProgressSample {
     /// Already-written byte ranges — the same source of truth used for resume
     /// bookkeeping (normalized and de-duplicated).
@@ -146,6 +273,7 @@ pub struct ProgressSample {
     pub total: u64,
     /// Total active download time accumulated across all resume runs,
     /// persisted in the `.fd` state so a resume continues the clock.
+    #[serde(with = "humantime_serde")]
     pub elapsed: Duration,
     /// Estimated time remaining, computed as `(total - downloaded) / rate`
     /// where `rate` is the smoothed recent `bps` (preferred: it reflects the
@@ -153,5 +281,11 @@ pub struct ProgressSample {
     /// different speed), falling back to `avg_bps` while `bps` is still zero.
     /// `None` until a rate can be measured (the very first emit, or a stalled
     /// connection) and `Some(Duration::ZERO)` once `downloaded == total`.
+    #[serde(with = "humantime_serde")]
     pub eta: Option<Duration>,
+    /// `progress` and the ranges the workers are pulling, resampled into
+    /// `Config::chunk_map_cells` cells for a segmented progress bar. `None`
+    /// while that is `0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<ChunkMap>,
 }
@@ -3,11 +3,17 @@
 mod config;
 mod core;
 mod event;
+mod record;
+#[cfg(feature = "rpc")]
+mod rpc;
 pub(crate) mod utils;
 
 pub use config::*;
 pub use core::*;
 pub use event::*;
+pub use record::*;
+#[cfg(feature = "rpc")]
+pub use rpc::*;
 
 pub use fast_down;
 
@@ -0,0 +1,661 @@
+use crate::{Event, MetalinkLoadError, PartialConfig, ProgressSample, Rx, StateError};
+use fast_down::{
+    Digest, DownloadOutcome, DownloadStatus, ProgressEntry, UrlInfo, WorkerId, WorkerSnapshot,
+    http::{HttpError, MirrorError},
+    reqwest::{ReqwestResponseError, SmartRedirectClient},
+};
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::{io, path::PathBuf, time::Duration};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use url::Url;
+
+/// A serializable mirror of [`Event`], for shipping events to another process
+/// or logging them as JSON lines.
+///
+/// The variant is stored in an `event` field with a stable `snake_case` tag,
+/// e.g. `{"event":"pull_error","worker":3,"error":{…}}`. Errors become
+/// [`ErrorRecord`]s: their [`ErrorKind`], message and, for HTTP errors, the
+/// status, URL and headers of the response, captured as plain data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
+pub enum EventRecord {
+    Prefetch(UrlInfo),
+    PrefetchError {
+        error: ErrorRecord,
+    },
+    MirrorRejected {
+        url: Url,
+        error: ErrorRecord,
+    },
+    SizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    Metalink {
+        jobs: Vec<MetalinkJobRecord>,
+    },
+    MetalinkError {
+        error: ErrorRecord,
+    },
+    GenPathError {
+        error: ErrorRecord,
+    },
+    StateSaveError {
+        error: ErrorRecord,
+    },
+    BuildClientError {
+        error: ErrorRecord,
+    },
+    BuildPusherError {
+        error: ErrorRecord,
+    },
+    RenameFailed {
+        error: ErrorRecord,
+    },
+    Verified {
+        digest: Digest,
+    },
+    DigestMismatch {
+        expected: Digest,
+        actual: Digest,
+    },
+    IntegrityError {
+        advertised: Digest,
+        actual: Digest,
+    },
+    PieceMismatch {
+        pieces: Vec<usize>,
+    },
+    VerifyError {
+        error: ErrorRecord,
+    },
+    Renamed {
+        path: PathBuf,
+    },
+    Start {
+        tmp_path: PathBuf,
+        config_path: PathBuf,
+        parsed_config: PartialConfig,
+    },
+    Resumed {
+        config_path: PathBuf,
+        progress: Vec<ProgressEntry>,
+        size: u64,
+    },
+    ResumeError {
+        error: ErrorRecord,
+    },
+    Pulling {
+        worker: WorkerId,
+    },
+    PullError {
+        worker: WorkerId,
+        error: ErrorRecord,
+    },
+    PullTimeout {
+        worker: WorkerId,
+    },
+    PullSlow {
+        worker: WorkerId,
+    },
+    Throttled {
+        #[serde(with = "humantime_serde")]
+        cooldown: Duration,
+    },
+    PullProgress {
+        worker: WorkerId,
+        range: ProgressEntry,
+    },
+    Pushing {
+        worker: WorkerId,
+        range: ProgressEntry,
+    },
+    PushError {
+        worker: WorkerId,
+        range: ProgressEntry,
+        error: ErrorRecord,
+    },
+    PushProgress {
+        range: ProgressEntry,
+    },
+    Progress(ProgressSample),
+    Workers {
+        workers: Vec<WorkerSnapshot>,
+    },
+    Concurrency {
+        threads: usize,
+    },
+    Flushing,
+    FlushError {
+        error: ErrorRecord,
+    },
+    PullFailed {
+        worker: WorkerId,
+        error: ErrorRecord,
+    },
+    PullTimeoutFailed {
+        worker: WorkerId,
+    },
+    PushFailed {
+        worker: WorkerId,
+        range: ProgressEntry,
+        error: ErrorRecord,
+    },
+    Finished {
+        worker: WorkerId,
+    },
+    Completed(OutcomeRecord),
+    Failed {
+        error: ErrorRecord,
+        outcome: Option<OutcomeRecord>,
+    },
+}
+
+impl From<&Event> for EventRecord {
+    #[allow(clippy::too_many_lines)]
+    fn from(event: &Event) -> Self {
+        match event {
+            Event::Prefetch(info) => Self::Prefetch(info.clone()),
+            Event::PrefetchError(e) => Self::PrefetchError { error: e.into() },
+            Event::MirrorRejected(url, e) => Self::MirrorRejected {
+                url: url.clone(),
+                error: e.into(),
+            },
+            Event::SizeMismatch { expected, actual } => Self::SizeMismatch {
+                expected: *expected,
+                actual: *actual,
+            },
+            Event::Metalink(jobs) => Self::Metalink {
+                jobs: jobs
+                    .iter()
+                    .map(|job| MetalinkJobRecord {
+                        url: job.url.clone(),
+                        config: job.config.clone(),
+                    })
+                    .collect(),
+            },
+            Event::MetalinkError(e) => Self::MetalinkError { error: e.into() },
+            Event::GenPathError(e) => Self::GenPathError { error: e.into() },
+            Event::StateSaveError(e) => Self::StateSaveError { error: e.into() },
+            Event::BuildClientError(e) => Self::BuildClientError { error: e.into() },
+            Event::BuildPusherError(e) => Self::BuildPusherError { error: e.into() },
+            Event::RenameFailed(e) => Self::RenameFailed { error: e.into() },
+            Event::Verified(digest) => Self::Verified {
+                digest: digest.clone(),
+            },
+            Event::DigestMismatch { expected, actual } => Self::DigestMismatch {
+                expected: expected.clone(),
+                actual: actual.clone(),
+            },
+            Event::IntegrityError { advertised, actual } => Self::IntegrityError {
+                advertised: advertised.clone(),
+                actual: actual.clone(),
+            },
+            Event::PieceMismatch(pieces) => Self::PieceMismatch {
+                pieces: pieces.clone(),
+            },
+            Event::VerifyError(e) => Self::VerifyError { error: e.into() },
+            Event::Renamed(path) => Self::Renamed { path: path.clone() },
+            Event::Start {
+                tmp_path,
+                config_path,
+                parsed_config,
+            } => Self::Start {
+                tmp_path: tmp_path.clone(),
+                config_path: config_path.clone(),
+                parsed_config: parsed_config.clone(),
+            },
+            Event::Resumed {
+                config_path,
+                progress,
+                size,
+            } => Self::Resumed {
+                config_path: config_path.clone(),
+                progress: progress.clone(),
+                size: *size,
+            },
+            Event::ResumeError(e) => Self::ResumeError { error: e.into() },
+            &Event::Pulling(worker) => Self::Pulling { worker },
+            Event::PullError(worker, e) => Self::PullError {
+                worker: *worker,
+                error: e.into(),
+            },
+            &Event::PullTimeout(worker) => Self::PullTimeout { worker },
+            &Event::PullSlow(worker) => Self::PullSlow { worker },
+            &Event::Throttled(cooldown) => Self::Throttled { cooldown },
+            Event::PullProgress(worker, range) => Self::PullProgress {
+                worker: *worker,
+                range: range.clone(),
+            },
+            Event::Pushing(worker, range) => Self::Pushing {
+                worker: *worker,
+                range: range.clone(),
+            },
+            Event::PushError(worker, range, e) => Self::PushError {
+                worker: *worker,
+                range: range.clone(),
+                error: e.into(),
+            },
+            Event::PushProgress(range) => Self::PushProgress {
+                range: range.clone(),
+            },
+            Event::Progress(sample) => Self::Progress(sample.clone()),
+            Event::Workers(workers) => Self::Workers {
+                workers: workers.clone(),
+            },
+            &Event::Concurrency(threads) => Self::Concurrency { threads },
+            Event::Flushing => Self::Flushing,
+            Event::FlushError(e) => Self::FlushError { error: e.into() },
+            Event::PullFailed(worker, e) => Self::PullFailed {
+                worker: *worker,
+                error: e.into(),
+            },
+            &Event::PullTimeoutFailed(worker) => Self::PullTimeoutFailed { worker },
+            Event::PushFailed(worker, range, e) => Self::PushFailed {
+                worker: *worker,
+                range: range.clone(),
+                error: e.into(),
+            },
+            &Event::Finished(worker) => Self::Finished { worker },
+            Event::Completed(outcome) => Self::Completed(outcome.into()),
+            Event::Failed { error, outcome } => Self::Failed {
+                error: error.into(),
+                outcome: outcome.as_ref().map(Into::into),
+            },
+        }
+    }
+}
+
+/// A job of [`EventRecord::Metalink`]: what [`download`](crate::download)
+/// needs to fetch one file of the metalink.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetalinkJobRecord {
+    pub url: Url,
+    pub config: PartialConfig,
+}
+
+/// What went wrong, as a stable category of [`ErrorRecord`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// A file system or other I/O error.
+    Io,
+    /// The request got no response: DNS, connect, TLS, timeout, etc.
+    Request,
+    /// The server answered with an error status.
+    Status,
+    /// The server asked to slow down with a `429` or `503`.
+    RateLimited,
+    /// Reading the response body failed, or it is not the expected file.
+    Body,
+    /// The `.fd` state file could not be decoded or encoded.
+    State,
+    /// The remote file changed since the `.fd` state was saved.
+    FileChanged,
+    /// The server does not support resumable downloads.
+    NotResumable,
+    /// A resume had no URL to continue from.
+    NoUrl,
+    /// Any other error.
+    Other,
+}
+
+/// An error, captured as plain data by [`EventRecord`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorRecord {
+    pub kind: ErrorKind,
+    /// The error and its causes, as `{:#}` formats them.
+    pub message: String,
+    /// The HTTP status of the response, when there was one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// The URL of the failed request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<Url>,
+    /// The headers of the response, in order; values that are not UTF-8 are
+    /// converted lossily.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<(String, String)>,
+}
+
+impl ErrorRecord {
+    const fn new(kind: ErrorKind, message: String) -> Self {
+        Self {
+            kind,
+            message,
+            status: None,
+            url: None,
+            headers: Vec::new(),
+        }
+    }
+
+    fn with_response(mut self, url: &Url, status: u16, headers: &HeaderMap) -> Self {
+        self.url = Some(url.clone());
+        self.status = Some(status);
+        self.headers = headers
+            .iter()
+            .map(|(name, value)| {
+                let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                (name.to_string(), value)
+            })
+            .collect();
+        self
+    }
+}
+
+impl From<&io::Error> for ErrorRecord {
+    fn from(e: &io::Error) -> Self {
+        Self::new(ErrorKind::Io, e.to_string())
+    }
+}
+
+impl From<&reqwest::Error> for ErrorRecord {
+    fn from(e: &reqwest::Error) -> Self {
+        let kind = if e.is_status() {
+            ErrorKind::Status
+        } else if e.is_body() || e.is_decode() {
+            ErrorKind::Body
+        } else {
+            ErrorKind::Request
+        };
+        let mut record = Self::new(kind, chain(e));
+        record.status = e.status().map(|s| s.as_u16());
+        record.url = e.url().cloned();
+        record
+    }
+}
+
+impl From<&ReqwestResponseError> for ErrorRecord {
+    fn from(e: &ReqwestResponseError) -> Self {
+        match e {
+            ReqwestResponseError::Request(e) => e.into(),
+            ReqwestResponseError::StatusCode(resp) => Self::new(ErrorKind::Status, e.to_string())
+                .with_response(resp.url(), resp.status().as_u16(), resp.headers()),
+            ReqwestResponseError::RateLimited(resp) => Self::new(
+                ErrorKind::RateLimited,
+                e.to_string(),
+            )
+            .with_response(resp.url(), resp.status().as_u16(), resp.headers()),
+        }
+    }
+}
+
+impl From<&StateError> for ErrorRecord {
+    fn from(e: &StateError) -> Self {
+        let message = e.to_string();
+        match e {
+            StateError::Open(_) | StateError::Save(_) => Self::new(ErrorKind::Io, message),
+            StateError::Decode(_) | StateError::Encode(_) => Self::new(ErrorKind::State, message),
+            StateError::FileChanged { .. } => Self::new(ErrorKind::FileChanged, message),
+            StateError::NotResumable(_, resp) => Self::new(ErrorKind::NotResumable, message)
+                .with_response(resp.url(), resp.status().as_u16(), resp.headers()),
+            StateError::NoUrl(_) => Self::new(ErrorKind::NoUrl, message),
+        }
+    }
+}
+
+impl From<&MetalinkLoadError> for ErrorRecord {
+    fn from(e: &MetalinkLoadError) -> Self {
+        match e {
+            MetalinkLoadError::BuildClient(e) => Self::from(e),
+            MetalinkLoadError::Fetch(_) => Self::new(ErrorKind::Request, e.to_string()),
+            MetalinkLoadError::Read(_) => Self::new(ErrorKind::Io, e.to_string()),
+            MetalinkLoadError::Parse(_) => Self::new(ErrorKind::Body, e.to_string()),
+        }
+    }
+}
+
+impl From<&HttpError<SmartRedirectClient>> for ErrorRecord {
+    fn from(e: &HttpError<SmartRedirectClient>) -> Self {
+        match e {
+            HttpError::Request(e)
+            | HttpError::Permanent(e)
+            | HttpError::Transient(e)
+            | HttpError::RateLimited(e) => e.into(),
+            HttpError::Chunk(_, resp) | HttpError::MismatchedBody(_, resp) => Self::new(
+                ErrorKind::Body,
+                e.to_string(),
+            )
+            .with_response(resp.url(), resp.status().as_u16(), resp.headers()),
+            HttpError::Irrecoverable => Self::new(ErrorKind::Other, e.to_string()),
+        }
+    }
+}
+
+impl From<&anyhow::Error> for ErrorRecord {
+    /// Recognizes the errors the engine wraps: pull errors of the HTTP puller,
+    /// I/O errors of the sink and the errors of the other `From` impls.
+    fn from(e: &anyhow::Error) -> Self {
+        let message = format!("{e:#}");
+        let http = e
+            .downcast_ref::<MirrorError<HttpError<SmartRedirectClient>>>()
+            .map(|e| &e.error)
+            .or_else(|| e.downcast_ref::<HttpError<SmartRedirectClient>>());
+        let record = http
+            .map(Self::from)
+            .or_else(|| e.downcast_ref::<ReqwestResponseError>().map(Self::from))
+            .or_else(|| e.downcast_ref::<reqwest::Error>().map(Self::from))
+            .or_else(|| e.downcast_ref::<StateError>().map(Self::from))
+            .or_else(|| e.downcast_ref::<io::Error>().map(Self::from))
+            .unwrap_or_else(|| Self::new(ErrorKind::Other, String::new()));
+        Self { message, ..record }
+    }
+}
+
+/// `e` and its causes, joined like `{:#}` joins those of an [`anyhow::Error`].
+fn chain(e: &dyn std::error::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+/// How a run ended, the plain-data form of [`DownloadOutcome`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutcomeRecord {
+    pub status: OutcomeStatus,
+    /// Why a failed run failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub bytes_pulled: u64,
+    pub bytes_pushed: u64,
+    #[serde(with = "humantime_serde")]
+    pub elapsed: Duration,
+}
+
+/// The status of an [`OutcomeRecord`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutcomeStatus {
+    Completed,
+    Aborted,
+    Failed,
+}
+
+impl From<&DownloadOutcome> for OutcomeRecord {
+    fn from(outcome: &DownloadOutcome) -> Self {
+        let (status, error) = match &outcome.status {
+            DownloadStatus::Completed => (OutcomeStatus::Completed, None),
+            DownloadStatus::Aborted => (OutcomeStatus::Aborted, None),
+            DownloadStatus::Failed(e) => (OutcomeStatus::Failed, Some(e.clone())),
+        };
+        Self {
+            status,
+            error,
+            bytes_pulled: outcome.bytes_pulled,
+            bytes_pushed: outcome.bytes_pushed,
+            elapsed: outcome.elapsed,
+        }
+    }
+}
+
+/// Writes [`Event`]s as newline-delimited JSON: one [`EventRecord`] per line,
+/// flushed right away so a reader on the other end sees events live.
+#[derive(Debug)]
+pub struct NdjsonWriter<W> {
+    writer: W,
+    line: Vec<u8>,
+}
+
+impl<W: AsyncWrite + Unpin> NdjsonWriter<W> {
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            line: Vec::new(),
+        }
+    }
+
+    /// Write one event as a JSON line.
+    ///
+    /// # Errors
+    /// Returns the error of the underlying writer.
+    pub async fn write(&mut self, event: &Event) -> io::Result<()> {
+        self.line.clear();
+        serde_json::to_writer(&mut self.line, &EventRecord::from(event))?;
+        self.line.push(b'\n');
+        self.writer.write_all(&self.line).await?;
+        self.writer.flush().await
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Write every event of `rx` to `writer` as a JSON line, until the download
+/// ends and the channel closes. Returns the number of events written.
+///
+/// Spawn it in place of the usual event loop to stream a download to a log
+/// file, a pipe or a socket; to look at the events too, call
+/// [`NdjsonWriter::write`] from your own loop instead.
+///
+/// # Errors
+/// Returns the first error of `writer`; the remaining events stay in `rx`.
+pub async fn write_ndjson<W>(rx: Rx, writer: W) -> io::Result<u64>
+where
+    W: AsyncWrite + Unpin + Send,
+{
+    let mut writer = NdjsonWriter::new(writer);
+    let mut written = 0;
+    while let Ok(event) = rx.recv().await {
+        writer.write(&event).await?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_channel;
+    use serde_json::json;
+
+    fn not_found() -> reqwest::Response {
+        let response = hyper::Response::builder()
+            .status(404)
+            .header("x-request-id", "abc")
+            .body(String::new())
+            .unwrap();
+        reqwest::Response::from(response)
+    }
+
+    #[test]
+    fn captures_http_errors_as_plain_data() {
+        let error = MirrorError {
+            mirror: 0,
+            error: HttpError::<SmartRedirectClient>::Permanent(ReqwestResponseError::StatusCode(
+                not_found(),
+            )),
+            exhausted: true,
+        };
+        let record = EventRecord::from(&Event::PullFailed(2, anyhow::anyhow!(error)));
+        let value = serde_json::to_value(&record).unwrap();
+        assert_eq!(value["event"], "pull_failed");
+        assert_eq!(value["worker"], 2);
+        assert_eq!(value["error"]["kind"], "status");
+        assert_eq!(value["error"]["status"], 404);
+        assert_eq!(value["error"]["headers"], json!([["x-request-id", "abc"]]));
+
+        let EventRecord::PullFailed { error, .. } = serde_json::from_value(value).unwrap() else {
+            panic!("tag must round-trip");
+        };
+        assert_eq!(error.kind, ErrorKind::Status);
+        assert!(error.message.contains("404"), "{}", error.message);
+
+        let io = io::Error::new(io::ErrorKind::PermissionDenied, "read-only");
+        let record = EventRecord::from(&Event::PushError(1, 0..8, anyhow::anyhow!(io)));
+        let value = serde_json::to_value(&record).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "event": "push_error",
+                "worker": 1,
+                "range": { "start": 0, "end": 8 },
+                "error": { "kind": "io", "message": "read-only" },
+            })
+        );
+    }
+
+    #[test]
+    fn captures_rate_limits() {
+        let response = hyper::Response::builder()
+            .status(429)
+            .body(String::new())
+            .unwrap();
+        let error = HttpError::<SmartRedirectClient>::RateLimited(
+            ReqwestResponseError::RateLimited(reqwest::Response::from(response)),
+        );
+        let value = serde_json::to_value(EventRecord::from(&Event::PullError(
+            0,
+            anyhow::anyhow!(error),
+        )))
+        .unwrap();
+        assert_eq!(value["error"]["kind"], "rate_limited");
+        assert_eq!(value["error"]["status"], 429);
+
+        let value = serde_json::to_value(EventRecord::from(&Event::Throttled(
+            Duration::from_secs(30),
+        )))
+        .unwrap();
+        assert_eq!(value, json!({ "event": "throttled", "cooldown": "30s" }));
+    }
+
+    #[tokio::test]
+    async fn writes_one_json_line_per_event() {
+        let (tx, rx) = create_channel();
+        tx.send(Event::Pulling(0)).unwrap();
+        tx.send(Event::Progress(ProgressSample {
+            progress: vec![0..10, 20..30],
+            bps: 5,
+            avg_bps: 4,
+            downloaded: 20,
+            percent: 20.0,
+            total: 100,
+            elapsed: Duration::from_millis(1500),
+            eta: None,
+            chunks: None,
+        }))
+        .unwrap();
+        tx.send(Event::Flushing).unwrap();
+        drop(tx);
+
+        let mut out = Vec::new();
+        assert_eq!(write_ndjson(rx, &mut out).await.unwrap(), 3);
+        let lines: Vec<serde_json::Value> = std::str::from_utf8(&out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines[0], json!({ "event": "pulling", "worker": 0 }));
+        assert_eq!(lines[1]["event"], "progress");
+        assert_eq!(lines[1]["elapsed"], "1s 500ms");
+        assert_eq!(lines[1]["eta"], serde_json::Value::Null);
+        assert_eq!(lines[2], json!({ "event": "flushing" }));
+    }
+}
@@ -0,0 +1,325 @@
+//! The aria2 methods, mapped onto the [`DownloadManager`](crate::DownloadManager).
+
+use super::{
+    RpcError, RpcServer,
+    options::{applies_live, parse_options},
+    status::{gid, select, status},
+};
+use crate::{DownloadJob, JobId, JobStatus};
+use inherit_config::ConfigLayer;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value, json};
+use url::Url;
+
+const METHODS: &[&str] = &[
+    "aria2.addUri",
+    "aria2.remove",
+    "aria2.forceRemove",
+    "aria2.pause",
+    "aria2.forcePause",
+    "aria2.unpause",
+    "aria2.tellStatus",
+    "aria2.tellActive",
+    "aria2.tellWaiting",
+    "aria2.tellStopped",
+    "aria2.changeOption",
+    "aria2.getGlobalStat",
+    "aria2.purgeDownloadResult",
+    "aria2.getVersion",
+    "system.multicall",
+    "system.listMethods",
+    "system.listNotifications",
+];
+
+const NOTIFICATIONS: &[&str] = &[
+    "aria2.onDownloadStart",
+    "aria2.onDownloadPause",
+    "aria2.onDownloadStop",
+    "aria2.onDownloadComplete",
+    "aria2.onDownloadError",
+];
+
+/// The positional parameters of a call, taken in order.
+struct Params(std::vec::IntoIter<Value>);
+
+impl Params {
+    /// The next parameter, `None` if the call stops before it.
+    fn optional<T: DeserializeOwned>(&mut self, name: &str) -> Result<Option<T>, RpcError> {
+        self.0
+            .next()
+            .map(|value| {
+                serde_json::from_value(value)
+                    .map_err(|e| RpcError::invalid_params(format!("{name}: {e}")))
+            })
+            .transpose()
+    }
+
+    fn required<T: DeserializeOwned>(&mut self, name: &str) -> Result<T, RpcError> {
+        self.optional(name)?
+            .ok_or_else(|| RpcError::invalid_params(format!("{name} is required")))
+    }
+
+    fn gid(&mut self) -> Result<JobId, RpcError> {
+        let gid: String = self.required("gid")?;
+        if gid.is_empty() || gid.len() > 16 {
+            return Err(RpcError::failed(format!("Bad GID {gid}")));
+        }
+        JobId::from_str_radix(&gid, 16).map_err(|_| RpcError::failed(format!("Bad GID {gid}")))
+    }
+}
+
+impl RpcServer {
+    pub(super) fn call(&self, method: &str, mut params: Vec<Value>) -> Result<Value, RpcError> {
+        match method {
+            "system.listMethods" => return Ok(json!(METHODS)),
+            "system.listNotifications" => return Ok(json!(NOTIFICATIONS)),
+            // Each call of the batch carries its own token.
+            "system.multicall" => return self.multicall(params),
+            _ => {}
+        }
+        self.authorize(&mut params)?;
+        let mut params = Params(params.into_iter());
+        match method {
+            "aria2.addUri" => self.add_uri(&mut params),
+            "aria2.remove" | "aria2.forceRemove" => self.remove(params.gid()?),
+            "aria2.pause" | "aria2.forcePause" => self.pause(params.gid()?),
+            "aria2.unpause" => self.unpause(params.gid()?),
+            "aria2.tellStatus" => {
+                let id = params.gid()?;
+                let keys: Option<Vec<String>> = params.optional("keys")?;
+                let status = self.status(id).ok_or_else(|| not_found(id))?;
+                Ok(select(status, keys.as_deref()))
+            }
+            "aria2.tellActive" => {
+                let keys: Option<Vec<String>> = params.optional("keys")?;
+                Ok(self.statuses(self.inner.manager.active(), keys.as_deref()))
+            }
+            "aria2.tellWaiting" | "aria2.tellStopped" => {
+                let offset = params.required("offset")?;
+                let num = params.required("num")?;
+                let keys: Option<Vec<String>> = params.optional("keys")?;
+                let ids = if method == "aria2.tellWaiting" {
+                    self.inner.manager.queued()
+                } else {
+                    self.stopped()
+                };
+                Ok(self.statuses(page(&ids, offset, num), keys.as_deref()))
+            }
+            "aria2.changeOption" => {
+                let id = params.gid()?;
+                let options: Map<String, Value> = params.required("options")?;
+                let patch = parse_options(&options)?;
+                let active = matches!(
+                    self.inner.manager.status(id),
+                    Some(JobStatus::Running | JobStatus::Paused)
+                );
+                if active && !applies_live(&patch) {
+                    return Err(RpcError::failed(
+                        "only split, max-connection-per-server, min-split-size and \
+                         max-download-limit can be changed on an active download",
+                    ));
+                }
+                if self.inner.manager.reconfigure(id, patch) {
+                    Ok("OK".into())
+                } else {
+                    Err(not_found(id))
+                }
+            }
+            "aria2.getGlobalStat" => Ok(self.global_stat()),
+            "aria2.purgeDownloadResult" => {
+                let stopped = self.stopped();
+                self.inner.manager.clear_finished();
+                let mut jobs = self.inner.jobs.lock();
+                for id in stopped {
+                    jobs.remove(&id);
+                }
+                drop(jobs);
+                Ok("OK".into())
+            }
+            "aria2.getVersion" => Ok(json!({
+                "version": env!("CARGO_PKG_VERSION"),
+                "enabledFeatures": [],
+            })),
+            _ => Err(RpcError::method_not_found(method)),
+        }
+    }
+
+    /// Check and drop the `token:` parameter. Without a secret a token is
+    /// accepted and ignored.
+    fn authorize(&self, params: &mut Vec<Value>) -> Result<(), RpcError> {
+        let token = match params.first() {
+            Some(Value::String(s)) => s.strip_prefix("token:"),
+            _ => None,
+        };
+        let authorized = self
+            .inner
+            .config
+            .secret
+            .as_deref()
+            .is_none_or(|secret| token == Some(secret));
+        if !authorized {
+            return Err(RpcError::failed("Unauthorized"));
+        }
+        if token.is_some() {
+            params.remove(0);
+        }
+        Ok(())
+    }
+
+    fn multicall(&self, params: Vec<Value>) -> Result<Value, RpcError> {
+        let mut params = Params(params.into_iter());
+        let calls: Vec<Map<String, Value>> = params.required("calls")?;
+        let results = calls
+            .into_iter()
+            .map(|mut call| {
+                let result = match (call.remove("methodName"), call.remove("params")) {
+                    (Some(Value::String(method)), _) if method == "system.multicall" => {
+                        Err(RpcError::failed("Recursive system.multicall forbidden."))
+                    }
+                    (Some(Value::String(method)), None) => self.call(&method, Vec::new()),
+                    (Some(Value::String(method)), Some(Value::Array(params))) => {
+                        self.call(&method, params)
+                    }
+                    _ => Err(RpcError::invalid_request()),
+                };
+                // As in XML-RPC: a result is wrapped in a list, an error is not.
+                result.map_or_else(|e| e.to_value(), |value| json!([value]))
+            })
+            .collect();
+        Ok(Value::Array(results))
+    }
+
+    fn add_uri(&self, params: &mut Params) -> Result<Value, RpcError> {
+        let uris: Vec<String> = params.required("uris")?;
+        let options: Option<Map<String, Value>> = params.optional("options")?;
+        // The position in the queue is not supported: jobs wait by priority.
+        let mut config = parse_options(&options.unwrap_or_default())?;
+        config.inherit_from(&self.inner.config.defaults);
+        let mut parsed = uris.iter().map(|uri| {
+            Url::parse(uri).map_err(|e| RpcError::failed(format!("Bad URI {uri}: {e}")))
+        });
+        let url = parsed
+            .next()
+            .ok_or_else(|| RpcError::invalid_params("uris must not be empty"))??;
+        let mirrors = parsed.collect::<Result<Vec<_>, _>>()?;
+        if !mirrors.is_empty() {
+            config.mirrors.get_or_insert_default().extend(mirrors);
+        }
+        let id = self.inner.manager.add(DownloadJob {
+            url,
+            config,
+            priority: 0,
+        });
+        Ok(gid(id).into())
+    }
+
+    fn remove(&self, id: JobId) -> Result<Value, RpcError> {
+        if self.inner.manager.cancel(id) {
+            Ok(gid(id).into())
+        } else {
+            Err(not_found(id))
+        }
+    }
+
+    fn pause(&self, id: JobId) -> Result<Value, RpcError> {
+        if self.inner.manager.pause(id) {
+            self.inner.notify("aria2.onDownloadPause", id);
+            Ok(gid(id).into())
+        } else {
+            Err(RpcError::failed(format!(
+                "GID#{} cannot be paused now",
+                gid(id)
+            )))
+        }
+    }
+
+    fn unpause(&self, id: JobId) -> Result<Value, RpcError> {
+        if self.inner.manager.status(id) == Some(JobStatus::Paused)
+            && self.inner.manager.unpause(id)
+        {
+            self.inner.notify("aria2.onDownloadStart", id);
+            Ok(gid(id).into())
+        } else {
+            Err(RpcError::failed(format!(
+                "GID#{} cannot be unpaused now",
+                gid(id)
+            )))
+        }
+    }
+
+    fn status(&self, id: JobId) -> Option<Map<String, Value>> {
+        let job = self.inner.manager.job(id)?;
+        let live = self
+            .inner
+            .manager
+            .handle(id)
+            .and_then(|handle| handle.progress());
+        let jobs = self.inner.jobs.lock();
+        let status = status(&job, jobs.get(&id), live);
+        drop(jobs);
+        Some(status)
+    }
+
+    fn statuses(&self, ids: impl IntoIterator<Item = JobId>, keys: Option<&[String]>) -> Value {
+        ids.into_iter()
+            .filter_map(|id| self.status(id))
+            .map(|status| select(status, keys))
+            .collect()
+    }
+
+    /// The finished jobs, oldest first.
+    fn stopped(&self) -> Vec<JobId> {
+        self.inner
+            .manager
+            .session()
+            .jobs
+            .iter()
+            .filter(|job| job.status.is_finished())
+            .map(|job| job.id)
+            .collect()
+    }
+
+    fn global_stat(&self) -> Value {
+        let manager = &self.inner.manager;
+        let active = manager.active();
+        let speed: u64 = active
+            .iter()
+            .filter_map(|&id| manager.handle(id)?.progress())
+            .map(|sample| sample.bps)
+            .sum();
+        let stopped = self.stopped().len();
+        json!({
+            "downloadSpeed": speed.to_string(),
+            "uploadSpeed": "0",
+            "numActive": active.len().to_string(),
+            "numWaiting": manager.queued().len().to_string(),
+            "numStopped": stopped.to_string(),
+            "numStoppedTotal": stopped.to_string(),
+        })
+    }
+}
+
+fn not_found(id: JobId) -> RpcError {
+    RpcError::failed(format!("GID {} is not found", gid(id)))
+}
+
+/// The `num` ids from `offset`, as `tellWaiting` and `tellStopped` page: a
+/// negative offset counts from the end, and the page then runs backwards.
+pub(super) fn page(ids: &[JobId], offset: i64, num: usize) -> Vec<JobId> {
+    let len = i64::try_from(ids.len()).unwrap_or(i64::MAX);
+    if offset >= 0 {
+        ids.iter()
+            .skip(usize::try_from(offset).unwrap_or(usize::MAX))
+            .take(num)
+            .copied()
+            .collect()
+    } else {
+        let end = usize::try_from(len + offset + 1).unwrap_or(0);
+        ids[..end.min(ids.len())]
+            .iter()
+            .rev()
+            .take(num)
+            .copied()
+            .collect()
+    }
+}
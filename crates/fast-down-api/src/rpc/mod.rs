@@ -0,0 +1,458 @@
+//! An aria2-compatible JSON-RPC server driving a [`DownloadManager`].
+
+mod methods;
+mod options;
+mod status;
+
+use crate::{DownloadManager, JobId, ManagerEvent, ManagerRx, PartialConfig};
+use axum::{
+    Router,
+    extract::{
+        State, WebSocketUpgrade,
+        ws::{Message, WebSocket},
+    },
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use parking_lot::Mutex;
+use serde_json::{Map, Value, json};
+use status::Tracked;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Weak},
+};
+use tokio::{net::TcpListener, sync::broadcast};
+use tokio_util::sync::CancellationToken;
+
+/// The path the server answers on, over both HTTP `POST` and WebSocket, as
+/// aria2 does.
+pub const RPC_PATH: &str = "/jsonrpc";
+
+/// Settings of an [`RpcServer`].
+#[derive(Debug, Clone, Default)]
+pub struct RpcConfig {
+    /// Like aria2's `--rpc-secret`: every call must then pass
+    /// `"token:<secret>"` as its first parameter.
+    pub secret: Option<String>,
+    /// Like aria2's `--rpc-allow-origin-all`: answer HTTP and WebSocket calls
+    /// from any origin, so front-ends served from elsewhere can use the server
+    /// from a browser. Otherwise a request whose `Origin` is not the server's
+    /// own is refused, so a web page cannot drive it behind the user's back.
+    pub allow_origin_all: bool,
+    /// The config every added job starts from; the options of `aria2.addUri`
+    /// are layered over it.
+    pub defaults: PartialConfig,
+}
+
+/// Serves the [aria2 JSON-RPC interface](https://aria2.github.io/manual/en/html/aria2c.html#rpc-interface)
+/// over a [`DownloadManager`], so existing aria2 front-ends can drive
+/// fast-down unchanged.
+///
+/// Each job is exposed under a GID made of its [`JobId`] in 16 hex digits.
+/// `aria2.addUri` adds a job, with the first URI as its URL and the others as
+/// [`mirrors`](crate::Config::mirrors), and its options translated into
+/// [`PartialConfig`] fields (`dir`, `out`, `split`, `min-split-size`,
+/// `max-download-limit`, `header`, `all-proxy`, `checksum`, …).
+/// `aria2.tellStatus` reports the job's [`ProgressSample`](crate::ProgressSample),
+/// `aria2.pause`, `aria2.unpause` and `aria2.remove` go to the manager, and
+/// `aria2.changeOption` to [`DownloadManager::reconfigure`]. WebSocket clients
+/// are pushed `aria2.onDownloadStart`, `onDownloadPause`, `onDownloadStop`,
+/// `onDownloadComplete` and `onDownloadError` notifications.
+///
+/// HTTP calls must be sent as `Content-Type: application/json`, which a
+/// browser does not send across origins without asking the server first.
+///
+/// The server is cheap to clone; clones share their state.
+#[derive(Clone)]
+pub struct RpcServer {
+    inner: Arc<RpcInner>,
+}
+
+struct RpcInner {
+    manager: DownloadManager,
+    config: RpcConfig,
+    jobs: Mutex<HashMap<JobId, Tracked>>,
+    notifications: broadcast::Sender<String>,
+}
+
+impl RpcServer {
+    /// A server over `manager`, following the jobs through `rx`, the receiver
+    /// of the channel the manager was made with.
+    ///
+    /// Must be called within a Tokio runtime: it spawns the task draining
+    /// `rx`, which ends once the server is dropped.
+    #[must_use]
+    pub fn new(manager: DownloadManager, rx: ManagerRx, config: RpcConfig) -> Self {
+        let (notifications, _) = broadcast::channel(256);
+        let inner = Arc::new(RpcInner {
+            manager,
+            config,
+            jobs: Mutex::new(HashMap::new()),
+            notifications,
+        });
+        tokio::spawn(follow(Arc::downgrade(&inner), rx));
+        Self { inner }
+    }
+
+    /// The manager the server drives.
+    #[must_use]
+    pub fn manager(&self) -> &DownloadManager {
+        &self.inner.manager
+    }
+
+    /// Answer JSON-RPC requests on `listener` until `token` is cancelled.
+    ///
+    /// # Errors
+    /// Returns an error if serving the listener fails.
+    pub async fn serve(
+        self,
+        listener: TcpListener,
+        token: CancellationToken,
+    ) -> std::io::Result<()> {
+        let app = Router::new()
+            .route(RPC_PATH, post(http_call).get(websocket).options(preflight))
+            .with_state((self, token.clone()));
+        axum::serve(listener, app)
+            .with_graceful_shutdown(token.cancelled_owned())
+            .await
+    }
+
+    /// Answer one JSON-RPC message: a request, or a batch of them. `None` when
+    /// nothing is to be answered, as for a notification.
+    #[must_use]
+    pub fn handle_message(&self, text: &str) -> Option<String> {
+        let reply = match serde_json::from_str(text) {
+            Err(e) => Some(response(Value::Null, Err(RpcError::parse_error(&e)))),
+            Ok(Value::Array(batch)) if batch.is_empty() => {
+                Some(response(Value::Null, Err(RpcError::invalid_request())))
+            }
+            Ok(Value::Array(batch)) => {
+                let replies: Vec<_> = batch.into_iter().filter_map(|r| self.handle(r)).collect();
+                (!replies.is_empty()).then_some(Value::Array(replies))
+            }
+            Ok(request) => self.handle(request),
+        };
+        reply.map(|r| r.to_string())
+    }
+
+    fn handle(&self, request: Value) -> Option<Value> {
+        let Value::Object(mut request) = request else {
+            return Some(response(Value::Null, Err(RpcError::invalid_request())));
+        };
+        let id = request.remove("id");
+        let result = match (request.remove("method"), request.remove("params")) {
+            (Some(Value::String(method)), None) => self.call(&method, Vec::new()),
+            (Some(Value::String(method)), Some(Value::Array(params))) => self.call(&method, params),
+            (Some(Value::String(_)), Some(_)) => {
+                Err(RpcError::invalid_params("params must be a list"))
+            }
+            _ => Err(RpcError::invalid_request()),
+        };
+        id.map(|id| response(id, result))
+    }
+
+    /// Whether a request may be answered: a browser request from another
+    /// origin only with [`allow_origin_all`](RpcConfig::allow_origin_all).
+    /// Requests without an `Origin`, as sent by programs, pass.
+    fn origin_allowed(&self, headers: &HeaderMap) -> bool {
+        let Some(origin) = headers.get(header::ORIGIN) else {
+            return true;
+        };
+        if self.inner.config.allow_origin_all {
+            return true;
+        }
+        let authority = origin.to_str().ok().and_then(|o| o.split_once("://"));
+        let host = headers.get(header::HOST).and_then(|h| h.to_str().ok());
+        matches!(
+            (authority, host),
+            (Some((_, authority)), Some(host)) if authority.eq_ignore_ascii_case(host)
+        )
+    }
+
+    fn allow_origin(&self, response: &mut Response) {
+        if self.inner.config.allow_origin_all {
+            let headers = response.headers_mut();
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_ORIGIN,
+                HeaderValue::from_static("*"),
+            );
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_HEADERS,
+                HeaderValue::from_static("Content-Type"),
+            );
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_METHODS,
+                HeaderValue::from_static("POST, GET, OPTIONS"),
+            );
+        }
+    }
+
+    async fn session(self, mut socket: WebSocket, token: CancellationToken) {
+        let mut notifications = self.inner.notifications.subscribe();
+        loop {
+            let reply = tokio::select! {
+                () = token.cancelled() => break,
+                message = socket.recv() => match message {
+                    Some(Ok(Message::Text(text))) => self.handle_message(&text),
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                    Some(Ok(_)) => None,
+                },
+                notification = notifications.recv() => match notification {
+                    Ok(notification) => Some(notification),
+                    Err(broadcast::error::RecvError::Lagged(_)) => None,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+            if let Some(reply) = reply
+                && socket.send(Message::Text(reply.into())).await.is_err()
+            {
+                break;
+            }
+        }
+    }
+}
+
+impl RpcInner {
+    /// Push an `aria2.on*` notification about job `id` to the WebSocket
+    /// clients.
+    fn notify(&self, method: &str, id: JobId) {
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": [{ "gid": status::gid(id) }],
+        });
+        let _ = self.notifications.send(notification.to_string());
+    }
+}
+
+/// Track the jobs' events and notify the clients of their starts and ends,
+/// until the server is dropped.
+async fn follow(inner: Weak<RpcInner>, rx: ManagerRx) {
+    while let Ok(event) = rx.recv().await {
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        match event {
+            ManagerEvent::Started(id) => inner.notify("aria2.onDownloadStart", id),
+            ManagerEvent::Job(id, event) => {
+                inner.jobs.lock().entry(id).or_default().observe(&event);
+            }
+            ManagerEvent::Ended(id, _) => {
+                if let Some(tracked) = inner.jobs.lock().get_mut(&id) {
+                    tracked.ended();
+                }
+                let method = match inner.manager.status(id) {
+                    Some(crate::JobStatus::Completed) => "aria2.onDownloadComplete",
+                    Some(crate::JobStatus::Failed) => "aria2.onDownloadError",
+                    Some(crate::JobStatus::Cancelled) => "aria2.onDownloadStop",
+                    // Interrupted by a shutdown of the manager.
+                    _ => continue,
+                };
+                inner.notify(method, id);
+            }
+            ManagerEvent::Expanded(..) | ManagerEvent::SessionSaveError(_) => {}
+        }
+    }
+}
+
+type Shared = State<(RpcServer, CancellationToken)>;
+
+async fn http_call(State((server, _)): Shared, headers: HeaderMap, body: String) -> Response {
+    if !server.origin_allowed(&headers) {
+        return (StatusCode::FORBIDDEN, "Cross-origin request refused").into_response();
+    }
+    if !is_json(&headers) {
+        return (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Content-Type must be application/json",
+        )
+            .into_response();
+    }
+    let mut response = server.handle_message(&body).map_or_else(
+        || StatusCode::NO_CONTENT.into_response(),
+        |reply| ([(header::CONTENT_TYPE, "application/json-rpc")], reply).into_response(),
+    );
+    server.allow_origin(&mut response);
+    response
+}
+
+async fn preflight(State((server, _)): Shared) -> Response {
+    let mut response = StatusCode::NO_CONTENT.into_response();
+    server.allow_origin(&mut response);
+    response
+}
+
+async fn websocket(
+    ws: WebSocketUpgrade,
+    State((server, token)): Shared,
+    headers: HeaderMap,
+) -> Response {
+    if !server.origin_allowed(&headers) {
+        return (StatusCode::FORBIDDEN, "Cross-origin request refused").into_response();
+    }
+    ws.on_upgrade(move |socket| server.session(socket, token))
+}
+
+/// Whether the body is declared as JSON (`application/json`, or the
+/// `application/json-rpc` aria2 answers with).
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|mime| {
+            let mime = mime.trim();
+            mime.eq_ignore_ascii_case("application/json")
+                || mime.eq_ignore_ascii_case("application/json-rpc")
+        })
+}
+
+/// A JSON-RPC error object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    const PARSE_ERROR: i64 = -32700;
+    const INVALID_REQUEST: i64 = -32600;
+    const METHOD_NOT_FOUND: i64 = -32601;
+    const INVALID_PARAMS: i64 = -32602;
+    /// What aria2 answers for every failure of a known method.
+    const FAILED: i64 = 1;
+
+    fn parse_error(e: &serde_json::Error) -> Self {
+        Self {
+            code: Self::PARSE_ERROR,
+            message: format!("Parse error: {e}"),
+        }
+    }
+
+    fn invalid_request() -> Self {
+        Self {
+            code: Self::INVALID_REQUEST,
+            message: "Invalid Request.".to_string(),
+        }
+    }
+
+    fn method_not_found(method: &str) -> Self {
+        Self {
+            code: Self::METHOD_NOT_FOUND,
+            message: format!("No such method: {method}"),
+        }
+    }
+
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self {
+            code: Self::INVALID_PARAMS,
+            message: message.into(),
+        }
+    }
+
+    fn failed(message: impl Into<String>) -> Self {
+        Self {
+            code: Self::FAILED,
+            message: message.into(),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        json!({ "code": self.code, "message": self.message })
+    }
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    let mut response = Map::new();
+    response.insert("id".into(), id);
+    response.insert("jsonrpc".into(), "2.0".into());
+    match result {
+        Ok(result) => response.insert("result".into(), result),
+        Err(e) => response.insert("error".into(), e.to_value()),
+    };
+    Value::Object(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_cancellation_token, create_manager_channel};
+
+    fn server(secret: Option<&str>) -> RpcServer {
+        let (tx, rx) = create_manager_channel();
+        let manager = DownloadManager::new(1, 0, tx, create_cancellation_token());
+        let config = RpcConfig {
+            secret: secret.map(str::to_string),
+            ..Default::default()
+        };
+        RpcServer::new(manager, rx, config)
+    }
+
+    fn call(server: &RpcServer, request: &Value) -> Value {
+        let reply = server.handle_message(&request.to_string()).unwrap();
+        serde_json::from_str(&reply).unwrap()
+    }
+
+    #[tokio::test]
+    async fn answers_json_rpc_requests() {
+        let server = server(Some("s3cret"));
+        let reply = call(
+            &server,
+            &json!({ "jsonrpc": "2.0", "id": "q", "method": "aria2.getVersion", "params": ["token:s3cret"] }),
+        );
+        assert_eq!(reply["id"], "q");
+        assert_eq!(reply["result"]["version"], env!("CARGO_PKG_VERSION"));
+
+        let reply = call(
+            &server,
+            &json!({ "jsonrpc": "2.0", "id": 1, "method": "aria2.tellActive", "params": ["token:nope"] }),
+        );
+        assert_eq!(reply["error"]["message"], "Unauthorized");
+        let reply = call(
+            &server,
+            &json!({ "jsonrpc": "2.0", "id": 1, "method": "aria2.tellStatus", "params": ["token:s3cret", "00000000000000ff"] }),
+        );
+        assert_eq!(
+            reply["error"]["message"],
+            "GID 00000000000000ff is not found"
+        );
+        let reply = call(
+            &server,
+            &json!({ "jsonrpc": "2.0", "id": 1, "method": "aria2.addTorrent", "params": ["token:s3cret"] }),
+        );
+        assert_eq!(reply["error"]["code"], RpcError::METHOD_NOT_FOUND);
+        let reply: Value = serde_json::from_str(&server.handle_message("{").unwrap()).unwrap();
+        assert_eq!(reply["error"]["code"], RpcError::PARSE_ERROR);
+        assert_eq!(
+            server.handle_message(r#"{"jsonrpc":"2.0","method":"system.listMethods"}"#),
+            None,
+            "a notification gets no reply"
+        );
+
+        let reply = call(
+            &server,
+            &json!([
+                { "jsonrpc": "2.0", "id": 1, "method": "system.listNotifications" },
+                { "jsonrpc": "2.0", "id": 2, "method": "system.multicall", "params": [[
+                    { "methodName": "aria2.getGlobalStat", "params": ["token:s3cret"] },
+                    { "methodName": "aria2.getGlobalStat" },
+                ]] },
+            ]),
+        );
+        assert_eq!(reply[0]["result"][0], "aria2.onDownloadStart");
+        let results = &reply[1]["result"];
+        assert_eq!(results[0][0]["numActive"], "0");
+        assert_eq!(results[1]["message"], "Unauthorized");
+    }
+
+    #[test]
+    fn pages_like_aria2() {
+        let ids = [1, 2, 3, 4];
+        assert_eq!(methods::page(&ids, 1, 2), [2, 3]);
+        assert_eq!(methods::page(&ids, 3, 9), [4]);
+        assert_eq!(methods::page(&ids, -1, 2), [4, 3]);
+        assert_eq!(methods::page(&ids, -4, 3), [1]);
+        assert!(methods::page(&ids, -5, 3).is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+//! aria2 download options, translated into a [`PartialConfig`].
+
+use super::RpcError;
+use crate::PartialConfig;
+use fast_down::{Digest, Proxy};
+use serde_json::{Map, Value};
+use std::{borrow::Cow, path::PathBuf, time::Duration};
+
+/// Translate the options of `aria2.addUri` / `aria2.changeOption`.
+///
+/// aria2 sends every value as a string; numbers and booleans are accepted too.
+/// Options without a fast-down counterpart are ignored, since front-ends send
+/// many of them along.
+pub(super) fn parse_options(options: &Map<String, Value>) -> Result<PartialConfig, RpcError> {
+    let mut config = PartialConfig::default();
+    let mut split = None;
+    let mut max_connections = None;
+    for (name, value) in options {
+        let invalid = |reason: &str| RpcError::invalid_params(format!("option {name}: {reason}"));
+        if name == "header" {
+            for line in lines(value).ok_or_else(|| invalid("expected a string or a list"))? {
+                let (key, val) = line
+                    .split_once(':')
+                    .ok_or_else(|| invalid("expected `Name: value`"))?;
+                config
+                    .headers
+                    .get_or_insert_default()
+                    .insert(key.trim().to_string(), val.trim().to_string());
+            }
+            continue;
+        }
+        let text = text(value).ok_or_else(|| invalid("expected a string"))?;
+        let text = text.trim();
+        let count = || text.parse().map_err(|_| invalid("expected a number"));
+        let size = || parse_size(text).ok_or_else(|| invalid("expected a size"));
+        let flag = || parse_bool(text).ok_or_else(|| invalid("expected true or false"));
+        let seconds = || {
+            text.parse()
+                .map(Duration::from_secs)
+                .map_err(|_| invalid("expected a number"))
+        };
+        match name.as_str() {
+            "dir" => config.save_dir = Some(PathBuf::from(text)),
+            "out" => {
+                config.filename = Some(text.to_string());
+                config.parse_filename = Some(false);
+            }
+            "split" => split = Some(count()?),
+            "max-connection-per-server" => max_connections = Some(count()?),
+            "min-split-size" => config.min_chunk_size = Some(size()?),
+            "max-download-limit" => config.speed_limit = Some(size()?),
+            "all-proxy" | "http-proxy" | "https-proxy" => {
+                config.proxy = Some(if text.is_empty() {
+                    Proxy::No
+                } else {
+                    Proxy::Custom(text.to_string())
+                });
+            }
+            "user-agent" | "referer" => {
+                let key = if name == "referer" {
+                    "Referer"
+                } else {
+                    "User-Agent"
+                };
+                config
+                    .headers
+                    .get_or_insert_default()
+                    .insert(key.to_string(), text.to_string());
+            }
+            "checksum" => {
+                // aria2 writes `sha-256=<hex>`, fast-down `sha-256:<hex>`.
+                let digest: Digest = text
+                    .replacen('=', ":", 1)
+                    .parse()
+                    .map_err(|e| invalid(&format!("{e}")))?;
+                config.expected_digest = Some(Some(digest));
+            }
+            "max-tries" => config.max_consecutive_errors = Some(count()?),
+            "retry-wait" => config.retry_gap = Some(seconds()?),
+            "timeout" => config.pull_timeout = Some(seconds()?),
+            "check-certificate" => {
+                let check = flag()?;
+                config.accept_invalid_certs = Some(!check);
+                config.accept_invalid_hostnames = Some(!check);
+            }
+            "allow-overwrite" => config.overwrite = Some(flag()?),
+            "continue" => config.resume = Some(flag()?),
+            _ => {}
+        }
+    }
+    config.threads = split.or(max_connections);
+    Ok(config)
+}
+
+/// Whether an active download takes all of `patch` right away, rather than
+/// on its next run; see [`DownloadManager::reconfigure`](crate::DownloadManager::reconfigure).
+pub(super) fn applies_live(patch: &PartialConfig) -> bool {
+    let rest = PartialConfig {
+        threads: None,
+        min_chunk_size: None,
+        max_speculative: None,
+        speed_limit: None,
+        ..patch.clone()
+    };
+    rest == PartialConfig::default()
+}
+
+fn text(value: &Value) -> Option<Cow<'_, str>> {
+    match value {
+        Value::String(s) => Some(Cow::Borrowed(s)),
+        Value::Number(n) => Some(Cow::Owned(n.to_string())),
+        Value::Bool(b) => Some(Cow::Owned(b.to_string())),
+        _ => None,
+    }
+}
+
+/// A `header` option: one line, or a list of them.
+fn lines(value: &Value) -> Option<Vec<&str>> {
+    match value {
+        Value::String(s) => Some(vec![s]),
+        Value::Array(items) => items.iter().map(Value::as_str).collect(),
+        _ => None,
+    }
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// A byte count with an optional `K` or `M` suffix, as aria2 writes sizes.
+fn parse_size(s: &str) -> Option<u64> {
+    let (digits, shift) = match s.as_bytes().last()? {
+        b'k' | b'K' => (&s[..s.len() - 1], 10),
+        b'm' | b'M' => (&s[..s.len() - 1], 20),
+        _ => (s, 0),
+    };
+    digits.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(options: &Value) -> Result<PartialConfig, RpcError> {
+        parse_options(options.as_object().unwrap())
+    }
+
+    #[test]
+    fn maps_aria2_options() {
+        let config = parse(&json!({
+            "dir": "/tmp/dl",
+            "out": "a.iso",
+            "split": "8",
+            "max-connection-per-server": "16",
+            "min-split-size": "1M",
+            "max-download-limit": 512,
+            "all-proxy": "socks5://127.0.0.1:1080",
+            "header": ["X-Token: abc", "Accept:*/*"],
+            "user-agent": "AriaNg",
+            "checksum": "md5=5eb63bbbe01eeed093cb22bb8f5acdc3",
+            "retry-wait": "2",
+            "check-certificate": "false",
+            "allow-overwrite": "true",
+            "seed-time": "0",
+        }))
+        .unwrap();
+        assert_eq!(config.save_dir, Some(PathBuf::from("/tmp/dl")));
+        assert_eq!(config.filename.as_deref(), Some("a.iso"));
+        assert_eq!(config.parse_filename, Some(false));
+        assert_eq!(config.threads, Some(8), "split wins");
+        assert_eq!(config.min_chunk_size, Some(1 << 20));
+        assert_eq!(config.speed_limit, Some(512));
+        assert_eq!(
+            config.proxy,
+            Some(Proxy::Custom("socks5://127.0.0.1:1080".to_string()))
+        );
+        let headers = config.headers.unwrap();
+        assert_eq!(headers["X-Token"], "abc");
+        assert_eq!(headers["Accept"], "*/*");
+        assert_eq!(headers["User-Agent"], "AriaNg");
+        assert_eq!(
+            config.expected_digest.flatten().unwrap().to_string(),
+            "md5:5eb63bbbe01eeed093cb22bb8f5acdc3"
+        );
+        assert_eq!(config.retry_gap, Some(Duration::from_secs(2)));
+        assert_eq!(config.accept_invalid_certs, Some(true));
+        assert_eq!(config.overwrite, Some(true));
+
+        let config = parse(&json!({ "max-connection-per-server": "4" })).unwrap();
+        assert_eq!(config.threads, Some(4));
+    }
+
+    #[test]
+    fn tells_live_options_apart() {
+        let live = json!({ "split": "4", "min-split-size": "1M", "max-download-limit": "1K" });
+        assert!(applies_live(&parse(&live).unwrap()));
+        assert!(applies_live(&parse(&json!({ "seed-time": "0" })).unwrap()));
+        assert!(!applies_live(
+            &parse(&json!({ "split": "4", "dir": "/tmp" })).unwrap()
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_values() {
+        for options in [
+            json!({ "split": "many" }),
+            json!({ "min-split-size": "1G" }),
+            json!({ "header": "no colon" }),
+            json!({ "checksum": "sha-256=zz" }),
+            json!({ "continue": "yes" }),
+        ] {
+            let err = parse(&options).unwrap_err();
+            assert_eq!(err.code, RpcError::INVALID_PARAMS, "{options}");
+        }
+    }
+}
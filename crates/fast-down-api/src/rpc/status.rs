@@ -0,0 +1,194 @@
+//! What `aria2.tellStatus` reports about a job.
+
+use crate::{Event, JobStatus, ProgressSample, SessionJob};
+use fast_down::WorkerId;
+use serde_json::{Map, Value, json};
+use std::{collections::HashSet, path::PathBuf};
+
+/// What the events of a job told so far.
+#[derive(Debug, Default)]
+pub(super) struct Tracked {
+    total: u64,
+    sample: Option<ProgressSample>,
+    workers: HashSet<WorkerId>,
+    path: Option<PathBuf>,
+    error: Option<String>,
+}
+
+impl Tracked {
+    pub(super) fn observe(&mut self, event: &Event) {
+        match event {
+            Event::Prefetch(info) => self.total = info.size,
+            Event::Resumed { size, .. } => self.total = *size,
+            Event::Progress(sample) => {
+                self.total = sample.total;
+                self.sample = Some(sample.clone());
+            }
+            Event::Pulling(id) | Event::PullProgress(id, _) => {
+                self.workers.insert(*id);
+            }
+            Event::Finished(id)
+            | Event::PullFailed(id, _)
+            | Event::PullTimeoutFailed(id)
+            | Event::PushFailed(id, ..) => {
+                self.workers.remove(id);
+            }
+            Event::Renamed(path) => self.path = Some(path.clone()),
+            Event::PrefetchError(e) => self.error = Some(e.to_string()),
+            Event::ResumeError(e) => self.error = Some(e.to_string()),
+            Event::Failed { error, .. } => {
+                self.error.get_or_insert_with(|| format!("{error:#}"));
+            }
+            _ => {}
+        }
+    }
+
+    /// The job is over; its workers are gone.
+    pub(super) fn ended(&mut self) {
+        self.workers.clear();
+    }
+}
+
+/// The aria2 GID of job `id`: 16 hex digits.
+pub(super) fn gid(id: u64) -> String {
+    format!("{id:016x}")
+}
+
+const fn status_name(status: JobStatus) -> &'static str {
+    match status {
+        JobStatus::Queued => "waiting",
+        JobStatus::Running => "active",
+        JobStatus::Paused => "paused",
+        JobStatus::Completed => "complete",
+        JobStatus::Failed => "error",
+        JobStatus::Cancelled => "removed",
+    }
+}
+
+/// The status struct of `aria2.tellStatus`. `live` is the progress of the
+/// running download, when there is one; numbers are strings, as in aria2.
+pub(super) fn status(
+    job: &SessionJob,
+    tracked: Option<&Tracked>,
+    live: Option<ProgressSample>,
+) -> Map<String, Value> {
+    let sample = live.or_else(|| tracked.and_then(|t| t.sample.clone()));
+    let total = sample
+        .as_ref()
+        .map_or_else(|| tracked.map_or(0, |t| t.total), |s| s.total);
+    let completed = sample.as_ref().map_or(0, |s| s.downloaded);
+    let speed = match job.status {
+        JobStatus::Running => sample.as_ref().map_or(0, |s| s.bps),
+        _ => 0,
+    };
+    let dir = job
+        .config
+        .save_dir
+        .clone()
+        .or_else(|| Some(job.tmp_path.as_ref()?.parent()?.to_path_buf()))
+        .unwrap_or_default();
+    let path = tracked
+        .and_then(|t| t.path.clone())
+        .or_else(|| job.tmp_path.as_ref().map(|p| p.with_extension("")))
+        .or_else(|| job.config.filename.as_ref().map(|name| dir.join(name)))
+        .unwrap_or_default();
+    let uris: Vec<_> = std::iter::once(&job.url)
+        .chain(job.config.mirrors.iter().flatten())
+        .map(|url| json!({ "uri": url, "status": "used" }))
+        .collect();
+    let mut status = Map::new();
+    status.insert("gid".into(), gid(job.id).into());
+    status.insert("status".into(), status_name(job.status).into());
+    status.insert("totalLength".into(), total.to_string().into());
+    status.insert("completedLength".into(), completed.to_string().into());
+    status.insert("uploadLength".into(), "0".into());
+    status.insert("downloadSpeed".into(), speed.to_string().into());
+    status.insert("uploadSpeed".into(), "0".into());
+    let connections = tracked.map_or(0, |t| t.workers.len());
+    status.insert("connections".into(), connections.to_string().into());
+    status.insert("dir".into(), dir.to_string_lossy().into());
+    status.insert(
+        "files".into(),
+        json!([{
+            "index": "1",
+            "path": path.to_string_lossy(),
+            "length": total.to_string(),
+            "completedLength": completed.to_string(),
+            "selected": "true",
+            "uris": uris,
+        }]),
+    );
+    if job.status == JobStatus::Failed {
+        let message = tracked
+            .and_then(|t| t.error.clone())
+            .unwrap_or_else(|| "download failed".to_string());
+        status.insert("errorCode".into(), "1".into());
+        status.insert("errorMessage".into(), message.into());
+    }
+    status
+}
+
+/// Keep only `keys` of `status`, as `tellStatus` does when given keys.
+pub(super) fn select(mut status: Map<String, Value>, keys: Option<&[String]>) -> Value {
+    if let Some(keys) = keys.filter(|k| !k.is_empty()) {
+        status.retain(|key, _| keys.contains(key));
+    }
+    Value::Object(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PartialConfig;
+    use std::time::Duration;
+    use url::Url;
+
+    #[test]
+    fn reports_progress_of_tracked_events() {
+        let job = SessionJob {
+            id: 0x2a,
+            status: JobStatus::Running,
+            priority: 0,
+            url: Url::parse("https://example.com/a.bin").unwrap(),
+            tmp_path: Some(PathBuf::from("/dl/a.bin.part")),
+            config: PartialConfig {
+                mirrors: Some(vec![Url::parse("https://mirror.example/a.bin").unwrap()]),
+                ..Default::default()
+            },
+        };
+        let mut tracked = Tracked::default();
+        tracked.observe(&Event::Pulling(0));
+        tracked.observe(&Event::Pulling(1));
+        tracked.observe(&Event::Finished(1));
+        tracked.observe(&Event::Progress(ProgressSample {
+            progress: vec![0..20, 50..70],
+            bps: 10,
+            avg_bps: 8,
+            downloaded: 40,
+            percent: 40.0,
+            total: 100,
+            elapsed: Duration::from_secs(4),
+            eta: Some(Duration::from_secs(6)),
+            chunks: None,
+        }));
+
+        let status = status(&job, Some(&tracked), None);
+        assert_eq!(status["gid"], "000000000000002a");
+        assert_eq!(status["status"], "active");
+        assert_eq!(status["totalLength"], "100");
+        assert_eq!(status["completedLength"], "40");
+        assert_eq!(status["downloadSpeed"], "10");
+        assert_eq!(status["connections"], "1");
+        assert_eq!(status["dir"], "/dl");
+        assert_eq!(status["files"][0]["path"], "/dl/a.bin");
+        assert_eq!(
+            status["files"][0]["uris"][1]["uri"],
+            "https://mirror.example/a.bin"
+        );
+        assert!(!status.contains_key("errorCode"));
+
+        let keys = ["gid".to_string(), "status".to_string()];
+        let selected = select(status, Some(&keys));
+        assert_eq!(selected.as_object().unwrap().len(), 2);
+    }
+}
@@ -55,6 +55,7 @@ mod tests {
             final_url: Url::parse("https://example.com/x").unwrap(),
             file_id: fast_down::FileId::new(None, None),
             content_type: content_type.map(str::to_string),
+            digests: Vec::new(),
         }
     }
 
@@ -18,12 +18,16 @@
 use std::convert::Infallible;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 
 use bytes::Bytes;
 use fast_down_api::{
-    Event, PartialConfig, Rx, StateError, WriteMethod, create_cancellation_token, create_channel,
-    download, resume,
+    DownloadJob, DownloadManager, ErrorKind, Event, EventRecord, JobStatus, ManagerEvent,
+    MetalinkLoadError, MetalinkSource, OutcomeStatus, PartialConfig, Rx, Session, StateError,
+    WriteMethod, create_cancellation_token, create_channel, create_manager_channel, download,
+    fast_down::{DownloadStatus, Verbosity, fast_puller::ConnectionPool},
+    load_metalink, resume, write_ndjson,
 };
 use futures::StreamExt;
 use futures::stream::unfold;
@@ -59,11 +63,15 @@ struct FileData {
     etag: String,
     last_modified: String,
     supports_range: bool,
+    /// Sent as `Repr-Digest` when set.
+    repr_digest: Option<String>,
 }
 
 #[derive(Clone)]
 struct TestServer {
     data: Arc<RwLock<FileData>>,
+    /// Number of ranged (`206`) responses served so far.
+    ranged_hits: Arc<AtomicUsize>,
 }
 
 impl TestServer {
@@ -72,6 +80,11 @@ impl TestServer {
         let listener = TcpListener::bind("127.0.0.1:0")
             .await
             .expect("bind server socket");
+        self.serve_on(listener)
+    }
+
+    /// Start serving on `listener`. Returns the base URL.
+    fn serve_on(&self, listener: TcpListener) -> String {
         let addr = listener.local_addr().expect("resolve local addr");
         let server = self.clone();
         tokio::spawn(async move {
@@ -99,6 +112,11 @@ impl TestServer {
         data.etag = etag.to_string();
         data.last_modified = last_modified.to_string();
     }
+
+    /// Advertise `digest` (an RFC 9530 `Repr-Digest` value) on every response.
+    async fn set_repr_digest(&self, digest: &str) {
+        self.data.write().await.repr_digest = Some(digest.to_string());
+    }
 }
 
 /// The hyper request handler: serves the current [`FileData`], honouring range
@@ -112,6 +130,14 @@ async fn handle(
     // 302-redirects to a transient final URL. Used by the rotated-URL resume test
     // to prove the `.fd` stores the initial URL and the download targets the final
     // one.
+    if req.uri().path() == "/missing" {
+        return Ok(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(BoxBody::new(http_body_util::Full::new(Bytes::from_static(
+                b"<html>not found</html>",
+            ))))
+            .expect("build 404 response"));
+    }
     if req.uri().path() == "/redirect" {
         return Ok(Response::builder()
             .status(StatusCode::FOUND)
@@ -125,6 +151,7 @@ async fn handle(
     let etag = data.etag.clone();
     let last_modified = data.last_modified.clone();
     let body = data.body.clone();
+    let repr_digest = data.repr_digest.clone();
     drop(data);
 
     let range = req
@@ -137,10 +164,15 @@ async fn handle(
         && let Some(header) = range
         && let Some((start, end)) = parse_range(&header, total)
     {
+        server.ranged_hits.fetch_add(1, Ordering::Relaxed);
         let chunk = body[start..end].to_vec();
         let end_inclusive = end - 1;
         let content_range = format!("bytes {start}-{end_inclusive}/{total}");
-        return Ok(Response::builder()
+        let mut builder = Response::builder();
+        if let Some(digest) = &repr_digest {
+            builder = builder.header("repr-digest", digest.as_str());
+        }
+        return Ok(builder
             .status(StatusCode::PARTIAL_CONTENT)
             .header(CONTENT_RANGE, content_range)
             .header(ACCEPT_RANGES, "bytes")
@@ -155,7 +187,11 @@ async fn handle(
     // prefetch to detect resumability) will not see a `Content-Range` header.
     // Throttled the same way as the ranged branch so a fresh (non-resumed)
     // download is still slow enough for a mid-flight cancel to land.
-    Ok(Response::builder()
+    let mut builder = Response::builder();
+    if let Some(digest) = &repr_digest {
+        builder = builder.header("repr-digest", digest.as_str());
+    }
+    Ok(builder
         .status(StatusCode::OK)
         .header(CONTENT_LENGTH, body.len().to_string())
         .header(ETAG, etag.as_str())
@@ -212,16 +248,23 @@ async fn start_server(
     last_modified: &str,
     supports_range: bool,
 ) -> (TestServer, String) {
-    let server = TestServer {
+    let server = new_server(body, etag, last_modified, supports_range);
+    let url = server.serve().await;
+    (server, url)
+}
+
+/// A [`TestServer`] that is not listening yet.
+fn new_server(body: Vec<u8>, etag: &str, last_modified: &str, supports_range: bool) -> TestServer {
+    TestServer {
         data: Arc::new(RwLock::new(FileData {
             body,
             etag: etag.to_string(),
             last_modified: last_modified.to_string(),
             supports_range,
+            repr_digest: None,
         })),
-    };
-    let url = server.serve().await;
-    (server, url)
+        ranged_hits: Arc::new(AtomicUsize::new(0)),
+    }
 }
 
 /// A fresh, unique, empty temp directory per test (runs in parallel safe).
@@ -993,219 +1036,1659 @@ async fn test_fresh_download_writes_full_file() {
     );
 }
 
-/// Verify the `Event::Progress` aggregator: it is emitted on the configured
-/// cadence (not merely once), carries the full progress snapshot + a transfer
-/// rate, reports the correct total size, and reaches 100% coverage on a complete
-/// download. Regression guard for the dedicated, independent progress reporter.
+/// SHA-256 of [`original_bytes`].
+const ORIGINAL_SHA256: &str =
+    "sha256:408b9e0acd8ed1ffe507418606aeb95e2257a59ac9f0246c508b2dd39452de47";
+
+/// With `expected_digest` set, a matching file is reported as `Verified` right
+/// before the rename — also when the run was resumed, so the prefix written by
+/// the first run has to be read back to complete the digest.
 #[tokio::test]
-async fn test_progress_event_emitted() {
-    let dir = temp_dir("progress_event");
+async fn test_expected_digest_verifies_before_rename() {
+    let dir = temp_dir("digest_verified");
     let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let cfg = PartialConfig {
+        expected_digest: Some(Some(ORIGINAL_SHA256.parse().expect("valid digest"))),
+        ..make_config(&dir)
+    };
 
-    let mut cfg = make_config(&dir);
-    // Emit Progress frequently so the cadence is observable.
-    cfg.progress_emit_gap = Some(Duration::from_millis(30));
-
-    let (tx, rx) = create_channel();
     let cancel = create_cancellation_token();
-    download(Url::parse(&url).expect("valid url"), cfg, tx, cancel);
+    partial_download_via_cancel_with(&url, cfg.clone(), cancel, CANCEL_AFTER_BYTES).await;
+    let part = dir.join("out.bin").with_added_extension("part");
+    let (tx, rx) = create_channel();
+    resume(
+        part,
+        Some(Url::parse(&url).expect("valid url")),
+        cfg,
+        tx,
+        create_cancellation_token(),
+    );
     let events = drain(rx).await;
 
-    // At least one Progress event, and it must fire on its own timer (not just
-    // the terminal one) — this is the whole point of the dedicated reporter.
-    let progresses: Vec<_> = events
+    let verified = events
         .iter()
-        .filter_map(|e| match e {
-            Event::Progress(s) => Some((
-                s.progress.clone(),
-                s.bps,
-                s.avg_bps,
-                s.downloaded,
-                s.percent,
-                s.total,
-                s.elapsed,
-                s.eta,
-            )),
-            _ => None,
-        })
-        .collect();
-    assert!(
-        !progresses.is_empty(),
-        "expected at least one Event::Progress"
-    );
-    assert!(
-        progresses.len() >= 2,
-        "Event::Progress must fire on its own cadence, not only once (got {})",
-        progresses.len()
+        .position(|e| matches!(e, Event::Verified(d) if d.to_string() == ORIGINAL_SHA256))
+        .expect("a matching file must emit Verified");
+    let renamed = events
+        .iter()
+        .position(|e| matches!(e, Event::Renamed(_)))
+        .expect("a verified file must be renamed");
+    assert!(verified < renamed, "verification must precede the rename");
+    assert!(matches!(events.last(), Some(Event::Completed(_))));
+}
+
+/// A file that does not match `expected_digest` is never renamed into place;
+/// the run fails and keeps the `.part` file.
+#[tokio::test]
+async fn test_digest_mismatch_refuses_rename() {
+    let dir = temp_dir("digest_mismatch");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let expected: fast_down_api::fast_down::Digest = format!("sha256:{}", "0".repeat(64))
+        .parse()
+        .expect("valid digest");
+    let cfg = PartialConfig {
+        expected_digest: Some(Some(expected.clone())),
+        ..make_config(&dir)
+    };
+
+    let (tx, rx) = create_channel();
+    download(
+        Url::parse(&url).expect("valid url"),
+        cfg,
+        tx,
+        create_cancellation_token(),
     );
+    let events = drain(rx).await;
 
-    // Every Progress reports the correct total size.
+    let mismatch = events.iter().find_map(|e| match e {
+        Event::DigestMismatch { expected, actual } => Some((expected, actual)),
+        _ => None,
+    });
+    let (reported, actual) = mismatch.expect("a mismatching file must emit DigestMismatch");
+    assert_eq!(reported, &expected);
+    assert_eq!(actual.to_string(), ORIGINAL_SHA256);
+    assert!(!events.iter().any(|e| matches!(e, Event::Renamed(_))));
+    assert!(matches!(events.last(), Some(Event::Failed { .. })));
+    let final_path = dir.join("out.bin");
     assert!(
-        progresses
-            .iter()
-            .all(|(_, _, _, _, _, total, _, _)| *total == FILE_SIZE as u64),
-        "Event::Progress.total must equal the file size"
+        !final_path.exists(),
+        "a mismatching file must not be renamed"
     );
+    assert!(final_path.with_added_extension("part").exists());
 
-    // Structural invariants for the new aggregate fields.
-    for (p, _, avg_bps, downloaded, percent, _, elapsed, _) in &progresses {
-        let sum: u64 = p.iter().map(|r| r.end - r.start).sum();
-        assert_eq!(
-            sum, *downloaded,
-            "Event::Progress.downloaded must equal sum(progress ranges)"
-        );
-        assert!(
-            (*percent >= 0.0) && (*percent <= 100.0),
-            "percent out of range: {percent}"
-        );
-        // avg_bps is session-wide bytes/sec; it can never exceed
-        // downloaded * 1000 (the rate if the whole file arrived in 1ms).
-        assert!(
-            *avg_bps <= (*downloaded).saturating_mul(1000).max(1),
-            "avg_bps implausibly high: {avg_bps}"
-        );
-        assert!(
-            *elapsed >= Duration::ZERO,
-            "elapsed must be non-negative: {elapsed:?}"
-        );
-    }
-
-    // `elapsed` (cumulative active time) must be non-decreasing across frames.
-    let mut prev_elapsed = Duration::ZERO;
-    for (_, _, _, _, _, _, el, _) in &progresses {
-        assert!(
-            *el >= prev_elapsed,
-            "Event::Progress.elapsed must be non-decreasing: {el:?} < {prev_elapsed:?}"
-        );
-        prev_elapsed = *el;
-    }
-
-    // `eta` (estimated remaining time) invariants:
-    // - At least one intermediate frame must carry a positive `eta`, proving ETA
-    //   is actually computed mid-download (the run is not instant).
-    // - `eta == (total - downloaded) / rate` with millisecond rounding, so we
-    //   check it within a 1ms tolerance against the effective rate.
-    assert!(
-        progresses
-            .iter()
-            .any(|(_, _, _, d, _, t, _, eta)| *d < *t && eta.is_some_and(|e| e > Duration::ZERO)),
-        "expected at least one intermediate Progress with eta > 0"
+    // The progress was forgotten: a rerun pulls the whole file again instead
+    // of resuming onto the bytes that failed verification.
+    let (tx, rx) = create_channel();
+    download(
+        Url::parse(&url).expect("valid url"),
+        PartialConfig {
+            expected_digest: Some(Some(ORIGINAL_SHA256.parse().expect("valid digest"))),
+            ..make_config(&dir)
+        },
+        tx,
+        create_cancellation_token(),
     );
-    for (_, bps, avg_bps, downloaded, _, total, _, eta) in &progresses {
-        let remaining = total.saturating_sub(*downloaded);
-        // Mirrors the production formula: prefer the smoothed recent `bps`,
-        // fall back to the session-wide `avg_bps` while the EMA warms up.
-        let rate = if *bps > 0 { *bps } else { *avg_bps };
-        if remaining == 0 {
-            // Fully written: eta must be ZERO (absent only if rate is still 0).
-            assert!(
-                eta.map_or(rate == 0, |e| e == Duration::ZERO),
-                "eta must be ZERO once fully downloaded, got {eta:?}"
-            );
-        } else if rate > 0 {
-            let Some(d) = eta else {
-                panic!("eta must be Some while downloading with a measurable rate");
-            };
-            let expected_ms = u128::from(
-                remaining
-                    .checked_mul(1000)
-                    .and_then(|x| x.checked_div(rate))
-                    .expect("rate > 0"),
-            );
-            let got_ms = d.as_millis();
-            let diff = got_ms.abs_diff(expected_ms);
-            assert!(
-                diff <= 1,
-                "eta {got_ms}ms disagrees with computed {expected_ms}ms"
-            );
-        } else {
-            assert!(eta.is_none(), "eta must be None with no measurable rate");
+    let events = drain(rx).await;
+    match events.last() {
+        Some(Event::Completed(outcome)) => {
+            assert_eq!(outcome.bytes_pulled, FILE_SIZE as u64);
         }
+        other => panic!("expected Completed, got {other:?}"),
     }
-
-    // The download actually progressed: at some point the full size was reported.
-    let max_downloaded: u64 = progresses
-        .iter()
-        .map(|(_, _, _, d, _, _, _, _)| *d)
-        .max()
-        .unwrap_or(0);
     assert_eq!(
-        max_downloaded, FILE_SIZE as u64,
-        "downloaded must reach the full file size"
+        tokio::fs::read(&final_path).await.expect("read final file"),
+        original_bytes()
     );
+}
 
-    // At least one intermediate frame reported a positive instantaneous rate.
+/// `Repr-Digest` of [`original_bytes`] (its SHA-256, base64).
+const ORIGINAL_REPR_DIGEST: &str = "sha-256=:QIueCs2O0f/lB0GGBq65XiJXpZrJ8CRsUIst05RS3kc=:";
+
+/// A digest advertised by the server is recorded in the `.fd` state and checked
+/// before the rename, also after a resume.
+#[tokio::test]
+async fn test_server_digest_is_persisted_and_verified() {
+    let dir = temp_dir("server_digest");
+    let (server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    server.set_repr_digest(ORIGINAL_REPR_DIGEST).await;
+    let cfg = make_config(&dir);
+
+    let cancel = create_cancellation_token();
+    partial_download_via_cancel_with(&url, cfg.clone(), cancel, CANCEL_AFTER_BYTES).await;
+    let part = dir.join("out.bin").with_added_extension("part");
+    let fd = tokio::fs::read_to_string(part.with_extension("fd"))
+        .await
+        .expect("read .fd");
     assert!(
-        progresses.iter().any(|(_, bps, _, _, _, _, _, _)| *bps > 0),
-        "expected at least one Event::Progress with bps > 0"
+        fd.contains(ORIGINAL_SHA256),
+        "the .fd records the digest: {fd}"
     );
 
-    // The terminal Progress reflects 100% coverage of the whole file.
-    let last = progresses.last().expect("last Progress");
-    assert_eq!(
-        last.0,
-        vec![0u64..FILE_SIZE as u64],
-        "final Event::Progress must cover the whole file"
+    let (tx, rx) = create_channel();
+    resume(
+        part,
+        Some(Url::parse(&url).expect("valid url")),
+        cfg,
+        tx,
+        create_cancellation_token(),
     );
-    assert_eq!(last.5, FILE_SIZE as u64);
+    let events = drain(rx).await;
     assert!(
-        (last.4 - 100.0).abs() < f64::EPSILON,
-        "final Event::Progress.percent must be 100.0, got {}",
-        last.4
+        events
+            .iter()
+            .any(|e| matches!(e, Event::Verified(d) if d.to_string() == ORIGINAL_SHA256))
     );
+    assert!(matches!(events.last(), Some(Event::Completed(_))));
 }
 
-/// Regression guard for persisted `elapsed`: after a mid-download cancel the
-/// `.fd` state records the active time spent; a subsequent `resume()` must
-/// continue that clock (its first `Progress` reports an `elapsed` at least as
-/// large as the cancelled run's last one) instead of resetting to zero, so the
-/// session-wide `avg_bps` and `downloaded` span the whole download.
+/// A file that does not match the digest the server advertised is reported as
+/// `IntegrityError` and never renamed; the check can be turned off.
 #[tokio::test]
-async fn test_progress_elapsed_persisted_across_resume() {
-    let dir = temp_dir("progress_elapsed_resume");
-    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
-
-    let cancel = create_cancellation_token();
-    let partial_events = partial_download_via_cancel(&url, &dir, cancel).await;
+async fn test_server_digest_mismatch_reports_integrity_error() {
+    let dir = temp_dir("server_digest_mismatch");
+    let (server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    server
+        .set_repr_digest("sha-256=:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=:")
+        .await;
 
-    let partial_elapsed_max = partial_events
-        .iter()
-        .filter_map(|e| match e {
-            Event::Progress(s) => Some(s.elapsed),
-            _ => None,
-        })
-        .max()
-        .expect("partial run must emit at least one Progress with elapsed");
+    let (tx, rx) = create_channel();
+    download(
+        Url::parse(&url).expect("valid url"),
+        make_config(&dir),
+        tx,
+        create_cancellation_token(),
+    );
+    let events = drain(rx).await;
+    let actual = events.iter().find_map(|e| match e {
+        Event::IntegrityError { actual, .. } => Some(actual.to_string()),
+        _ => None,
+    });
+    assert_eq!(actual.as_deref(), Some(ORIGINAL_SHA256));
+    assert!(matches!(events.last(), Some(Event::Failed { .. })));
+    assert!(!dir.join("out.bin").exists());
 
-    let final_path = dir.join("out.bin");
-    let part = final_path.with_added_extension("part");
-    let fd = final_path.with_added_extension("fd");
-    assert!(
-        part.exists() && fd.exists(),
-        "cancel must preserve both .part and .fd"
+    let (tx, rx) = create_channel();
+    download(
+        Url::parse(&url).expect("valid url"),
+        PartialConfig {
+            verify_server_digest: Some(false),
+            ..make_config(&dir)
+        },
+        tx,
+        create_cancellation_token(),
     );
+    let events = drain(rx).await;
+    assert!(
+        !events
+            .iter()
+            .any(|e| matches!(e, Event::IntegrityError { .. }))
+    );
+    match events.last() {
+        Some(Event::Completed(outcome)) => assert_eq!(
+            outcome.bytes_pulled, FILE_SIZE as u64,
+            "the file that failed the check is pulled again"
+        ),
+        other => panic!("expected Completed, got {other:?}"),
+    }
+}
+
+/// Mirrors serving the same file share the download with the primary URL; a
+/// mirror whose identity differs is rejected during prefetch and never used.
+#[tokio::test]
+async fn test_mirrors_share_download_and_mismatch_is_rejected() {
+    let dir = temp_dir("mirrors");
+    let (primary, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let (mirror, mirror_url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let (stale, stale_url) = start_server(original_bytes(), "stale", "LM-B", true).await;
+    let stale_url = Url::parse(&stale_url).expect("valid url");
+    let cfg = PartialConfig {
+        mirrors: Some(vec![
+            Url::parse(&mirror_url).expect("valid url"),
+            stale_url.clone(),
+        ]),
+        ..make_config_with(&dir, 8, 256 * 1024)
+    };
 
-    let cfg = make_config(&dir);
     let (tx, rx) = create_channel();
-    let resume_cancel = create_cancellation_token();
-    resume(
-        part.clone(),
-        Some(Url::parse(&url).expect("valid url")),
+    download(
+        Url::parse(&url).expect("valid url"),
         cfg,
         tx,
-        resume_cancel,
+        create_cancellation_token(),
     );
     let events = drain(rx).await;
 
-    let resume_progress: Vec<(Vec<fast_down::ProgressEntry>, u64, Duration, f64, u64)> = events
+    let rejected: Vec<_> = events
         .iter()
         .filter_map(|e| match e {
-            Event::Progress(s) => Some((
-                s.progress.clone(),
-                s.downloaded,
-                s.elapsed,
-                s.percent,
-                s.total,
+            Event::MirrorRejected(url, _) => Some(url),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(rejected, [&stale_url], "only the stale mirror is rejected");
+    assert!(matches!(events.last(), Some(Event::Completed(_))));
+    let got = tokio::fs::read(dir.join("out.bin"))
+        .await
+        .expect("read final file");
+    assert_eq!(got, original_bytes());
+    assert!(primary.ranged_hits.load(Ordering::Relaxed) > 0);
+    assert!(
+        mirror.ranged_hits.load(Ordering::Relaxed) > 0,
+        "the accepted mirror must serve ranges too"
+    );
+    // Only the prefetch probe reached the rejected mirror.
+    assert!(stale.ranged_hits.load(Ordering::Relaxed) <= 1);
+}
+
+/// A `.meta4` URL turns into a job downloading from its preferred source with
+/// the others as mirrors, saved under the listed name and verified against the
+/// listed hash. The hash lets a mirror with a different `ETag` take part.
+#[tokio::test]
+async fn test_metalink_drives_mirrors_and_verification() {
+    let dir = temp_dir("metalink");
+    let (primary, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let (mirror, mirror_url) = start_server(original_bytes(), "other", "LM-B", true).await;
+    let sha256 = ORIGINAL_SHA256.trim_start_matches("sha256:");
+    let xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<metalink xmlns="urn:ietf:params:xml:ns:metalink">
+  <file name="sub/app.bin">
+    <size>{FILE_SIZE}</size>
+    <hash type="sha-256">{sha256}</hash>
+    <url priority="2">{mirror_url}/app.bin</url>
+    <url priority="1">{url}/app.bin</url>
+  </file>
+</metalink>"#
+    );
+    let (_meta_server, meta_url) = start_server(xml.into_bytes(), "meta", "LM-M", false).await;
+    let base = PartialConfig {
+        filename: None,
+        ..make_config_with(&dir, 8, 256 * 1024)
+    };
+
+    let jobs = load_metalink(
+        MetalinkSource::Url(Url::parse(&format!("{meta_url}/app.meta4")).expect("valid url")),
+        &base,
+        None,
+    )
+    .await
+    .expect("load metalink");
+    let [job] = &jobs[..] else {
+        panic!("expected one job, got {}", jobs.len());
+    };
+    assert_eq!(job.url.as_str(), format!("{url}/app.bin"));
+
+    let (tx, rx) = create_channel();
+    download(
+        job.url.clone(),
+        job.config.clone(),
+        tx,
+        create_cancellation_token(),
+    );
+    let events = drain(rx).await;
+
+    assert!(
+        !events
+            .iter()
+            .any(|e| matches!(e, Event::MirrorRejected(..)))
+    );
+    assert!(
+        events
+            .iter()
+            .any(|e| matches!(e, Event::Verified(d) if d.to_string() == ORIGINAL_SHA256))
+    );
+    assert!(matches!(events.last(), Some(Event::Completed(_))));
+    let got = tokio::fs::read(dir.join("sub").join("app.bin"))
+        .await
+        .expect("read final file");
+    assert_eq!(got, original_bytes());
+    assert!(primary.ranged_hits.load(Ordering::Relaxed) > 0);
+    assert!(mirror.ranged_hits.load(Ordering::Relaxed) > 0);
+}
+
+/// The metalink of `url`, one `app.bin` of `FILE_SIZE` bytes with a source on
+/// `primary` in the US and one on `mirror` in Germany, at equal priority.
+fn located_metalink(primary: &str, mirror: &str) -> String {
+    let sha256 = ORIGINAL_SHA256.trim_start_matches("sha256:");
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<metalink xmlns="urn:ietf:params:xml:ns:metalink">
+  <file name="app.bin">
+    <size>{FILE_SIZE}</size>
+    <hash type="sha-256">{sha256}</hash>
+    <url location="us">{primary}/app.bin</url>
+    <url location="de">{mirror}/app.bin</url>
+  </file>
+</metalink>"#
+    )
+}
+
+/// `download` of a `.meta4` URL saves nothing and hands back the jobs of the
+/// listed files, preferring the sources in `metalink_location` and carrying
+/// the listed size.
+#[tokio::test]
+async fn test_download_of_metalink_url_expands_into_jobs() {
+    let dir = temp_dir("metalink_expand");
+    let (_primary, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let (_mirror, mirror_url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let xml = located_metalink(&url, &mirror_url);
+    let (_meta_server, meta_url) = start_server(xml.into_bytes(), "meta", "LM-M", false).await;
+    let base = PartialConfig {
+        filename: None,
+        metalink_location: Some(Some("DE".to_string())),
+        ..make_config_with(&dir, 8, 256 * 1024)
+    };
+
+    let (tx, rx) = create_channel();
+    download(
+        Url::parse(&format!("{meta_url}/app.meta4")).expect("valid url"),
+        base.clone(),
+        tx,
+        create_cancellation_token(),
+    );
+    let events = drain(rx).await;
+    let Some(Event::Metalink(jobs)) = events.last() else {
+        panic!("expected the run to end with Metalink, got {events:?}");
+    };
+    let [job] = &jobs[..] else {
+        panic!("expected one job, got {}", jobs.len());
+    };
+    assert_eq!(job.url.as_str(), format!("{mirror_url}/app.bin"));
+    assert_eq!(job.config.expected_size, Some(Some(FILE_SIZE as u64)));
+    assert_eq!(job.config.follow_metalink, Some(false));
+    assert!(
+        std::fs::read_dir(&dir).expect("read dir").next().is_none(),
+        "the metalink itself is not saved"
+    );
+
+    let (tx, rx) = create_channel();
+    let saved = PartialConfig {
+        follow_metalink: Some(false),
+        ..base
+    };
+    download(
+        Url::parse(&format!("{meta_url}/app.meta4")).expect("valid url"),
+        saved,
+        tx,
+        create_cancellation_token(),
+    );
+    let events = drain(rx).await;
+    assert!(matches!(events.last(), Some(Event::Completed(_))));
+    assert!(dir.join("app.meta4").exists());
+}
+
+/// A `DownloadManager` job of a `.meta4` URL queues one job per listed file
+/// and counts as completed.
+#[tokio::test]
+async fn test_manager_expands_metalink_jobs() {
+    let dir = temp_dir("metalink_manager");
+    let (_primary, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let (_mirror, mirror_url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let xml = located_metalink(&url, &mirror_url);
+    let (_meta_server, meta_url) = start_server(xml.into_bytes(), "meta", "LM-M", false).await;
+
+    let (tx, rx) = create_manager_channel();
+    let manager = DownloadManager::new(1, 8, tx, create_cancellation_token());
+    let parent = manager.add(DownloadJob {
+        url: Url::parse(&format!("{meta_url}/app.meta4")).expect("valid url"),
+        config: PartialConfig {
+            filename: None,
+            ..make_config_with(&dir, 8, 256 * 1024)
+        },
+        priority: 3,
+    });
+    timeout(Duration::from_mins(1), manager.join())
+        .await
+        .expect("every job must end");
+    drop(manager);
+    let mut children = Vec::new();
+    let mut completed = Vec::new();
+    while let Ok(event) = rx.recv().await {
+        match event {
+            ManagerEvent::Expanded(id, ids) => {
+                assert_eq!(id, parent);
+                children = ids;
+            }
+            ManagerEvent::Ended(id, outcome) => {
+                completed.push((id, outcome.is_some_and(|o| o.is_completed())));
+            }
+            _ => {}
+        }
+    }
+    let [child] = children[..] else {
+        panic!("expected one child job, got {children:?}");
+    };
+    assert_eq!(completed, [(parent, false), (child, true)]);
+    let got = tokio::fs::read(dir.join("app.bin"))
+        .await
+        .expect("read final file");
+    assert_eq!(got, original_bytes());
+}
+
+/// An error page is reported as a failed fetch, not as a bad document.
+#[tokio::test]
+async fn test_metalink_error_page_is_a_fetch_error() {
+    let (_server, url) = start_server(Vec::new(), "meta", "LM-M", false).await;
+    let err = load_metalink(
+        MetalinkSource::Url(Url::parse(&format!("{url}/missing")).expect("valid url")),
+        &PartialConfig::default(),
+        None,
+    )
+    .await
+    .expect_err("a 404 has no jobs");
+    assert!(matches!(err, MetalinkLoadError::Fetch(_)), "{err:?}");
+}
+
+/// A server reporting another size than `expected_size` is refused before
+/// anything is written.
+#[tokio::test]
+async fn test_size_mismatch_stops_before_writing() {
+    let dir = temp_dir("size_mismatch");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let config = PartialConfig {
+        expected_size: Some(Some(FILE_SIZE as u64 + 1)),
+        ..make_config(&dir)
+    };
+
+    let (tx, rx) = create_channel();
+    download(
+        Url::parse(&url).expect("valid url"),
+        config,
+        tx,
+        create_cancellation_token(),
+    );
+    let events = drain(rx).await;
+    assert!(matches!(
+        events.last(),
+        Some(Event::SizeMismatch { expected, actual })
+            if *expected == FILE_SIZE as u64 + 1 && *actual == FILE_SIZE as u64
+    ));
+    assert!(!dir.join("out.bin.part").exists());
+}
+
+/// Pieces that fail their hash are dropped from the `.fd` progress, so the
+/// next run pulls only them again.
+#[tokio::test]
+async fn test_piece_mismatch_refetches_only_bad_pieces() {
+    const PIECE: u64 = 1024 * 1024;
+    const PIECE_SHA256: &str =
+        "sha256:c4145364a3ba46002fb14242872f795535bae6738b1e47ba21eb405cfdf820a5";
+    let dir = temp_dir("piece_mismatch");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let pieces = |bad: Option<usize>| {
+        let hashes = (0..FILE_SIZE / PIECE as usize)
+            .map(|i| {
+                let hash = if Some(i) == bad {
+                    ORIGINAL_SHA256
+                } else {
+                    PIECE_SHA256
+                };
+                hash.parse().expect("valid digest")
+            })
+            .collect();
+        PartialConfig {
+            expected_pieces: Some(Some(fast_down_api::fast_down::PieceHashes {
+                length: PIECE,
+                hashes,
+            })),
+            ..make_config_with(&dir, 8, 256 * 1024)
+        }
+    };
+
+    let (tx, rx) = create_channel();
+    download(
+        Url::parse(&url).expect("valid url"),
+        pieces(Some(2)),
+        tx,
+        create_cancellation_token(),
+    );
+    let events = drain(rx).await;
+    assert!(
+        events
+            .iter()
+            .any(|e| matches!(e, Event::PieceMismatch(bad) if bad == &[2]))
+    );
+
+    let (tx, rx) = create_channel();
+    download(
+        Url::parse(&url).expect("valid url"),
+        pieces(None),
+        tx,
+        create_cancellation_token(),
+    );
+    let events = drain(rx).await;
+    let Some(Event::Completed(outcome)) = events.last() else {
+        panic!("expected Completed, got {:?}", events.last());
+    };
+    assert_eq!(outcome.bytes_pulled, PIECE, "only the bad piece is pulled");
+    let got = tokio::fs::read(dir.join("out.bin"))
+        .await
+        .expect("read final file");
+    assert_eq!(got, original_bytes());
+}
+
+/// A run that reaches the engine ends with exactly one definitive final event:
+/// `Completed` after a successful rename, `Failed` carrying an `Aborted`
+/// outcome after a cancel.
+#[tokio::test]
+async fn test_run_ends_with_one_final_event() {
+    let dir = temp_dir("final_event");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+
+    let cancel = create_cancellation_token();
+    let events = partial_download_via_cancel(&url, &dir, cancel).await;
+    let finals = events
+        .iter()
+        .filter(|e| matches!(e, Event::Completed(_) | Event::Failed { .. }))
+        .count();
+    assert_eq!(finals, 1, "exactly one final event per run");
+    match events.last() {
+        Some(Event::Failed {
+            outcome: Some(outcome),
+            ..
+        }) => assert_eq!(outcome.status, DownloadStatus::Aborted),
+        other => panic!("a cancelled run must end with Failed, got {other:?}"),
+    }
+
+    let (tx, rx) = create_channel();
+    download(
+        Url::parse(&url).expect("valid url"),
+        make_config(&dir),
+        tx,
+        create_cancellation_token(),
+    );
+    let events = drain(rx).await;
+    let finals = events
+        .iter()
+        .filter(|e| matches!(e, Event::Completed(_) | Event::Failed { .. }))
+        .count();
+    assert_eq!(finals, 1, "exactly one final event per run");
+    match events.last() {
+        Some(Event::Completed(outcome)) => {
+            assert!(outcome.is_completed());
+            assert!(outcome.bytes_pushed > 0);
+            assert!(outcome.bytes_pushed <= FILE_SIZE as u64);
+        }
+        other => panic!("a finished run must end with Completed, got {other:?}"),
+    }
+}
+
+/// `write_ndjson` streams a whole run as JSON lines that parse back into
+/// `EventRecord`s, from the prefetch to the final `completed` record.
+#[tokio::test]
+async fn test_events_stream_as_ndjson() {
+    let dir = temp_dir("ndjson");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+
+    let (tx, rx) = create_channel();
+    download(
+        Url::parse(&url).expect("valid url"),
+        make_config(&dir),
+        tx,
+        create_cancellation_token(),
+    );
+    let mut out = Vec::new();
+    let written = write_ndjson(rx, &mut out).await.expect("write to memory");
+    let records: Vec<EventRecord> = std::str::from_utf8(&out)
+        .expect("UTF-8 lines")
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("each line is an EventRecord"))
+        .collect();
+    assert_eq!(records.len() as u64, written);
+    assert!(
+        matches!(records.first(), Some(EventRecord::Prefetch(info)) if info.size == FILE_SIZE as u64)
+    );
+    assert!(
+        records
+            .iter()
+            .any(|r| matches!(r, EventRecord::Start { .. }))
+    );
+    match records.last() {
+        Some(EventRecord::Completed(outcome)) => {
+            assert_eq!(outcome.status, OutcomeStatus::Completed);
+        }
+        other => panic!("the stream must end with completed, got {other:?}"),
+    }
+}
+
+/// `Config::verbosity` keeps events above the level off the channel, while the
+/// run still tracks every written range: the file completes, and the handle's
+/// progress covers all of it.
+#[tokio::test]
+async fn test_verbosity_filters_events() {
+    for verbosity in [Verbosity::Lifecycle, Verbosity::Progress] {
+        let dir = temp_dir(&format!("verbosity_{verbosity:?}"));
+        let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+
+        let (tx, rx) = create_channel();
+        let mut config = make_config(&dir);
+        config.verbosity = Some(verbosity);
+        let handle = download(
+            Url::parse(&url).expect("valid url"),
+            config,
+            tx,
+            create_cancellation_token(),
+        );
+        let events = drain(rx).await;
+        assert!(
+            events.iter().all(|e| verbosity.includes(e.verbosity())),
+            "{verbosity:?} let through {events:?}"
+        );
+        assert!(!events.iter().any(|e| matches!(
+            e,
+            Event::PullProgress(..) | Event::Pushing(..) | Event::PushProgress(_)
+        )));
+        assert_eq!(
+            events.iter().any(|e| matches!(e, Event::Progress(_))),
+            verbosity == Verbosity::Progress
+        );
+        assert!(matches!(events.last(), Some(Event::Completed(_))));
+        assert_eq!(
+            std::fs::read(dir.join("out.bin")).expect("read output"),
+            original_bytes()
+        );
+        let progress = handle.progress().expect("the engine ran");
+        assert_eq!(progress.downloaded, FILE_SIZE as u64);
+    }
+}
+
+/// Pausing through the `DownloadHandle` stops every write without ending the
+/// run; unpausing continues the same session to a complete, correct file.
+#[tokio::test]
+async fn test_handle_pause_and_unpause() {
+    let dir = temp_dir("handle_pause");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+
+    let (tx, rx) = create_channel();
+    let handle = download(
+        Url::parse(&url).expect("valid url"),
+        make_config(&dir),
+        tx,
+        create_cancellation_token(),
+    );
+    let mut events = Vec::new();
+    while let Ok(e) = rx.recv().await {
+        let first_write = matches!(e, Event::PushProgress(_));
+        events.push(e);
+        if first_write {
+            break;
+        }
+    }
+    handle.pause();
+    assert!(handle.is_paused());
+    handle.set_threads(4);
+
+    // Chunks already in flight may still land right after the pause.
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    while let Ok(e) = rx.try_recv() {
+        events.push(e);
+    }
+    let mut paused_writes = 0;
+    let window = timeout(Duration::from_secs(1), async {
+        while let Ok(e) = rx.recv().await {
+            if matches!(e, Event::PushProgress(_)) {
+                paused_writes += 1;
+            }
+            events.push(e);
+        }
+    })
+    .await;
+    assert!(window.is_err(), "a paused run must not end on its own");
+    assert_eq!(paused_writes, 0, "nothing may be written while paused");
+
+    handle.unpause();
+    events.extend(drain(rx).await);
+    assert!(
+        matches!(events.last(), Some(Event::Completed(_))),
+        "an unpaused run must complete, got {:?}",
+        events.last()
+    );
+    assert_eq!(
+        events
+            .iter()
+            .filter(|e| matches!(e, Event::Start { .. }))
+            .count(),
+        1,
+        "pausing must not restart the session"
+    );
+    let got = tokio::fs::read(dir.join("out.bin"))
+        .await
+        .expect("read final file");
+    assert_eq!(got, original_bytes());
+}
+
+/// Tuning set through the handle before the engine starts is applied when it
+/// does, `progress` reports live samples, and `join` returns the outcome of the
+/// final event.
+#[tokio::test]
+async fn test_handle_tuning_progress_and_join() {
+    let dir = temp_dir("handle_tuning");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+
+    let (tx, rx) = create_channel();
+    let handle = download(
+        Url::parse(&url).expect("valid url"),
+        make_config_with(&dir, 1, 1024 * 1024),
+        tx,
+        create_cancellation_token(),
+    );
+    handle.set_threads(4);
+    handle.set_min_chunk_size(256 * 1024);
+    handle.set_max_speculative(2);
+    let events = tokio::spawn(drain(rx));
+
+    let live = timeout(Duration::from_secs(30), async {
+        loop {
+            if let Some(sample) = handle.progress()
+                && sample.downloaded > 0
+            {
+                break sample;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("progress must report written bytes while running");
+    assert_eq!(live.total, FILE_SIZE as u64);
+
+    let outcome = timeout(Duration::from_mins(1), handle.join())
+        .await
+        .expect("join must resolve once the run ends")
+        .expect("a run that reached the engine has an outcome");
+    assert!(outcome.is_completed());
+    assert!(handle.is_finished());
+    assert_eq!(
+        handle.progress().map(|s| s.downloaded),
+        Some(FILE_SIZE as u64)
+    );
+
+    let events = events.await.expect("drain task");
+    match events.last() {
+        Some(Event::Completed(last)) => assert_eq!(last, &outcome),
+        other => panic!("a finished run must end with Completed, got {other:?}"),
+    }
+    let workers: std::collections::BTreeSet<_> = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Pulling(id) => Some(*id),
+            _ => None,
+        })
+        .collect();
+    assert!(
+        workers.len() > 1,
+        "set_threads before the start must override threads = 1, got {workers:?}"
+    );
+
+    // A run that never reaches the engine joins with no outcome.
+    let (tx, _rx) = create_channel();
+    let handle = resume(
+        dir.join("out.bin"),
+        None,
+        PartialConfig::default(),
+        tx,
+        create_cancellation_token(),
+    );
+    assert_eq!(handle.join().await, None);
+}
+
+/// A manager limited to one active download starts queued jobs by priority,
+/// tags every forwarded event with its job, and reports a job cancelled while
+/// queued as ended without starting it.
+#[tokio::test]
+async fn test_manager_runs_jobs_by_priority() {
+    let dir = temp_dir("manager");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let job = |name: &str, priority| DownloadJob {
+        url: Url::parse(&url).expect("valid url"),
+        config: PartialConfig {
+            filename: Some(name.to_string()),
+            ..make_config_with(&dir, 8, 256 * 1024)
+        },
+        priority,
+    };
+
+    let (tx, rx) = create_manager_channel();
+    let manager = DownloadManager::new(1, 4, tx, create_cancellation_token());
+    let first = manager.add(job("a.bin", 0));
+    let bumped = manager.add(job("b.bin", 0));
+    let normal = manager.add(job("c.bin", 5));
+    let dropped = manager.add(job("d.bin", 9));
+    assert_eq!(manager.active(), [first]);
+    assert_eq!(manager.queued(), [dropped, normal, bumped]);
+    assert!(manager.set_priority(bumped, 7));
+    assert!(manager.cancel(dropped));
+    assert_eq!(manager.queued(), [bumped, normal]);
+    let fewer = PartialConfig {
+        threads: Some(2),
+        ..Default::default()
+    };
+    assert!(manager.reconfigure(normal, fewer.clone()));
+    assert!(!manager.reconfigure(dropped, fewer));
+    let reconfigured = manager.job(normal).expect("known job").config;
+    assert_eq!(reconfigured.threads, Some(2));
+    assert_eq!(reconfigured.filename.as_deref(), Some("c.bin"), "kept");
+
+    timeout(Duration::from_mins(1), manager.join())
+        .await
+        .expect("every job must end");
+    drop(manager);
+    let events = timeout(Duration::from_secs(5), async {
+        let mut events = Vec::new();
+        while let Ok(e) = rx.recv().await {
+            events.push(e);
+        }
+        events
+    })
+    .await
+    .expect("the channel closes once the manager and its jobs are gone");
+
+    let started: Vec<_> = events
+        .iter()
+        .filter_map(|e| match e {
+            ManagerEvent::Started(id) => Some(*id),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(started, [first, bumped, normal]);
+    assert!(
+        events
+            .iter()
+            .any(|e| matches!(e, ManagerEvent::Ended(id, None) if *id == dropped))
+    );
+    for (id, name) in [(first, "a.bin"), (bumped, "b.bin"), (normal, "c.bin")] {
+        let ended = events.iter().position(
+            |e| matches!(e, ManagerEvent::Ended(i, Some(o)) if *i == id && o.is_completed()),
+        );
+        let completed = events
+            .iter()
+            .position(|e| matches!(e, ManagerEvent::Job(i, Event::Completed(_)) if *i == id));
+        assert!(
+            completed.is_some() && completed < ended,
+            "job {id} must forward Completed before it ends"
+        );
+        let got = tokio::fs::read(dir.join(name)).await.expect("read file");
+        assert_eq!(got, original_bytes());
+    }
+}
+
+/// Cancelling a manager with a session file leaves the running job recorded
+/// as running with its `.part` path and the queued one as queued; restoring
+/// the session resumes the first from its progress and runs the second.
+#[tokio::test]
+async fn test_manager_session_restores_interrupted_and_queued_jobs() {
+    let dir = temp_dir("manager_session");
+    let session_path = dir.join("session.toml");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let job = |name: &str| DownloadJob {
+        url: Url::parse(&url).expect("valid url"),
+        config: PartialConfig {
+            filename: Some(name.to_string()),
+            ..make_config(&dir)
+        },
+        priority: 0,
+    };
+
+    let (tx, rx) = create_manager_channel();
+    let token = create_cancellation_token();
+    let manager = DownloadManager::restore(&session_path, 1, 0, tx, token.clone())
+        .await
+        .expect("a missing session file is an empty session");
+    let running = manager.add(job("a.bin"));
+    let queued = manager.add(job("b.bin"));
+    let mut written = 0;
+    while written < CANCEL_AFTER_BYTES {
+        match rx.recv().await.expect("manager events") {
+            ManagerEvent::Job(id, Event::PushProgress(p)) if id == running => {
+                written += p.end - p.start;
+            }
+            _ => {}
+        }
+    }
+    token.cancel();
+    timeout(Duration::from_secs(30), manager.join())
+        .await
+        .expect("a cancelled manager goes idle");
+    manager.save_session().await.expect("save session");
+    assert_eq!(manager.status(running), Some(JobStatus::Running));
+    assert_eq!(manager.status(queued), Some(JobStatus::Queued));
+    drop(manager);
+
+    let session = Session::load(&session_path).await.expect("load session");
+    let [a, b] = &session.jobs[..] else {
+        panic!("expected two jobs, got {session:?}");
+    };
+    assert_eq!((a.id, a.status), (running, JobStatus::Running));
+    assert_eq!(a.tmp_path.as_deref(), Some(&*dir.join("a.bin.part")));
+    assert_eq!(
+        (b.id, b.status, b.tmp_path.as_ref()),
+        (queued, JobStatus::Queued, None)
+    );
+
+    let (tx, rx) = create_manager_channel();
+    let manager = DownloadManager::restore(&session_path, 1, 0, tx, create_cancellation_token())
+        .await
+        .expect("restore session");
+    timeout(Duration::from_mins(1), manager.join())
+        .await
+        .expect("restored jobs must end");
+    manager.save_session().await.expect("save session");
+    drop(manager);
+    let mut events = Vec::new();
+    while let Ok(e) = rx.recv().await {
+        events.push(e);
+    }
+    assert!(
+        events.iter().any(
+            |e| matches!(e, ManagerEvent::Job(id, Event::Resumed { progress, .. }) if *id == running && !progress.is_empty())
+        ),
+        "the interrupted job must resume from its .part file"
+    );
+    let session = Session::load(&session_path).await.expect("load session");
+    assert!(
+        session
+            .jobs
+            .iter()
+            .all(|j| j.status == JobStatus::Completed),
+        "{session:?}"
+    );
+    for name in ["a.bin", "b.bin"] {
+        let got = tokio::fs::read(dir.join(name)).await.expect("read file");
+        assert_eq!(got, original_bytes());
+    }
+}
+
+/// Drive a download through the aria2 JSON-RPC server the way a front-end
+/// does: `addUri` over HTTP, notifications and `tellStatus` over WebSocket.
+#[cfg(feature = "rpc")]
+#[tokio::test]
+async fn test_rpc_server_drives_manager_like_aria2() {
+    use fast_down_api::{RPC_PATH, RpcConfig, RpcServer};
+    use futures::SinkExt;
+    use serde_json::{Value, json};
+    use tokio_tungstenite::tungstenite::Message;
+
+    async fn next_json<S, E>(ws: &mut S) -> Value
+    where
+        S: futures::Stream<Item = Result<Message, E>> + Unpin,
+        E: std::fmt::Debug,
+    {
+        loop {
+            if let Message::Text(text) = ws.next().await.expect("socket open").expect("frame") {
+                return serde_json::from_str(&text).expect("JSON");
+            }
+        }
+    }
+
+    let dir = temp_dir("rpc");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let (tx, rx) = create_manager_channel();
+    let token = create_cancellation_token();
+    let manager = DownloadManager::new(2, 0, tx, token.clone());
+    let config = RpcConfig {
+        secret: Some("s3cret".to_string()),
+        defaults: make_config_with(&dir, 4, 256 * 1024),
+        ..Default::default()
+    };
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let addr = listener.local_addr().expect("local addr");
+    tokio::spawn(RpcServer::new(manager, rx, config).serve(listener, token.clone()));
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{addr}{RPC_PATH}"))
+        .await
+        .expect("websocket connects");
+    let reply = reqwest::Client::new()
+        .post(format!("http://{addr}{RPC_PATH}"))
+        .header("Content-Type", "application/json")
+        .body(
+            json!({
+                "jsonrpc": "2.0",
+                "id": "add",
+                "method": "aria2.addUri",
+                "params": ["token:s3cret", [url], { "out": "rpc.bin", "split": "2" }],
+            })
+            .to_string(),
+        )
+        .send()
+        .await
+        .expect("addUri is answered")
+        .text()
+        .await
+        .expect("reply body");
+    let reply: Value = serde_json::from_str(&reply).expect("JSON reply");
+    let gid = reply["result"]
+        .as_str()
+        .expect("addUri returns a GID")
+        .to_string();
+    assert_eq!(gid.len(), 16);
+
+    let notifications = timeout(Duration::from_mins(1), async {
+        let mut methods = Vec::new();
+        while methods.last() != Some(&"aria2.onDownloadComplete".to_string()) {
+            let message = next_json(&mut ws).await;
+            assert_eq!(message["params"][0]["gid"], gid.as_str());
+            methods.push(message["method"].as_str().expect("method").to_string());
+        }
+        methods
+    })
+    .await
+    .expect("the download must complete");
+    assert_eq!(
+        notifications,
+        ["aria2.onDownloadStart", "aria2.onDownloadComplete"]
+    );
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 7,
+        "method": "aria2.tellStatus",
+        "params": ["token:s3cret", gid, ["status", "totalLength", "completedLength", "files"]],
+    });
+    ws.send(Message::Text(request.to_string().into()))
+        .await
+        .expect("send tellStatus");
+    let reply = next_json(&mut ws).await;
+    assert_eq!(reply["id"], 7);
+    let status = &reply["result"];
+    assert_eq!(status["status"], "complete");
+    assert_eq!(status["totalLength"], FILE_SIZE.to_string());
+    assert_eq!(status["completedLength"], FILE_SIZE.to_string());
+    let path = status["files"][0]["path"].as_str().expect("file path");
+    assert_eq!(Path::new(path), dir.join("rpc.bin"));
+    assert_eq!(std::fs::read(path).expect("saved file"), original_bytes());
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 8,
+        "method": "aria2.remove",
+        "params": ["token:s3cret", gid],
+    });
+    ws.send(Message::Text(request.to_string().into()))
+        .await
+        .expect("send remove");
+    assert_eq!(
+        next_json(&mut ws).await["error"]["code"],
+        1,
+        "a finished job cannot be removed"
+    );
+
+    token.cancel();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Start an RPC server without a secret on a loopback port, returning its
+/// address.
+#[cfg(feature = "rpc")]
+async fn start_rpc_server(
+    defaults: PartialConfig,
+    allow_origin_all: bool,
+    token: &CancellationToken,
+) -> std::net::SocketAddr {
+    use fast_down_api::{RpcConfig, RpcServer};
+
+    let (tx, rx) = create_manager_channel();
+    let manager = DownloadManager::new(2, 0, tx, token.clone());
+    let config = RpcConfig {
+        allow_origin_all,
+        defaults,
+        ..Default::default()
+    };
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let addr = listener.local_addr().expect("local addr");
+    tokio::spawn(RpcServer::new(manager, rx, config).serve(listener, token.clone()));
+    addr
+}
+
+/// A web page must not reach the RPC server: a cross-origin `POST` or
+/// WebSocket upgrade is refused, as is a body not declared as JSON, which a
+/// browser can send without a preflight.
+#[cfg(feature = "rpc")]
+#[tokio::test]
+async fn test_rpc_server_refuses_cross_origin_requests() {
+    use fast_down_api::RPC_PATH;
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http::HeaderValue};
+
+    let token = create_cancellation_token();
+    let addr = start_rpc_server(PartialConfig::default(), false, &token).await;
+    let client = reqwest::Client::new();
+    let body = r#"{"jsonrpc":"2.0","id":1,"method":"aria2.getGlobalStat"}"#;
+    let post = |origin: Option<String>, content_type: &'static str| {
+        let mut request = client
+            .post(format!("http://{addr}{RPC_PATH}"))
+            .header("Content-Type", content_type)
+            .body(body);
+        if let Some(origin) = origin {
+            request = request.header("Origin", origin);
+        }
+        async move { request.send().await.expect("answered").status().as_u16() }
+    };
+    assert_eq!(post(None, "application/json").await, 200);
+    assert_eq!(
+        post(Some(format!("http://{addr}")), "application/json").await,
+        200
+    );
+    assert_eq!(
+        post(Some("http://evil.example".into()), "application/json").await,
+        403
+    );
+    assert_eq!(post(None, "text/plain").await, 415);
+
+    let upgrade = |origin: &str| {
+        let mut request = format!("ws://{addr}{RPC_PATH}")
+            .into_client_request()
+            .expect("ws request");
+        request
+            .headers_mut()
+            .insert("Origin", HeaderValue::from_str(origin).expect("header"));
+        tokio_tungstenite::connect_async(request)
+    };
+    assert!(upgrade(&format!("http://{addr}")).await.is_ok());
+    match upgrade("http://evil.example").await {
+        Err(tokio_tungstenite::tungstenite::Error::Http(response)) => {
+            assert_eq!(response.status(), 403);
+        }
+        other => panic!("cross-origin upgrade must be refused, got {other:?}"),
+    }
+
+    let open = start_rpc_server(PartialConfig::default(), true, &token).await;
+    let reply = client
+        .post(format!("http://{open}{RPC_PATH}"))
+        .header("Content-Type", "application/json")
+        .header("Origin", "http://evil.example")
+        .body(body)
+        .send()
+        .await
+        .expect("answered");
+    assert_eq!(reply.status(), 200, "allow_origin_all lets any origin in");
+    token.cancel();
+}
+
+/// `aria2.changeOption` lifts the speed limit of a running job right away,
+/// and refuses options the job would only take on its next run.
+#[cfg(feature = "rpc")]
+#[tokio::test]
+async fn test_rpc_change_option_applies_speed_limit_live() {
+    use fast_down_api::RPC_PATH;
+    use serde_json::{Value, json};
+
+    let dir = temp_dir("rpc_change_option");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let token = create_cancellation_token();
+    let addr = start_rpc_server(make_config_with(&dir, 4, 256 * 1024), false, &token).await;
+    let client = reqwest::Client::new();
+    let call = |method: &'static str, params: Value| {
+        let request = client
+            .post(format!("http://{addr}{RPC_PATH}"))
+            .header("Content-Type", "application/json")
+            .body(
+                json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params })
+                    .to_string(),
+            );
+        async move {
+            let reply = request.send().await.expect("answered");
+            serde_json::from_str::<Value>(&reply.text().await.expect("body")).expect("JSON")
+        }
+    };
+
+    let reply = call(
+        "aria2.addUri",
+        json!([[url], { "out": "limited.bin", "max-download-limit": "1K" }]),
+    )
+    .await;
+    let gid = reply["result"].as_str().expect("GID").to_string();
+    let reply = call("aria2.changeOption", json!([gid, { "dir": "/elsewhere" }])).await;
+    assert_eq!(reply["error"]["code"], 1, "dir cannot change mid-run");
+    let reply = call(
+        "aria2.changeOption",
+        json!([gid, { "max-download-limit": "0" }]),
+    )
+    .await;
+    assert_eq!(reply["result"], "OK");
+
+    timeout(Duration::from_mins(1), async {
+        loop {
+            let reply = call("aria2.tellStatus", json!([gid, ["status"]])).await;
+            if reply["result"]["status"] == "complete" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("lifting the 1 KiB/s limit must let the download finish");
+    let got = tokio::fs::read(dir.join("limited.bin"))
+        .await
+        .expect("read final file");
+    assert_eq!(got, original_bytes());
+    token.cancel();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// `Event::Workers` lists the running workers on the progress cadence: unique
+/// ids, each with a range inside the file, while `DownloadHandle::workers`
+/// empties once the run is over.
+#[tokio::test]
+async fn test_workers_event_emitted() {
+    let dir = temp_dir("workers_event");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+
+    let mut cfg = make_config_with(&dir, 4, 1024 * 1024);
+    cfg.progress_emit_gap = Some(Duration::from_millis(30));
+    let (tx, rx) = create_channel();
+    let handle = download(
+        Url::parse(&url).expect("valid url"),
+        cfg,
+        tx,
+        create_cancellation_token(),
+    );
+    let events = drain(rx).await;
+
+    let snapshots: Vec<_> = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Workers(workers) => Some(workers),
+            _ => None,
+        })
+        .collect();
+    assert!(
+        snapshots.iter().any(|workers| !workers.is_empty()),
+        "expected a non-empty Event::Workers, got {snapshots:?}"
+    );
+    for workers in snapshots {
+        let mut ids: Vec<_> = workers.iter().map(|w| w.id).collect();
+        ids.dedup();
+        assert_eq!(ids.len(), workers.len(), "duplicate worker: {workers:?}");
+        for worker in workers {
+            let range = worker.range.clone().expect("a ranged download");
+            assert!(range.start <= range.end && range.end <= FILE_SIZE as u64);
+        }
+    }
+    assert!(matches!(events.last(), Some(Event::Completed(_))));
+    assert!(handle.workers().is_empty());
+}
+
+/// `threads = "auto"` starts the engine with a few workers, announced by an
+/// `Event::Concurrency` right after `Start`, and still downloads the whole
+/// file; `set_max_threads` caps the first pick.
+#[tokio::test]
+async fn test_auto_threads_reports_concurrency() {
+    let dir = temp_dir("auto_threads");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+
+    let mut cfg = make_config_with(&dir, 0, 1024 * 1024);
+    cfg.progress_emit_gap = Some(Duration::from_millis(30));
+    let (tx, rx) = create_channel();
+    let handle = download(
+        Url::parse(&url).expect("valid url"),
+        cfg,
+        tx,
+        create_cancellation_token(),
+    );
+    handle.set_max_threads(3);
+    let events = drain(rx).await;
+
+    let picks: Vec<_> = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Concurrency(threads) => Some(*threads),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(picks.first(), Some(&3), "got {picks:?}");
+    assert!(picks.iter().all(|n| (1..=3).contains(n)), "got {picks:?}");
+    let running = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Workers(workers) => Some(workers.len()),
+            _ => None,
+        })
+        .max()
+        .unwrap_or_default();
+    assert!(running <= 3, "{running} workers ran past the cap");
+    assert!(matches!(events.last(), Some(Event::Completed(_))));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Workers sharing one connection pool still each pull their own ranges and
+/// assemble the file correctly.
+#[tokio::test]
+async fn test_shared_connection_pool_downloads() {
+    let dir = temp_dir("shared_pool");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+
+    let mut cfg = make_config_with(&dir, 8, 64 * 1024);
+    cfg.connection_pool = Some(ConnectionPool::Shared);
+    let (tx, rx) = create_channel();
+    download(
+        Url::parse(&url).expect("valid url"),
+        cfg,
+        tx,
+        create_cancellation_token(),
+    );
+    let events = drain(rx).await;
+
+    assert!(matches!(events.last(), Some(Event::Completed(_))));
+    let got = tokio::fs::read(dir.join("out.bin"))
+        .await
+        .expect("read final file");
+    assert_eq!(got, original_bytes(), "file content mismatch");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// A `resolve` override sends a host that does not exist to the test server,
+/// for the prefetch and every worker alike.
+#[tokio::test]
+async fn test_resolve_override_reaches_the_server() {
+    let dir = temp_dir("resolve_override");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let port = Url::parse(&url).expect("valid url").port().expect("port");
+
+    let mut cfg = make_config_with(&dir, 4, 64 * 1024);
+    cfg.resolve = Some(vec![
+        format!("staging.invalid:{port}:127.0.0.1")
+            .parse()
+            .expect("valid override"),
+    ]);
+    cfg.dns_round_robin = Some(true);
+    let (tx, rx) = create_channel();
+    download(
+        Url::parse(&format!("http://staging.invalid:{port}/")).expect("valid url"),
+        cfg,
+        tx,
+        create_cancellation_token(),
+    );
+    let events = drain(rx).await;
+
+    assert!(matches!(events.last(), Some(Event::Completed(_))));
+    let got = tokio::fs::read(dir.join("out.bin"))
+        .await
+        .expect("read final file");
+    assert_eq!(got, original_bytes(), "file content mismatch");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// A URL without a port matches an override on the default port of its scheme.
+/// Skipped where port 80 cannot be bound.
+#[tokio::test]
+async fn test_resolve_override_keeps_the_default_port() {
+    let Ok(listener) = TcpListener::bind("127.0.0.1:80").await else {
+        return;
+    };
+    let dir = temp_dir("resolve_default_port");
+    let server = new_server(original_bytes(), "orig", "LM-A", true);
+    server.serve_on(listener);
+
+    let mut cfg = make_config_with(&dir, 4, 64 * 1024);
+    cfg.resolve = Some(vec![
+        "staging.invalid:80:127.0.0.1"
+            .parse()
+            .expect("valid override"),
+    ]);
+    let (tx, rx) = create_channel();
+    download(
+        Url::parse("http://staging.invalid/").expect("valid url"),
+        cfg,
+        tx,
+        create_cancellation_token(),
+    );
+    let events = drain(rx).await;
+
+    assert!(matches!(events.last(), Some(Event::Completed(_))));
+    let got = tokio::fs::read(dir.join("out.bin"))
+        .await
+        .expect("read final file");
+    assert_eq!(got, original_bytes(), "file content mismatch");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// An override for another port leaves the host to DNS, like curl's
+/// `--resolve`.
+#[tokio::test]
+async fn test_resolve_override_skips_other_ports() {
+    let dir = temp_dir("resolve_other_port");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+    let port = Url::parse(&url).expect("valid url").port().expect("port");
+
+    let mut cfg = make_config_with(&dir, 4, 64 * 1024);
+    cfg.resolve = Some(vec![
+        format!("staging.invalid:{}:127.0.0.1", port.wrapping_add(1))
+            .parse()
+            .expect("valid override"),
+    ]);
+    cfg.retry_times = Some(1);
+    cfg.retry_gap = Some(Duration::from_millis(10));
+    let (tx, rx) = create_channel();
+    download(
+        Url::parse(&format!("http://staging.invalid:{port}/")).expect("valid url"),
+        cfg,
+        tx,
+        create_cancellation_token(),
+    );
+    let events = timeout(Duration::from_secs(20), drain(rx))
+        .await
+        .expect("drain timed out");
+
+    assert!(
+        events.iter().any(|e| matches!(e, Event::PrefetchError(_))),
+        "the override must not answer for port {port}"
+    );
+    assert!(!events.iter().any(|e| matches!(e, Event::Completed(_))));
+    assert!(!dir.join("out.bin").exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Verify the `Event::Progress` aggregator: it is emitted on the configured
+/// cadence (not merely once), carries the full progress snapshot + a transfer
+/// rate, reports the correct total size, and reaches 100% coverage on a complete
+/// download. Regression guard for the dedicated, independent progress reporter.
+#[tokio::test]
+async fn test_progress_event_emitted() {
+    let dir = temp_dir("progress_event");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+
+    let mut cfg = make_config(&dir);
+    // Emit Progress frequently so the cadence is observable.
+    cfg.progress_emit_gap = Some(Duration::from_millis(30));
+
+    let (tx, rx) = create_channel();
+    let cancel = create_cancellation_token();
+    download(Url::parse(&url).expect("valid url"), cfg, tx, cancel);
+    let events = drain(rx).await;
+
+    // At least one Progress event, and it must fire on its own timer (not just
+    // the terminal one) — this is the whole point of the dedicated reporter.
+    let progresses: Vec<_> = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Progress(s) => Some((
+                s.progress.clone(),
+                s.bps,
+                s.avg_bps,
+                s.downloaded,
+                s.percent,
+                s.total,
+                s.elapsed,
+                s.eta,
+            )),
+            _ => None,
+        })
+        .collect();
+    assert!(
+        !progresses.is_empty(),
+        "expected at least one Event::Progress"
+    );
+    assert!(
+        progresses.len() >= 2,
+        "Event::Progress must fire on its own cadence, not only once (got {})",
+        progresses.len()
+    );
+
+    // Every Progress reports the correct total size.
+    assert!(
+        progresses
+            .iter()
+            .all(|(_, _, _, _, _, total, _, _)| *total == FILE_SIZE as u64),
+        "Event::Progress.total must equal the file size"
+    );
+
+    // Structural invariants for the new aggregate fields.
+    for (p, _, avg_bps, downloaded, percent, _, elapsed, _) in &progresses {
+        let sum: u64 = p.iter().map(|r| r.end - r.start).sum();
+        assert_eq!(
+            sum, *downloaded,
+            "Event::Progress.downloaded must equal sum(progress ranges)"
+        );
+        assert!(
+            (*percent >= 0.0) && (*percent <= 100.0),
+            "percent out of range: {percent}"
+        );
+        // avg_bps is session-wide bytes/sec; it can never exceed
+        // downloaded * 1000 (the rate if the whole file arrived in 1ms).
+        assert!(
+            *avg_bps <= (*downloaded).saturating_mul(1000).max(1),
+            "avg_bps implausibly high: {avg_bps}"
+        );
+        assert!(
+            *elapsed >= Duration::ZERO,
+            "elapsed must be non-negative: {elapsed:?}"
+        );
+    }
+
+    // `elapsed` (cumulative active time) must be non-decreasing across frames.
+    let mut prev_elapsed = Duration::ZERO;
+    for (_, _, _, _, _, _, el, _) in &progresses {
+        assert!(
+            *el >= prev_elapsed,
+            "Event::Progress.elapsed must be non-decreasing: {el:?} < {prev_elapsed:?}"
+        );
+        prev_elapsed = *el;
+    }
+
+    // `eta` (estimated remaining time) invariants:
+    // - At least one intermediate frame must carry a positive `eta`, proving ETA
+    //   is actually computed mid-download (the run is not instant).
+    // - `eta == (total - downloaded) / rate` with millisecond rounding, so we
+    //   check it within a 1ms tolerance against the effective rate.
+    assert!(
+        progresses
+            .iter()
+            .any(|(_, _, _, d, _, t, _, eta)| *d < *t && eta.is_some_and(|e| e > Duration::ZERO)),
+        "expected at least one intermediate Progress with eta > 0"
+    );
+    for (_, bps, avg_bps, downloaded, _, total, _, eta) in &progresses {
+        let remaining = total.saturating_sub(*downloaded);
+        // Mirrors the production formula: prefer the smoothed recent `bps`,
+        // fall back to the session-wide `avg_bps` while the EMA warms up.
+        let rate = if *bps > 0 { *bps } else { *avg_bps };
+        if remaining == 0 {
+            // Fully written: eta must be ZERO (absent only if rate is still 0).
+            assert!(
+                eta.map_or(rate == 0, |e| e == Duration::ZERO),
+                "eta must be ZERO once fully downloaded, got {eta:?}"
+            );
+        } else if rate > 0 {
+            let Some(d) = eta else {
+                panic!("eta must be Some while downloading with a measurable rate");
+            };
+            let expected_ms = u128::from(
+                remaining
+                    .checked_mul(1000)
+                    .and_then(|x| x.checked_div(rate))
+                    .expect("rate > 0"),
+            );
+            let got_ms = d.as_millis();
+            let diff = got_ms.abs_diff(expected_ms);
+            assert!(
+                diff <= 1,
+                "eta {got_ms}ms disagrees with computed {expected_ms}ms"
+            );
+        } else {
+            assert!(eta.is_none(), "eta must be None with no measurable rate");
+        }
+    }
+
+    // The download actually progressed: at some point the full size was reported.
+    let max_downloaded: u64 = progresses
+        .iter()
+        .map(|(_, _, _, d, _, _, _, _)| *d)
+        .max()
+        .unwrap_or(0);
+    assert_eq!(
+        max_downloaded, FILE_SIZE as u64,
+        "downloaded must reach the full file size"
+    );
+
+    // At least one intermediate frame reported a positive instantaneous rate.
+    assert!(
+        progresses.iter().any(|(_, bps, _, _, _, _, _, _)| *bps > 0),
+        "expected at least one Event::Progress with bps > 0"
+    );
+
+    // The terminal Progress reflects 100% coverage of the whole file.
+    let last = progresses.last().expect("last Progress");
+    assert_eq!(
+        last.0,
+        vec![0u64..FILE_SIZE as u64],
+        "final Event::Progress must cover the whole file"
+    );
+    assert_eq!(last.5, FILE_SIZE as u64);
+    assert!(
+        (last.4 - 100.0).abs() < f64::EPSILON,
+        "final Event::Progress.percent must be 100.0, got {}",
+        last.4
+    );
+}
+
+/// Regression guard for persisted `elapsed`: after a mid-download cancel the
+/// `.fd` state records the active time spent; a subsequent `resume()` must
+/// continue that clock (its first `Progress` reports an `elapsed` at least as
+/// large as the cancelled run's last one) instead of resetting to zero, so the
+/// session-wide `avg_bps` and `downloaded` span the whole download.
+#[tokio::test]
+async fn test_progress_elapsed_persisted_across_resume() {
+    let dir = temp_dir("progress_elapsed_resume");
+    let (_server, url) = start_server(original_bytes(), "orig", "LM-A", true).await;
+
+    let cancel = create_cancellation_token();
+    let partial_events = partial_download_via_cancel(&url, &dir, cancel).await;
+
+    let partial_elapsed_max = partial_events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Progress(s) => Some(s.elapsed),
+            _ => None,
+        })
+        .max()
+        .expect("partial run must emit at least one Progress with elapsed");
+
+    let final_path = dir.join("out.bin");
+    let part = final_path.with_added_extension("part");
+    let fd = final_path.with_added_extension("fd");
+    assert!(
+        part.exists() && fd.exists(),
+        "cancel must preserve both .part and .fd"
+    );
+
+    let cfg = make_config(&dir);
+    let (tx, rx) = create_channel();
+    let resume_cancel = create_cancellation_token();
+    resume(
+        part.clone(),
+        Some(Url::parse(&url).expect("valid url")),
+        cfg,
+        tx,
+        resume_cancel,
+    );
+    let events = drain(rx).await;
+
+    let resume_progress: Vec<(Vec<fast_down::ProgressEntry>, u64, Duration, f64, u64)> = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Progress(s) => Some((
+                s.progress.clone(),
+                s.downloaded,
+                s.elapsed,
+                s.percent,
+                s.total,
             )),
             _ => None,
         })
@@ -1486,6 +2969,18 @@ async fn test_prefetch_retries_then_gives_up() {
         events.iter().any(|e| matches!(e, Event::PrefetchError(_))),
         "expected Event::PrefetchError from the failing server"
     );
+    let record = events
+        .iter()
+        .find(|e| matches!(e, Event::PrefetchError(_)))
+        .map(EventRecord::from);
+    match record {
+        Some(EventRecord::PrefetchError { error }) => {
+            assert_eq!(error.kind, ErrorKind::Status);
+            assert_eq!(error.status, Some(500));
+            assert!(error.url.is_some(), "the record keeps the failed URL");
+        }
+        other => panic!("expected a prefetch_error record, got {other:?}"),
+    }
     assert!(
         events
             .iter()
@@ -1865,7 +3360,7 @@ async fn test_rename_failed_when_final_path_is_directory() {
     let (tx, rx) = create_channel();
     let cancel = create_cancellation_token();
     download(Url::parse(&url).expect("valid url"), cfg, tx, cancel);
-    let events = timeout(Duration::from_secs(60), drain(rx))
+    let events = timeout(Duration::from_mins(1), drain(rx))
         .await
         .expect("drain timed out");
 
@@ -1903,7 +3398,7 @@ async fn test_overwrite_true_replaces_existing_final_file() {
     let (tx, rx) = create_channel();
     let cancel = create_cancellation_token();
     download(Url::parse(&url).expect("valid url"), cfg, tx, cancel);
-    let events = timeout(Duration::from_secs(60), drain(rx))
+    let events = timeout(Duration::from_mins(1), drain(rx))
         .await
         .expect("drain timed out");
 
@@ -0,0 +1,498 @@
+use clap::Parser;
+use fast_down_api::{
+    PartialConfig, RetryBackoff, WriteMethod,
+    fast_down::{
+        Digest, Proxy,
+        fast_puller::ConnectionPool,
+        resolver::{AddressFamily, ResolveOverride},
+    },
+};
+use std::{
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use url::Url;
+
+/// Download files over HTTP(S) with many concurrent connections.
+#[derive(Debug, Parser)]
+#[command(name = "fast-down", version)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Cli {
+    /// URLs to download, one after another. A metalink (`.meta4`) URL or
+    /// local file downloads the files it lists instead.
+    #[arg(value_parser = parse_target)]
+    pub urls: Vec<Url>,
+
+    /// Read more URLs from FILE, one per line (`-` for stdin). Blank lines and
+    /// lines starting with `#` are skipped.
+    #[arg(short, long, value_name = "FILE")]
+    pub input_file: Option<PathBuf>,
+
+    /// Continue the interrupted download of FILE.part. The URL is optional:
+    /// without one, the URL recorded in the `.fd` state file is used.
+    #[arg(long, value_name = "FILE.part", conflicts_with = "input_file")]
+    pub resume: Option<PathBuf>,
+
+    /// Do not draw the progress bar.
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Directory to save files in.
+    #[arg(short = 'd', long, value_name = "DIR", default_value = ".")]
+    pub dir: PathBuf,
+
+    /// Save the file under NAME instead of the name the server suggests.
+    #[arg(short, long, value_name = "NAME")]
+    pub output: Option<String>,
+
+    /// Number of concurrent connections, or `auto` to tune it while
+    /// downloading.
+    #[arg(short = 'n', long, value_parser = parse_threads)]
+    pub threads: Option<usize>,
+
+    /// Proxy: `no`, `system` or a proxy URL (http, https, socks5).
+    #[arg(long, value_parser = parse_proxy)]
+    pub proxy: Option<Proxy<String>>,
+
+    /// Extra request header, e.g. `-H "Authorization: Bearer …"`. Repeatable.
+    #[arg(short = 'H', long = "header", value_name = "NAME: VALUE", value_parser = parse_header)]
+    pub headers: Vec<(String, String)>,
+
+    /// Smallest range a connection is given, e.g. `8M`.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub min_chunk_size: Option<u64>,
+
+    /// Total speed limit in bytes per second, e.g. `2M`; `0` is unlimited.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub speed_limit: Option<u64>,
+
+    /// How data is written: `mmap` or `std`.
+    #[arg(long, value_name = "METHOD", value_parser = parse_write_method)]
+    pub write_method: Option<WriteMethod>,
+
+    /// Write buffer size of the `std` write method, e.g. `16M`.
+    #[arg(long, value_name = "SIZE", value_parser = parse_usize_size)]
+    pub write_buffer_size: Option<usize>,
+
+    /// Write queue capacity, in pulled chunks.
+    #[arg(long, value_name = "N")]
+    pub write_queue_cap: Option<usize>,
+
+    /// fsync the file before renaming it.
+    #[arg(long)]
+    pub sync_all: bool,
+
+    /// Wait between retries, e.g. `500ms`.
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub retry_gap: Option<Duration>,
+
+    /// How the retry wait grows: `fixed` or `exponential`.
+    #[arg(long, value_name = "BACKOFF", value_parser = parse_retry_backoff)]
+    pub retry_backoff: Option<RetryBackoff>,
+
+    /// Longest wait between retries with exponential backoff, e.g. `30s`.
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub max_retry_gap: Option<Duration>,
+
+    /// Give up after this many consecutive errors of one connection; `0` never
+    /// gives up.
+    #[arg(long, value_name = "N")]
+    pub max_consecutive_errors: Option<usize>,
+
+    /// Give up after this many consecutive errors across all connections; `0`
+    /// never gives up.
+    #[arg(long, value_name = "N")]
+    pub max_session_errors: Option<usize>,
+
+    /// Number of attempts to fetch the file's metadata.
+    #[arg(long, value_name = "N")]
+    pub retry_times: Option<usize>,
+
+    /// Drop a connection that received nothing for this long, e.g. `5s`.
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub pull_timeout: Option<Duration>,
+
+    /// Reconnect a connection pulling less than this per second over
+    /// `--slow-thread-window`, e.g. `16K`; `0` never does.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub slow_thread_speed: Option<u64>,
+
+    /// Window over which `--slow-thread-speed` is measured, e.g. `10s`.
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub slow_thread_window: Option<Duration>,
+
+    /// Give up on a download pulling less than this per second over
+    /// `--low-speed-time`, e.g. `1K`; `0` never does.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub low_speed_limit: Option<u64>,
+
+    /// Window over which `--low-speed-limit` is measured, e.g. `30s`.
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub low_speed_time: Option<Duration>,
+
+    /// Refresh interval of the progress bar, e.g. `200ms`.
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub progress_interval: Option<Duration>,
+
+    /// Number of connections that may race on the last unsplittable range.
+    #[arg(long, value_name = "N")]
+    pub max_speculative: Option<usize>,
+
+    /// Maximum number of redirects to follow.
+    #[arg(long, value_name = "N")]
+    pub max_redirects: Option<usize>,
+
+    /// Local address to connect from. Repeat to spread connections over
+    /// several interfaces.
+    #[arg(long = "bind", value_name = "IP")]
+    pub local_address: Vec<IpAddr>,
+
+    /// Another URL serving the same file. Repeatable.
+    #[arg(short, long = "mirror", value_name = "URL")]
+    pub mirrors: Vec<Url>,
+
+    /// Expected digest of the file, e.g. `sha256:e3b0c4…`.
+    #[arg(long, value_name = "ALGO:HEX")]
+    pub checksum: Option<Digest>,
+
+    /// Do not check the file against digests the server advertises.
+    #[arg(long)]
+    pub no_verify_server_digest: bool,
+
+    /// Save a metalink (`.meta4`) URL as it is instead of downloading the
+    /// files it lists.
+    #[arg(long)]
+    pub no_follow_metalink: bool,
+
+    /// Prefer the metalink sources in this country, e.g. `de`.
+    #[arg(long, value_name = "CC")]
+    pub metalink_location: Option<String>,
+
+    /// How connections are shared: `per-clone` (a new connection per worker),
+    /// `shared` (workers reuse each other's idle connections) or `multiplexed`
+    /// (one HTTP/2 connection where the server supports it).
+    #[arg(long, value_name = "POOL", value_parser = parse_connection_pool)]
+    pub pool: Option<ConnectionPool>,
+
+    /// Connect to ADDR for HOST on PORT (`*` for any) instead of looking it
+    /// up, e.g. `example.com:443:10.0.0.7`. Repeatable.
+    #[arg(long, value_name = "HOST:PORT:ADDR")]
+    pub resolve: Vec<ResolveOverride>,
+
+    /// IP versions to connect over: `any`, `prefer-v4`, `prefer-v6`, `v4` or
+    /// `v6`.
+    #[arg(long, value_name = "FAMILY", value_parser = parse_address_family)]
+    pub ip_family: Option<AddressFamily>,
+
+    /// Spread connections over every address of the host.
+    #[arg(long)]
+    pub dns_round_robin: bool,
+
+    /// Keep cookies set by the server across requests and redirects.
+    #[arg(long)]
+    pub cookies: bool,
+
+    /// Accept invalid TLS certificates and host names (dangerous).
+    #[arg(short = 'k', long)]
+    pub insecure: bool,
+
+    /// Start over instead of continuing an existing `.part` file.
+    #[arg(long)]
+    pub no_resume: bool,
+
+    /// Replace an existing file instead of saving under a new name.
+    #[arg(long)]
+    pub overwrite: bool,
+}
+
+impl Cli {
+    /// The [`PartialConfig`] the flags describe; flags left out stay unset so
+    /// the library defaults (or a resumed `.fd` state) apply.
+    pub fn partial_config(&self) -> PartialConfig {
+        let flag = |set: bool| set.then_some(true);
+        PartialConfig {
+            save_dir: Some(self.dir.clone()),
+            filename: self.output.clone(),
+            parse_filename: self.output.as_ref().map(|_| false),
+            threads: self.threads,
+            proxy: self.proxy.clone(),
+            headers: (!self.headers.is_empty()).then(|| self.headers.iter().cloned().collect()),
+            min_chunk_size: self.min_chunk_size,
+            speed_limit: self.speed_limit,
+            write_method: self.write_method.clone(),
+            write_buffer_size: self.write_buffer_size,
+            write_queue_cap: self.write_queue_cap,
+            sync_all: flag(self.sync_all),
+            retry_gap: self.retry_gap,
+            retry_backoff: self.retry_backoff,
+            max_retry_gap: self.max_retry_gap,
+            max_consecutive_errors: self.max_consecutive_errors,
+            max_session_errors: self.max_session_errors,
+            retry_times: self.retry_times,
+            pull_timeout: self.pull_timeout,
+            slow_thread_speed: self.slow_thread_speed,
+            slow_thread_window: self.slow_thread_window,
+            low_speed_limit: self.low_speed_limit,
+            low_speed_time: self.low_speed_time,
+            progress_emit_gap: self.progress_interval,
+            max_speculative: self.max_speculative,
+            max_redirects: self.max_redirects,
+            local_address: (!self.local_address.is_empty()).then(|| self.local_address.clone()),
+            mirrors: (!self.mirrors.is_empty()).then(|| self.mirrors.clone()),
+            expected_digest: self.checksum.clone().map(Some),
+            verify_server_digest: self.no_verify_server_digest.then_some(false),
+            follow_metalink: self.no_follow_metalink.then_some(false),
+            metalink_location: self.metalink_location.clone().map(Some),
+            cookie_store: flag(self.cookies),
+            connection_pool: self.pool,
+            resolve: (!self.resolve.is_empty()).then(|| self.resolve.clone()),
+            address_family: self.ip_family,
+            dns_round_robin: flag(self.dns_round_robin),
+            accept_invalid_certs: flag(self.insecure),
+            accept_invalid_hostnames: flag(self.insecure),
+            resume: self.no_resume.then_some(false),
+            overwrite: flag(self.overwrite),
+            ..Default::default()
+        }
+    }
+}
+
+/// A URL, or the path of a local `.meta4` file as a `file:` URL.
+fn parse_target(s: &str) -> Result<Url, String> {
+    match Url::parse(s) {
+        Ok(url) => Ok(url),
+        Err(e) if is_meta4(Path::new(s)) => std::path::absolute(s)
+            .ok()
+            .and_then(|path| Url::from_file_path(path).ok())
+            .ok_or_else(|| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Whether `path` names a metalink file.
+pub fn is_meta4(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("meta4"))
+}
+
+fn parse_proxy(s: &str) -> Result<Proxy<String>, String> {
+    match s {
+        "no" | "none" => Ok(Proxy::No),
+        "system" => Ok(Proxy::System),
+        _ => Url::parse(s)
+            .map(|_| Proxy::Custom(s.to_string()))
+            .map_err(|e| e.to_string()),
+    }
+}
+
+fn parse_header(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected `NAME: VALUE`, got {s:?}"))?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(format!("empty header name in {s:?}"));
+    }
+    Ok((name.to_string(), value.trim().to_string()))
+}
+
+/// A byte count with an optional binary suffix: `512`, `64K`, `8M`, `1G`.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let digits = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let shift = match s[digits.len()..].to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 10,
+        "m" | "mb" | "mib" => 20,
+        "g" | "gb" | "gib" => 30,
+        suffix => return Err(format!("unknown size suffix {suffix:?}")),
+    };
+    let n: u64 = digits
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid size {s:?}"))?;
+    n.checked_mul(1 << shift)
+        .ok_or_else(|| format!("size {s:?} is too large"))
+}
+
+fn parse_usize_size(s: &str) -> Result<usize, String> {
+    usize::try_from(parse_size(s)?).map_err(|e| e.to_string())
+}
+
+/// A connection count, or `auto` (stored as `0`).
+fn parse_threads(s: &str) -> Result<usize, String> {
+    if s.eq_ignore_ascii_case("auto") {
+        return Ok(0);
+    }
+    match s.parse() {
+        Ok(0) | Err(_) => Err(format!("expected a positive number or `auto`, got {s:?}")),
+        Ok(n) => Ok(n),
+    }
+}
+
+fn parse_write_method(s: &str) -> Result<WriteMethod, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "mmap" => Ok(WriteMethod::Mmap),
+        "std" => Ok(WriteMethod::Std),
+        _ => Err(format!("expected `mmap` or `std`, got {s:?}")),
+    }
+}
+
+fn parse_retry_backoff(s: &str) -> Result<RetryBackoff, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "fixed" => Ok(RetryBackoff::Fixed),
+        "exponential" | "exp" => Ok(RetryBackoff::Exponential),
+        _ => Err(format!("expected `fixed` or `exponential`, got {s:?}")),
+    }
+}
+
+fn parse_connection_pool(s: &str) -> Result<ConnectionPool, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "per-clone" => Ok(ConnectionPool::PerClone),
+        "shared" => Ok(ConnectionPool::Shared),
+        "multiplexed" | "h2" => Ok(ConnectionPool::Multiplexed),
+        _ => Err(format!(
+            "expected `per-clone`, `shared` or `multiplexed`, got {s:?}"
+        )),
+    }
+}
+
+fn parse_address_family(s: &str) -> Result<AddressFamily, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "any" => Ok(AddressFamily::Any),
+        "prefer-v4" => Ok(AddressFamily::PreferV4),
+        "prefer-v6" => Ok(AddressFamily::PreferV6),
+        "v4" | "4" => Ok(AddressFamily::V4Only),
+        "v6" | "6" => Ok(AddressFamily::V6Only),
+        _ => Err(format!(
+            "expected `any`, `prefer-v4`, `prefer-v6`, `v4` or `v6`, got {s:?}"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn flags_map_to_partial_config() {
+        let cli = Cli::parse_from([
+            "fast-down",
+            "https://example.com/a.bin",
+            "-n",
+            "4",
+            "-o",
+            "b.bin",
+            "-H",
+            "Authorization: Bearer x",
+            "--min-chunk-size",
+            "2M",
+            "--retry-gap",
+            "250ms",
+            "--write-method",
+            "std",
+            "--low-speed-limit",
+            "1K",
+            "--low-speed-time",
+            "1m",
+            "--mirror",
+            "https://mirror.example.com/a.bin",
+            "--checksum",
+            "md5:5eb63bbbe01eeed093cb22bb8f5acdc3",
+            "--no-resume",
+            "--resolve",
+            "example.com:443:10.0.0.7",
+            "--ip-family",
+            "v4",
+            "--metalink-location",
+            "de",
+            "-k",
+        ]);
+        let config = cli.partial_config();
+        assert_eq!(config.threads, Some(4));
+        assert_eq!(config.filename.as_deref(), Some("b.bin"));
+        assert_eq!(config.parse_filename, Some(false));
+        assert_eq!(
+            config.headers.unwrap()["Authorization"],
+            "Bearer x",
+            "value is trimmed"
+        );
+        assert_eq!(config.min_chunk_size, Some(2 << 20));
+        assert_eq!(config.retry_gap, Some(Duration::from_millis(250)));
+        assert_eq!(config.write_method, Some(WriteMethod::Std));
+        assert_eq!(config.low_speed_limit, Some(1 << 10));
+        assert_eq!(config.low_speed_time, Some(Duration::from_mins(1)));
+        assert_eq!(config.mirrors.unwrap().len(), 1);
+        assert!(matches!(config.expected_digest, Some(Some(_))));
+        assert_eq!(config.resume, Some(false));
+        assert_eq!(config.resolve.unwrap()[0].host, "example.com");
+        assert_eq!(config.address_family, Some(AddressFamily::V4Only));
+        assert_eq!(config.metalink_location, Some(Some("de".to_string())));
+        assert_eq!(config.follow_metalink, None);
+        assert_eq!(config.accept_invalid_certs, Some(true));
+        assert_eq!(config.accept_invalid_hostnames, Some(true));
+    }
+
+    #[test]
+    fn omitted_flags_stay_unset() {
+        let config = Cli::parse_from(["fast-down"]).partial_config();
+        assert_eq!(config.save_dir, Some(PathBuf::from(".")));
+        assert_eq!(config.threads, None);
+        assert_eq!(config.headers, None);
+        assert_eq!(
+            config.overwrite, None,
+            "a bool flag left out keeps the default"
+        );
+        assert_eq!(config.resume, None);
+        assert!(config.expected_digest.is_none());
+    }
+
+    #[test]
+    fn parses_values() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("64K"), Ok(64 << 10));
+        assert_eq!(parse_size("1gib"), Ok(1 << 30));
+        assert!(parse_size("1T").is_err());
+        assert!(parse_size("M").is_err());
+        assert_eq!(parse_proxy("no"), Ok(Proxy::No));
+        assert_eq!(
+            parse_proxy("socks5://127.0.0.1:1080"),
+            Ok(Proxy::Custom("socks5://127.0.0.1:1080".to_string()))
+        );
+        assert!(parse_proxy("not a url").is_err());
+        assert!(parse_header("no colon").is_err());
+        assert!(parse_header(": empty").is_err());
+        assert_eq!(parse_threads("8"), Ok(8));
+        assert_eq!(parse_threads("Auto"), Ok(0));
+        assert!(parse_threads("0").is_err());
+        assert!(parse_threads("many").is_err());
+        assert_eq!(parse_connection_pool("H2"), Ok(ConnectionPool::Multiplexed));
+        assert!(parse_connection_pool("pooled").is_err());
+        assert_eq!(
+            parse_address_family("Prefer-V6"),
+            Ok(AddressFamily::PreferV6)
+        );
+        assert!(parse_address_family("ipx").is_err());
+    }
+
+    #[test]
+    fn local_metalink_files_become_file_urls() {
+        let url = parse_target("release.meta4").unwrap();
+        assert_eq!(url.scheme(), "file");
+        assert!(url.path().ends_with("/release.meta4"));
+        assert_eq!(
+            parse_target("https://example.com/a.meta4")
+                .unwrap()
+                .scheme(),
+            "https"
+        );
+        assert!(parse_target("release.bin").is_err());
+    }
+}
@@ -0,0 +1,100 @@
+use fast_down_api::Event;
+use std::process::ExitCode;
+
+/// Why a download did not produce its file, each with its own exit code so
+/// scripts can tell a bad URL from a full disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Failure {
+    /// The transfer itself failed or was given up on.
+    Download = 1,
+    /// The command line or URL list is unusable.
+    Usage = 2,
+    /// The file's metadata could not be fetched, or a metalink could not be read.
+    Prefetch = 3,
+    /// The `.fd` state file could not be used to resume.
+    State = 4,
+    /// The `.part` file could not be created, written or flushed.
+    Write = 5,
+    /// The finished `.part` file could not be renamed into place.
+    Rename = 6,
+    /// The file does not match its expected size or checksum.
+    Verify = 7,
+    /// Interrupted with Ctrl-C; the `.part` file is kept for `--resume`.
+    Interrupted = 130,
+}
+
+impl Failure {
+    /// The failure a fatal event stands for, `None` for every other event.
+    pub const fn of(event: &Event) -> Option<Self> {
+        Some(match event {
+            Event::PrefetchError(_) | Event::BuildClientError(_) | Event::MetalinkError(_) => {
+                Self::Prefetch
+            }
+            Event::ResumeError(_) => Self::State,
+            Event::GenPathError(_)
+            | Event::BuildPusherError(_)
+            | Event::PushFailed(..)
+            | Event::FlushError(_) => Self::Write,
+            Event::RenameFailed(_) => Self::Rename,
+            Event::SizeMismatch { .. }
+            | Event::DigestMismatch { .. }
+            | Event::IntegrityError { .. }
+            | Event::PieceMismatch(_)
+            | Event::VerifyError(_) => Self::Verify,
+            Event::PullFailed(..) | Event::PullTimeoutFailed(_) => Self::Download,
+            _ => return None,
+        })
+    }
+}
+
+impl From<Failure> for ExitCode {
+    fn from(failure: Failure) -> Self {
+        Self::from(failure as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fast_down_api::StateError;
+    use std::io;
+
+    #[test]
+    fn classifies_fatal_events() {
+        let io = || io::Error::other("boom");
+        assert_eq!(
+            Failure::of(&Event::ResumeError(StateError::Open(io()))),
+            Some(Failure::State)
+        );
+        assert_eq!(
+            Failure::of(&Event::BuildPusherError(io())),
+            Some(Failure::Write)
+        );
+        assert_eq!(
+            Failure::of(&Event::RenameFailed(io())),
+            Some(Failure::Rename)
+        );
+        assert_eq!(
+            Failure::of(&Event::PieceMismatch(vec![0])),
+            Some(Failure::Verify)
+        );
+        assert_eq!(
+            Failure::of(&Event::SizeMismatch {
+                expected: 1,
+                actual: 2
+            }),
+            Some(Failure::Verify)
+        );
+        assert_eq!(
+            Failure::of(&Event::PullTimeoutFailed(0)),
+            Some(Failure::Download)
+        );
+        assert_eq!(
+            Failure::of(&Event::StateSaveError(StateError::Save(io()))),
+            None,
+            "not fatal"
+        );
+        assert_eq!(Failure::of(&Event::Flushing), None);
+        assert_eq!(Failure::of(&Event::Metalink(Vec::new())), None);
+    }
+}
@@ -0,0 +1,59 @@
+use anyhow::Context;
+use std::path::Path;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use url::Url;
+
+/// Read a URL list from `path`, or from stdin when `path` is `-`.
+pub async fn read_url_list(path: &Path) -> anyhow::Result<Vec<Url>> {
+    let text = if path == Path::new("-") {
+        read_to_string(tokio::io::stdin())
+            .await
+            .context("failed to read URL list from stdin")?
+    } else {
+        tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("failed to read URL list {}", path.display()))?
+    };
+    parse_url_list(&text)
+}
+
+async fn read_to_string(mut reader: impl AsyncRead + Unpin) -> std::io::Result<String> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text).await?;
+    Ok(text)
+}
+
+/// One URL per line; blank lines and `#` comments are skipped.
+fn parse_url_list(text: &str) -> anyhow::Result<Vec<Url>> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(n, line)| {
+            Url::parse(line).with_context(|| format!("line {n}: invalid URL {line:?}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_blank_lines_and_comments() {
+        let urls = parse_url_list(
+            "# nightly builds\nhttps://example.com/a\n\n  https://example.com/b  \r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            urls.iter().map(Url::as_str).collect::<Vec<_>>(),
+            ["https://example.com/a", "https://example.com/b"]
+        );
+    }
+
+    #[test]
+    fn reports_the_line_of_a_bad_url() {
+        let err = parse_url_list("https://example.com/a\nnot a url\n").unwrap_err();
+        assert!(err.to_string().starts_with("line 2:"), "{err}");
+    }
+}
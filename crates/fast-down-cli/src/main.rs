@@ -0,0 +1,197 @@
+//! `fast-down`: download files from the command line with
+//! [`fast_down_api`].
+//!
+//! Every URL is downloaded in turn with the configuration the flags describe;
+//! the files of a metalink are downloaded right after it, in its place. The
+//! exit code is that of the first download that failed; see [`Failure`].
+
+mod args;
+mod exit;
+mod input;
+mod progress;
+
+use args::{Cli, is_meta4};
+use clap::Parser;
+use exit::Failure;
+use fast_down_api::{
+    DownloadHandle, Event, MetalinkJob, MetalinkSource, PartialConfig, Tx,
+    create_cancellation_token, create_channel, download, load_metalink, resume,
+};
+use input::read_url_list;
+use progress::ProgressView;
+use std::{collections::VecDeque, path::Path, process::ExitCode};
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let config = cli.partial_config();
+    let mut urls = cli.urls.clone();
+    if let Some(path) = &cli.input_file {
+        match read_url_list(path).await {
+            Ok(list) => urls.extend(list),
+            Err(e) => return usage(&format!("{e:#}")),
+        }
+    }
+
+    let token = create_cancellation_token();
+    tokio::spawn({
+        let token = token.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                token.cancel();
+            }
+        }
+    });
+
+    if let Some(part) = &cli.resume {
+        if urls.len() > 1 {
+            return usage("--resume takes at most one URL");
+        }
+        let url = urls.pop();
+        let result = run(cli.quiet, &token, |tx| {
+            resume(part, url, config, tx, token.clone())
+        })
+        .await;
+        return result.map_or_else(Into::into, |_| ExitCode::SUCCESS);
+    }
+
+    if urls.is_empty() {
+        return usage("no URL given; pass URLs or --input-file");
+    }
+    let mut queue: VecDeque<_> = urls.into_iter().map(|url| (url, config.clone())).collect();
+    let mut first_failure = None;
+    while let Some((url, config)) = queue.pop_front() {
+        if token.is_cancelled() {
+            first_failure.get_or_insert(Failure::Interrupted);
+            break;
+        }
+        let result = if url.scheme() == "file" && is_meta4(Path::new(url.path())) {
+            read_metalink(&url, &config).await
+        } else {
+            run(cli.quiet, &token, |tx| {
+                download(url, config, tx, token.clone())
+            })
+            .await
+        };
+        match result {
+            Ok(jobs) => {
+                for job in jobs.into_iter().rev() {
+                    queue.push_front((job.url, job.config));
+                }
+            }
+            Err(failure) => {
+                first_failure.get_or_insert(failure);
+            }
+        }
+    }
+    first_failure.map_or(ExitCode::SUCCESS, Into::into)
+}
+
+/// The jobs of the local metalink file at the `file:` URL `url`.
+async fn read_metalink(url: &Url, config: &PartialConfig) -> Result<Vec<MetalinkJob>, Failure> {
+    let Ok(path) = url.to_file_path() else {
+        eprintln!("error: {url} is not a local path");
+        return Err(Failure::Usage);
+    };
+    let location = config.metalink_location.clone().flatten();
+    load_metalink(MetalinkSource::File(path), config, location.as_deref())
+        .await
+        .map_err(|e| {
+            eprintln!("error: {e}");
+            Failure::Prefetch
+        })
+}
+
+fn usage(message: &str) -> ExitCode {
+    eprintln!("error: {message}");
+    Failure::Usage.into()
+}
+
+/// Drive one download to its end, reporting it on the terminal. A metalink
+/// URL succeeds with the jobs of the files it lists; any other with none.
+async fn run(
+    quiet: bool,
+    token: &CancellationToken,
+    start: impl FnOnce(Tx) -> DownloadHandle,
+) -> Result<Vec<MetalinkJob>, Failure> {
+    let (tx, rx) = create_channel();
+    let _handle = start(tx);
+    let mut view = ProgressView::new(quiet);
+    let mut failure = None;
+    let mut completed = false;
+    let mut metalink = None;
+    while let Ok(event) = rx.recv().await {
+        if failure.is_none() {
+            failure = Failure::of(&event);
+        }
+        view.worker(&event);
+        match event {
+            Event::Prefetch(info) => view.prefetched(&info),
+            Event::Start { tmp_path, .. } => {
+                let name = tmp_path.with_extension("");
+                view.started(&name.file_name().unwrap_or_default().to_string_lossy());
+            }
+            Event::Resumed { progress, size, .. } => {
+                let done: u64 = progress.iter().map(|r| r.end - r.start).sum();
+                view.println(format!("resuming at {done} of {size} bytes"));
+            }
+            Event::Progress(sample) => view.sample(&sample),
+            Event::Renamed(path) => {
+                view.finish();
+                println!("{}", path.display());
+            }
+            Event::Completed(_) => completed = true,
+            Event::Metalink(jobs) => {
+                view.println(format!("metalink lists {} file(s)", jobs.len()));
+                metalink = Some(jobs);
+            }
+            Event::MetalinkError(e) => view.println(format!("error: {e}")),
+            Event::SizeMismatch { expected, actual } => view.println(format!(
+                "error: size mismatch: expected {expected} bytes, the server has {actual}"
+            )),
+            Event::MirrorRejected(url, e) => view.println(format!("warning: mirror {url}: {e:#}")),
+            Event::StateSaveError(e) => view.println(format!("warning: {e}")),
+            Event::PrefetchError(e) => view.println(format!("error: {e}")),
+            Event::BuildClientError(e) => view.println(format!("error: {e}")),
+            Event::ResumeError(e) => view.println(format!("error: cannot resume: {e}")),
+            Event::GenPathError(e) | Event::BuildPusherError(e) => {
+                view.println(format!("error: cannot create file: {e}"));
+            }
+            Event::RenameFailed(e) => view.println(format!("error: cannot rename file: {e}")),
+            Event::DigestMismatch { expected, actual } => view.println(format!(
+                "error: checksum mismatch: expected {expected}, got {actual}"
+            )),
+            Event::IntegrityError { advertised, actual } => view.println(format!(
+                "error: server advertised {advertised}, got {actual}"
+            )),
+            Event::PieceMismatch(pieces) => {
+                view.println(format!("error: pieces {pieces:?} are corrupt"));
+            }
+            Event::VerifyError(e) => view.println(format!("error: cannot verify file: {e}")),
+            Event::Throttled(cooldown) => view.println(format!(
+                "warning: the server is rate limiting, waiting {}",
+                indicatif::HumanDuration(cooldown)
+            )),
+            Event::PullFailed(_, e) => view.println(format!("error: {e:#}")),
+            Event::PullTimeoutFailed(_) => view.println("error: the server stopped responding"),
+            Event::PushFailed(_, _, e) | Event::FlushError(e) => {
+                view.println(format!("error: cannot write file: {e:#}"));
+            }
+            // Already reported in detail by the event that set `failure`.
+            Event::Failed { error, .. } if failure.is_none() && !token.is_cancelled() => {
+                view.println(format!("error: {error:#}"));
+            }
+            _ => {}
+        }
+    }
+    view.finish();
+    if completed || metalink.is_some() {
+        Ok(metalink.unwrap_or_default())
+    } else if token.is_cancelled() {
+        Err(Failure::Interrupted)
+    } else {
+        Err(failure.unwrap_or(Failure::Download))
+    }
+}
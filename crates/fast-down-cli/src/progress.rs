@@ -0,0 +1,162 @@
+use fast_down_api::{
+    Event, ProgressSample,
+    fast_down::{UrlInfo, WorkerId},
+};
+use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::collections::BTreeMap;
+
+/// Widest worker strip drawn; more workers are summarized by the counts.
+const STRIP_WIDTH: usize = 48;
+
+/// What a live worker is doing, as far as its last event tells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Activity {
+    Pulling,
+    Retrying,
+}
+
+/// Terminal progress bar of one download, drawn to stderr.
+///
+/// The bar follows [`Event::Progress`]; below it, one cell per live worker
+/// shows which connections are pulling (`●`) and which are retrying (`○`).
+pub struct ProgressView {
+    bar: ProgressBar,
+    workers: BTreeMap<WorkerId, Activity>,
+    finished: usize,
+}
+
+impl ProgressView {
+    pub fn new(quiet: bool) -> Self {
+        let bar = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr());
+        if quiet {
+            bar.set_draw_target(ProgressDrawTarget::hidden());
+        }
+        bar.set_style(spinner_style());
+        Self {
+            bar,
+            workers: BTreeMap::new(),
+            finished: 0,
+        }
+    }
+
+    /// Size the bar once the file's metadata is known; unknown sizes keep the
+    /// spinner.
+    pub fn prefetched(&self, info: &UrlInfo) {
+        if info.size > 0 {
+            self.bar.set_length(info.size);
+            self.bar.set_style(bar_style());
+        }
+    }
+
+    /// Label the bar with the name the file is being written under.
+    pub fn started(&self, name: &str) {
+        self.bar.set_prefix(name.to_string());
+    }
+
+    pub fn sample(&self, sample: &ProgressSample) {
+        self.bar.set_position(sample.downloaded);
+        let eta = sample
+            .eta
+            .map_or_else(|| "?".to_string(), |eta| HumanDuration(eta).to_string());
+        self.bar.set_message(format!(
+            "{}/s, eta {eta}\n{}",
+            HumanBytes(sample.bps),
+            self.activity()
+        ));
+    }
+
+    /// Track the per-worker events; every other event is ignored.
+    pub fn worker(&mut self, event: &Event) {
+        match *event {
+            Event::Pulling(id) | Event::PullProgress(id, _) => {
+                self.workers.insert(id, Activity::Pulling);
+            }
+            Event::PullError(id, _)
+            | Event::PullTimeout(id)
+            | Event::PullSlow(id)
+            | Event::PushError(id, ..) => {
+                self.workers.insert(id, Activity::Retrying);
+            }
+            Event::Finished(id) if self.workers.remove(&id).is_some() => self.finished += 1,
+            _ => {}
+        }
+    }
+
+    /// Print a line above the bar.
+    pub fn println(&self, line: impl AsRef<str>) {
+        self.bar.suspend(|| eprintln!("{}", line.as_ref()));
+    }
+
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+
+    fn activity(&self) -> String {
+        let retrying = self
+            .workers
+            .values()
+            .filter(|a| **a == Activity::Retrying)
+            .count();
+        let mut strip: String = self
+            .workers
+            .values()
+            .take(STRIP_WIDTH)
+            .map(|a| match a {
+                Activity::Pulling => '●',
+                Activity::Retrying => '○',
+            })
+            .collect();
+        if self.workers.len() > STRIP_WIDTH {
+            strip.push('…');
+        }
+        format!(
+            "{strip} {} active, {retrying} retrying, {} done",
+            self.workers.len() - retrying,
+            self.finished
+        )
+    }
+}
+
+#[allow(clippy::literal_string_with_formatting_args)]
+fn bar_style() -> ProgressStyle {
+    ProgressStyle::with_template(
+        "{prefix:.bold} [{bar:40.cyan/blue}] {percent:>3}% {binary_bytes}/{binary_total_bytes} {msg}",
+    )
+    .expect("valid template")
+    .progress_chars("=> ")
+}
+
+#[allow(clippy::literal_string_with_formatting_args)]
+fn spinner_style() -> ProgressStyle {
+    ProgressStyle::with_template("{spinner} {prefix:.bold} {binary_bytes} {msg}")
+        .expect("valid template")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_follows_worker_events() {
+        let mut view = ProgressView::new(true);
+        view.worker(&Event::Pulling(0));
+        view.worker(&Event::Pulling(1));
+        view.worker(&Event::PullTimeout(1));
+        view.worker(&Event::Pulling(2));
+        view.worker(&Event::Finished(2));
+        assert_eq!(view.activity(), "●○ 1 active, 1 retrying, 1 done");
+        view.worker(&Event::PullProgress(1, 0..1));
+        assert_eq!(view.activity(), "●● 2 active, 0 retrying, 1 done");
+    }
+
+    #[test]
+    fn strip_is_capped() {
+        let mut view = ProgressView::new(true);
+        for id in 0..=STRIP_WIDTH {
+            view.worker(&Event::Pulling(id));
+        }
+        let activity = view.activity();
+        assert_eq!(activity.chars().filter(|c| *c == '●').count(), STRIP_WIDTH);
+        assert!(activity.contains('…'));
+    }
+}
@@ -0,0 +1,71 @@
+# Type stubs of the `fast_down` extension module, built from src/python.
+
+from collections.abc import AsyncIterator, Generator, Iterator
+from os import PathLike
+from typing import Any, Literal, Optional
+
+class ProgressSample:
+    progress: list[tuple[int, int]]
+    bps: int
+    avg_bps: int
+    downloaded: int
+    percent: float
+    total: int
+    elapsed: float
+    eta: Optional[float]
+    chunks: Optional[list[tuple[float, bool, bool]]]
+
+class Worker:
+    id: int
+    range: Optional[tuple[int, int]]
+    bps: int
+    speculative: bool
+    pulled: int
+    pull_errors: int
+    pull_timeouts: int
+    slow_reconnects: int
+    push_errors: int
+
+class Event:
+    kind: str
+    worker: Optional[int]
+    range: Optional[tuple[int, int]]
+    message: Optional[str]
+    downloaded: int
+    total: int
+    progress: Optional[ProgressSample]
+    workers: Optional[list[Worker]]
+    threads: Optional[int]
+    cooldown: Optional[float]
+
+class Outcome:
+    status: Literal["completed", "failed", "aborted"]
+    error: Optional[str]
+    bytes_pulled: int
+    bytes_pushed: int
+    elapsed: float
+
+class Download:
+    def pause(self) -> None: ...
+    def unpause(self) -> None: ...
+    def is_paused(self) -> bool: ...
+    def set_threads(self, threads: int) -> None: ...
+    def set_max_threads(self, max_threads: int) -> None: ...
+    def set_min_chunk_size(self, min_chunk_size: int) -> None: ...
+    def set_max_speculative(self, max_speculative: int) -> None: ...
+    def progress(self) -> Optional[ProgressSample]: ...
+    def workers(self) -> list[Worker]: ...
+    def cancel(self) -> None: ...
+    def is_cancelled(self) -> bool: ...
+    def is_finished(self) -> bool: ...
+    def join(self) -> Optional[Outcome]: ...
+    def __await__(self) -> Generator[Any, None, Optional[Outcome]]: ...
+    def __iter__(self) -> Iterator[Event]: ...
+    def __next__(self) -> Event: ...
+    def __aiter__(self) -> AsyncIterator[Event]: ...
+    async def __anext__(self) -> Event: ...
+
+def download(url: str, **config: Any) -> Download: ...
+def resume(
+    tmp_path: str | PathLike[str], url: Optional[str] = None, **config: Any
+) -> Download: ...
@@ -0,0 +1,257 @@
+#ifndef FAST_DOWN_H
+#define FAST_DOWN_H
+
+/* Generated by cbindgen from the fast-down-ffi sources; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The syntax of a config string: a [`PartialConfig`] as TOML, like the `.fd`
+// state files, or as JSON.
+typedef enum FdConfigFormat {
+  FD_CONFIG_FORMAT_TOML,
+  FD_CONFIG_FORMAT_JSON,
+} FdConfigFormat;
+
+// Which [`Event`] an [`FdEvent`] reports; one kind per variant.
+typedef enum FdEventKind {
+  FD_EVENT_KIND_PREFETCH,
+  FD_EVENT_KIND_PREFETCH_ERROR,
+  FD_EVENT_KIND_MIRROR_REJECTED,
+  FD_EVENT_KIND_SIZE_MISMATCH,
+  FD_EVENT_KIND_METALINK,
+  FD_EVENT_KIND_METALINK_ERROR,
+  FD_EVENT_KIND_GEN_PATH_ERROR,
+  FD_EVENT_KIND_STATE_SAVE_ERROR,
+  FD_EVENT_KIND_BUILD_CLIENT_ERROR,
+  FD_EVENT_KIND_BUILD_PUSHER_ERROR,
+  FD_EVENT_KIND_RENAME_FAILED,
+  FD_EVENT_KIND_VERIFIED,
+  FD_EVENT_KIND_DIGEST_MISMATCH,
+  FD_EVENT_KIND_INTEGRITY_ERROR,
+  FD_EVENT_KIND_PIECE_MISMATCH,
+  FD_EVENT_KIND_VERIFY_ERROR,
+  FD_EVENT_KIND_RENAMED,
+  FD_EVENT_KIND_START,
+  FD_EVENT_KIND_RESUMED,
+  FD_EVENT_KIND_RESUME_ERROR,
+  FD_EVENT_KIND_PULLING,
+  FD_EVENT_KIND_PULL_ERROR,
+  FD_EVENT_KIND_PULL_TIMEOUT,
+  FD_EVENT_KIND_PULL_SLOW,
+  FD_EVENT_KIND_THROTTLED,
+  FD_EVENT_KIND_PULL_PROGRESS,
+  FD_EVENT_KIND_PUSHING,
+  FD_EVENT_KIND_PUSH_ERROR,
+  FD_EVENT_KIND_PUSH_PROGRESS,
+  FD_EVENT_KIND_PROGRESS,
+  FD_EVENT_KIND_WORKERS,
+  FD_EVENT_KIND_CONCURRENCY,
+  FD_EVENT_KIND_FLUSHING,
+  FD_EVENT_KIND_FLUSH_ERROR,
+  FD_EVENT_KIND_PULL_FAILED,
+  FD_EVENT_KIND_PULL_TIMEOUT_FAILED,
+  FD_EVENT_KIND_PUSH_FAILED,
+  FD_EVENT_KIND_FINISHED,
+  FD_EVENT_KIND_COMPLETED,
+  FD_EVENT_KIND_FAILED,
+} FdEventKind;
+
+// What [`fd_download_poll`] found.
+typedef enum FdPoll {
+  // An event was returned.
+  FD_POLL_EVENT,
+  // No event arrived in time.
+  FD_POLL_EMPTY,
+  // The download is over and every event was returned.
+  FD_POLL_CLOSED,
+} FdPoll;
+
+// How a download ended, as returned by
+// [`fd_download_join`](crate::fd_download_join).
+typedef enum FdOutcome {
+  // The file was downloaded, verified and renamed into place.
+  FD_OUTCOME_COMPLETED,
+  // The engine ran and failed, or a step after it did.
+  FD_OUTCOME_FAILED,
+  // Cancelled; the `.part` and `.fd` files are kept for a resume.
+  FD_OUTCOME_ABORTED,
+  // The run ended before the engine started, e.g. on a prefetch error.
+  FD_OUTCOME_NOT_STARTED,
+} FdOutcome;
+
+// A running download. Opaque to C.
+typedef struct FdDownload FdDownload;
+
+// The Tokio runtime downloads run on. Opaque to C.
+typedef struct FdRuntime FdRuntime;
+
+// One event of a download, flattened into a C struct.
+//
+// Fields an event does not carry are zero, `eta_ms` is `-1` and `message` is
+// null.
+typedef struct FdEvent {
+  enum FdEventKind kind;
+  // The worker of per-worker events; the number of running workers for
+  // `Workers` and the worker count picked for `Concurrency`.
+  size_t worker;
+  // The byte range of `PullProgress`, `Pushing`, `PushError`,
+  // `PushProgress` and `PushFailed`, end exclusive.
+  uint64_t start;
+  uint64_t end;
+  // Bytes written: of `Progress`, of the progress `Resumed` continues from,
+  // and pushed by a `Completed` or `Failed` run.
+  uint64_t downloaded;
+  // The file size, from `Prefetch`, `Resumed` and `Progress`; the size the
+  // server reports for `SizeMismatch`.
+  uint64_t total;
+  // Smoothed and average rates of `Progress`, in bytes per second; `bps`
+  // is the summed speed of the workers for `Workers`.
+  uint64_t bps;
+  uint64_t avg_bps;
+  // Time spent downloading, of `Progress`, `Completed` and `Failed`.
+  uint64_t elapsed_ms;
+  // Time left of `Progress`, `-1` while unknown; the cooldown of
+  // `Throttled`.
+  int64_t eta_ms;
+  // UTF-8 text: the error of error events, the path of `Start` and
+  // `Renamed`, the digest of `Verified`, the URLs of the files of
+  // `Metalink`, one per line. Owned by the library, like the event itself.
+  const char *message;
+} FdEvent;
+
+// Called with every event of a download, in order, on a runtime thread. The
+// event and its `message` are only valid during the call.
+typedef void (*FdEventCallback)(void *user_data, const struct FdEvent *event);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The message of the last call that failed on this thread, or null. Valid
+// until the next call that fails on this thread.
+const char *fd_last_error(void);
+
+// Start a multi-threaded runtime with `worker_threads` threads, or one per
+// core when `0`. Returns null on failure, see [`fd_last_error`].
+//
+// Free it with [`fd_runtime_free`] once every download on it was freed.
+struct FdRuntime *fd_runtime_new(size_t worker_threads);
+
+// Shut `runtime` down, stopping the downloads still running on it. Null is
+// ignored.
+//
+// # Safety
+// `runtime` must be null or come from [`fd_runtime_new`], and not be used
+// afterwards. Must not be called from an event callback.
+void fd_runtime_free(struct FdRuntime *runtime);
+
+// Download `url` into a new file, like [`download`]: an interrupted download
+// of the same file is resumed when its state allows.
+//
+// `config` is a [`PartialConfig`] in `format`, or null for the defaults;
+// `save_dir` is required in practice. With a `callback`, events are
+// delivered to it; without, read them with [`fd_download_poll`]. Returns null
+// on invalid arguments, see [`fd_last_error`](crate::fd_last_error); later failures are reported
+// as events.
+//
+// # Safety
+// `runtime` must come from [`fd_runtime_new`](crate::fd_runtime_new). `url` and `config` must be
+// null or valid NUL-terminated strings. `callback` must be safe to call with
+// `user_data` from any thread until [`fd_download_join`] returned, or until
+// a call in progress when [`fd_download_free`] returned finished.
+struct FdDownload *fd_download_start(const struct FdRuntime *runtime,
+                                     const char *url,
+                                     const char *config,
+                                     enum FdConfigFormat format,
+                                     FdEventCallback callback,
+                                     void *user_data);
+
+// Resume the `.part` file at `tmp_path`, like [`resume`].
+//
+// Unlike [`fd_download_start`], a download that cannot be continued is
+// reported as a `ResumeError` event instead of starting over. `url` may be
+// null to reuse the one recorded in the `.fd` state.
+//
+// # Safety
+// Same as [`fd_download_start`]; `tmp_path` must be a valid NUL-terminated
+// string.
+struct FdDownload *fd_download_resume(const struct FdRuntime *runtime,
+                                      const char *tmp_path,
+                                      const char *url,
+                                      const char *config,
+                                      enum FdConfigFormat format,
+                                      FdEventCallback callback,
+                                      void *user_data);
+
+// Wait up to `timeout_ms` milliseconds for the next event of a download
+// started without a callback, and point `event` at it. The event is valid
+// until the next call on `download`.
+//
+// # Safety
+// `download` must come from [`fd_download_start`] / [`fd_download_resume`]
+// and `event` be a valid pointer. Must not be called from an event callback.
+enum FdPoll fd_download_poll(struct FdDownload *download,
+                             uint64_t timeout_ms,
+                             const struct FdEvent **event);
+
+// Pause the download: workers stop and release their connections.
+//
+// # Safety
+// `download` must come from [`fd_download_start`] / [`fd_download_resume`].
+void fd_download_pause(const struct FdDownload *download);
+
+// Continue a paused download.
+//
+// # Safety
+// `download` must come from [`fd_download_start`] / [`fd_download_resume`].
+void fd_download_unpause(const struct FdDownload *download);
+
+// Change the number of workers of the running download.
+//
+// # Safety
+// `download` must come from [`fd_download_start`] / [`fd_download_resume`].
+void fd_download_set_threads(const struct FdDownload *download, size_t threads);
+
+// Cap the workers of a download whose `threads` is `"auto"`.
+//
+// # Safety
+// `download` must come from [`fd_download_start`] / [`fd_download_resume`].
+void fd_download_set_max_threads(const struct FdDownload *download, size_t max_threads);
+
+// Cancel the download, keeping the `.part` and `.fd` files for a resume.
+//
+// # Safety
+// `download` must come from [`fd_download_start`] / [`fd_download_resume`].
+void fd_download_cancel(const struct FdDownload *download);
+
+// Whether the download is over.
+//
+// # Safety
+// `download` must come from [`fd_download_start`] / [`fd_download_resume`].
+bool fd_download_is_finished(const struct FdDownload *download);
+
+// Block until the download is over and, with a callback, every event was
+// delivered to it.
+//
+// # Safety
+// `download` must come from [`fd_download_start`] / [`fd_download_resume`].
+// Must not be called from an event callback.
+enum FdOutcome fd_download_join(struct FdDownload *download);
+
+// Free a download handle. The download keeps running unless cancelled, but
+// its events are no longer delivered. Null is ignored.
+//
+// # Safety
+// `download` must be null or come from [`fd_download_start`] /
+// [`fd_download_resume`], and not be used afterwards.
+void fd_download_free(struct FdDownload *download);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FAST_DOWN_H */
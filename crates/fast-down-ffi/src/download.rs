@@ -0,0 +1,480 @@
+use crate::{FdEvent, FdOutcome, FdRuntime, event::OwnedEvent, set_error};
+use fast_down_api::{DownloadHandle, PartialConfig, Rx, create_channel, download, resume};
+use std::{
+    ffi::{CStr, c_void},
+    os::raw::c_char,
+    ptr,
+    time::Duration,
+};
+use tokio::{runtime::Handle, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+/// The syntax of a config string: a [`PartialConfig`] as TOML, like the `.fd`
+/// state files, or as JSON.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdConfigFormat {
+    Toml,
+    Json,
+}
+
+/// Called with every event of a download, in order, on a runtime thread. The
+/// event and its `message` are only valid during the call.
+pub type FdEventCallback =
+    Option<unsafe extern "C" fn(user_data: *mut c_void, event: *const FdEvent)>;
+
+/// What [`fd_download_poll`] found.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdPoll {
+    /// An event was returned.
+    Event,
+    /// No event arrived in time.
+    Empty,
+    /// The download is over and every event was returned.
+    Closed,
+}
+
+/// A running download. Opaque to C.
+pub struct FdDownload {
+    handle: DownloadHandle,
+    runtime: Handle,
+    /// The events not delivered to a callback.
+    rx: Option<Rx>,
+    /// The last event returned by [`fd_download_poll`].
+    current: Option<OwnedEvent>,
+    /// The task delivering events to a callback.
+    forwarder: Option<JoinHandle<()>>,
+}
+
+/// A callback and its user data, moved to the task calling it.
+struct Listener {
+    callback: unsafe extern "C" fn(*mut c_void, *const FdEvent),
+    user_data: *mut c_void,
+}
+
+// SAFETY: the caller of `fd_download_start` / `fd_download_resume` promises
+// that the callback may be called with `user_data` from any thread.
+unsafe impl Send for Listener {}
+
+impl Listener {
+    async fn forward(self, rx: Rx) {
+        while let Ok(event) = rx.recv().await {
+            let event = OwnedEvent::new(&event);
+            // SAFETY: see `fd_download_start`.
+            unsafe { (self.callback)(self.user_data, event.raw()) };
+        }
+    }
+}
+
+/// Read a nullable C string.
+///
+/// # Safety
+/// `text` must be null or a valid NUL-terminated string.
+unsafe fn read<'a>(text: *const c_char, name: &str) -> Result<Option<&'a str>, String> {
+    if text.is_null() {
+        return Ok(None);
+    }
+    // SAFETY: upheld by the caller.
+    unsafe { CStr::from_ptr(text) }
+        .to_str()
+        .map(Some)
+        .map_err(|e| format!("{name} is not UTF-8: {e}"))
+}
+
+fn parse_config(text: Option<&str>, format: FdConfigFormat) -> Result<PartialConfig, String> {
+    let Some(text) = text else {
+        return Ok(PartialConfig::default());
+    };
+    match format {
+        FdConfigFormat::Toml => toml::from_str(text).map_err(|e| format!("invalid config: {e}")),
+        FdConfigFormat::Json => {
+            serde_json::from_str(text).map_err(|e| format!("invalid config: {e}"))
+        }
+    }
+}
+
+fn parse_url(text: &str) -> Result<Url, String> {
+    Url::parse(text).map_err(|e| format!("invalid url {text:?}: {e}"))
+}
+
+/// Spawn a download on `runtime` and wire its events.
+fn start(
+    runtime: &FdRuntime,
+    callback: FdEventCallback,
+    user_data: *mut c_void,
+    spawn: impl FnOnce(fast_down_api::Tx, CancellationToken) -> DownloadHandle,
+) -> *mut FdDownload {
+    let _guard = runtime.runtime.enter();
+    let (tx, rx) = create_channel();
+    let handle = spawn(tx, CancellationToken::new());
+    let (rx, forwarder) = match callback {
+        Some(callback) => {
+            let listener = Listener {
+                callback,
+                user_data,
+            };
+            (None, Some(tokio::spawn(listener.forward(rx))))
+        }
+        None => (Some(rx), None),
+    };
+    Box::into_raw(Box::new(FdDownload {
+        handle,
+        runtime: runtime.runtime.handle().clone(),
+        rx,
+        current: None,
+        forwarder,
+    }))
+}
+
+/// Download `url` into a new file, like [`download`]: an interrupted download
+/// of the same file is resumed when its state allows.
+///
+/// `config` is a [`PartialConfig`] in `format`, or null for the defaults;
+/// `save_dir` is required in practice. With a `callback`, events are
+/// delivered to it; without, read them with [`fd_download_poll`]. Returns null
+/// on invalid arguments, see [`fd_last_error`](crate::fd_last_error); later failures are reported
+/// as events.
+///
+/// # Safety
+/// `runtime` must come from [`fd_runtime_new`](crate::fd_runtime_new). `url` and `config` must be
+/// null or valid NUL-terminated strings. `callback` must be safe to call with
+/// `user_data` from any thread until [`fd_download_join`] returned, or until
+/// a call in progress when [`fd_download_free`] returned finished.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fd_download_start(
+    runtime: *const FdRuntime,
+    url: *const c_char,
+    config: *const c_char,
+    format: FdConfigFormat,
+    callback: FdEventCallback,
+    user_data: *mut c_void,
+) -> *mut FdDownload {
+    // SAFETY: upheld by the caller.
+    let parsed = unsafe { (runtime.as_ref(), read(url, "url"), read(config, "config")) };
+    let args = match parsed {
+        (Some(runtime), Ok(Some(url)), Ok(config)) => {
+            parse_url(url).and_then(|url| Ok((runtime, url, parse_config(config, format)?)))
+        }
+        (None, ..) => Err("runtime is null".to_string()),
+        (_, Ok(None), _) => Err("url is null".to_string()),
+        (_, Err(e), _) | (_, _, Err(e)) => Err(e),
+    };
+    match args {
+        Ok((runtime, url, config)) => start(runtime, callback, user_data, |tx, token| {
+            download(url, config, tx, token)
+        }),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Resume the `.part` file at `tmp_path`, like [`resume`].
+///
+/// Unlike [`fd_download_start`], a download that cannot be continued is
+/// reported as a `ResumeError` event instead of starting over. `url` may be
+/// null to reuse the one recorded in the `.fd` state.
+///
+/// # Safety
+/// Same as [`fd_download_start`]; `tmp_path` must be a valid NUL-terminated
+/// string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fd_download_resume(
+    runtime: *const FdRuntime,
+    tmp_path: *const c_char,
+    url: *const c_char,
+    config: *const c_char,
+    format: FdConfigFormat,
+    callback: FdEventCallback,
+    user_data: *mut c_void,
+) -> *mut FdDownload {
+    // SAFETY: upheld by the caller.
+    let parsed = unsafe {
+        (
+            runtime.as_ref(),
+            read(tmp_path, "tmp_path"),
+            read(url, "url"),
+            read(config, "config"),
+        )
+    };
+    let args = match parsed {
+        (Some(runtime), Ok(Some(tmp_path)), Ok(url), Ok(config)) => url
+            .map(parse_url)
+            .transpose()
+            .and_then(|url| Ok((runtime, tmp_path, url, parse_config(config, format)?))),
+        (None, ..) => Err("runtime is null".to_string()),
+        (_, Ok(None), ..) => Err("tmp_path is null".to_string()),
+        (_, Err(e), ..) | (_, _, Err(e), _) | (.., Err(e)) => Err(e),
+    };
+    match args {
+        Ok((runtime, tmp_path, url, config)) => start(runtime, callback, user_data, |tx, token| {
+            resume(tmp_path, url, config, tx, token)
+        }),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Wait up to `timeout_ms` milliseconds for the next event of a download
+/// started without a callback, and point `event` at it. The event is valid
+/// until the next call on `download`.
+///
+/// # Safety
+/// `download` must come from [`fd_download_start`] / [`fd_download_resume`]
+/// and `event` be a valid pointer. Must not be called from an event callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fd_download_poll(
+    download: *mut FdDownload,
+    timeout_ms: u64,
+    event: *mut *const FdEvent,
+) -> FdPoll {
+    // SAFETY: upheld by the caller.
+    let download = unsafe { &mut *download };
+    download.current = None;
+    let Some(rx) = &download.rx else {
+        return FdPoll::Closed;
+    };
+    let timeout = Duration::from_millis(timeout_ms);
+    let received = download
+        .runtime
+        .block_on(async { tokio::time::timeout(timeout, rx.recv()).await });
+    match received {
+        Ok(Ok(received)) => {
+            let current = download.current.insert(OwnedEvent::new(&received));
+            // SAFETY: upheld by the caller.
+            unsafe { *event = current.raw() };
+            FdPoll::Event
+        }
+        Ok(Err(_)) => FdPoll::Closed,
+        Err(_) => FdPoll::Empty,
+    }
+}
+
+/// Pause the download: workers stop and release their connections.
+///
+/// # Safety
+/// `download` must come from [`fd_download_start`] / [`fd_download_resume`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fd_download_pause(download: *const FdDownload) {
+    // SAFETY: upheld by the caller.
+    unsafe { &*download }.handle.pause();
+}
+
+/// Continue a paused download.
+///
+/// # Safety
+/// `download` must come from [`fd_download_start`] / [`fd_download_resume`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fd_download_unpause(download: *const FdDownload) {
+    // SAFETY: upheld by the caller.
+    unsafe { &*download }.handle.unpause();
+}
+
+/// Change the number of workers of the running download.
+///
+/// # Safety
+/// `download` must come from [`fd_download_start`] / [`fd_download_resume`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fd_download_set_threads(download: *const FdDownload, threads: usize) {
+    // SAFETY: upheld by the caller.
+    unsafe { &*download }.handle.set_threads(threads);
+}
+
+/// Cap the workers of a download whose `threads` is `"auto"`.
+///
+/// # Safety
+/// `download` must come from [`fd_download_start`] / [`fd_download_resume`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fd_download_set_max_threads(
+    download: *const FdDownload,
+    max_threads: usize,
+) {
+    // SAFETY: upheld by the caller.
+    unsafe { &*download }.handle.set_max_threads(max_threads);
+}
+
+/// Cancel the download, keeping the `.part` and `.fd` files for a resume.
+///
+/// # Safety
+/// `download` must come from [`fd_download_start`] / [`fd_download_resume`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fd_download_cancel(download: *const FdDownload) {
+    // SAFETY: upheld by the caller.
+    unsafe { &*download }.handle.cancel();
+}
+
+/// Whether the download is over.
+///
+/// # Safety
+/// `download` must come from [`fd_download_start`] / [`fd_download_resume`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fd_download_is_finished(download: *const FdDownload) -> bool {
+    // SAFETY: upheld by the caller.
+    unsafe { &*download }.handle.is_finished()
+}
+
+/// Block until the download is over and, with a callback, every event was
+/// delivered to it.
+///
+/// # Safety
+/// `download` must come from [`fd_download_start`] / [`fd_download_resume`].
+/// Must not be called from an event callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fd_download_join(download: *mut FdDownload) -> FdOutcome {
+    // SAFETY: upheld by the caller.
+    let download = unsafe { &mut *download };
+    let forwarder = download.forwarder.take();
+    let outcome = download.runtime.block_on(async {
+        let outcome = download.handle.join().await;
+        if let Some(forwarder) = forwarder {
+            let _ = forwarder.await;
+        }
+        outcome
+    });
+    FdOutcome::of(outcome.as_ref().map(|o| &o.status))
+}
+
+/// Free a download handle. The download keeps running unless cancelled, but
+/// its events are no longer delivered. Null is ignored.
+///
+/// # Safety
+/// `download` must be null or come from [`fd_download_start`] /
+/// [`fd_download_resume`], and not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fd_download_free(download: *mut FdDownload) {
+    if download.is_null() {
+        return;
+    }
+    // SAFETY: the caller passes a pointer from `fd_download_start`, once.
+    let download = unsafe { Box::from_raw(download) };
+    if let Some(forwarder) = download.forwarder {
+        forwarder.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        FdEventKind, fd_last_error, fd_runtime_free, fd_runtime_new,
+        testing::{BODY, serve, temp_dir},
+    };
+    use std::{
+        ffi::CString,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    fn toml_config(dir: &std::path::Path) -> CString {
+        CString::new(format!(
+            "save_dir = {:?}\nthreads = 1\n",
+            dir.display().to_string()
+        ))
+        .unwrap()
+    }
+
+    unsafe extern "C" fn count(user_data: *mut c_void, event: *const FdEvent) {
+        // SAFETY: the test passes an `AtomicUsize` and the library a valid event.
+        let (counter, event) = unsafe { (&*user_data.cast::<AtomicUsize>(), &*event) };
+        if event.kind == FdEventKind::Completed {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn downloads_through_poll_and_callback() {
+        let runtime = fd_runtime_new(2);
+        assert!(!runtime.is_null());
+        let url = CString::new(serve()).unwrap();
+
+        let dir = temp_dir("poll");
+        let config = toml_config(&dir);
+        let download = unsafe {
+            fd_download_start(
+                runtime,
+                url.as_ptr(),
+                config.as_ptr(),
+                FdConfigFormat::Toml,
+                None,
+                ptr::null_mut(),
+            )
+        };
+        assert!(!download.is_null());
+        let mut kinds = Vec::new();
+        let mut event = ptr::null();
+        loop {
+            match unsafe { fd_download_poll(download, 5000, &raw mut event) } {
+                FdPoll::Event => kinds.push(unsafe { (*event).kind }),
+                FdPoll::Empty => panic!("no event within 5s, got {kinds:?}"),
+                FdPoll::Closed => break,
+            }
+        }
+        assert_eq!(unsafe { fd_download_join(download) }, FdOutcome::Completed);
+        assert!(unsafe { fd_download_is_finished(download) });
+        unsafe { fd_download_free(download) };
+        assert_eq!(kinds.first(), Some(&FdEventKind::Prefetch));
+        assert_eq!(kinds.last(), Some(&FdEventKind::Completed));
+        assert_eq!(std::fs::read(dir.join("file.bin")).unwrap(), BODY);
+
+        let dir = temp_dir("callback");
+        let config = toml_config(&dir);
+        let completed = AtomicUsize::new(0);
+        let download = unsafe {
+            fd_download_start(
+                runtime,
+                url.as_ptr(),
+                config.as_ptr(),
+                FdConfigFormat::Toml,
+                Some(count),
+                (&raw const completed).cast_mut().cast(),
+            )
+        };
+        assert_eq!(unsafe { fd_download_join(download) }, FdOutcome::Completed);
+        assert_eq!(completed.load(Ordering::SeqCst), 1);
+        unsafe {
+            fd_download_free(download);
+            fd_runtime_free(runtime);
+        }
+        assert_eq!(std::fs::read(dir.join("file.bin")).unwrap(), BODY);
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        let runtime = fd_runtime_new(1);
+        let url = CString::new("not a url").unwrap();
+        let download = unsafe {
+            fd_download_start(
+                runtime,
+                url.as_ptr(),
+                ptr::null(),
+                FdConfigFormat::Toml,
+                None,
+                ptr::null_mut(),
+            )
+        };
+        assert!(download.is_null());
+        let error = unsafe { CStr::from_ptr(fd_last_error()) }.to_str().unwrap();
+        assert!(error.starts_with("invalid url"), "{error}");
+
+        let url = CString::new("http://127.0.0.1/").unwrap();
+        let config = CString::new("{\"threads\": \"many\"}").unwrap();
+        let download = unsafe {
+            fd_download_start(
+                runtime,
+                url.as_ptr(),
+                config.as_ptr(),
+                FdConfigFormat::Json,
+                None,
+                ptr::null_mut(),
+            )
+        };
+        assert!(download.is_null());
+        let error = unsafe { CStr::from_ptr(fd_last_error()) }.to_str().unwrap();
+        assert!(error.starts_with("invalid config"), "{error}");
+        unsafe { fd_runtime_free(runtime) };
+    }
+}
@@ -0,0 +1,326 @@
+use fast_down_api::{Event, ProgressSample, fast_down::DownloadStatus};
+use std::{ffi::CString, fmt::Display, os::raw::c_char, ptr};
+
+/// Which [`Event`] an [`FdEvent`] reports; one kind per variant.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdEventKind {
+    Prefetch,
+    PrefetchError,
+    MirrorRejected,
+    SizeMismatch,
+    Metalink,
+    MetalinkError,
+    GenPathError,
+    StateSaveError,
+    BuildClientError,
+    BuildPusherError,
+    RenameFailed,
+    Verified,
+    DigestMismatch,
+    IntegrityError,
+    PieceMismatch,
+    VerifyError,
+    Renamed,
+    Start,
+    Resumed,
+    ResumeError,
+    Pulling,
+    PullError,
+    PullTimeout,
+    PullSlow,
+    Throttled,
+    PullProgress,
+    Pushing,
+    PushError,
+    PushProgress,
+    Progress,
+    Workers,
+    Concurrency,
+    Flushing,
+    FlushError,
+    PullFailed,
+    PullTimeoutFailed,
+    PushFailed,
+    Finished,
+    Completed,
+    Failed,
+}
+
+/// One event of a download, flattened into a C struct.
+///
+/// Fields an event does not carry are zero, `eta_ms` is `-1` and `message` is
+/// null.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FdEvent {
+    pub kind: FdEventKind,
+    /// The worker of per-worker events; the number of running workers for
+    /// `Workers` and the worker count picked for `Concurrency`.
+    pub worker: usize,
+    /// The byte range of `PullProgress`, `Pushing`, `PushError`,
+    /// `PushProgress` and `PushFailed`, end exclusive.
+    pub start: u64,
+    pub end: u64,
+    /// Bytes written: of `Progress`, of the progress `Resumed` continues from,
+    /// and pushed by a `Completed` or `Failed` run.
+    pub downloaded: u64,
+    /// The file size, from `Prefetch`, `Resumed` and `Progress`; the size the
+    /// server reports for `SizeMismatch`.
+    pub total: u64,
+    /// Smoothed and average rates of `Progress`, in bytes per second; `bps`
+    /// is the summed speed of the workers for `Workers`.
+    pub bps: u64,
+    pub avg_bps: u64,
+    /// Time spent downloading, of `Progress`, `Completed` and `Failed`.
+    pub elapsed_ms: u64,
+    /// Time left of `Progress`, `-1` while unknown; the cooldown of
+    /// `Throttled`.
+    pub eta_ms: i64,
+    /// UTF-8 text: the error of error events, the path of `Start` and
+    /// `Renamed`, the digest of `Verified`, the URLs of the files of
+    /// `Metalink`, one per line. Owned by the library, like the event itself.
+    pub message: *const c_char,
+}
+
+impl FdEvent {
+    const fn new(kind: FdEventKind) -> Self {
+        Self {
+            kind,
+            worker: 0,
+            start: 0,
+            end: 0,
+            downloaded: 0,
+            total: 0,
+            bps: 0,
+            avg_bps: 0,
+            elapsed_ms: 0,
+            eta_ms: -1,
+            message: ptr::null(),
+        }
+    }
+}
+
+/// An [`FdEvent`] together with the text its `message` points to.
+pub struct OwnedEvent {
+    raw: FdEvent,
+    _message: Option<CString>,
+}
+
+impl OwnedEvent {
+    pub fn new(event: &Event) -> Self {
+        let (mut raw, message) = flatten(event);
+        let message = message.map(c_string);
+        raw.message = message.as_ref().map_or(ptr::null(), |m| m.as_ptr());
+        Self {
+            raw,
+            _message: message,
+        }
+    }
+
+    pub const fn raw(&self) -> &FdEvent {
+        &self.raw
+    }
+}
+
+/// `text` as a C string; interior NULs cannot be represented and are dropped.
+pub fn c_string(text: String) -> CString {
+    CString::new(text).unwrap_or_else(|e| {
+        let mut bytes = e.into_vec();
+        bytes.retain(|&b| b != 0);
+        CString::new(bytes).unwrap_or_default()
+    })
+}
+
+fn millis(duration: std::time::Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[allow(clippy::too_many_lines)]
+pub fn flatten(event: &Event) -> (FdEvent, Option<String>) {
+    use FdEventKind as K;
+    let with = |kind, message: &dyn Display| (FdEvent::new(kind), Some(format!("{message:#}")));
+    let worker = |kind, id| {
+        let mut raw = FdEvent::new(kind);
+        raw.worker = id;
+        raw
+    };
+    let range = |kind, id, range: &std::ops::Range<u64>| {
+        let mut raw = worker(kind, id);
+        raw.start = range.start;
+        raw.end = range.end;
+        raw
+    };
+    match event {
+        Event::Prefetch(info) => {
+            let mut raw = FdEvent::new(K::Prefetch);
+            raw.total = info.size;
+            (raw, None)
+        }
+        Event::PrefetchError(e) => with(K::PrefetchError, e),
+        Event::MirrorRejected(url, e) => (
+            FdEvent::new(K::MirrorRejected),
+            Some(format!("{url}: {e:#}")),
+        ),
+        Event::SizeMismatch { expected, actual } => {
+            let mut raw = FdEvent::new(K::SizeMismatch);
+            raw.total = *actual;
+            (
+                raw,
+                Some(format!("expected {expected} bytes, got {actual}")),
+            )
+        }
+        Event::Metalink(jobs) => {
+            let urls: Vec<_> = jobs.iter().map(|job| job.url.as_str()).collect();
+            (FdEvent::new(K::Metalink), Some(urls.join("\n")))
+        }
+        Event::MetalinkError(e) => with(K::MetalinkError, e),
+        Event::GenPathError(e) => with(K::GenPathError, e),
+        Event::StateSaveError(e) => with(K::StateSaveError, e),
+        Event::BuildClientError(e) => with(K::BuildClientError, e),
+        Event::BuildPusherError(e) => with(K::BuildPusherError, e),
+        Event::RenameFailed(e) => with(K::RenameFailed, e),
+        Event::Verified(digest) => with(K::Verified, digest),
+        Event::DigestMismatch { expected, actual } => (
+            FdEvent::new(K::DigestMismatch),
+            Some(format!("expected {expected}, got {actual}")),
+        ),
+        Event::IntegrityError { advertised, actual } => (
+            FdEvent::new(K::IntegrityError),
+            Some(format!("server advertised {advertised}, got {actual}")),
+        ),
+        Event::PieceMismatch(pieces) => {
+            let pieces: Vec<_> = pieces.iter().map(ToString::to_string).collect();
+            (FdEvent::new(K::PieceMismatch), Some(pieces.join(",")))
+        }
+        Event::VerifyError(e) => with(K::VerifyError, e),
+        Event::Renamed(path) => with(K::Renamed, &path.display()),
+        Event::Start { tmp_path, .. } => with(K::Start, &tmp_path.display()),
+        Event::Resumed { progress, size, .. } => {
+            let mut raw = FdEvent::new(K::Resumed);
+            raw.downloaded = progress.iter().map(|r| r.end - r.start).sum();
+            raw.total = *size;
+            (raw, None)
+        }
+        Event::ResumeError(e) => with(K::ResumeError, e),
+        Event::Pulling(id) => (worker(K::Pulling, *id), None),
+        Event::PullError(id, e) => (worker(K::PullError, *id), Some(format!("{e:#}"))),
+        Event::PullTimeout(id) => (worker(K::PullTimeout, *id), None),
+        Event::PullSlow(id) => (worker(K::PullSlow, *id), None),
+        Event::Throttled(cooldown) => {
+            let mut raw = FdEvent::new(K::Throttled);
+            raw.eta_ms = i64::try_from(cooldown.as_millis()).unwrap_or(i64::MAX);
+            (raw, None)
+        }
+        Event::PullProgress(id, r) => (range(K::PullProgress, *id, r), None),
+        Event::Pushing(id, r) => (range(K::Pushing, *id, r), None),
+        Event::PushError(id, r, e) => (range(K::PushError, *id, r), Some(format!("{e:#}"))),
+        Event::PushProgress(r) => (range(K::PushProgress, 0, r), None),
+        Event::Progress(sample) => (progress(sample), None),
+        Event::Workers(workers) => {
+            let mut raw = worker(K::Workers, workers.len());
+            raw.bps = workers.iter().map(|w| w.bps).sum();
+            (raw, None)
+        }
+        Event::Concurrency(threads) => (worker(K::Concurrency, *threads), None),
+        Event::Flushing => (FdEvent::new(K::Flushing), None),
+        Event::FlushError(e) => with(K::FlushError, e),
+        Event::PullFailed(id, e) => (worker(K::PullFailed, *id), Some(format!("{e:#}"))),
+        Event::PullTimeoutFailed(id) => (worker(K::PullTimeoutFailed, *id), None),
+        Event::PushFailed(id, r, e) => (range(K::PushFailed, *id, r), Some(format!("{e:#}"))),
+        Event::Finished(id) => (worker(K::Finished, *id), None),
+        Event::Completed(outcome) => {
+            let mut raw = FdEvent::new(K::Completed);
+            raw.downloaded = outcome.bytes_pushed;
+            raw.elapsed_ms = millis(outcome.elapsed);
+            (raw, None)
+        }
+        Event::Failed { error, outcome } => {
+            let mut raw = FdEvent::new(K::Failed);
+            if let Some(outcome) = outcome {
+                raw.downloaded = outcome.bytes_pushed;
+                raw.elapsed_ms = millis(outcome.elapsed);
+            }
+            (raw, Some(format!("{error:#}")))
+        }
+    }
+}
+
+fn progress(sample: &ProgressSample) -> FdEvent {
+    let mut raw = FdEvent::new(FdEventKind::Progress);
+    raw.downloaded = sample.downloaded;
+    raw.total = sample.total;
+    raw.bps = sample.bps;
+    raw.avg_bps = sample.avg_bps;
+    raw.elapsed_ms = millis(sample.elapsed);
+    raw.eta_ms = sample
+        .eta
+        .map_or(-1, |eta| i64::try_from(eta.as_millis()).unwrap_or(i64::MAX));
+    raw
+}
+
+/// How a download ended, as returned by
+/// [`fd_download_join`](crate::fd_download_join).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdOutcome {
+    /// The file was downloaded, verified and renamed into place.
+    Completed,
+    /// The engine ran and failed, or a step after it did.
+    Failed,
+    /// Cancelled; the `.part` and `.fd` files are kept for a resume.
+    Aborted,
+    /// The run ended before the engine started, e.g. on a prefetch error.
+    NotStarted,
+}
+
+impl FdOutcome {
+    pub(crate) const fn of(status: Option<&DownloadStatus>) -> Self {
+        match status {
+            Some(DownloadStatus::Completed) => Self::Completed,
+            Some(DownloadStatus::Failed(_)) => Self::Failed,
+            Some(DownloadStatus::Aborted) => Self::Aborted,
+            None => Self::NotStarted,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{ffi::CStr, time::Duration};
+
+    #[test]
+    fn flattens_events() {
+        let event = OwnedEvent::new(&Event::Progress(ProgressSample {
+            progress: vec![0..10, 20..30],
+            bps: 5,
+            avg_bps: 4,
+            downloaded: 20,
+            percent: 20.0,
+            total: 100,
+            elapsed: Duration::from_secs(5),
+            eta: None,
+            chunks: None,
+        }));
+        let raw = event.raw();
+        assert_eq!(raw.kind, FdEventKind::Progress);
+        assert_eq!((raw.downloaded, raw.total, raw.bps), (20, 100, 5));
+        assert_eq!((raw.elapsed_ms, raw.eta_ms), (5000, -1));
+        assert!(raw.message.is_null());
+
+        let event = OwnedEvent::new(&Event::PushError(3, 8..16, anyhow::anyhow!("disk full")));
+        let raw = event.raw();
+        assert_eq!(
+            (raw.kind, raw.worker, raw.start, raw.end),
+            (FdEventKind::PushError, 3, 8, 16)
+        );
+        let message = unsafe { CStr::from_ptr(raw.message) };
+        assert_eq!(message.to_str().unwrap(), "disk full");
+    }
+
+    #[test]
+    fn drops_interior_nuls() {
+        assert_eq!(c_string("a\0b".to_string()).as_bytes(), b"ab");
+    }
+}
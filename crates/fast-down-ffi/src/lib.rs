@@ -0,0 +1,68 @@
+#![doc = include_str!("../README.md")]
+
+mod download;
+mod event;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(test)]
+mod testing;
+
+pub use download::*;
+pub use event::{FdEvent, FdEventKind, FdOutcome};
+
+use std::{cell::RefCell, ffi::CString, fmt::Display, os::raw::c_char, ptr};
+
+/// The Tokio runtime downloads run on. Opaque to C.
+pub struct FdRuntime {
+    runtime: tokio::runtime::Runtime,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Record why the current call failed, for [`fd_last_error`].
+fn set_error(error: impl Display) {
+    let message = event::c_string(format!("{error:#}"));
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// The message of the last call that failed on this thread, or null. Valid
+/// until the next call that fails on this thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn fd_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+/// Start a multi-threaded runtime with `worker_threads` threads, or one per
+/// core when `0`. Returns null on failure, see [`fd_last_error`].
+///
+/// Free it with [`fd_runtime_free`] once every download on it was freed.
+#[unsafe(no_mangle)]
+pub extern "C" fn fd_runtime_new(worker_threads: usize) -> *mut FdRuntime {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    if worker_threads > 0 {
+        builder.worker_threads(worker_threads);
+    }
+    match builder.enable_all().build() {
+        Ok(runtime) => Box::into_raw(Box::new(FdRuntime { runtime })),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Shut `runtime` down, stopping the downloads still running on it. Null is
+/// ignored.
+///
+/// # Safety
+/// `runtime` must be null or come from [`fd_runtime_new`], and not be used
+/// afterwards. Must not be called from an event callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fd_runtime_free(runtime: *mut FdRuntime) {
+    if !runtime.is_null() {
+        // SAFETY: the caller passes a pointer from `fd_runtime_new`, once.
+        drop(unsafe { Box::from_raw(runtime) });
+    }
+}
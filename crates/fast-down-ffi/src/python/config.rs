@@ -0,0 +1,151 @@
+//! Keyword arguments, translated into a [`PartialConfig`].
+
+use fast_down_api::PartialConfig;
+use pyo3::{
+    exceptions::{PyTypeError, PyValueError},
+    prelude::*,
+    types::{PyBool, PyDelta, PyDict, PyFloat, PyInt, PyList, PySet, PyString, PyTuple},
+};
+use serde_json::{Map, Value};
+#[cfg(test)]
+use std::ffi::CStr;
+
+/// Build a [`PartialConfig`] from the keyword arguments of `download` /
+/// `resume`. Their names and values are those of the TOML / JSON config,
+/// except that durations may also be `timedelta`s and paths `os.PathLike`s.
+pub(super) fn parse_config(kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<PartialConfig> {
+    let Some(kwargs) = kwargs else {
+        return Ok(PartialConfig::default());
+    };
+    let mut fields = Map::new();
+    for (key, value) in kwargs.iter() {
+        let key: String = key.extract()?;
+        let value = to_json(&value).map_err(|e| PyTypeError::new_err(format!("{key}: {e}")))?;
+        fields.insert(key, value);
+    }
+    let config: PartialConfig = serde_json::from_value(Value::Object(fields.clone()))
+        .map_err(|e| PyValueError::new_err(format!("invalid config: {e}")))?;
+    // Unknown fields are ignored by serde; a typo must not go unnoticed.
+    let known = serde_json::to_value(&config)
+        .map_err(|e| PyValueError::new_err(format!("invalid config: {e}")))?;
+    if let Some((key, _)) = fields
+        .iter()
+        .find(|(key, value)| !value.is_null() && known.get(key.as_str()).is_none())
+    {
+        return Err(PyTypeError::new_err(format!(
+            "unexpected keyword argument '{key}'"
+        )));
+    }
+    Ok(config)
+}
+
+fn to_json(value: &Bound<'_, PyAny>) -> Result<Value, String> {
+    if value.is_none() {
+        return Ok(Value::Null);
+    }
+    if let Ok(b) = value.cast::<PyBool>() {
+        return Ok(b.is_true().into());
+    }
+    if value.is_instance_of::<PyInt>() {
+        return value
+            .extract::<u64>()
+            .map(Value::from)
+            .or_else(|_| value.extract::<i64>().map(Value::from))
+            .map_err(|e| e.to_string());
+    }
+    if let Ok(f) = value.cast::<PyFloat>() {
+        return Ok(f.value().into());
+    }
+    if let Ok(s) = value.cast::<PyString>() {
+        return Ok(s.to_string().into());
+    }
+    if value.is_instance_of::<PyDelta>() {
+        let seconds: f64 = value
+            .call_method0("total_seconds")
+            .and_then(|s| s.extract())
+            .map_err(|e| e.to_string())?;
+        if seconds < 0.0 {
+            return Err("durations cannot be negative".to_string());
+        }
+        return Ok(format!("{}us", (seconds * 1e6).round()).into());
+    }
+    if let Ok(dict) = value.cast::<PyDict>() {
+        let mut map = Map::new();
+        for (key, value) in dict.iter() {
+            let key = key.extract::<String>().map_err(|e| e.to_string())?;
+            map.insert(key, to_json(&value)?);
+        }
+        return Ok(Value::Object(map));
+    }
+    if value.is_instance_of::<PyList>()
+        || value.is_instance_of::<PyTuple>()
+        || value.is_instance_of::<PySet>()
+    {
+        let items = value.try_iter().map_err(|e| e.to_string())?;
+        return items
+            .map(|item| to_json(&item.map_err(|e| e.to_string())?))
+            .collect::<Result<_, _>>()
+            .map(Value::Array);
+    }
+    if value.hasattr("__fspath__").unwrap_or(false) {
+        let path = value
+            .call_method0("__fspath__")
+            .map_err(|e| e.to_string())?;
+        return to_json(&path);
+    }
+    let type_name = value
+        .get_type()
+        .name()
+        .map_or_else(|_| "?".to_string(), |n| n.to_string());
+    Err(format!("unsupported value of type {type_name}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{path::PathBuf, time::Duration};
+
+    fn parse(kwargs: &CStr) -> PyResult<PartialConfig> {
+        Python::initialize();
+        Python::attach(|py| {
+            let kwargs = py.eval(kwargs, None, None)?;
+            parse_config(Some(kwargs.cast()?))
+        })
+    }
+
+    #[test]
+    fn maps_keyword_arguments() {
+        let config = parse(
+            cr#"dict(
+                save_dir=__import__("pathlib").Path("/tmp/dl"),
+                threads=8,
+                retry_gap=__import__("datetime").timedelta(milliseconds=250),
+                pull_timeout="2s",
+                headers={"X-Token": "abc"},
+                mirrors=("https://mirror.example/a.bin",),
+                expected_digest=None,
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(config.save_dir, Some(PathBuf::from("/tmp/dl")));
+        assert_eq!(config.threads, Some(8));
+        assert_eq!(config.retry_gap, Some(Duration::from_millis(250)));
+        assert_eq!(config.pull_timeout, Some(Duration::from_secs(2)));
+        assert_eq!(config.headers.unwrap()["X-Token"], "abc");
+        assert_eq!(config.mirrors.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rejects_unknown_and_invalid_arguments() {
+        Python::initialize();
+        let err = parse(c"dict(thread=8)").unwrap_err();
+        Python::attach(|py| assert!(err.is_instance_of::<PyTypeError>(py), "{err}"));
+        assert!(err.to_string().contains("'thread'"), "{err}");
+
+        let err = parse(c"dict(threads='many')").unwrap_err();
+        Python::attach(|py| assert!(err.is_instance_of::<PyValueError>(py), "{err}"));
+
+        let err = parse(c"dict(threads=object())").unwrap_err();
+        Python::attach(|py| assert!(err.is_instance_of::<PyTypeError>(py), "{err}"));
+    }
+}
@@ -0,0 +1,273 @@
+//! What a download reports to Python.
+
+use crate::{FdEventKind, event::flatten};
+use fast_down_api::fast_down::{DownloadOutcome, DownloadStatus, WorkerSnapshot};
+use pyo3::prelude::*;
+use std::time::Duration;
+
+/// One event of a download.
+///
+/// `kind` names the `Event` variant, e.g. `"PullError"`; the other fields are
+/// `None` or zero when the event does not carry them.
+#[pyclass(module = "fast_down", frozen, get_all, skip_from_py_object)]
+#[derive(Debug, Clone)]
+pub struct Event {
+    kind: String,
+    /// The worker of per-worker events.
+    worker: Option<usize>,
+    /// The byte range of `PullProgress`, `Pushing`, `PushError`,
+    /// `PushProgress` and `PushFailed`, end exclusive.
+    range: Option<(u64, u64)>,
+    /// The error of error events, the path of `Start` and `Renamed`, the
+    /// digest of `Verified`, the URLs of the files of `Metalink`, one per
+    /// line.
+    message: Option<String>,
+    /// Bytes written: of `Progress`, of the progress `Resumed` continues from,
+    /// and pushed by a `Completed` or `Failed` run.
+    downloaded: u64,
+    /// The file size, from `Prefetch`, `Resumed` and `Progress`; the size the
+    /// server reports for `SizeMismatch`.
+    total: u64,
+    /// The sample of `Progress`.
+    progress: Option<ProgressSample>,
+    /// The running workers of `Workers`.
+    workers: Option<Vec<Worker>>,
+    /// The worker count picked for `Concurrency`.
+    threads: Option<usize>,
+    /// The cooldown of `Throttled`, in seconds.
+    cooldown: Option<f64>,
+}
+
+impl Event {
+    pub(super) fn new(event: &fast_down_api::Event) -> Self {
+        use FdEventKind as K;
+        let (raw, message) = flatten(event);
+        let worker = matches!(
+            raw.kind,
+            K::Pulling
+                | K::PullError
+                | K::PullTimeout
+                | K::PullSlow
+                | K::PullProgress
+                | K::Pushing
+                | K::PushError
+                | K::PullFailed
+                | K::PullTimeoutFailed
+                | K::PushFailed
+                | K::Finished
+        )
+        .then_some(raw.worker);
+        let range = matches!(
+            raw.kind,
+            K::PullProgress | K::Pushing | K::PushError | K::PushProgress | K::PushFailed
+        )
+        .then_some((raw.start, raw.end));
+        let threads = (raw.kind == K::Concurrency).then_some(raw.worker);
+        let cooldown = match event {
+            fast_down_api::Event::Throttled(cooldown) => Some(cooldown.as_secs_f64()),
+            _ => None,
+        };
+        let (progress, workers) = match event {
+            fast_down_api::Event::Progress(sample) => (Some(sample.clone().into()), None),
+            fast_down_api::Event::Workers(workers) => {
+                (None, Some(workers.iter().map(Worker::from).collect()))
+            }
+            _ => (None, None),
+        };
+        Self {
+            kind: format!("{:?}", raw.kind),
+            worker,
+            range,
+            message,
+            downloaded: raw.downloaded,
+            total: raw.total,
+            progress,
+            workers,
+            threads,
+            cooldown,
+        }
+    }
+}
+
+#[pymethods]
+impl Event {
+    fn __repr__(&self) -> String {
+        let mut fields = vec![format!("kind={:?}", self.kind)];
+        if let Some(worker) = self.worker {
+            fields.push(format!("worker={worker}"));
+        }
+        if let Some((start, end)) = self.range {
+            fields.push(format!("range=({start}, {end})"));
+        }
+        if let Some(message) = &self.message {
+            fields.push(format!("message={message:?}"));
+        }
+        if let Some(progress) = &self.progress {
+            fields.push(format!("progress={}", progress.__repr__()));
+        }
+        if let Some(workers) = &self.workers {
+            fields.push(format!("workers=[{} workers]", workers.len()));
+        }
+        if let Some(threads) = self.threads {
+            fields.push(format!("threads={threads}"));
+        }
+        if let Some(cooldown) = self.cooldown {
+            fields.push(format!("cooldown={cooldown}"));
+        }
+        format!("Event({})", fields.join(", "))
+    }
+}
+
+/// A snapshot of the download progress. Durations are in seconds.
+#[pyclass(module = "fast_down", frozen, get_all, skip_from_py_object, eq)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressSample {
+    /// The ranges written so far, end exclusive.
+    progress: Vec<(u64, u64)>,
+    bps: u64,
+    avg_bps: u64,
+    downloaded: u64,
+    percent: f64,
+    total: u64,
+    elapsed: f64,
+    /// `None` while the rate is unknown.
+    eta: Option<f64>,
+    /// `(fill, done, active)` for each cell of the chunk map; `None` unless
+    /// `chunk_map_cells` is set.
+    chunks: Option<Vec<(f32, bool, bool)>>,
+}
+
+impl From<fast_down_api::ProgressSample> for ProgressSample {
+    fn from(sample: fast_down_api::ProgressSample) -> Self {
+        Self {
+            progress: sample.progress.iter().map(|r| (r.start, r.end)).collect(),
+            bps: sample.bps,
+            avg_bps: sample.avg_bps,
+            downloaded: sample.downloaded,
+            percent: sample.percent,
+            total: sample.total,
+            elapsed: sample.elapsed.as_secs_f64(),
+            eta: sample.eta.as_ref().map(Duration::as_secs_f64),
+            chunks: sample.chunks.map(|map| {
+                (0..map.cells())
+                    .map(|i| (map.fill[i], map.is_done(i), map.is_active(i)))
+                    .collect()
+            }),
+        }
+    }
+}
+
+#[pymethods]
+impl ProgressSample {
+    fn __repr__(&self) -> String {
+        format!(
+            "ProgressSample(downloaded={}, total={}, percent={:.2}, bps={}, avg_bps={}, \
+             elapsed={:.3}, eta={})",
+            self.downloaded,
+            self.total,
+            self.percent,
+            self.bps,
+            self.avg_bps,
+            self.elapsed,
+            self.eta
+                .map_or_else(|| "None".to_string(), |eta| format!("{eta:.3}")),
+        )
+    }
+}
+
+/// One running worker, as listed by `Download.workers()` and `Workers`
+/// events.
+#[pyclass(module = "fast_down", frozen, get_all, skip_from_py_object, eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Worker {
+    id: usize,
+    /// What is left of its range, end exclusive; `None` when the server
+    /// cannot serve ranges and one worker pulls the whole body.
+    range: Option<(u64, u64)>,
+    /// Pull rate over about the last second.
+    bps: u64,
+    /// Whether another worker races this one on the same range.
+    speculative: bool,
+    pulled: u64,
+    pull_errors: usize,
+    pull_timeouts: usize,
+    slow_reconnects: usize,
+    push_errors: usize,
+}
+
+impl From<&WorkerSnapshot> for Worker {
+    fn from(worker: &WorkerSnapshot) -> Self {
+        Self {
+            id: worker.id,
+            range: worker.range.as_ref().map(|r| (r.start, r.end)),
+            bps: worker.bps,
+            speculative: worker.speculative,
+            pulled: worker.counts.pulled,
+            pull_errors: worker.counts.pull_errors,
+            pull_timeouts: worker.counts.pull_timeouts,
+            slow_reconnects: worker.counts.slow_reconnects,
+            push_errors: worker.counts.push_errors,
+        }
+    }
+}
+
+#[pymethods]
+impl Worker {
+    fn __repr__(&self) -> String {
+        let range = self.range.map_or_else(
+            || "None".to_string(),
+            |(start, end)| format!("({start}, {end})"),
+        );
+        format!(
+            "Worker(id={}, range={range}, bps={}, speculative={}, pulled={})",
+            self.id,
+            self.bps,
+            if self.speculative { "True" } else { "False" },
+            self.pulled
+        )
+    }
+}
+
+/// How a download ended. `status` is `"completed"`, `"failed"` or
+/// `"aborted"`; `error` says why a failed download failed.
+#[pyclass(module = "fast_down", frozen, get_all, skip_from_py_object)]
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    status: &'static str,
+    error: Option<String>,
+    bytes_pulled: u64,
+    bytes_pushed: u64,
+    /// Seconds.
+    elapsed: f64,
+}
+
+impl From<DownloadOutcome> for Outcome {
+    fn from(outcome: DownloadOutcome) -> Self {
+        let (status, error) = match outcome.status {
+            DownloadStatus::Completed => ("completed", None),
+            DownloadStatus::Failed(e) => ("failed", Some(e)),
+            DownloadStatus::Aborted => ("aborted", None),
+        };
+        Self {
+            status,
+            error,
+            bytes_pulled: outcome.bytes_pulled,
+            bytes_pushed: outcome.bytes_pushed,
+            elapsed: outcome.elapsed.as_secs_f64(),
+        }
+    }
+}
+
+#[pymethods]
+impl Outcome {
+    fn __repr__(&self) -> String {
+        let error = self
+            .error
+            .as_ref()
+            .map_or_else(String::new, |e| format!(", error={e:?}"));
+        format!(
+            "Outcome(status={:?}{error}, bytes_pushed={}, elapsed={:.3})",
+            self.status, self.bytes_pushed, self.elapsed
+        )
+    }
+}
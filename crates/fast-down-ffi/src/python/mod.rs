@@ -0,0 +1,381 @@
+//! The `fast_down` Python extension module.
+
+mod config;
+mod event;
+
+use config::parse_config;
+use event::{Event, Outcome, ProgressSample, Worker};
+use fast_down_api::{DownloadHandle, Rx, Tx, create_channel};
+use pyo3::{
+    IntoPyObjectExt,
+    exceptions::PyStopAsyncIteration,
+    prelude::*,
+    types::{PyCFunction, PyDict, PyTuple},
+};
+use std::{path::PathBuf, pin::pin, sync::OnceLock, time::Duration};
+use tokio::runtime::Runtime;
+use tokio_util::sync::CancellationToken;
+
+/// How often a blocking call wakes up to let `KeyboardInterrupt` through.
+const SIGNAL_CHECK_GAP: Duration = Duration::from_millis(100);
+
+/// The runtime every download of the process runs on.
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("failed to start the tokio runtime")
+    })
+}
+
+/// A running download, returned by [`download`] and [`resume`].
+///
+/// Await it, or call `join()`, for its `Outcome`; iterate it, with `for` or
+/// `async for`, for its `Event`s.
+#[pyclass(module = "fast_down", frozen)]
+pub struct Download {
+    handle: DownloadHandle,
+    rx: Rx,
+}
+
+impl Download {
+    fn spawn(start: impl FnOnce(Tx, CancellationToken) -> DownloadHandle) -> Self {
+        let _guard = runtime().enter();
+        let (tx, rx) = create_channel();
+        let handle = start(tx, CancellationToken::new());
+        Self { handle, rx }
+    }
+}
+
+#[pymethods]
+impl Download {
+    /// Pause the download: workers stop and release their connections.
+    fn pause(&self) {
+        self.handle.pause();
+    }
+
+    /// Continue a paused download.
+    fn unpause(&self) {
+        self.handle.unpause();
+    }
+
+    fn is_paused(&self) -> bool {
+        self.handle.is_paused()
+    }
+
+    /// Change the number of workers of the running download.
+    fn set_threads(&self, threads: usize) {
+        self.handle.set_threads(threads);
+    }
+
+    /// Cap the workers of a `threads = "auto"` download.
+    fn set_max_threads(&self, max_threads: usize) {
+        self.handle.set_max_threads(max_threads);
+    }
+
+    fn set_min_chunk_size(&self, min_chunk_size: u64) {
+        self.handle.set_min_chunk_size(min_chunk_size);
+    }
+
+    fn set_max_speculative(&self, max_speculative: usize) {
+        self.handle.set_max_speculative(max_speculative);
+    }
+
+    /// The latest progress sample, or `None` before the engine started.
+    fn progress(&self) -> Option<ProgressSample> {
+        self.handle.progress().map(Into::into)
+    }
+
+    /// The workers running right now; empty while the engine is not running.
+    fn workers(&self) -> Vec<Worker> {
+        self.handle.workers().iter().map(Worker::from).collect()
+    }
+
+    /// Cancel the download, keeping the `.part` and `.fd` files for a resume.
+    fn cancel(&self) {
+        self.handle.cancel();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.handle.is_cancelled()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Block until the download is over. `None` when it ended before the
+    /// engine started; the events tell why.
+    fn join(&self, py: Python<'_>) -> PyResult<Option<Outcome>> {
+        let outcome = block_on(py, self.handle.token(), self.handle.join())?;
+        Ok(outcome.map(Into::into))
+    }
+
+    /// `await download` is the asynchronous `join()`.
+    fn __await__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let handle = self.handle.clone();
+        let future = awaitable(py, self.handle.token().clone(), async move {
+            Ok(handle.join().await.map(Outcome::from))
+        })?;
+        future.call_method0("__await__")
+    }
+
+    const fn __iter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<Event>> {
+        let event = block_on(py, self.handle.token(), self.rx.recv())?;
+        Ok(event.ok().map(|e| Event::new(&e)))
+    }
+
+    const fn __aiter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let rx = self.rx.clone();
+        awaitable(py, self.handle.token().clone(), async move {
+            rx.recv().await.map_or_else(
+                |_| Err(PyStopAsyncIteration::new_err(())),
+                |event| Ok(Event::new(&event)),
+            )
+        })
+    }
+}
+
+/// Run `future` to completion with the GIL released. On `KeyboardInterrupt`
+/// the download is cancelled and the exception raised.
+fn block_on<F>(py: Python<'_>, token: &CancellationToken, future: F) -> PyResult<F::Output>
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    let mut future = pin!(future);
+    loop {
+        let polled = py.detach(|| {
+            runtime()
+                .block_on(async { tokio::time::timeout(SIGNAL_CHECK_GAP, future.as_mut()).await })
+        });
+        if let Ok(output) = polled {
+            return Ok(output);
+        }
+        if let Err(e) = py.check_signals() {
+            token.cancel();
+            return Err(e);
+        }
+    }
+}
+
+/// An asyncio future of the running event loop, resolved with the output of
+/// `future` run on the runtime.
+///
+/// Cancelling it, as cancelling the Python task awaiting it does, stops
+/// `future` and cancels the download through `token`.
+fn awaitable<'py, T>(
+    py: Python<'py>,
+    token: CancellationToken,
+    future: impl Future<Output = PyResult<T>> + Send + 'static,
+) -> PyResult<Bound<'py, PyAny>>
+where
+    T: for<'a> IntoPyObject<'a> + Send + 'static,
+{
+    let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+    let py_future = event_loop.call_method0("create_future")?;
+    let task = runtime().spawn({
+        let event_loop = event_loop.clone().unbind();
+        let py_future = py_future.clone().unbind();
+        async move {
+            let output = future.await;
+            Python::attach(|py| {
+                let (value, error) = match output.and_then(|v| v.into_py_any(py)) {
+                    Ok(value) => (value, None),
+                    Err(e) => (py.None(), Some(e.into_value(py))),
+                };
+                // The loop may be closed by now; nobody is waiting then.
+                let _ = wrap_pyfunction!(resolve, py).and_then(|resolve| {
+                    event_loop
+                        .bind(py)
+                        .call_method1("call_soon_threadsafe", (resolve, py_future, value, error))
+                });
+            });
+        }
+    });
+    let on_done = PyCFunction::new_closure(
+        py,
+        None,
+        None,
+        move |args: &Bound<'_, PyTuple>, _: Option<&Bound<'_, PyDict>>| -> PyResult<()> {
+            if args.get_item(0)?.call_method0("cancelled")?.is_truthy()? {
+                task.abort();
+                token.cancel();
+            }
+            Ok(())
+        },
+    )?;
+    py_future.call_method1("add_done_callback", (on_done,))?;
+    Ok(py_future)
+}
+
+/// Settle `future` on its event loop, unless it was cancelled meanwhile.
+#[pyfunction]
+fn resolve(
+    future: &Bound<'_, PyAny>,
+    value: &Bound<'_, PyAny>,
+    error: Option<&Bound<'_, PyAny>>,
+) -> PyResult<()> {
+    if future.call_method0("done")?.is_truthy()? {
+        return Ok(());
+    }
+    match error {
+        Some(error) => future.call_method1("set_exception", (error,))?,
+        None => future.call_method1("set_result", (value,))?,
+    };
+    Ok(())
+}
+
+/// Download `url` into a new file, resuming an interrupted download of the
+/// same file when its state allows. Keyword arguments are the fields of
+/// `PartialConfig`.
+#[pyfunction]
+#[pyo3(signature = (url, **config))]
+fn download(url: &str, config: Option<&Bound<'_, PyDict>>) -> PyResult<Download> {
+    let url = parse_url(url)?;
+    let config = parse_config(config)?;
+    Ok(Download::spawn(|tx, token| {
+        fast_down_api::download(url, config, tx, token)
+    }))
+}
+
+/// Resume the `.part` file at `tmp_path`. `url` defaults to the one recorded
+/// in the `.fd` state; keyword arguments are the fields of `PartialConfig`.
+#[pyfunction]
+#[pyo3(signature = (tmp_path, url = None, **config))]
+fn resume(
+    tmp_path: PathBuf,
+    url: Option<&str>,
+    config: Option<&Bound<'_, PyDict>>,
+) -> PyResult<Download> {
+    let url = url.map(parse_url).transpose()?;
+    let config = parse_config(config)?;
+    Ok(Download::spawn(|tx, token| {
+        fast_down_api::resume(tmp_path, url, config, tx, token)
+    }))
+}
+
+fn parse_url(text: &str) -> PyResult<url::Url> {
+    url::Url::parse(text)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("invalid url {text:?}: {e}")))
+}
+
+#[pymodule]
+fn fast_down(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(download, module)?)?;
+    module.add_function(wrap_pyfunction!(resume, module)?)?;
+    module.add_class::<Download>()?;
+    module.add_class::<Event>()?;
+    module.add_class::<ProgressSample>()?;
+    module.add_class::<Worker>()?;
+    module.add_class::<Outcome>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{BODY, serve, temp_dir};
+    use std::ffi::CStr;
+
+    /// Run `code` with `fast_down`, `url` and `save_dir` in scope.
+    fn run(code: &CStr, url: &str, save_dir: &std::path::Path) {
+        Python::initialize();
+        Python::attach(|py| {
+            let globals = PyDict::new(py);
+            globals
+                .set_item("fast_down", pyo3::wrap_pymodule!(fast_down)(py))
+                .unwrap();
+            globals.set_item("url", url).unwrap();
+            globals.set_item("save_dir", save_dir).unwrap();
+            if let Err(e) = py.run(code, Some(&globals), None) {
+                e.display(py);
+                panic!("{e}");
+            }
+        });
+    }
+
+    #[test]
+    fn downloads_with_asyncio() {
+        let dir = temp_dir("python_async");
+        run(
+            cr#"
+import asyncio
+
+async def main():
+    download = fast_down.download(url, save_dir=save_dir, threads=1)
+    kinds = [event.kind async for event in download]
+    assert kinds[0] == "Prefetch", kinds
+    assert kinds[-1] == "Completed", kinds
+    outcome = await download
+    assert outcome.status == "completed", outcome
+    assert download.is_finished()
+
+asyncio.run(main())
+"#,
+            &serve(),
+            &dir,
+        );
+        assert_eq!(std::fs::read(dir.join("file.bin")).unwrap(), BODY);
+    }
+
+    #[test]
+    fn downloads_blocking() {
+        let dir = temp_dir("python_blocking");
+        run(
+            cr#"
+download = fast_down.download(url, save_dir=save_dir, threads=1)
+events = list(download)
+assert events[-1].kind == "Completed", events
+outcome = download.join()
+assert outcome.status == "completed" and outcome.error is None, outcome
+"#,
+            &serve(),
+            &dir,
+        );
+        assert_eq!(std::fs::read(dir.join("file.bin")).unwrap(), BODY);
+    }
+
+    #[test]
+    fn cancelling_the_task_cancels_the_download() {
+        // Accepts connections but never answers, so the prefetch hangs.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file.bin", listener.local_addr().unwrap());
+        run(
+            cr"
+import asyncio
+
+async def main():
+    download = fast_down.download(url, save_dir=save_dir)
+
+    async def wait():
+        return await download
+
+    task = asyncio.create_task(wait())
+    await asyncio.sleep(0.2)
+    assert not download.is_cancelled()
+    task.cancel()
+    try:
+        await task
+    except asyncio.CancelledError:
+        pass
+    assert download.is_cancelled()
+
+asyncio.run(main())
+",
+            &url,
+            &temp_dir("python_cancel"),
+        );
+        drop(listener);
+    }
+}
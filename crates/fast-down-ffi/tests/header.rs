@@ -0,0 +1,28 @@
+//! Keeps `include/fast_down.h` in step with the exported functions and types.
+
+use std::path::Path;
+
+#[test]
+fn header_is_up_to_date() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let config =
+        cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).expect("cbindgen.toml");
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_crate(crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("generate the header")
+        .write(&mut generated);
+    let generated = String::from_utf8(generated).expect("UTF-8 header");
+
+    let path = crate_dir.join("include/fast_down.h");
+    if std::env::var_os("UPDATE_HEADER").is_some() {
+        std::fs::write(&path, &generated).expect("write the header");
+    }
+    let committed = std::fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        committed == generated,
+        "include/fast_down.h is stale; rerun with UPDATE_HEADER=1"
+    );
+}
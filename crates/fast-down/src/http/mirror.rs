@@ -0,0 +1,537 @@
+//! Pulling one resource from several mirrors at once.
+//!
+//! [`MirrorPuller`] wraps one [`Puller`] per mirror (typically an
+//! [`HttpPuller`](crate::http::HttpPuller) or a `FastDownPuller`) and picks a
+//! mirror for every range a worker pulls: mirrors that deliver faster and fail
+//! less get more of the work, a worker whose mirror just failed moves to another
+//! one, and a mirror that failed permanently is left out for good.
+//! [`check_mirror`] is the matching prefetch check that every mirror serves the
+//! same file.
+
+use crate::{FileId, UrlInfo};
+use bytes::Bytes;
+use core::{
+    fmt,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    task::{Context, Poll},
+    time::Duration,
+};
+use fast_pull::{ProgressEntry, PullResult, PullStream, Puller, PullerError};
+use futures::{Stream, TryStream};
+use std::{sync::Arc, time::Instant};
+
+/// Why a mirror was found not to serve the same file as the primary URL.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MirrorMismatch {
+    #[error("mirror has size {got}, expected {expected}")]
+    Size { expected: u64, got: u64 },
+    #[error("mirror has file id {got:?}, expected {expected:?}")]
+    FileId { expected: FileId, got: FileId },
+    #[error("mirror does not support range requests")]
+    NoRange,
+}
+
+/// Check the prefetch result of a mirror against the one of the primary URL.
+///
+/// A mirror can only take over ranges if it serves the same bytes, so its size
+/// and [`FileId`] must match, and it must support range requests.
+///
+/// # Errors
+/// Returns the first property the mirror does not share with `primary`.
+pub fn check_mirror(primary: &UrlInfo, mirror: &UrlInfo) -> Result<(), MirrorMismatch> {
+    if mirror.size != primary.size {
+        return Err(MirrorMismatch::Size {
+            expected: primary.size,
+            got: mirror.size,
+        });
+    }
+    if mirror.file_id != primary.file_id {
+        return Err(MirrorMismatch::FileId {
+            expected: primary.file_id.clone(),
+            got: mirror.file_id.clone(),
+        });
+    }
+    if !mirror.fast_download {
+        return Err(MirrorMismatch::NoRange);
+    }
+    Ok(())
+}
+
+/// An error of the mirror at index `mirror`.
+#[derive(Debug)]
+pub struct MirrorError<E> {
+    pub mirror: usize,
+    pub error: E,
+    /// Every mirror has failed permanently, so no other one can take over.
+    pub exhausted: bool,
+}
+
+impl<E: fmt::Display> fmt::Display for MirrorError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for MirrorError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl<E: PullerError> PullerError for MirrorError<E> {
+    fn is_irrecoverable(&self) -> bool {
+        self.error.is_irrecoverable()
+    }
+
+    /// Only once every mirror has failed permanently; until then another mirror
+    /// retries the range.
+    fn is_permanent(&self) -> bool {
+        self.exhausted
+    }
+
+    fn is_rate_limited(&self) -> bool {
+        self.error.is_rate_limited()
+    }
+}
+
+/// Live counters of one mirror, shared by every clone of a [`MirrorPuller`].
+#[derive(Debug, Default)]
+struct MirrorStats {
+    bytes: AtomicU64,
+    /// Time spent waiting for the bytes in `bytes`.
+    busy_nanos: AtomicU64,
+    /// Workers currently pulling from this mirror.
+    active: AtomicUsize,
+    consecutive_errors: AtomicUsize,
+    errors: AtomicUsize,
+    dead: AtomicBool,
+}
+
+impl MirrorStats {
+    #[allow(clippy::cast_precision_loss)]
+    fn throughput(&self) -> Option<f64> {
+        let nanos = self.busy_nanos.load(Ordering::Relaxed);
+        (nanos > 0).then(|| self.bytes.load(Ordering::Relaxed) as f64 * 1e9 / nanos as f64)
+    }
+}
+
+/// Snapshot of a mirror's record, returned by [`MirrorPuller::mirrors`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MirrorState {
+    /// Bytes pulled from this mirror by all workers.
+    pub bytes: u64,
+    /// Observed throughput in bytes per second, `None` until data arrived.
+    pub throughput: Option<f64>,
+    /// Workers currently pulling from this mirror.
+    pub active: usize,
+    pub errors: usize,
+    /// The mirror failed permanently and is no longer used.
+    pub dead: bool,
+}
+
+/// A [`Puller`] spreading the ranges of a download over several mirrors.
+///
+/// Each pull goes to the live mirror with the best share of throughput per
+/// worker, discounted by its run of consecutive errors. Mirrors that have not
+/// delivered anything yet are rated like the best one so they get tried. After
+/// an error the worker avoids the failing mirror on its next pull, which is
+/// the failover; a permanent error (e.g. an HTTP `404` from that one mirror)
+/// takes the mirror out of rotation, and only when all of them are out does the
+/// error end the session.
+///
+/// The first pull at offset `0` goes to the first mirror, so a prefetch
+/// response handed to it can be reused.
+pub struct MirrorPuller<P> {
+    pullers: Vec<P>,
+    stats: Arc<[MirrorStats]>,
+    /// Mirror this clone failed on last, avoided by its next pull, or
+    /// [`NO_MIRROR`]. Shared with the streams of this clone, so an error in
+    /// the middle of a body fails over as well.
+    avoid: Arc<AtomicUsize>,
+}
+
+const NO_MIRROR: usize = usize::MAX;
+
+impl<P: Clone> Clone for MirrorPuller<P> {
+    fn clone(&self) -> Self {
+        Self {
+            pullers: self.pullers.clone(),
+            stats: self.stats.clone(),
+            avoid: Arc::new(AtomicUsize::new(NO_MIRROR)),
+        }
+    }
+}
+
+impl<P: fmt::Debug> fmt::Debug for MirrorPuller<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MirrorPuller")
+            .field("pullers", &self.pullers)
+            .field("mirrors", &self.mirrors())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<P> MirrorPuller<P> {
+    /// Pull from `pullers`, one per mirror, the first being the primary.
+    ///
+    /// # Panics
+    /// Panics if `pullers` is empty.
+    #[must_use]
+    pub fn new(pullers: Vec<P>) -> Self {
+        assert!(
+            !pullers.is_empty(),
+            "MirrorPuller needs at least one mirror"
+        );
+        Self {
+            stats: pullers.iter().map(|_| MirrorStats::default()).collect(),
+            pullers,
+            avoid: Arc::new(AtomicUsize::new(NO_MIRROR)),
+        }
+    }
+
+    /// The record of every mirror, in the order they were given.
+    #[must_use]
+    pub fn mirrors(&self) -> Vec<MirrorState> {
+        self.stats
+            .iter()
+            .map(|s| MirrorState {
+                bytes: s.bytes.load(Ordering::Relaxed),
+                throughput: s.throughput(),
+                active: s.active.load(Ordering::Relaxed),
+                errors: s.errors.load(Ordering::Relaxed),
+                dead: s.dead.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn pick(&self, range: Option<&ProgressEntry>) -> usize {
+        let live = |i: &usize| !self.stats[*i].dead.load(Ordering::Relaxed);
+        let first = &self.stats[0];
+        if range.is_none_or(|r| r.start == 0)
+            && live(&0)
+            && first.bytes.load(Ordering::Relaxed) == 0
+            && first.errors.load(Ordering::Relaxed) == 0
+        {
+            return 0;
+        }
+        let mut candidates: Vec<usize> = (0..self.stats.len()).filter(live).collect();
+        if candidates.is_empty() {
+            candidates = (0..self.stats.len()).collect();
+        }
+        if candidates.len() > 1 {
+            let avoid = self.avoid.load(Ordering::Relaxed);
+            candidates.retain(|&i| i != avoid);
+        }
+        let best = self
+            .stats
+            .iter()
+            .filter_map(MirrorStats::throughput)
+            .fold(1.0, f64::max);
+        let score = |i: usize| {
+            let s = &self.stats[i];
+            let errors = s.consecutive_errors.load(Ordering::Relaxed) as f64;
+            s.throughput().unwrap_or(best)
+                / (s.active.load(Ordering::Relaxed) as f64 + 1.0)
+                / (errors + 1.0).powi(2)
+        };
+        candidates
+            .into_iter()
+            .max_by(|&a, &b| score(a).total_cmp(&score(b)).then(b.cmp(&a)))
+            .unwrap_or(0)
+    }
+
+    /// Record an error of `mirror` and wrap it.
+    fn fail<E: PullerError>(stats: &[MirrorStats], mirror: usize, error: E) -> MirrorError<E> {
+        let s = &stats[mirror];
+        s.errors.fetch_add(1, Ordering::Relaxed);
+        s.consecutive_errors.fetch_add(1, Ordering::Relaxed);
+        if error.is_permanent() {
+            s.dead.store(true, Ordering::Relaxed);
+        }
+        MirrorError {
+            mirror,
+            exhausted: error.is_permanent() && stats.iter().all(|s| s.dead.load(Ordering::Relaxed)),
+            error,
+        }
+    }
+}
+
+impl<P: Puller> Puller for MirrorPuller<P> {
+    type Error = MirrorError<P::Error>;
+
+    async fn pull(
+        &mut self,
+        range: Option<&ProgressEntry>,
+    ) -> PullResult<impl PullStream<Self::Error>, Self::Error> {
+        let mirror = self.pick(range);
+        self.avoid.store(NO_MIRROR, Ordering::Relaxed);
+        match self.pullers[mirror].pull(range).await {
+            Ok(inner) => {
+                self.stats[mirror].active.fetch_add(1, Ordering::Relaxed);
+                Ok(MirrorStream {
+                    inner,
+                    stats: self.stats.clone(),
+                    avoid: self.avoid.clone(),
+                    mirror,
+                    last: Instant::now(),
+                })
+            }
+            Err((e, retry_after)) => {
+                self.avoid.store(mirror, Ordering::Relaxed);
+                Err((Self::fail(&self.stats, mirror, e), retry_after))
+            }
+        }
+    }
+}
+
+/// The stream of one pull, charging its bytes and errors to its mirror.
+struct MirrorStream<S> {
+    inner: S,
+    stats: Arc<[MirrorStats]>,
+    /// The `avoid` slot of the puller that opened the stream.
+    avoid: Arc<AtomicUsize>,
+    mirror: usize,
+    last: Instant,
+}
+
+impl<S> Drop for MirrorStream<S> {
+    fn drop(&mut self) {
+        self.stats[self.mirror]
+            .active
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl<E, S> Stream for MirrorStream<S>
+where
+    E: PullerError,
+    S: TryStream<Ok = Bytes, Error = (E, Option<Duration>)> + Unpin,
+{
+    type Item = Result<Bytes, (MirrorError<E>, Option<Duration>)>;
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let polled = Pin::new(&mut this.inner).try_poll_next(cx);
+        let s = &this.stats[this.mirror];
+        match polled {
+            Poll::Ready(Some(Ok(chunk))) => {
+                let now = Instant::now();
+                let waited = now.duration_since(this.last).as_nanos() as u64;
+                this.last = now;
+                s.bytes.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                s.busy_nanos.fetch_add(waited.max(1), Ordering::Relaxed);
+                s.consecutive_errors.store(0, Ordering::Relaxed);
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Ready(Some(Err((e, retry_after)))) => {
+                this.avoid.store(this.mirror, Ordering::Relaxed);
+                Poll::Ready(Some(Err((
+                    MirrorPuller::<()>::fail(&this.stats, this.mirror, e),
+                    retry_after,
+                ))))
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use fast_pull::mock::build_mock_data;
+    use futures::{TryStreamExt, stream};
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("mirror down")]
+    struct Down {
+        permanent: bool,
+    }
+    impl PullerError for Down {
+        fn is_permanent(&self) -> bool {
+            self.permanent
+        }
+    }
+
+    /// Serves `data`, or fails every pull with the given kind of error, after
+    /// the first chunk with `midway`.
+    #[derive(Debug, Clone)]
+    struct TestMirror {
+        data: Arc<[u8]>,
+        fail: Option<bool>,
+        midway: bool,
+    }
+    impl Puller for TestMirror {
+        type Error = Down;
+        #[allow(clippy::cast_possible_truncation)]
+        async fn pull(
+            &mut self,
+            range: Option<&ProgressEntry>,
+        ) -> PullResult<impl PullStream<Self::Error>, Self::Error> {
+            let range = range.cloned().unwrap_or(0..self.data.len() as u64);
+            let mut chunks: Vec<Result<Bytes, (Down, Option<Duration>)>> = self.data
+                [range.start as usize..range.end as usize]
+                .chunks(4)
+                .map(|c| Ok(Bytes::copy_from_slice(c)))
+                .collect();
+            if let Some(permanent) = self.fail {
+                chunks.truncate(usize::from(self.midway));
+                chunks.push(Err((Down { permanent }, None)));
+            }
+            Ok(stream::iter(chunks))
+        }
+    }
+
+    fn mirror(data: &[u8], fail: Option<bool>) -> TestMirror {
+        TestMirror {
+            data: Arc::from(data),
+            fail,
+            midway: false,
+        }
+    }
+
+    async fn pull_all(
+        puller: &mut MirrorPuller<TestMirror>,
+        range: &ProgressEntry,
+    ) -> Result<Vec<u8>, MirrorError<Down>> {
+        let stream = puller.pull(Some(range)).await.map_err(|(e, _)| e)?;
+        stream
+            .map_err(|(e, _)| e)
+            .try_fold(Vec::new(), |mut v, c| async move {
+                v.extend_from_slice(&c);
+                Ok(v)
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn worker_fails_over_to_the_next_mirror() {
+        let data = build_mock_data(64);
+        let mut puller = MirrorPuller::new(vec![mirror(&data, Some(false)), mirror(&data, None)]);
+        let err = pull_all(&mut puller, &(0..32)).await.unwrap_err();
+        assert_eq!(err.mirror, 0);
+        assert!(!err.is_permanent());
+        assert_eq!(pull_all(&mut puller, &(0..32)).await.unwrap(), data[..32]);
+        let mirrors = puller.mirrors();
+        assert_eq!((mirrors[0].errors, mirrors[1].bytes), (1, 32));
+        assert!(mirrors.iter().all(|m| m.active == 0));
+    }
+
+    #[tokio::test]
+    async fn error_mid_body_fails_over_to_the_next_mirror() {
+        let data = build_mock_data(64);
+        let broken = TestMirror {
+            midway: true,
+            ..mirror(&data, Some(false))
+        };
+        let mut puller = MirrorPuller::new(vec![broken, mirror(&data, None)]);
+        // Busy with other workers, the second mirror scores no better than the
+        // first, so only the failover sends the retry there.
+        puller.stats[1].active.fetch_add(3, Ordering::Relaxed);
+        let err = pull_all(&mut puller, &(0..32)).await.unwrap_err();
+        assert_eq!(err.mirror, 0);
+        assert_eq!(puller.mirrors()[0].bytes, 4);
+        assert_eq!(pull_all(&mut puller, &(4..32)).await.unwrap(), data[4..32]);
+        assert_eq!(puller.mirrors()[1].bytes, 28);
+    }
+
+    #[tokio::test]
+    async fn permanent_error_ends_only_when_every_mirror_is_dead() {
+        let data = build_mock_data(64);
+        let mut puller = MirrorPuller::new(vec![mirror(&data, Some(true)), mirror(&data, None)]);
+        let err = pull_all(&mut puller, &(0..8)).await.unwrap_err();
+        assert!(!err.is_permanent(), "another mirror can still serve");
+        assert!(puller.mirrors()[0].dead);
+        // A fresh clone no longer avoids anything, yet never picks the dead one.
+        let mut clone = puller.clone();
+        assert_eq!(pull_all(&mut clone, &(0..8)).await.unwrap(), data[..8]);
+
+        let mut puller = MirrorPuller::new(vec![mirror(&data, Some(true)); 2]);
+        assert!(
+            !pull_all(&mut puller, &(0..8))
+                .await
+                .unwrap_err()
+                .is_permanent()
+        );
+        assert!(
+            pull_all(&mut puller, &(0..8))
+                .await
+                .unwrap_err()
+                .is_permanent()
+        );
+    }
+
+    #[tokio::test]
+    async fn busy_mirrors_share_out_concurrent_pulls() {
+        let data = build_mock_data(64);
+        let puller = MirrorPuller::new(vec![mirror(&data, None); 3]);
+        let (mut a, mut b, mut c) = (puller.clone(), puller.clone(), puller.clone());
+        let streams = (
+            a.pull(Some(&(8..16))).await.ok().unwrap(),
+            b.pull(Some(&(16..24))).await.ok().unwrap(),
+            c.pull(Some(&(24..32))).await.ok().unwrap(),
+        );
+        assert!(puller.mirrors().iter().all(|m| m.active == 1));
+        drop(streams);
+        assert!(puller.mirrors().iter().all(|m| m.active == 0));
+    }
+
+    #[test]
+    fn check_mirror_requires_the_same_file() {
+        let primary = UrlInfo {
+            size: 10,
+            raw_name: "a".into(),
+            supports_range: true,
+            fast_download: true,
+            final_url: url::Url::parse("http://a/x").unwrap(),
+            file_id: FileId::new(Some("e"), None),
+            content_type: None,
+            #[cfg(any(
+                feature = "sha256",
+                feature = "sha1",
+                feature = "md5",
+                feature = "blake3"
+            ))]
+            digests: Vec::new(),
+        };
+        let same = UrlInfo {
+            final_url: url::Url::parse("http://b/x").unwrap(),
+            ..primary.clone()
+        };
+        assert_eq!(check_mirror(&primary, &same), Ok(()));
+        assert!(matches!(
+            check_mirror(
+                &primary,
+                &UrlInfo {
+                    size: 11,
+                    ..same.clone()
+                }
+            ),
+            Err(MirrorMismatch::Size { .. })
+        ));
+        assert!(matches!(
+            check_mirror(
+                &primary,
+                &UrlInfo {
+                    file_id: FileId::default(),
+                    ..same.clone()
+                }
+            ),
+            Err(MirrorMismatch::FileId { .. })
+        ));
+        assert_eq!(
+            check_mirror(
+                &primary,
+                &UrlInfo {
+                    fast_download: false,
+                    ..same
+                }
+            ),
+            Err(MirrorMismatch::NoRange)
+        );
+    }
+}
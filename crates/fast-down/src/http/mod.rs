@@ -10,6 +10,13 @@
 //! * [`Prefetch`]: resolves a [`crate::UrlInfo`] for a URL via a prefetch request.
 //! * [`ContentDisposition`]: parses the `Content-Disposition` header for filenames.
 //! * [`manual_redirect`]: RFC 9110-aware `Referer` computation for redirect following.
+//! * [`MirrorPuller`]: spreads the ranges of one download over several mirrors,
+//!   with [`check_mirror`] to verify they serve the same file.
+//! * `parse_digest_headers`: reads the whole-file digests a server advertises
+//!   (`Repr-Digest`, `Digest`, `Content-MD5`, `x-goog-hash`); needs a hash
+//!   feature.
+//! * [`StatusClass`]: permanent / transient / rate-limited classification of
+//!   non-success status codes.
 //! * [`HttpError`]: the error type produced by this layer.
 //!
 //! Most users do not use these types directly; instead they use
@@ -18,12 +25,30 @@
 
 mod content_disposition;
 pub mod manual_redirect;
+mod mirror;
 mod prefetch;
 mod puller;
+#[cfg(any(
+    feature = "sha256",
+    feature = "sha1",
+    feature = "md5",
+    feature = "blake3"
+))]
+mod repr_digest;
+mod status;
 pub use content_disposition::*;
 pub use manual_redirect::*;
+pub use mirror::*;
 pub use prefetch::*;
 pub use puller::*;
+#[cfg(any(
+    feature = "sha256",
+    feature = "sha1",
+    feature = "md5",
+    feature = "blake3"
+))]
+pub use repr_digest::*;
+pub use status::*;
 
 use crate::url_info::FileId;
 use bytes::Bytes;
@@ -39,7 +64,7 @@ pub trait HttpClient: Clone + Send + Sync + Unpin + 'static {
 /// Abstraction over an HTTP request builder that can be sent to produce a response.
 pub trait HttpRequestBuilder {
     type Response: HttpResponse;
-    type RequestError: std::error::Error + Send + Sync + Unpin;
+    type RequestError: std::error::Error + Send + Sync + Unpin + StatusCodeError;
     fn send(
         self,
     ) -> impl Future<Output = Result<Self::Response, (Self::RequestError, Option<Duration>)>> + Send;
@@ -76,11 +101,22 @@ pub type GetHeaderError<Client> = <GetHeader<Client> as HttpHeaders>::GetHeaderE
 /// Errors that can occur during HTTP download operations.
 ///
 /// Maps to the various stages of an HTTP request: building, streaming chunks,
-/// detecting mismatched file identity, and irrecoverable failures.
+/// detecting mismatched file identity, and irrecoverable failures. A request
+/// that got a non-success response is split by its [`StatusClass`] into
+/// [`Permanent`](Self::Permanent), [`Transient`](Self::Transient) and
+/// [`RateLimited`](Self::RateLimited); [`Request`](Self::Request) is left for
+/// failures without a response. Use [`HttpError::from_request`] to pick the
+/// right one.
 #[derive(thiserror::Error)]
 pub enum HttpError<Client: HttpClient> {
     #[error("HTTP request failed: {0:?}")]
     Request(GetRequestError<Client>),
+    #[error("HTTP request failed permanently: {0:?}")]
+    Permanent(GetRequestError<Client>),
+    #[error("HTTP request failed transiently: {0:?}")]
+    Transient(GetRequestError<Client>),
+    #[error("HTTP request was rate limited: {0:?}")]
+    RateLimited(GetRequestError<Client>),
     #[error("HTTP chunk read failed: {0:?}\n  response: {1:?}")]
     Chunk(GetChunkError<Client>, GetResponse<Client>),
     #[error("irrecoverable pull error")]
@@ -93,6 +129,9 @@ impl<Client: HttpClient> std::fmt::Debug for HttpError<Client> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Request(e) => f.debug_tuple("Request").field(e).finish(),
+            Self::Permanent(e) => f.debug_tuple("Permanent").field(e).finish(),
+            Self::Transient(e) => f.debug_tuple("Transient").field(e).finish(),
+            Self::RateLimited(e) => f.debug_tuple("RateLimited").field(e).finish(),
             Self::Chunk(e, r) => f.debug_tuple("Chunk").field(e).field(r).finish(),
             Self::Irrecoverable => f.write_str("Irrecoverable"),
             Self::MismatchedBody(id, r) => {
@@ -102,16 +141,41 @@ impl<Client: HttpClient> std::fmt::Debug for HttpError<Client> {
     }
 }
 
+impl<Client: HttpClient> HttpError<Client> {
+    /// Wrap a failed request in the variant matching its [`StatusClass`].
+    ///
+    /// Errors without a status code (the request never got a response) become
+    /// [`Request`](Self::Request).
+    pub fn from_request(e: GetRequestError<Client>) -> Self {
+        match e.status_class() {
+            None => Self::Request(e),
+            Some(StatusClass::Permanent) => Self::Permanent(e),
+            Some(StatusClass::Transient) => Self::Transient(e),
+            Some(StatusClass::RateLimited) => Self::RateLimited(e),
+        }
+    }
+}
+
 impl<C: HttpClient> PullerError for HttpError<C> {
     fn is_irrecoverable(&self) -> bool {
         matches!(self, Self::Irrecoverable)
     }
+
+    fn is_permanent(&self) -> bool {
+        matches!(self, Self::Permanent(_))
+    }
+
+    fn is_rate_limited(&self) -> bool {
+        matches!(self, Self::RateLimited(_))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     #![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
-    use super::{HttpClient, HttpError, HttpHeaders, HttpRequestBuilder, HttpResponse};
+    use super::{
+        HttpClient, HttpError, HttpHeaders, HttpRequestBuilder, HttpResponse, StatusCodeError,
+    };
     use crate::url_info::FileId;
     use bytes::Bytes;
     use fast_pull::PullerError;
@@ -174,6 +238,35 @@ mod tests {
     #[derive(Debug, thiserror::Error)]
     #[error("MockError")]
     struct MockErr;
+    impl StatusCodeError for MockErr {}
+
+    #[derive(Clone, Debug)]
+    struct StatusClient;
+    impl HttpClient for StatusClient {
+        type RequestBuilder = StatusRequestBuilder;
+        fn get(&self, _url: Url, _range: Option<fast_pull::ProgressEntry>) -> Self::RequestBuilder {
+            StatusRequestBuilder
+        }
+    }
+    struct StatusRequestBuilder;
+    impl HttpRequestBuilder for StatusRequestBuilder {
+        type Response = MockResponse;
+        type RequestError = StatusErr;
+        fn send(
+            self,
+        ) -> impl Future<Output = Result<Self::Response, (Self::RequestError, Option<Duration>)>> + Send
+        {
+            std::future::ready(Err((StatusErr(404), None)))
+        }
+    }
+    #[derive(Debug, thiserror::Error)]
+    #[error("status {0}")]
+    struct StatusErr(u16);
+    impl StatusCodeError for StatusErr {
+        fn status_code(&self) -> Option<u16> {
+            Some(self.0)
+        }
+    }
 
     #[test]
     fn debug_impl_formats_every_variant() {
@@ -183,6 +276,15 @@ mod tests {
         let chunk = HttpError::<MockClient>::Chunk(MockErr, MockResponse::new());
         assert!(format!("{chunk:?}").contains("Chunk"));
 
+        let permanent = HttpError::<MockClient>::Permanent(MockErr);
+        assert!(format!("{permanent:?}").contains("Permanent"));
+
+        let transient = HttpError::<MockClient>::Transient(MockErr);
+        assert!(format!("{transient:?}").contains("Transient"));
+
+        let rate_limited = HttpError::<MockClient>::RateLimited(MockErr);
+        assert!(format!("{rate_limited:?}").contains("RateLimited"));
+
         let irrecoverable = HttpError::<MockClient>::Irrecoverable;
         assert_eq!(format!("{irrecoverable:?}"), "Irrecoverable");
 
@@ -206,4 +308,41 @@ mod tests {
             .is_irrecoverable()
         );
     }
+
+    #[test]
+    fn from_request_picks_variant_by_status_class() {
+        assert!(matches!(
+            HttpError::<MockClient>::from_request(MockErr),
+            HttpError::Request(_)
+        ));
+        assert!(matches!(
+            HttpError::<StatusClient>::from_request(StatusErr(404)),
+            HttpError::Permanent(StatusErr(404))
+        ));
+        assert!(matches!(
+            HttpError::<StatusClient>::from_request(StatusErr(502)),
+            HttpError::Transient(StatusErr(502))
+        ));
+        assert!(matches!(
+            HttpError::<StatusClient>::from_request(StatusErr(429)),
+            HttpError::RateLimited(StatusErr(429))
+        ));
+    }
+
+    #[test]
+    fn is_permanent_only_for_permanent_status() {
+        assert!(HttpError::<StatusClient>::Permanent(StatusErr(404)).is_permanent());
+        assert!(!HttpError::<StatusClient>::Transient(StatusErr(500)).is_permanent());
+        assert!(!HttpError::<StatusClient>::RateLimited(StatusErr(429)).is_permanent());
+        assert!(!HttpError::<MockClient>::Request(MockErr).is_permanent());
+        assert!(!HttpError::<MockClient>::Irrecoverable.is_permanent());
+    }
+
+    #[test]
+    fn is_rate_limited_only_for_rate_limited_status() {
+        assert!(HttpError::<StatusClient>::RateLimited(StatusErr(503)).is_rate_limited());
+        assert!(!HttpError::<StatusClient>::Transient(StatusErr(500)).is_rate_limited());
+        assert!(!HttpError::<StatusClient>::Permanent(StatusErr(404)).is_rate_limited());
+        assert!(!HttpError::<MockClient>::Request(MockErr).is_rate_limited());
+    }
 }
@@ -2,8 +2,8 @@
 //!
 //! [`Prefetch::prefetch`] issues the initial GET (and a range probe) through a
 //! [`crate::http::HttpClient`], then assembles a [`crate::UrlInfo`] describing
-//! the resource: its size, suggested filename, content type, range support, and
-//! the [`crate::FileId`] used for resumable downloads.
+//! the resource: its size, suggested filename, content type, range support,
+//! advertised digests, and the [`crate::FileId`] used for resumable downloads.
 
 use crate::{
     UrlInfo,
@@ -97,6 +97,13 @@ async fn prefetch_no_range<Client: HttpClient>(
                 headers.get("last-modified").ok().as_deref(),
             ),
             content_type: headers.get("content-type").ok().map(String::from),
+            #[cfg(any(
+                feature = "sha256",
+                feature = "sha1",
+                feature = "md5",
+                feature = "blake3"
+            ))]
+            digests: crate::http::parse_digest_headers(headers),
         },
         resp,
     ))
@@ -110,7 +117,7 @@ async fn is_support_range<Client: HttpClient>(
         .get(url, Some(0..1))
         .send()
         .await
-        .map_err(|(e, d)| (HttpError::Request(e), d))?;
+        .map_err(|(e, d)| (HttpError::from_request(e), d))?;
     let headers = resp.headers();
     let supports_range = headers
         .get("content-range")
@@ -162,7 +162,7 @@ impl<Client: HttpClient> Stream for RandRequestStream<Client> {
                     }
                     Poll::Ready(Err((e, d))) => {
                         self.state = ResponseState::None;
-                        Poll::Ready(Some(Err((HttpError::Request(e), d))))
+                        Poll::Ready(Some(Err((HttpError::from_request(e), d))))
                     }
                     Poll::Pending => Poll::Pending,
                 },
@@ -259,6 +259,7 @@ mod tests {
     #[derive(Debug, thiserror::Error)]
     #[error("MockError")]
     struct MockError;
+    impl crate::http::StatusCodeError for MockError {}
 
     struct DelayChunk {
         polled_once: bool,
@@ -0,0 +1,158 @@
+//! Whole-file digests advertised in response headers.
+//!
+//! [`parse_digest_headers`] reads, in order of preference, RFC 9530
+//! `Repr-Digest` (`sha-256=:<base64>:`), the RFC 3230 `Digest` it obsoletes
+//! (`SHA-256=<base64>`), `Content-MD5` and Google Cloud Storage's
+//! `x-goog-hash` (`md5=<base64>`). Algorithms this build cannot compute are
+//! skipped, as is every digest of a response whose body is content-coded.
+
+use crate::{Digest, HashAlgorithm, http::HttpHeaders};
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+/// Collect the digests of the full representation a `200` response advertises,
+/// at most one per algorithm.
+///
+/// Returns nothing when the response carries a `Content-Encoding` other than
+/// `identity` or no `Content-Length`: the advertised digests then describe the
+/// encoded bytes, which an HTTP client that decodes transparently (and drops
+/// both headers) never writes to disk.
+pub fn parse_digest_headers(headers: &impl HttpHeaders) -> Vec<Digest> {
+    let header = |name| headers.get(name).ok().filter(|v| !v.trim().is_empty());
+    let encoded =
+        header("content-encoding").is_some_and(|v| !v.trim().eq_ignore_ascii_case("identity"));
+    if encoded || header("content-length").is_none() {
+        return Vec::new();
+    }
+    let mut digests: Vec<Digest> = Vec::new();
+    let mut add = |algorithm: &str, value: &str| {
+        let Ok(algorithm) = algorithm.trim().parse::<HashAlgorithm>() else {
+            return;
+        };
+        let Ok(bytes) = STANDARD.decode(value.trim()) else {
+            return;
+        };
+        if bytes.len() == algorithm.output_len() && digests.iter().all(|d| d.algorithm != algorithm)
+        {
+            digests.push(Digest { algorithm, bytes });
+        }
+    };
+    if let Some(v) = header("repr-digest") {
+        for (algorithm, value) in dictionary(&v) {
+            if let Some(value) = value.strip_prefix(':').and_then(|v| v.strip_suffix(':')) {
+                add(algorithm, value);
+            }
+        }
+    }
+    if let Some(v) = header("digest") {
+        for (algorithm, value) in dictionary(&v) {
+            add(algorithm, value);
+        }
+    }
+    if let Some(v) = header("content-md5") {
+        add("md5", &v);
+    }
+    if let Some(v) = header("x-goog-hash") {
+        for (algorithm, value) in dictionary(&v) {
+            add(algorithm, value);
+        }
+    }
+    digests
+}
+
+/// Split `a=1, b=2` into `(key, value)` pairs, splitting each member at its
+/// first `=` since base64 values end in `=` padding. Members without a value
+/// are skipped.
+fn dictionary(value: &str) -> impl Iterator<Item = (&str, &str)> {
+    value
+        .split(',')
+        .filter_map(|member| member.split_once('='))
+        .map(|(k, v)| (k.trim(), v.trim()))
+}
+
+#[cfg(test)]
+#[cfg(all(feature = "sha256", feature = "md5"))]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use std::{borrow::Cow, collections::HashMap};
+
+    struct MapHeaders(HashMap<&'static str, &'static str>);
+    impl HttpHeaders for MapHeaders {
+        type GetHeaderError = std::fmt::Error;
+        fn get(&self, header: &str) -> Result<Cow<'_, str>, Self::GetHeaderError> {
+            self.0
+                .get(header)
+                .map(|v| Cow::Borrowed(*v))
+                .ok_or(std::fmt::Error)
+        }
+    }
+
+    fn parse(headers: &[(&'static str, &'static str)]) -> Vec<String> {
+        let mut map: HashMap<_, _> = headers.iter().copied().collect();
+        map.entry("content-length").or_insert("11");
+        parse_digest_headers(&MapHeaders(map))
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    // Digests of "hello world".
+    const SHA256: &str = "sha256:b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+    const MD5: &str = "md5:5eb63bbbe01eeed093cb22bb8f5acdc3";
+
+    #[test]
+    fn reads_every_header_kind() {
+        assert_eq!(
+            parse(&[(
+                "repr-digest",
+                "sha-512=:AAAA:, sha-256=:uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek=:"
+            )]),
+            [SHA256]
+        );
+        assert_eq!(
+            parse(&[(
+                "digest",
+                "MD5=XrY7u+Ae7tCTyyK7j1rNww==,SHA-256=uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek="
+            )]),
+            [MD5, SHA256]
+        );
+        assert_eq!(parse(&[("content-md5", "XrY7u+Ae7tCTyyK7j1rNww==")]), [MD5]);
+        assert_eq!(
+            parse(&[(
+                "x-goog-hash",
+                "crc32c=yZRlqg==,md5=XrY7u+Ae7tCTyyK7j1rNww=="
+            )]),
+            [MD5]
+        );
+    }
+
+    #[test]
+    fn prefers_repr_digest_and_skips_malformed_values() {
+        assert_eq!(
+            parse(&[
+                (
+                    "repr-digest",
+                    "sha-256=:uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek=:"
+                ),
+                (
+                    "digest",
+                    "SHA-256=AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="
+                ),
+                ("content-md5", "not base64!"),
+            ]),
+            [SHA256]
+        );
+        assert!(parse(&[("content-md5", "AAAA")]).is_empty(), "wrong length");
+    }
+
+    #[test]
+    fn ignores_content_coded_responses() {
+        let md5 = ("content-md5", "XrY7u+Ae7tCTyyK7j1rNww==");
+        assert!(parse(&[md5, ("content-encoding", "gzip")]).is_empty());
+        assert_eq!(parse(&[md5, ("content-encoding", "identity")]), [MD5]);
+        let mut map = HashMap::from([md5]);
+        assert!(parse_digest_headers(&MapHeaders(map.clone())).is_empty());
+        map.insert("content-length", "11");
+        assert_eq!(parse_digest_headers(&MapHeaders(map)).len(), 1);
+    }
+}
@@ -0,0 +1,128 @@
+//! Classification of non-success HTTP status codes.
+//!
+//! A downloader has to decide, for every failed response, whether asking again
+//! can possibly help. [`StatusClass`] sorts status codes into the three answers
+//! to that question, and [`StatusCodeError`] lets the backend-agnostic layer read
+//! the status code back out of an [`HttpRequestBuilder::RequestError`](crate::http::HttpRequestBuilder::RequestError).
+
+/// How a non-success HTTP status should be treated by a retrying client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatusClass {
+    /// Retrying the same request will yield the same answer, e.g. `404 Not Found`
+    /// or `403 Forbidden`. The download should stop.
+    Permanent,
+    /// The server or an intermediary failed this time, e.g. `500` / `502` / `504`
+    /// or `408 Request Timeout`. Retrying after a short gap is expected to help.
+    Transient,
+    /// The server asks the client to slow down: `429 Too Many Requests` or
+    /// `503 Service Unavailable`. Retrying helps, but only after backing off.
+    RateLimited,
+}
+
+impl StatusClass {
+    /// Classify an HTTP status code.
+    ///
+    /// - `429` and `503` are [`RateLimited`](Self::RateLimited).
+    /// - `408` and `425` are [`Transient`](Self::Transient): the request itself
+    ///   was fine, it just did not complete in time.
+    /// - Every other `4xx`, plus `501 Not Implemented` and `505 HTTP Version Not
+    ///   Supported`, is [`Permanent`](Self::Permanent).
+    /// - A `3xx` reaching the classifier is a redirect that could not be followed
+    ///   (no usable `Location`, or the redirect limit was hit), which is
+    ///   [`Permanent`](Self::Permanent) as well.
+    /// - Everything else, notably the remaining `5xx`, is
+    ///   [`Transient`](Self::Transient).
+    #[must_use]
+    pub const fn from_status(status: u16) -> Self {
+        match status {
+            429 | 503 => Self::RateLimited,
+            408 | 425 => Self::Transient,
+            300..=499 | 501 | 505 => Self::Permanent,
+            _ => Self::Transient,
+        }
+    }
+}
+
+/// Exposes the HTTP status code carried by a request error, if any.
+///
+/// Every [`HttpRequestBuilder::RequestError`](crate::http::HttpRequestBuilder::RequestError)
+/// implements this so [`HttpError::from_request`](crate::http::HttpError::from_request)
+/// can pick the matching [`HttpError`](crate::http::HttpError) variant. Errors
+/// raised before a response arrived (connection refused, TLS failure, …) keep
+/// the default `None`.
+pub trait StatusCodeError {
+    /// The status code of the failed response, or `None` if there was none.
+    fn status_code(&self) -> Option<u16> {
+        None
+    }
+
+    /// The [`StatusClass`] of [`status_code`](Self::status_code).
+    fn status_class(&self) -> Option<StatusClass> {
+        self.status_code().map(StatusClass::from_status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_errors_are_permanent() {
+        for status in [400, 401, 403, 404, 405, 410, 416, 451] {
+            assert_eq!(
+                StatusClass::from_status(status),
+                StatusClass::Permanent,
+                "{status}"
+            );
+        }
+    }
+
+    #[test]
+    fn timeouts_and_server_errors_are_transient() {
+        for status in [408, 425, 500, 502, 504, 507, 599] {
+            assert_eq!(
+                StatusClass::from_status(status),
+                StatusClass::Transient,
+                "{status}"
+            );
+        }
+    }
+
+    #[test]
+    fn throttling_is_rate_limited() {
+        assert_eq!(StatusClass::from_status(429), StatusClass::RateLimited);
+        assert_eq!(StatusClass::from_status(503), StatusClass::RateLimited);
+    }
+
+    #[test]
+    fn unsupported_server_features_are_permanent() {
+        assert_eq!(StatusClass::from_status(501), StatusClass::Permanent);
+        assert_eq!(StatusClass::from_status(505), StatusClass::Permanent);
+    }
+
+    #[test]
+    fn unfollowed_redirects_are_permanent() {
+        for status in [301, 302, 303, 307, 308] {
+            assert_eq!(
+                StatusClass::from_status(status),
+                StatusClass::Permanent,
+                "{status}"
+            );
+        }
+    }
+
+    #[test]
+    fn default_status_class_follows_status_code() {
+        struct NoStatus;
+        impl StatusCodeError for NoStatus {}
+        struct Status(u16);
+        impl StatusCodeError for Status {
+            fn status_code(&self) -> Option<u16> {
+                Some(self.0)
+            }
+        }
+        assert_eq!(NoStatus.status_class(), None);
+        assert_eq!(Status(404).status_class(), Some(StatusClass::Permanent));
+        assert_eq!(Status(429).status_class(), Some(StatusClass::RateLimited));
+    }
+}
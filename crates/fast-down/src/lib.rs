@@ -9,6 +9,8 @@ pub use url_info::*;
 
 #[cfg(feature = "http")]
 pub mod http;
+#[cfg(feature = "metalink")]
+pub mod metalink;
 #[cfg(feature = "reqwest")]
 pub mod reqwest;
 mod utils;
@@ -0,0 +1,339 @@
+//! Metalink 4 (RFC 5854, `.meta4`) documents.
+//!
+//! [`Metalink::parse`] turns a document into one [`MetalinkFile`] per
+//! `<file>` element: the download job for that file, with its mirror URLs,
+//! expected size, whole-file hashes and piece hashes. Hashes of algorithms not
+//! enabled in this build are skipped, as are `<metaurl>` entries (torrents and
+//! other metadata formats).
+
+use crate::{Digest, HashAlgorithm, PieceHashes};
+use core::str::FromStr;
+use roxmltree::{Document, Node};
+use std::path::{Component, Path};
+use url::Url;
+
+/// Namespace of Metalink 4 elements.
+pub const METALINK_NS: &str = "urn:ietf:params:xml:ns:metalink";
+
+/// Media type of Metalink 4 documents.
+pub const METALINK_MEDIA_TYPE: &str = "application/metalink4+xml";
+
+/// A parsed Metalink 4 document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metalink {
+    pub files: Vec<MetalinkFile>,
+}
+
+/// One `<file>` of a metalink: a single download job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetalinkFile {
+    /// Relative path the file should be saved under. Guaranteed to contain no
+    /// root, prefix or `..` component.
+    pub name: String,
+    pub size: Option<u64>,
+    /// Whole-file hashes, in document order.
+    pub hashes: Vec<Digest>,
+    pub pieces: Option<PieceHashes>,
+    /// Sources in document order; see [`MetalinkFile::sorted_urls`].
+    pub urls: Vec<MetalinkUrl>,
+}
+
+/// A `<url>` of a [`MetalinkFile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetalinkUrl {
+    pub url: Url,
+    /// `1` is the most preferred source; `None` ranks after every priority.
+    pub priority: Option<u32>,
+    /// ISO 3166-1 alpha-2 country code of the source, lowercased.
+    pub location: Option<String>,
+}
+
+/// Why a document is not a usable metalink.
+#[derive(thiserror::Error, Debug)]
+pub enum MetalinkError {
+    #[error("invalid XML: {0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error("root element is not a Metalink 4 <metalink>")]
+    NotMetalink,
+    #[error("<file> without a name")]
+    MissingName,
+    #[error("unsafe file name {0:?}")]
+    UnsafeName(String),
+    #[error("invalid {element} {value:?} for file {file:?}")]
+    InvalidValue {
+        file: String,
+        element: &'static str,
+        value: String,
+    },
+    #[error("file {0:?} has no usable URL")]
+    NoUrl(String),
+}
+
+impl Metalink {
+    /// Parse a Metalink 4 document.
+    ///
+    /// # Errors
+    /// Returns [`MetalinkError`] when the document is not well-formed Metalink 4,
+    /// a file name would escape the download directory, a size, priority or
+    /// hash is malformed, or a file lists no HTTP(S) URL.
+    pub fn parse(xml: &str) -> Result<Self, MetalinkError> {
+        let doc = Document::parse(xml)?;
+        let root = doc.root_element();
+        if !root.has_tag_name((METALINK_NS, "metalink")) {
+            return Err(MetalinkError::NotMetalink);
+        }
+        let files = children(root, "file")
+            .map(parse_file)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { files })
+    }
+}
+
+impl FromStr for Metalink {
+    type Err = MetalinkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl MetalinkFile {
+    /// The strongest whole-file hash of this build's algorithms, if any.
+    #[must_use]
+    pub fn best_hash(&self) -> Option<&Digest> {
+        self.hashes.iter().max_by_key(|d| d.algorithm.strength())
+    }
+
+    /// The URLs ordered by priority, ties kept in document order. When
+    /// `location` is given, sources in that country come first among equal
+    /// priorities.
+    #[must_use]
+    pub fn sorted_urls(&self, location: Option<&str>) -> Vec<&MetalinkUrl> {
+        let mut urls: Vec<_> = self.urls.iter().collect();
+        urls.sort_by_key(|u| {
+            let local = location.is_some_and(|l| u.location.as_deref() == Some(l));
+            (u.priority.unwrap_or(u32::MAX), !local)
+        });
+        urls
+    }
+}
+
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |n| n.has_tag_name((METALINK_NS, name)))
+}
+
+fn text<'a>(node: Node<'a, '_>) -> &'a str {
+    node.text().unwrap_or_default().trim()
+}
+
+fn parse_file(node: Node<'_, '_>) -> Result<MetalinkFile, MetalinkError> {
+    let name = node.attribute("name").ok_or(MetalinkError::MissingName)?;
+    if !is_safe_name(name) {
+        return Err(MetalinkError::UnsafeName(name.to_string()));
+    }
+    let invalid = |element, value: &str| MetalinkError::InvalidValue {
+        file: name.to_string(),
+        element,
+        value: value.to_string(),
+    };
+    let size = children(node, "size")
+        .next()
+        .map(|n| text(n).parse().map_err(|_| invalid("size", text(n))))
+        .transpose()?;
+    let hashes = children(node, "hash")
+        .filter_map(|n| parse_hash(n.attribute("type")?, text(n)))
+        .collect::<Result<_, _>>()
+        .map_err(|value: String| invalid("hash", &value))?;
+    let pieces = children(node, "pieces")
+        .find(|n| {
+            n.attribute("type")
+                .is_some_and(|t| t.parse::<HashAlgorithm>().is_ok())
+        })
+        .map(|n| {
+            let length = n.attribute("length").unwrap_or_default();
+            let length = length
+                .parse()
+                .ok()
+                .filter(|&l| l > 0)
+                .ok_or_else(|| invalid("pieces length", length))?;
+            let algorithm = n.attribute("type").unwrap_or_default();
+            let hashes = children(n, "hash")
+                .filter_map(|h| parse_hash(algorithm, text(h)))
+                .collect::<Result<_, _>>()
+                .map_err(|value: String| invalid("piece hash", &value))?;
+            Ok::<_, MetalinkError>(PieceHashes { length, hashes })
+        })
+        .transpose()?;
+    let mut urls = Vec::new();
+    for n in children(node, "url") {
+        let Ok(url) = Url::parse(text(n)) else {
+            continue;
+        };
+        if !matches!(url.scheme(), "http" | "https") {
+            continue;
+        }
+        let priority = n
+            .attribute("priority")
+            .map(|p| p.parse().map_err(|_| invalid("url priority", p)))
+            .transpose()?;
+        let location = n.attribute("location").map(str::to_ascii_lowercase);
+        urls.push(MetalinkUrl {
+            url,
+            priority,
+            location,
+        });
+    }
+    if urls.is_empty() {
+        return Err(MetalinkError::NoUrl(name.to_string()));
+    }
+    Ok(MetalinkFile {
+        name: name.to_string(),
+        size,
+        hashes,
+        pieces,
+        urls,
+    })
+}
+
+/// `None` for an algorithm this build does not support, `Err` with the
+/// offending value for a malformed hash.
+fn parse_hash(algorithm: &str, hex: &str) -> Option<Result<Digest, String>> {
+    algorithm.parse::<HashAlgorithm>().ok()?;
+    Some(
+        format!("{algorithm}:{hex}")
+            .parse()
+            .map_err(|_| hex.to_string()),
+    )
+}
+
+/// RFC 5854 §4.1.2.1: the name may contain directories but must not escape
+/// the download directory.
+fn is_safe_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains('\\')
+        && Path::new(name)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metalink xmlns="urn:ietf:params:xml:ns:metalink">
+  <published>2024-01-01T00:00:00Z</published>
+  <file name="dist/app.tar.gz">
+    <size>10</size>
+    <hash type="md5">781e5e245d69b566979b86e28d23f2c7</hash>
+    <hash type="sha-256">84d89877f0d4041efb6bf91a16f0248f2fd573e6af05c19f96bedb9f882f7882</hash>
+    <hash type="sha-512">00</hash>
+    <pieces length="4" type="sha-256">
+      <hash>1be2e452b46d7a0d9656bbb1f768e8248eba1b75baed65f5d99eafa948899a6a</hash>
+      <hash>db2e7f1bd5ab9968ae76199b7cc74795ca7404d5a08d78567715ce532f9d2669</hash>
+      <hash>cd70bea023f752a0564abb6ed08d42c1440f2e33e29914e55e0be1595e24f45a</hash>
+    </pieces>
+    <url location="DE" priority="2">https://de.example.com/app.tar.gz</url>
+    <url>https://fallback.example.com/app.tar.gz</url>
+    <url location="us" priority="2">https://us.example.com/app.tar.gz</url>
+    <url priority="1">ftp://ftp.example.com/app.tar.gz</url>
+    <metaurl mediatype="torrent">https://example.com/app.torrent</metaurl>
+  </file>
+</metalink>"#;
+
+    #[test]
+    fn parses_sources_sizes_and_hashes() {
+        let metalink = Metalink::parse(DOC).unwrap();
+        let [file] = &metalink.files[..] else {
+            panic!("expected one file");
+        };
+        assert_eq!(file.name, "dist/app.tar.gz");
+        assert_eq!(file.size, Some(10));
+        assert_eq!(file.hashes.len(), 2, "sha-512 is skipped");
+        assert_eq!(
+            file.best_hash().unwrap().algorithm,
+            HashAlgorithm::Sha256,
+            "sha-256 beats md5"
+        );
+        let pieces = file.pieces.as_ref().unwrap();
+        assert_eq!(pieces.length, 4);
+        assert!(pieces.verify(&b"0123456789"[..]).unwrap().is_empty());
+        let urls: Vec<_> = file
+            .urls
+            .iter()
+            .map(|u| u.url.host_str().unwrap())
+            .collect();
+        assert_eq!(
+            urls,
+            ["de.example.com", "fallback.example.com", "us.example.com"],
+            "non-HTTP sources are skipped"
+        );
+        assert_eq!(file.urls[0].location.as_deref(), Some("de"));
+    }
+
+    #[test]
+    fn sorts_urls_by_priority_then_location() {
+        let metalink = Metalink::parse(DOC).unwrap();
+        let file = &metalink.files[0];
+        let hosts = |location| {
+            file.sorted_urls(location)
+                .into_iter()
+                .map(|u| u.url.host_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            hosts(None),
+            ["de.example.com", "us.example.com", "fallback.example.com"]
+        );
+        assert_eq!(
+            hosts(Some("us")),
+            ["us.example.com", "de.example.com", "fallback.example.com"]
+        );
+    }
+
+    #[test]
+    fn rejects_unsafe_and_malformed_documents() {
+        let file = |body: &str| {
+            Metalink::parse(&format!(
+                r#"<metalink xmlns="urn:ietf:params:xml:ns:metalink">{body}</metalink>"#
+            ))
+        };
+        let url = "<url>https://example.com/a</url>";
+        assert!(matches!(
+            file(&format!(r#"<file name="../a">{url}</file>"#)),
+            Err(MetalinkError::UnsafeName(_))
+        ));
+        assert!(matches!(
+            file(&format!(r#"<file name="/etc/a">{url}</file>"#)),
+            Err(MetalinkError::UnsafeName(_))
+        ));
+        assert!(matches!(
+            file(&format!(r#"<file name="a"><size>x</size>{url}</file>"#)),
+            Err(MetalinkError::InvalidValue {
+                element: "size",
+                ..
+            })
+        ));
+        assert!(matches!(
+            file(&format!(
+                r#"<file name="a"><hash type="sha-256">00</hash>{url}</file>"#
+            )),
+            Err(MetalinkError::InvalidValue {
+                element: "hash",
+                ..
+            })
+        ));
+        assert!(matches!(
+            file(r#"<file name="a"></file>"#),
+            Err(MetalinkError::NoUrl(_))
+        ));
+        assert!(matches!(
+            Metalink::parse("<metalink/>"),
+            Err(MetalinkError::NotMetalink)
+        ));
+    }
+}
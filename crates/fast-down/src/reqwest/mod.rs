@@ -15,7 +15,7 @@
 //! correctly-configured [`SmartRedirectClient`].
 
 use crate::http::{
-    HttpClient, HttpHeaders, HttpRequestBuilder, HttpResponse,
+    HttpClient, HttpHeaders, HttpRequestBuilder, HttpResponse, StatusClass, StatusCodeError,
     manual_redirect::{ReferrerPolicy, compute_referer},
 };
 use fast_pull::ProgressEntry;
@@ -52,12 +52,10 @@ impl HttpRequestBuilder for RequestBuilder {
             .send()
             .await
             .map_err(|e| (ReqwestResponseError::Request(e), None))?;
-        let status = res.status();
-        if status.is_success() {
+        if res.status().is_success() {
             Ok(res)
         } else {
-            let retry_after = parse_retry_after(res.headers());
-            Err((ReqwestResponseError::StatusCode(res), retry_after))
+            Err(ReqwestResponseError::from_response(res))
         }
     }
 }
@@ -98,6 +96,34 @@ pub enum ReqwestResponseError {
     Request(reqwest::Error),
     #[error("Url: {}, Status Code: {}, Headers: {:?}", .0.url(), .0.status(), .0.headers())]
     StatusCode(Response),
+    /// The server asked to slow down with a `429` or `503`; see
+    /// [`StatusClass::RateLimited`].
+    #[error("Url: {}, Rate Limited: {}, Headers: {:?}", .0.url(), .0.status(), .0.headers())]
+    RateLimited(Response),
+}
+
+impl ReqwestResponseError {
+    /// The error for a non-success response, with its `Retry-After`:
+    /// [`RateLimited`](Self::RateLimited) for `429` / `503`,
+    /// [`StatusCode`](Self::StatusCode) otherwise.
+    fn from_response(resp: Response) -> (Self, Option<Duration>) {
+        let retry_after = parse_retry_after(resp.headers());
+        let e = if StatusClass::from_status(resp.status().as_u16()) == StatusClass::RateLimited {
+            Self::RateLimited(resp)
+        } else {
+            Self::StatusCode(resp)
+        };
+        (e, retry_after)
+    }
+}
+
+impl StatusCodeError for ReqwestResponseError {
+    fn status_code(&self) -> Option<u16> {
+        match self {
+            Self::Request(e) => e.status().map(|s| s.as_u16()),
+            Self::StatusCode(resp) | Self::RateLimited(resp) => Some(resp.status().as_u16()),
+        }
+    }
 }
 
 /// Parse the `Retry-After` response header into a [`Duration`].
@@ -238,8 +264,14 @@ impl HttpRequestBuilder for ManualRedirectRequestBuilder {
                     req = req.header(header::COOKIE, cookie);
                 }
             }
-            let resp = req
-                .send()
+            let send = req.send();
+            // Overrides of the resolver only apply to the port of this hop.
+            #[cfg(all(feature = "fast-puller", not(target_family = "wasm")))]
+            let send = crate::resolver::with_request_port(
+                self.url.port_or_known_default().unwrap_or_default(),
+                send,
+            );
+            let resp = send
                 .await
                 .map_err(|e| (ReqwestResponseError::Request(e), None))?;
 
@@ -259,8 +291,7 @@ impl HttpRequestBuilder for ManualRedirectRequestBuilder {
                 return if status.is_success() {
                     Ok(resp)
                 } else {
-                    let retry_after = parse_retry_after(resp.headers());
-                    Err((ReqwestResponseError::StatusCode(resp), retry_after))
+                    Err(ReqwestResponseError::from_response(resp))
                 };
             }
             if self.redirect_count >= self.max_redirects {
@@ -322,11 +353,11 @@ mod tests {
     )]
     use super::*;
     use crate::{
-        http::{HttpPuller, Prefetch},
+        http::{HttpError, HttpPuller, Prefetch, StatusClass},
         url_info::FileId,
     };
     use fast_pull::{
-        Event, Merge,
+        Event, Merge, PauseToken, SpeedLimiter, Verbosity,
         mem::MemPusher,
         mock::build_mock_data,
         multi::{self, download_multi},
@@ -438,13 +469,69 @@ mod tests {
             Ok(info) => unreachable!("404 status code should not success: {info:?}"),
             Err((err, _)) => match err {
                 ReqwestResponseError::Request(error) => unreachable!("{error:?}"),
-                ReqwestResponseError::StatusCode(resp) => {
+                ReqwestResponseError::RateLimited(resp) => unreachable!("{resp:?}"),
+                ReqwestResponseError::StatusCode(ref resp) => {
                     assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+                    assert_eq!(err.status_code(), Some(404));
+                    assert_eq!(err.status_class(), Some(StatusClass::Permanent));
                 }
             },
         }
     }
 
+    #[tokio::test]
+    async fn test_permanent_status_fails_multi_download() {
+        let mut server = mockito::Server::new_async().await;
+        let client = Client::builder().no_proxy().build().unwrap();
+        let _mock = server
+            .mock("GET", "/gone")
+            .with_status(410)
+            .expect_at_least(1)
+            .create_async()
+            .await;
+        let puller = HttpPuller::new(
+            Arc::new(format!("{}/gone", server.url()).parse().unwrap()),
+            client,
+            None,
+            FileId::default(),
+        );
+        let pusher = MemPusher::with_capacity(1024);
+        #[allow(clippy::single_range_in_vec_init)]
+        let download_chunks = [0..1024];
+        let result = download_multi(
+            puller,
+            pusher,
+            multi::DownloadOptions {
+                concurrent: 4,
+                retry_gap: Duration::from_secs(1),
+                push_queue_cap: 1024,
+                download_chunks: download_chunks.iter().cloned(),
+                pull_timeout: Duration::from_secs(5),
+                min_chunk_size: 1,
+                max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
+            },
+        );
+        let mut failed = false;
+        while let Ok(e) = result.event_chain().recv().await {
+            match e {
+                Event::PullFailed(_, HttpError::Permanent(e)) => {
+                    assert_eq!(e.status_code(), Some(410));
+                    failed = true;
+                }
+                Event::PullError(_, e) => unreachable!("410 must not be retried: {e:?}"),
+                _ => {}
+            }
+        }
+        assert!(failed, "expected a terminal PullFailed event");
+        assert!(result.is_aborted());
+    }
+
     #[tokio::test]
     async fn test_concurrent_download() {
         let mock_data = build_mock_data(300 * 1024 * 1024);
@@ -476,6 +563,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
 
@@ -612,6 +705,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(30),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
 
@@ -722,6 +821,11 @@ mod tests {
             single::DownloadOptions {
                 retry_gap: Duration::from_secs(1),
                 push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
             },
         );
 
@@ -888,6 +992,49 @@ mod tests {
         assert!(matches!(err.0, ReqwestResponseError::StatusCode(_)));
     }
 
+    #[tokio::test]
+    async fn test_smart_redirect_rate_limit_returns_rate_limited_error() {
+        let mut server = mockito::Server::new_async().await;
+        let client = Client::builder()
+            .no_proxy()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+        let _mock_429 = server
+            .mock("GET", "/429")
+            .with_status(429)
+            .with_header("Retry-After", "7")
+            .create_async()
+            .await;
+        let _mock_503 = server
+            .mock("GET", "/503")
+            .with_status(503)
+            .create_async()
+            .await;
+        let redirect_client = SmartRedirectClient::new(client, None, None, None, None, None, 10);
+        let url = Url::parse(&format!("{}/429", server.url())).unwrap();
+        let (err, retry_after) = redirect_client
+            .get(url, None)
+            .send()
+            .await
+            .expect_err("429 should produce a RateLimited error");
+        assert!(matches!(err, ReqwestResponseError::RateLimited(_)));
+        assert_eq!(err.status_class(), Some(StatusClass::RateLimited));
+        assert_eq!(retry_after, Some(Duration::from_secs(7)));
+        assert!(matches!(
+            HttpError::<SmartRedirectClient>::from_request(err),
+            HttpError::RateLimited(_)
+        ));
+        let url = Url::parse(&format!("{}/503", server.url())).unwrap();
+        let (err, retry_after) = redirect_client
+            .get(url, None)
+            .send()
+            .await
+            .expect_err("503 should produce a RateLimited error");
+        assert!(matches!(err, ReqwestResponseError::RateLimited(_)));
+        assert_eq!(retry_after, None);
+    }
+
     #[tokio::test]
     async fn test_smart_redirect_302_without_location_errors() {
         let mut server = mockito::Server::new_async().await;
@@ -11,8 +11,9 @@ use url::Url;
 
 /// Metadata about a downloadable resource, gathered from the initial HTTP request.
 ///
-/// Includes file size, filename, content type, range support, and file identity
-/// (`ETag` / `Last-Modified`) for incremental downloads.
+/// Includes file size, filename, content type, range support, file identity
+/// (`ETag` / `Last-Modified`) for incremental downloads and, with a hash
+/// feature, the digests the server advertised.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UrlInfo {
@@ -38,6 +39,16 @@ pub struct UrlInfo {
     pub file_id: FileId,
     /// The `Content-Type` header value, if the server provided one.
     pub content_type: Option<String>,
+    /// Whole-file digests the server advertised (`Repr-Digest`, `Digest`,
+    /// `Content-MD5`, `x-goog-hash`), at most one per algorithm.
+    #[cfg(any(
+        feature = "sha256",
+        feature = "sha1",
+        feature = "md5",
+        feature = "blake3"
+    ))]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub digests: Vec<crate::Digest>,
 }
 
 #[cfg(feature = "sanitize-filename")]
@@ -111,6 +122,13 @@ mod tests {
             final_url: Url::parse("http://example.com/x").unwrap(),
             file_id: FileId::default(),
             content_type: Some("text/plain".to_string()),
+            #[cfg(any(
+                feature = "sha256",
+                feature = "sha1",
+                feature = "md5",
+                feature = "blake3"
+            ))]
+            digests: Vec::new(),
         };
         #[cfg(feature = "sanitize-filename")]
         {
@@ -135,6 +153,13 @@ mod tests {
             final_url: Url::parse("http://example.com/x").unwrap(),
             file_id: FileId::default(),
             content_type: None,
+            #[cfg(any(
+                feature = "sha256",
+                feature = "sha1",
+                feature = "md5",
+                feature = "blake3"
+            ))]
+            digests: Vec::new(),
         };
         let name = info.filename();
         assert!(
@@ -165,6 +190,13 @@ mod tests {
             final_url: Url::parse("http://example.com/x").unwrap(),
             file_id: FileId::default(),
             content_type: None,
+            #[cfg(any(
+                feature = "sha256",
+                feature = "sha1",
+                feature = "md5",
+                feature = "blake3"
+            ))]
+            digests: Vec::new(),
         };
         assert_eq!(info.filename(), "");
     }
@@ -13,24 +13,75 @@ use crate::{
     FileId, ProgressEntry, PullResult, PullStream,
     http::{HttpError, HttpPuller, ReferrerPolicy},
     reqwest::SmartRedirectClient,
+    resolver::Resolver,
 };
 use fast_pull::Puller;
 use parking_lot::Mutex;
 use reqwest::{ClientBuilder, Response, header::HeaderMap, redirect::Policy};
-use std::sync::Arc;
+use std::{collections::HashMap, net::IpAddr, sync::Arc};
 use url::Url;
 
+/// How the clones of a [`FastDownPuller`] get their connections.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ConnectionPool {
+    /// Every clone builds its own client, so every worker (and every
+    /// reconnect) pays a fresh TCP and TLS handshake.
+    #[default]
+    PerClone,
+    /// Clones share one client per local address and hand its idle HTTP/1.1
+    /// connections to each other. Each worker still gets its own connection.
+    Shared,
+    /// Like [`Shared`](Self::Shared), but HTTP/2 is negotiated where the
+    /// server offers it (over TLS), multiplexing every range request of a
+    /// local address over a single connection. Requires the `http2` feature;
+    /// without it, or against an HTTP/1.1 server, it behaves like `Shared`.
+    Multiplexed,
+}
+
+/// Hosts are looked up with `resolver`, or the system resolver if it is
+/// `None`.
+///
 /// # Errors
 /// Returns an error if the HTTP client cannot be built (invalid proxy URL,
 /// TLS backend failure, or other `reqwest::ClientBuilder` errors).
+#[allow(clippy::too_many_arguments)]
 pub fn build_client(
+    headers: HeaderMap,
+    proxy: Proxy<&str>,
+    accept_invalid_certs: bool,
+    accept_invalid_hostnames: bool,
+    cookie_store: bool,
+    local_addr: Option<IpAddr>,
+    max_redirects: usize,
+    resolver: Option<&Resolver>,
+) -> Result<SmartRedirectClient, reqwest::Error> {
+    build_pooled_client(
+        headers,
+        proxy,
+        accept_invalid_certs,
+        accept_invalid_hostnames,
+        cookie_store,
+        local_addr,
+        max_redirects,
+        resolver,
+        ConnectionPool::PerClone,
+    )
+}
+
+/// [`build_client`] for a [`ConnectionPool`]: a `Shared` client speaks only
+/// HTTP/1.1 so that its workers do not end up multiplexed.
+#[allow(clippy::too_many_arguments)]
+fn build_pooled_client(
     mut headers: HeaderMap,
     proxy: Proxy<&str>,
     #[allow(unused)] accept_invalid_certs: bool,
     #[allow(unused)] accept_invalid_hostnames: bool,
     #[allow(unused)] cookie_store: bool,
-    local_addr: Option<std::net::IpAddr>,
+    local_addr: Option<IpAddr>,
     max_redirects: usize,
+    resolver: Option<&Resolver>,
+    pool: ConnectionPool,
 ) -> Result<SmartRedirectClient, reqwest::Error> {
     let referer = headers.remove("referer");
     let referrer_policy = headers
@@ -50,6 +101,12 @@ pub fn build_client(
         Proxy::System => client,
         Proxy::Custom(p) => client.proxy(reqwest::Proxy::all(p)?),
     };
+    if pool == ConnectionPool::Shared {
+        client = client.http1_only();
+    }
+    if let Some(resolver) = resolver.filter(|r| !r.is_system()) {
+        client = client.dns_resolver(resolver.clone());
+    }
     #[cfg(feature = "reqwest-tls")]
     {
         client = client
@@ -74,8 +131,10 @@ pub fn build_client(
 /// The default [`Puller`] implementation for the fast-down crate.
 ///
 /// Wraps an [`HttpPuller`] with a [`SmartRedirectClient`], IP rotation,
-/// and proxy support. Cloning creates a new HTTP client with an optionally
-/// rotated local address for multi-interface setups.
+/// and proxy support. Cloning moves on to the next local address for
+/// multi-interface setups and, depending on the [`ConnectionPool`], either
+/// builds a new HTTP client or reuses the one shared by every clone bound to
+/// that address.
 #[derive(Debug)]
 pub struct FastDownPuller {
     inner: HttpPuller<SmartRedirectClient>,
@@ -87,9 +146,13 @@ pub struct FastDownPuller {
     cookie_store: bool,
     file_id: FileId,
     resp: Option<Arc<Mutex<Option<Response>>>>,
-    available_ips: Arc<[std::net::IpAddr]>,
+    available_ips: Arc<[IpAddr]>,
     turn: Arc<std::sync::atomic::AtomicUsize>,
     max_redirects: usize,
+    resolver: Option<Resolver>,
+    pool: ConnectionPool,
+    /// The client of each local address, filled only for a shared pool.
+    clients: Arc<Mutex<HashMap<Option<IpAddr>, SmartRedirectClient>>>,
 }
 // Field-level docs live on [`FastDownPullerOptions`], the public construction
 // surface; the runtime struct mirrors those fields.
@@ -114,9 +177,15 @@ pub struct FastDownPullerOptions<'a> {
     /// An already-open response to reuse for the first request (e.g. from a prefetch).
     pub resp: Option<Arc<Mutex<Option<Response>>>>,
     /// Candidate local source IPs for outbound connections; rotated across clones.
-    pub available_ips: Arc<[std::net::IpAddr]>,
+    pub available_ips: Arc<[IpAddr]>,
     /// Maximum number of redirects to follow before failing.
     pub max_redirects: usize,
+    /// Resolves hosts for every clone, or the system resolver if `None`.
+    /// With [`Resolver`] round-robin, each new connection takes the next
+    /// address of the host.
+    pub resolver: Option<Resolver>,
+    /// Whether clones share their connections.
+    pub pool: ConnectionPool,
 }
 
 impl FastDownPuller {
@@ -126,24 +195,22 @@ impl FastDownPuller {
     pub fn new(option: FastDownPullerOptions<'_>) -> Result<Self, reqwest::Error> {
         let turn = Arc::new(std::sync::atomic::AtomicUsize::new(1));
         let available_ips = option.available_ips;
-        let client = build_client(
+        let local_addr = next_ip(&available_ips, &turn);
+        let client = build_pooled_client(
             option.headers.as_ref().clone(),
             option.proxy,
             option.accept_invalid_certs,
             option.accept_invalid_hostnames,
             option.cookie_store,
-            if available_ips.is_empty() {
-                None
-            } else {
-                available_ips
-                    .get(
-                        turn.fetch_add(1, std::sync::atomic::Ordering::AcqRel)
-                            % available_ips.len(),
-                    )
-                    .copied()
-            },
+            local_addr,
             option.max_redirects,
+            option.resolver.as_ref(),
+            option.pool,
         )?;
+        let mut clients = HashMap::new();
+        if option.pool != ConnectionPool::PerClone {
+            clients.insert(local_addr, client.clone());
+        }
         let url = Arc::new(option.url);
         Ok(Self {
             inner: HttpPuller::new(
@@ -163,44 +230,65 @@ impl FastDownPuller {
             available_ips,
             turn,
             max_redirects: option.max_redirects,
+            resolver: option.resolver,
+            pool: option.pool,
+            clients: Arc::new(Mutex::new(clients)),
         })
     }
+
+    /// The client for a clone bound to `local_addr`: the shared one if the
+    /// pool has it, else a new one, kept for later clones of a shared pool.
+    fn client(&self, local_addr: Option<IpAddr>) -> Result<SmartRedirectClient, reqwest::Error> {
+        let mut clients = self.clients.lock();
+        if let Some(client) = clients.get(&local_addr) {
+            return Ok(client.clone());
+        }
+        let client = build_pooled_client(
+            self.headers.as_ref().clone(),
+            self.proxy.as_deref(),
+            self.accept_invalid_certs,
+            self.accept_invalid_hostnames,
+            self.cookie_store,
+            local_addr,
+            self.max_redirects,
+            self.resolver.as_ref(),
+            self.pool,
+        )?;
+        if self.pool != ConnectionPool::PerClone {
+            clients.insert(local_addr, client.clone());
+        }
+        drop(clients);
+        Ok(client)
+    }
+}
+
+/// The next local address in the rotation, if any are configured.
+fn next_ip(available_ips: &[IpAddr], turn: &std::sync::atomic::AtomicUsize) -> Option<IpAddr> {
+    if available_ips.is_empty() {
+        None
+    } else {
+        available_ips
+            .get(turn.fetch_add(1, std::sync::atomic::Ordering::AcqRel) % available_ips.len())
+            .copied()
+    }
 }
 
 impl Clone for FastDownPuller {
     fn clone(&self) -> Self {
-        let available_ips = self.available_ips.clone();
-        let turn = self.turn.clone();
         Self {
-            inner: build_client(
-                self.headers.as_ref().clone(),
-                self.proxy.as_deref(),
-                self.accept_invalid_certs,
-                self.accept_invalid_hostnames,
-                self.cookie_store,
-                if available_ips.is_empty() {
-                    None
-                } else {
-                    available_ips
-                        .get(
-                            turn.fetch_add(1, std::sync::atomic::Ordering::AcqRel)
-                                % available_ips.len(),
+            inner: self
+                .client(next_ip(&self.available_ips, &self.turn))
+                .map_or_else(
+                    |_| self.inner.clone(),
+                    |client| {
+                        HttpPuller::new(
+                            self.url.clone(),
+                            client,
+                            self.resp.clone(),
+                            self.file_id.clone(),
                         )
-                        .copied()
-                },
-                self.max_redirects,
-            )
-            .map_or_else(
-                |_| self.inner.clone(),
-                |client| {
-                    HttpPuller::new(
-                        self.url.clone(),
-                        client,
-                        self.resp.clone(),
-                        self.file_id.clone(),
-                    )
-                },
-            ),
+                    },
+                ),
             resp: self.resp.clone(),
             headers: self.headers.clone(),
             proxy: self.proxy.clone(),
@@ -209,9 +297,12 @@ impl Clone for FastDownPuller {
             accept_invalid_hostnames: self.accept_invalid_hostnames,
             cookie_store: self.cookie_store,
             file_id: self.file_id.clone(),
-            available_ips,
-            turn,
+            available_ips: self.available_ips.clone(),
+            turn: self.turn.clone(),
             max_redirects: self.max_redirects,
+            resolver: self.resolver.clone(),
+            pool: self.pool,
+            clients: self.clients.clone(),
         }
     }
 }
@@ -230,6 +321,8 @@ impl Puller for FastDownPuller {
 mod tests {
     #![allow(clippy::unwrap_used, clippy::expect_used)]
     use super::*;
+    use bytes::Bytes;
+    use futures::TryStreamExt;
 
     fn make_options(url: Url) -> FastDownPullerOptions<'static> {
         FastDownPullerOptions {
@@ -243,6 +336,8 @@ mod tests {
             resp: None,
             available_ips: Arc::from(Vec::<std::net::IpAddr>::new()),
             max_redirects: 10,
+            resolver: None,
+            pool: ConnectionPool::PerClone,
         }
     }
 
@@ -272,6 +367,7 @@ mod tests {
             false,
             None,
             10,
+            None,
         )
         .expect("build_client with a custom proxy must succeed");
         let _ = client;
@@ -302,7 +398,7 @@ mod tests {
     #[test]
     fn build_client_system_proxy_succeeds() {
         let headers = HeaderMap::new();
-        let client = build_client(headers, Proxy::System, false, false, false, None, 10)
+        let client = build_client(headers, Proxy::System, false, false, false, None, 10, None)
             .expect("build_client with the system proxy must succeed");
         let _ = client;
     }
@@ -361,4 +457,83 @@ mod tests {
         assert_eq!(cloned.turn.load(std::sync::atomic::Ordering::Acquire), 3);
         assert_eq!(puller.turn.load(std::sync::atomic::Ordering::Acquire), 3);
     }
+
+    #[test]
+    fn per_clone_pool_keeps_no_clients() {
+        let puller = FastDownPuller::new(make_options(
+            Url::parse("http://example.com/a.bin").unwrap(),
+        ))
+        .expect("new must succeed");
+        let _cloned = puller.clone();
+        assert!(puller.clients.lock().is_empty());
+    }
+
+    #[test]
+    fn shared_pool_keeps_one_client_per_local_address() {
+        let ips: Vec<std::net::IpAddr> =
+            vec!["127.0.0.1".parse().unwrap(), "127.0.0.2".parse().unwrap()];
+        let mut opts = make_options(Url::parse("http://example.com/a.bin").unwrap());
+        opts.available_ips = Arc::from(ips.clone());
+        opts.pool = ConnectionPool::Shared;
+        let puller = FastDownPuller::new(opts).expect("new must succeed");
+        let clones: Vec<_> = (0..5).map(|_| puller.clone()).collect();
+        assert!(Arc::ptr_eq(&puller.clients, &clones[4].clients));
+        let mut bound: Vec<_> = puller.clients.lock().keys().copied().collect();
+        bound.sort();
+        assert_eq!(bound, ips.into_iter().map(Some).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn multiplexed_pool_clones_pull() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/a.bin")
+            .with_status(206)
+            .with_header("Content-Range", "bytes 0-3/8")
+            .with_body("abcd")
+            .expect(3)
+            .create_async()
+            .await;
+        let mut opts = make_options(Url::parse(&format!("{}/a.bin", server.url())).unwrap());
+        opts.pool = ConnectionPool::Multiplexed;
+        let puller = FastDownPuller::new(opts).expect("new must succeed");
+        for mut puller in [puller.clone(), puller.clone(), puller] {
+            let stream = FastDownPuller::pull(&mut puller, Some(&(0..4)))
+                .await
+                .expect("pull must succeed");
+            let body: Vec<Bytes> = stream.try_collect().await.expect("body must arrive");
+            assert_eq!(body.concat(), b"abcd");
+        }
+        mock.assert_async().await;
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn resolver_overrides_reach_every_clone() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/a.bin")
+            .with_body("abcd")
+            .expect(2)
+            .create_async()
+            .await;
+        let port = server.socket_address().port();
+        let mut opts =
+            make_options(Url::parse(&format!("http://cdn.invalid:{port}/a.bin")).unwrap());
+        opts.resolver = Some(Resolver::new(
+            vec![format!("cdn.invalid:{port}:127.0.0.1").parse().unwrap()],
+            crate::resolver::AddressFamily::Any,
+            true,
+        ));
+        let puller = FastDownPuller::new(opts).expect("new must succeed");
+        for mut puller in [puller.clone(), puller] {
+            let stream = FastDownPuller::pull(&mut puller, None)
+                .await
+                .expect("pull must succeed");
+            let body: Vec<Bytes> = stream.try_collect().await.expect("body must arrive");
+            assert_eq!(body.concat(), b"abcd");
+        }
+        mock.assert_async().await;
+        drop(server);
+    }
 }
@@ -3,6 +3,9 @@
 //! * [`fast_puller`] (feature `fast-puller`): the `FastDownPuller`
 //!   type and its `FastDownPullerOptions`, plus `build_client`
 //!   which constructs a correctly-configured `SmartRedirectClient`.
+//! * [`resolver`] (feature `fast-puller`): the `Resolver` those clients look
+//!   hosts up with, for `--resolve`-style overrides, IPv4/IPv6 preference and
+//!   round-robin over a host's addresses.
 //! * [`getifaddrs`] (feature `getifaddrs`): enumerates the machine's non-virtual
 //!   local IP addresses for multi-interface download setups.
 
@@ -11,3 +14,6 @@
 pub mod fast_puller;
 #[cfg(feature = "getifaddrs")]
 pub mod getifaddrs;
+#[cfg(feature = "fast-puller")]
+#[cfg(not(target_family = "wasm"))]
+pub mod resolver;
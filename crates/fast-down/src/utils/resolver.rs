@@ -0,0 +1,369 @@
+//! DNS resolution for [`FastDownPuller`](crate::fast_puller::FastDownPuller).
+//!
+//! [`Resolver`] plugs into reqwest's [`Resolve`] hook. It answers hosts listed
+//! in a [`ResolveOverride`] (curl's `--resolve host:port:addr`) without asking
+//! DNS, orders or filters the addresses by [`AddressFamily`], and can hand
+//! every new connection the next address of the host, so the workers of a
+//! download spread over all A/AAAA records of a CDN name.
+//!
+//! reqwest only hands a resolver the host, so the port an override is matched
+//! against comes from [`with_request_port`], which
+//! [`SmartRedirectClient`](crate::reqwest::SmartRedirectClient) wraps around
+//! every request it sends.
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::{
+    fmt,
+    io::{self, ErrorKind},
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// Which IP versions to connect over.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AddressFamily {
+    /// The order the system resolver returns.
+    #[default]
+    Any,
+    /// IPv4 addresses first, IPv6 as a fallback.
+    PreferV4,
+    /// IPv6 addresses first, IPv4 as a fallback.
+    PreferV6,
+    /// IPv4 addresses only.
+    V4Only,
+    /// IPv6 addresses only.
+    V6Only,
+}
+
+impl AddressFamily {
+    /// Apply the preference to `addrs`, keeping the order within a version.
+    fn sort(self, addrs: &mut Vec<SocketAddr>) {
+        match self {
+            Self::Any => {}
+            Self::PreferV4 => addrs.sort_by_key(SocketAddr::is_ipv6),
+            Self::PreferV6 => addrs.sort_by_key(SocketAddr::is_ipv4),
+            Self::V4Only => addrs.retain(SocketAddr::is_ipv4),
+            Self::V6Only => addrs.retain(SocketAddr::is_ipv6),
+        }
+    }
+}
+
+/// A static answer for one host, written like curl's `--resolve`:
+/// `host:port:addr[,addr]…`, with IPv6 addresses in brackets.
+///
+/// It applies only to requests whose URL has `port`, named or the default of
+/// its scheme, so `example.com:443:…` leaves `http://example.com/` alone;
+/// `*` as the port (`None`) matches every port. Hosts match
+/// case-insensitively.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResolveOverride {
+    pub host: String,
+    pub port: Option<u16>,
+    pub addrs: Vec<IpAddr>,
+}
+
+/// Why a [`ResolveOverride`] could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseResolveError(String);
+
+impl fmt::Display for ParseResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseResolveError {}
+
+impl FromStr for ResolveOverride {
+    type Err = ParseResolveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = |what: &str| ParseResolveError(format!("{what} in {s:?}"));
+        let mut parts = s.splitn(3, ':');
+        let (Some(host), Some(port), Some(addrs)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(err("expected `host:port:addr`"));
+        };
+        if host.is_empty() {
+            return Err(err("empty host"));
+        }
+        let port = match port {
+            "*" => None,
+            port => Some(port.parse().map_err(|_| err("bad port"))?),
+        };
+        let addrs = addrs
+            .split(',')
+            .map(|addr| {
+                let addr = addr.trim();
+                addr.strip_prefix('[')
+                    .and_then(|a| a.strip_suffix(']'))
+                    .unwrap_or(addr)
+                    .parse()
+                    .map_err(|_| err("bad address"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            host: host.to_ascii_lowercase(),
+            port,
+            addrs,
+        })
+    }
+}
+
+impl fmt::Display for ResolveOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.port {
+            Some(port) => write!(f, "{}:{port}:", self.host)?,
+            None => write!(f, "{}:*:", self.host)?,
+        }
+        for (i, addr) in self.addrs.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            match addr {
+                IpAddr::V4(addr) => write!(f, "{addr}")?,
+                IpAddr::V6(addr) => write!(f, "[{addr}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ResolveOverride {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ResolveOverride {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+tokio::task_local! {
+    static REQUEST_PORT: u16;
+}
+
+/// Run `request` with `port` as the port [`Resolver`] matches the port of a
+/// [`ResolveOverride`] against: the port of the request's URL, or the default
+/// of its scheme.
+///
+/// Outside of it, only overrides with the port `*` apply.
+pub async fn with_request_port<F: Future>(port: u16, request: F) -> F::Output {
+    REQUEST_PORT.scope(port, request).await
+}
+
+/// The DNS resolver of a [`FastDownPuller`](crate::fast_puller::FastDownPuller)
+/// and its clones.
+///
+/// Cheap to clone; clones share the round-robin position, so pass the same
+/// resolver to every client of one download.
+#[derive(Debug, Clone, Default)]
+pub struct Resolver {
+    overrides: Arc<[ResolveOverride]>,
+    family: AddressFamily,
+    round_robin: bool,
+    turn: Arc<AtomicUsize>,
+}
+
+impl Resolver {
+    /// Answer the hosts in `overrides` without DNS, order or filter every
+    /// answer by `family`, and with `round_robin`, start each lookup at the
+    /// next address so that new connections spread over all of them.
+    #[must_use]
+    pub fn new(overrides: Vec<ResolveOverride>, family: AddressFamily, round_robin: bool) -> Self {
+        Self {
+            overrides: overrides.into(),
+            family,
+            round_robin,
+            turn: Arc::default(),
+        }
+    }
+
+    /// Whether the resolver behaves exactly like the system one, so a client
+    /// need not install it.
+    #[must_use]
+    pub fn is_system(&self) -> bool {
+        self.overrides.is_empty() && self.family == AddressFamily::Any && !self.round_robin
+    }
+
+    /// The addresses to connect to for `host` on `port` (`None` when not
+    /// known), in the order to try them.
+    ///
+    /// # Errors
+    /// Returns an error if the system lookup fails or no address of the
+    /// wanted [`AddressFamily`] is left.
+    pub async fn lookup(&self, host: &str, port: Option<u16>) -> io::Result<Vec<SocketAddr>> {
+        let mut addrs: Vec<_> = match self
+            .overrides
+            .iter()
+            .find(|o| o.host.eq_ignore_ascii_case(host) && (o.port.is_none() || o.port == port))
+        {
+            // Port 0 leaves the port of the URL, or of its scheme, in place.
+            Some(o) => o.addrs.iter().map(|&ip| SocketAddr::new(ip, 0)).collect(),
+            None => tokio::net::lookup_host((host, 0)).await?.collect(),
+        };
+        self.family.sort(&mut addrs);
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::NotFound,
+                format!("no {:?} address for {host}", self.family),
+            ));
+        }
+        if self.round_robin {
+            let turn = self.turn.fetch_add(1, Ordering::Relaxed) % addrs.len();
+            addrs.rotate_left(turn);
+        }
+        Ok(addrs)
+    }
+}
+
+impl Resolve for Resolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        // Read while still inside the request's task.
+        let port = REQUEST_PORT.try_with(|port| *port).ok();
+        Box::pin(async move {
+            let addrs = resolver.lookup(name.as_str(), port).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used, clippy::expect_used)]
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_curl_syntax() {
+        let o: ResolveOverride = "Example.com:443:127.0.0.1,[::1]".parse().unwrap();
+        assert_eq!(o.host, "example.com");
+        assert_eq!(o.port, Some(443));
+        assert_eq!(o.addrs, [ip("127.0.0.1"), ip("::1")]);
+        assert_eq!(o.to_string(), "example.com:443:127.0.0.1,[::1]");
+        assert_eq!(o.to_string().parse::<ResolveOverride>().unwrap(), o);
+        let any: ResolveOverride = "a:*:::1".parse().unwrap();
+        assert_eq!((any.port, any.addrs), (None, vec![ip("::1")]));
+        assert_eq!(
+            "a:*:10.0.0.1"
+                .parse::<ResolveOverride>()
+                .unwrap()
+                .to_string(),
+            "a:*:10.0.0.1"
+        );
+        assert!("example.com:443".parse::<ResolveOverride>().is_err());
+        assert!(":443:127.0.0.1".parse::<ResolveOverride>().is_err());
+        assert!("a:https:127.0.0.1".parse::<ResolveOverride>().is_err());
+        assert!("a:443:localhost".parse::<ResolveOverride>().is_err());
+    }
+
+    #[tokio::test]
+    async fn overrides_skip_dns_and_follow_the_family() {
+        let overrides = vec!["cdn.test:*:10.0.0.1,[fd00::1],10.0.0.2".parse().unwrap()];
+        let resolver = Resolver::new(overrides.clone(), AddressFamily::PreferV6, false);
+        let addrs = resolver.lookup("CDN.test", Some(443)).await.unwrap();
+        assert_eq!(
+            addrs.iter().map(SocketAddr::ip).collect::<Vec<_>>(),
+            [ip("fd00::1"), ip("10.0.0.1"), ip("10.0.0.2")]
+        );
+        let v4 = Resolver::new(overrides.clone(), AddressFamily::V4Only, false);
+        assert_eq!(v4.lookup("cdn.test", None).await.unwrap().len(), 2);
+        let v6 = Resolver::new(
+            vec!["cdn.test:*:10.0.0.1".parse().unwrap()],
+            AddressFamily::V6Only,
+            false,
+        );
+        assert!(v6.lookup("cdn.test", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn overrides_apply_to_their_port_only() {
+        let resolver = Resolver::new(
+            vec!["localhost:8443:10.0.0.1".parse().unwrap()],
+            AddressFamily::Any,
+            false,
+        );
+        // Port 0 leaves the connector to use the port of the URL.
+        let addrs = resolver.lookup("localhost", Some(8443)).await.unwrap();
+        assert_eq!(addrs, [SocketAddr::new(ip("10.0.0.1"), 0)]);
+        for port in [Some(80), None] {
+            let addrs = resolver.lookup("localhost", port).await.unwrap();
+            assert!(addrs.iter().all(|a| a.ip().is_loopback()), "{addrs:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_takes_the_port_of_the_request() {
+        let resolver = Resolver::new(
+            vec!["localhost:8443:10.0.0.1".parse().unwrap()],
+            AddressFamily::Any,
+            false,
+        );
+        let on = |port| {
+            let resolver = resolver.clone();
+            with_request_port(port, async move {
+                let mut addrs = resolver
+                    .resolve("localhost".parse().unwrap())
+                    .await
+                    .unwrap();
+                addrs.next().unwrap().ip()
+            })
+        };
+        assert_eq!(on(8443).await, ip("10.0.0.1"));
+        assert!(on(443).await.is_loopback());
+        // The port is read when the lookup starts, so it must start inside.
+        let outside = resolver.resolve("localhost".parse().unwrap());
+        let first = with_request_port(8443, outside)
+            .await
+            .unwrap()
+            .next()
+            .unwrap();
+        assert!(first.ip().is_loopback());
+    }
+
+    #[tokio::test]
+    async fn round_robin_rotates_across_clones() {
+        let resolver = Resolver::new(
+            vec!["cdn.test:*:10.0.0.1,10.0.0.2,10.0.0.3".parse().unwrap()],
+            AddressFamily::Any,
+            true,
+        );
+        let clone = resolver.clone();
+        let mut firsts = Vec::new();
+        for r in [&resolver, &clone, &resolver, &clone] {
+            firsts.push(r.lookup("cdn.test", None).await.unwrap()[0].ip());
+        }
+        assert_eq!(
+            firsts,
+            [
+                ip("10.0.0.1"),
+                ip("10.0.0.2"),
+                ip("10.0.0.3"),
+                ip("10.0.0.1")
+            ]
+        );
+        assert!(Resolver::default().is_system());
+        assert!(!resolver.is_system());
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_system_resolver() {
+        let resolver = Resolver::new(Vec::new(), AddressFamily::V4Only, false);
+        let addrs = resolver.lookup("localhost", None).await.unwrap();
+        assert!(addrs.iter().all(SocketAddr::is_ipv4));
+    }
+}
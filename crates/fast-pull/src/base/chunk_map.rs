@@ -0,0 +1,161 @@
+//! Fixed-resolution rendering of [`ProgressEntry`](crate::ProgressEntry) lists,
+//! for segmented progress bars.
+
+use crate::{ProgressEntry, Total};
+
+/// Progress of a download resampled into a fixed number of cells.
+///
+/// Cell `i` of `n` covers bytes `total * i / n .. total * (i + 1) / n`. When
+/// there are more cells than bytes, each cell covers the byte it starts in, so
+/// neighbouring cells may share a byte. A zero-size download renders every
+/// cell empty, as its percentage is `0` too.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkMap {
+    /// Downloaded share of each cell, in `0.0..=1.0`.
+    pub fill: Vec<f32>,
+    /// One bit per cell, most significant bit first: set when every byte of
+    /// the cell is downloaded.
+    pub done: Vec<u8>,
+    /// One bit per cell, most significant bit first: set when a range that
+    /// is being pulled overlaps the cell.
+    pub active: Vec<u8>,
+}
+
+impl ChunkMap {
+    /// Render `progress` and the in-flight `active` ranges into `cells` cells.
+    ///
+    /// `progress` must be **sorted by `start` and non-overlapping**, as produced
+    /// by [`Merge::merge_progress`](crate::Merge::merge_progress); `active` may
+    /// be in any order, such as the ranges of
+    /// [`DownloadResult::workers`](crate::DownloadResult::workers).
+    #[must_use]
+    pub fn new(
+        progress: &[ProgressEntry],
+        active: &[ProgressEntry],
+        total: u64,
+        cells: usize,
+    ) -> Self {
+        debug_assert!(
+            progress.windows(2).all(|w| w[0].end <= w[1].start),
+            "ChunkMap requires a sorted, non-overlapping list; merge the entries first"
+        );
+        let mut map = Self {
+            fill: Vec::with_capacity(cells),
+            done: vec![0; cells.div_ceil(8)],
+            active: vec![0; cells.div_ceil(8)],
+        };
+        for i in 0..cells {
+            let Some(cell) = cell_range(i, cells, total) else {
+                map.fill.push(0.0);
+                continue;
+            };
+            let first = progress.partition_point(|r| r.end <= cell.start);
+            let covered: u64 = progress[first..]
+                .iter()
+                .take_while(|r| r.start < cell.end)
+                .map(|r| (r.start.max(cell.start)..r.end.min(cell.end)).total())
+                .sum();
+            let len = cell.total();
+            #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+            map.fill.push((covered as f64 / len as f64) as f32);
+            if covered == len {
+                set_bit(&mut map.done, i);
+            }
+            if active
+                .iter()
+                .any(|r| r.start < cell.end && r.end > cell.start)
+            {
+                set_bit(&mut map.active, i);
+            }
+        }
+        map
+    }
+
+    /// Number of cells.
+    #[must_use]
+    pub const fn cells(&self) -> usize {
+        self.fill.len()
+    }
+
+    /// Whether cell `i` is fully downloaded; `false` past the last cell.
+    #[must_use]
+    pub fn is_done(&self, i: usize) -> bool {
+        i < self.cells() && get_bit(&self.done, i)
+    }
+
+    /// Whether a range being pulled overlaps cell `i`; `false` past the last
+    /// cell.
+    #[must_use]
+    pub fn is_active(&self, i: usize) -> bool {
+        i < self.cells() && get_bit(&self.active, i)
+    }
+}
+
+/// Bytes covered by cell `i` of `cells`, never empty; `None` when `total == 0`.
+fn cell_range(i: usize, cells: usize, total: u64) -> Option<ProgressEntry> {
+    if total == 0 {
+        return None;
+    }
+    let at = |i: usize| {
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            (u128::from(total) * i as u128 / cells as u128) as u64
+        }
+    };
+    let start = at(i);
+    Some(start..at(i + 1).max(start + 1))
+}
+
+fn set_bit(bits: &mut [u8], i: usize) {
+    bits[i / 8] |= 0x80 >> (i % 8);
+}
+
+fn get_bit(bits: &[u8], i: usize) -> bool {
+    bits[i / 8] & (0x80 >> (i % 8)) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::single_range_in_vec_init, clippy::float_cmp)]
+    use super::*;
+
+    #[test]
+    fn chunk_map_fill_and_bits() {
+        let map = ChunkMap::new(&[0..25, 30..40], &[40..45], 100, 10);
+        assert_eq!(map.cells(), 10);
+        assert_eq!(map.fill, [1.0, 1.0, 0.5, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(map.done, [0b1101_0000, 0]);
+        assert_eq!(map.active, [0b0000_1000, 0]);
+        assert!(map.is_done(3));
+        assert!(!map.is_done(2));
+        assert!(map.is_active(4));
+        assert!(!map.is_active(3));
+        assert!(!map.is_done(10));
+    }
+
+    #[test]
+    fn chunk_map_more_cells_than_bytes() {
+        let map = ChunkMap::new(&[0..1], &[2..3], 3, 6);
+        assert_eq!(map.fill, [1.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(map.done, [0b1100_0000]);
+        assert_eq!(map.active, [0b0000_1100]);
+    }
+
+    #[test]
+    fn chunk_map_done_is_exact() {
+        // The f32 fill rounds to 1.0, but the bit must stay clear.
+        let total = 1 << 30;
+        let map = ChunkMap::new(&[1..total], &[], total, 1);
+        assert_eq!(map.fill, [1.0]);
+        assert!(!map.is_done(0));
+    }
+
+    #[test]
+    fn chunk_map_empty_download() {
+        let map = ChunkMap::new(&[], &[], 0, 4);
+        assert_eq!(map.fill, [0.0; 4]);
+        assert_eq!(map.done, [0]);
+        assert_eq!(ChunkMap::new(&[0..10], &[], 10, 0), ChunkMap::default());
+    }
+}
@@ -2,6 +2,7 @@
 //! [`event_chain`](crate::DownloadResult::event_chain).
 
 use crate::ProgressEntry;
+use core::time::Duration;
 
 /// Numeric identifier assigned to each worker thread/task.
 pub type WorkerId = usize;
@@ -14,11 +15,93 @@ pub enum Event<PullError, PushError> {
     Pulling(WorkerId),
     PullError(WorkerId, PullError),
     PullTimeout(WorkerId),
+    /// A worker dropped its connection for pulling slower than
+    /// [`multi::DownloadOptions::slow_worker`](crate::multi::DownloadOptions::slow_worker)
+    /// and reconnects from where it stopped.
+    PullSlow(WorkerId),
+    /// The server [rate limited](crate::PullerError::is_rate_limited) a pull,
+    /// so no worker connects for this long and fewer reconnect afterwards.
+    /// Sent once per cooldown; rate limits during it only extend it.
+    Throttled(Duration),
     PullProgress(WorkerId, ProgressEntry),
     Pushing(WorkerId, ProgressEntry),
     PushError(WorkerId, ProgressEntry, PushError),
     PushProgress(ProgressEntry),
     Flushing,
     FlushError(PushError),
+    /// A worker hit a [permanent](crate::PullerError::is_permanent) pull error,
+    /// or the [`RetryPolicy`](crate::RetryPolicy) gave up retrying a pull error.
+    ///
+    /// Terminal: the session is cancelled right after this event, so no data is
+    /// flushed and every worker exits.
+    PullFailed(WorkerId, PullError),
+    /// The [`RetryPolicy`](crate::RetryPolicy) gave up after a pull timeout.
+    ///
+    /// Terminal, like [`PullFailed`](Self::PullFailed).
+    PullTimeoutFailed(WorkerId),
+    /// The [`RetryPolicy`](crate::RetryPolicy) gave up retrying a push error.
+    ///
+    /// Terminal, like [`PullFailed`](Self::PullFailed).
+    PushFailed(WorkerId, ProgressEntry, PushError),
     Finished(WorkerId),
 }
+
+impl<PullError, PushError> Event<PullError, PushError> {
+    /// The lowest [`Verbosity`] a session must run at to emit this event.
+    pub const fn verbosity(&self) -> Verbosity {
+        match self {
+            Self::Pulling(_)
+            | Self::Flushing
+            | Self::PullFailed(..)
+            | Self::PullTimeoutFailed(_)
+            | Self::PushFailed(..)
+            | Self::Finished(_) => Verbosity::Lifecycle,
+            Self::PullError(..)
+            | Self::PullTimeout(_)
+            | Self::PullSlow(_)
+            | Self::Throttled(_)
+            | Self::PushError(..)
+            | Self::FlushError(_) => Verbosity::Errors,
+            Self::PushProgress(_) => Verbosity::Progress,
+            Self::PullProgress(..) | Self::Pushing(..) => Verbosity::Trace,
+        }
+    }
+}
+
+/// How much of the [`Event`] stream a session emits.
+///
+/// Each level includes the ones below it. Events above the level are never
+/// built nor sent, which saves the consumer from draining one event per chunk
+/// on fast links.
+///
+/// Session bookkeeping, such as the [`SessionStats`](crate::SessionStats)
+/// counters, does not depend on the level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Verbosity {
+    /// Worker lifecycle ([`Event::Pulling`], [`Event::Finished`]),
+    /// [`Event::Flushing`] and the terminal `*Failed` events.
+    Lifecycle,
+    /// Plus the errors a retry recovers from: [`Event::PullError`],
+    /// [`Event::PullTimeout`], [`Event::PullSlow`], [`Event::Throttled`],
+    /// [`Event::PushError`] and [`Event::FlushError`].
+    Errors,
+    /// Plus [`Event::PushProgress`], once per range written by the pusher.
+    Progress,
+    /// Every event, including the per-chunk [`Event::PullProgress`] and
+    /// [`Event::Pushing`].
+    #[default]
+    Trace,
+}
+
+impl Verbosity {
+    /// Whether a session running at this level emits events of `level`.
+    #[must_use]
+    pub const fn includes(self, level: Self) -> bool {
+        self as u8 >= level as u8
+    }
+}
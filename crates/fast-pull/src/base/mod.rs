@@ -4,8 +4,9 @@
 //! [`Pusher`](crate::Pusher) — plus the supporting types used to describe
 //! progress and events: [`ProgressEntry`](crate::ProgressEntry),
 //! [`Event`](crate::Event), [`WorkerId`](crate::WorkerId), and helpers for
-//! merging and inverting progress ranges.
+//! merging, inverting and rendering progress ranges.
 
+mod chunk_map;
 mod event;
 mod invert;
 mod merge;
@@ -13,6 +14,7 @@ mod progress;
 mod puller;
 mod pusher;
 
+pub use chunk_map::*;
 pub use event::*;
 pub use invert::*;
 pub use merge::*;
@@ -50,6 +50,26 @@ pub trait PullerError: std::error::Error + Send + Sync + Unpin + 'static {
     fn is_irrecoverable(&self) -> bool {
         false
     }
+
+    /// Whether retrying can never succeed, so the whole session should end.
+    ///
+    /// Unlike [`is_irrecoverable`](Self::is_irrecoverable), which only restarts
+    /// the affected range, a permanent error (e.g. an HTTP `404`) makes the
+    /// engine emit [`Event::PullFailed`](crate::Event::PullFailed) and cancel
+    /// the session. The default is `false`.
+    fn is_permanent(&self) -> bool {
+        false
+    }
+
+    /// Whether the server asked to slow down, e.g. with an HTTP `429` or
+    /// `503`.
+    ///
+    /// A multi-threaded session then holds every worker back for the retry
+    /// delay and reopens fewer connections for a while, emitting
+    /// [`Event::Throttled`](crate::Event::Throttled). The default is `false`.
+    fn is_rate_limited(&self) -> bool {
+        false
+    }
 }
 
 impl PullerError for std::convert::Infallible {
@@ -57,6 +77,11 @@ impl PullerError for std::convert::Infallible {
         #[allow(clippy::uninhabited_references)]
         match *self {}
     }
+
+    fn is_permanent(&self) -> bool {
+        #[allow(clippy::uninhabited_references)]
+        match *self {}
+    }
 }
 
 #[cfg(test)]
@@ -83,6 +108,13 @@ mod tests {
         assert_eq!(format!("{DefaultErr}"), "default error");
     }
 
+    #[test]
+    fn default_is_permanent_is_false() {
+        assert!(!DefaultErr.is_permanent());
+        assert!(!FatalErr.is_permanent());
+        assert!(!DefaultErr.is_rate_limited());
+    }
+
     /// A `PullerError` that overrides `is_irrecoverable` to report a fatal error.
     #[derive(Debug)]
     struct FatalErr;
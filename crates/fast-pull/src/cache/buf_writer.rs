@@ -122,7 +122,7 @@ impl<P: Pusher> Pusher for BufWriterPusher<P> {
 
 #[cfg(test)]
 mod tests {
-    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unwrap_used, clippy::significant_drop_tightening)]
     use super::*;
     use std::sync::{Arc, Mutex};
 
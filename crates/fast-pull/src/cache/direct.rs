@@ -136,7 +136,7 @@ impl<P: Pusher> Pusher for CacheDirectPusher<P> {
 
 #[cfg(test)]
 mod tests {
-    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unwrap_used, clippy::significant_drop_tightening)]
     use super::*;
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::{Arc, Mutex};
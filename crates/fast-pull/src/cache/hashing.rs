@@ -0,0 +1,643 @@
+//! Pusher decorator that hashes the written bytes in offset order.
+
+use crate::{ProgressEntry, ProgressListener, Pusher};
+use bytes::Bytes;
+use core::{fmt, str::FromStr};
+use parking_lot::Mutex;
+use std::{collections::BTreeMap, io::Read, sync::Arc};
+
+/// A hash function [`HashingPusher`] can compute, each behind the feature of the
+/// same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    #[cfg(feature = "sha256")]
+    Sha256,
+    #[cfg(feature = "sha1")]
+    Sha1,
+    #[cfg(feature = "md5")]
+    Md5,
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// The lowercase name used by [`Digest`]'s text form, e.g. `sha256`.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "sha256")]
+            Self::Sha256 => "sha256",
+            #[cfg(feature = "sha1")]
+            Self::Sha1 => "sha1",
+            #[cfg(feature = "md5")]
+            Self::Md5 => "md5",
+            #[cfg(feature = "blake3")]
+            Self::Blake3 => "blake3",
+        }
+    }
+
+    /// Relative collision resistance, higher is stronger. Picks one digest when
+    /// several are available for the same file.
+    #[must_use]
+    pub const fn strength(self) -> u8 {
+        match self {
+            #[cfg(feature = "md5")]
+            Self::Md5 => 1,
+            #[cfg(feature = "sha1")]
+            Self::Sha1 => 2,
+            #[cfg(feature = "sha256")]
+            Self::Sha256 => 3,
+            #[cfg(feature = "blake3")]
+            Self::Blake3 => 4,
+        }
+    }
+
+    /// Length of the digest in bytes.
+    #[must_use]
+    pub const fn output_len(self) -> usize {
+        match self {
+            #[cfg(feature = "sha256")]
+            Self::Sha256 => 32,
+            #[cfg(feature = "sha1")]
+            Self::Sha1 => 20,
+            #[cfg(feature = "md5")]
+            Self::Md5 => 16,
+            #[cfg(feature = "blake3")]
+            Self::Blake3 => 32,
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = ParseDigestError;
+
+    /// Case-insensitive, accepting the hyphenated spellings of HTTP digest
+    /// headers (`sha-256`, `SHA-1`) as well.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            #[cfg(feature = "sha256")]
+            "sha256" | "sha-256" => Ok(Self::Sha256),
+            #[cfg(feature = "sha1")]
+            "sha1" | "sha-1" => Ok(Self::Sha1),
+            #[cfg(feature = "md5")]
+            "md5" => Ok(Self::Md5),
+            #[cfg(feature = "blake3")]
+            "blake3" => Ok(Self::Blake3),
+            _ => Err(ParseDigestError::UnknownAlgorithm(s.to_string())),
+        }
+    }
+}
+
+/// A digest tagged with the algorithm that produced it.
+///
+/// Its text form is `<algorithm>:<lowercase hex>`, e.g. `sha256:e3b0c4…`,
+/// which is also how it (de)serializes with the `serde` feature.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Digest {
+    pub algorithm: HashAlgorithm,
+    pub bytes: Vec<u8>,
+}
+
+impl Digest {
+    #[must_use]
+    pub fn to_hex(&self) -> String {
+        use fmt::Write;
+        self.bytes
+            .iter()
+            .fold(String::with_capacity(self.bytes.len() * 2), |mut s, b| {
+                let _ = write!(s, "{b:02x}");
+                s
+            })
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm, self.to_hex())
+    }
+}
+
+impl FromStr for Digest {
+    type Err = ParseDigestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, hex) = s
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| ParseDigestError::MissingAlgorithm(s.to_string()))?;
+        let algorithm: HashAlgorithm = algorithm.parse()?;
+        let invalid = || ParseDigestError::InvalidHex(hex.to_string());
+        if hex.len() != algorithm.output_len() * 2 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { algorithm, bytes })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Digest {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Digest {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Why a [`Digest`] or [`HashAlgorithm`] could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseDigestError {
+    /// The text has no `<algorithm>:` prefix.
+    MissingAlgorithm(String),
+    /// The algorithm is unknown or its feature is disabled.
+    UnknownAlgorithm(String),
+    /// The digest is not hex of the algorithm's output length.
+    InvalidHex(String),
+}
+
+impl fmt::Display for ParseDigestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingAlgorithm(s) => write!(f, "digest `{s}` has no `<algorithm>:` prefix"),
+            Self::UnknownAlgorithm(s) => write!(f, "unsupported hash algorithm `{s}`"),
+            Self::InvalidHex(s) => write!(f, "`{s}` is not a hex digest of the expected length"),
+        }
+    }
+}
+
+impl std::error::Error for ParseDigestError {}
+
+#[derive(Clone)]
+enum Hasher {
+    #[cfg(feature = "sha256")]
+    Sha256(sha2::Sha256),
+    #[cfg(feature = "sha1")]
+    Sha1(sha1::Sha1),
+    #[cfg(feature = "md5")]
+    Md5(md5::Md5),
+    #[cfg(feature = "blake3")]
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        #[cfg(any(feature = "sha256", feature = "sha1", feature = "md5"))]
+        use digest::Digest as _;
+        match algorithm {
+            #[cfg(feature = "sha256")]
+            HashAlgorithm::Sha256 => Self::Sha256(sha2::Sha256::new()),
+            #[cfg(feature = "sha1")]
+            HashAlgorithm::Sha1 => Self::Sha1(sha1::Sha1::new()),
+            #[cfg(feature = "md5")]
+            HashAlgorithm::Md5 => Self::Md5(md5::Md5::new()),
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3 => Self::Blake3(Box::default()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        #[cfg(any(feature = "sha256", feature = "sha1", feature = "md5"))]
+        use digest::Digest as _;
+        match self {
+            #[cfg(feature = "sha256")]
+            Self::Sha256(h) => h.update(data),
+            #[cfg(feature = "sha1")]
+            Self::Sha1(h) => h.update(data),
+            #[cfg(feature = "md5")]
+            Self::Md5(h) => h.update(data),
+            #[cfg(feature = "blake3")]
+            Self::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        #[cfg(any(feature = "sha256", feature = "sha1", feature = "md5"))]
+        use digest::Digest as _;
+        match self {
+            #[cfg(feature = "sha256")]
+            Self::Sha256(h) => h.finalize().to_vec(),
+            #[cfg(feature = "sha1")]
+            Self::Sha1(h) => h.finalize().to_vec(),
+            #[cfg(feature = "md5")]
+            Self::Md5(h) => h.finalize().to_vec(),
+            #[cfg(feature = "blake3")]
+            Self::Blake3(h) => h.finalize().as_bytes().to_vec(),
+        }
+    }
+}
+
+/// Checksums of the consecutive `length`-byte pieces of a file, e.g. from a
+/// metalink `<pieces>` element. The last piece may be shorter.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PieceHashes {
+    pub length: u64,
+    pub hashes: Vec<Digest>,
+}
+
+impl PieceHashes {
+    /// Hash `reader` piece by piece and return the indices of the pieces that do
+    /// not match, including those `reader` ends before. Bytes after the last
+    /// piece are ignored.
+    ///
+    /// # Errors
+    /// Returns the first error of `reader`.
+    pub fn verify(&self, mut reader: impl Read) -> std::io::Result<Vec<usize>> {
+        let mut buf = vec![0; 64 * 1024];
+        let mut bad = Vec::new();
+        for (i, expected) in self.hashes.iter().enumerate() {
+            let mut hasher = Hasher::new(expected.algorithm);
+            let mut left = self.length;
+            while left > 0 {
+                #[allow(clippy::cast_possible_truncation)]
+                let want = left.min(buf.len() as u64) as usize;
+                let n = match reader.read(&mut buf[..want]) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+                hasher.update(&buf[..n]);
+                left -= n as u64;
+            }
+            if hasher.finalize() != expected.bytes {
+                bad.push(i);
+            }
+        }
+        Ok(bad)
+    }
+}
+
+struct HashState {
+    algorithm: HashAlgorithm,
+    hasher: Hasher,
+    /// Every byte before this offset has been hashed.
+    position: u64,
+    /// Ranges written ahead of `position`, keyed by offset.
+    pending: BTreeMap<u64, Bytes>,
+    pending_size: usize,
+    max_pending: usize,
+    /// Ranges from here on are no longer buffered and must be read back with
+    /// [`DigestHandle::update_from`].
+    deferred_from: u64,
+}
+
+impl HashState {
+    #[allow(clippy::cast_possible_truncation)]
+    fn feed(&mut self, start: u64, mut bytes: Bytes) {
+        if start >= self.deferred_from {
+            return;
+        }
+        let end = start + bytes.len() as u64;
+        if end > self.deferred_from {
+            bytes.truncate((self.deferred_from - start) as usize);
+        }
+        if start > self.position {
+            self.buffer(start, bytes);
+        } else {
+            self.advance(start, &bytes);
+            self.drain();
+        }
+    }
+
+    /// Hash the part of `bytes` (starting at `start <= position`) past
+    /// `position`.
+    #[allow(clippy::cast_possible_truncation)]
+    fn advance(&mut self, start: u64, bytes: &[u8]) {
+        let skip = (self.position - start) as usize;
+        if skip < bytes.len() {
+            self.hasher.update(&bytes[skip..]);
+            self.position = start + bytes.len() as u64;
+        }
+    }
+
+    fn buffer(&mut self, start: u64, bytes: Bytes) {
+        if bytes.is_empty() {
+            return;
+        }
+        match self.pending.get(&start) {
+            Some(old) if old.len() >= bytes.len() => return,
+            Some(old) => self.pending_size -= old.len(),
+            None => {}
+        }
+        self.pending_size += bytes.len();
+        self.pending.insert(start, bytes);
+        // Give up on the ranges furthest from being hashable first.
+        while self.pending_size > self.max_pending
+            && let Some((start, bytes)) = self.pending.pop_last()
+        {
+            self.pending_size -= bytes.len();
+            self.deferred_from = self.deferred_from.min(start);
+        }
+    }
+
+    fn drain(&mut self) {
+        while let Some(entry) = self.pending.first_entry()
+            && *entry.key() <= self.position
+        {
+            let start = *entry.key();
+            let bytes = entry.remove();
+            self.pending_size -= bytes.len();
+            self.advance(start, &bytes);
+        }
+    }
+}
+
+/// Shared view of the digest computed by a [`HashingPusher`], usable after the
+/// pusher has been moved into a session.
+#[derive(Clone)]
+pub struct DigestHandle {
+    state: Arc<Mutex<HashState>>,
+}
+
+impl fmt::Debug for DigestHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("DigestHandle")
+            .field("algorithm", &state.algorithm)
+            .field("hashed_len", &state.position)
+            .field("pending_size", &state.pending_size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DigestHandle {
+    #[must_use]
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.state.lock().algorithm
+    }
+
+    /// Length of the prefix hashed so far. The digest covers the whole file only
+    /// once this equals its size.
+    #[must_use]
+    pub fn hashed_len(&self) -> u64 {
+        self.state.lock().position
+    }
+
+    /// Hash everything `reader` yields as the bytes following
+    /// [`hashed_len`](Self::hashed_len), e.g. the rest of the written file after
+    /// a resumed session or one whose out-of-order ranges did not fit the
+    /// buffer. Returns the number of bytes read.
+    ///
+    /// # Errors
+    /// Returns the first error of `reader`; the bytes read before it stay hashed.
+    pub fn update_from(&self, mut reader: impl Read) -> std::io::Result<u64> {
+        let mut buf = vec![0; 64 * 1024];
+        let mut total = 0;
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let mut state = self.state.lock();
+            state.hasher.update(&buf[..n]);
+            state.position += n as u64;
+            drop(state);
+            total += n as u64;
+        }
+        let mut state = self.state.lock();
+        state.deferred_from = u64::MAX;
+        state.drain();
+        drop(state);
+        Ok(total)
+    }
+
+    /// The digest of the first [`hashed_len`](Self::hashed_len) bytes. Hashing
+    /// may continue afterwards.
+    #[must_use]
+    pub fn digest(&self) -> Digest {
+        let state = self.state.lock();
+        Digest {
+            algorithm: state.algorithm,
+            bytes: state.hasher.clone().finalize(),
+        }
+    }
+}
+
+/// Pusher wrapper that feeds every successfully written byte into a hash, in
+/// offset order.
+///
+/// Ranges that continue the hashed prefix are hashed right away; ranges ahead of
+/// it are kept (the `Bytes` are shared with the inner pusher, not copied) until
+/// the gap before them is filled. Once more than `max_pending` bytes are held,
+/// the ranges furthest ahead are dropped and everything from the first dropped
+/// offset on is deferred: the digest then stops at that offset, and the caller
+/// completes it by reading the written data back with
+/// [`DigestHandle::update_from`]. Bytes written twice, e.g. by speculative
+/// workers, are hashed once.
+pub struct HashingPusher<P> {
+    inner: P,
+    handle: DigestHandle,
+}
+
+impl<P: fmt::Debug> fmt::Debug for HashingPusher<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashingPusher")
+            .field("inner", &self.inner)
+            .field("handle", &self.handle)
+            .finish()
+    }
+}
+
+impl<P: Pusher> HashingPusher<P> {
+    /// Wrap `inner`, hashing with `algorithm` and holding at most `max_pending`
+    /// bytes of out-of-order ranges.
+    pub fn new(inner: P, algorithm: HashAlgorithm, max_pending: usize) -> Self {
+        Self {
+            inner,
+            handle: DigestHandle {
+                state: Arc::new(Mutex::new(HashState {
+                    algorithm,
+                    hasher: Hasher::new(algorithm),
+                    position: 0,
+                    pending: BTreeMap::new(),
+                    pending_size: 0,
+                    max_pending,
+                    deferred_from: u64::MAX,
+                })),
+            },
+        }
+    }
+
+    /// The handle to read the digest through once the session is over.
+    pub fn digest_handle(&self) -> DigestHandle {
+        self.handle.clone()
+    }
+}
+
+impl<P: Pusher> Pusher for HashingPusher<P> {
+    type Error = P::Error;
+
+    fn set_listener(&mut self, cb: ProgressListener) {
+        self.inner.set_listener(cb);
+    }
+
+    fn push(&mut self, range: &ProgressEntry, content: Bytes) -> Result<(), (Self::Error, Bytes)> {
+        let result = self.inner.push(range, content.clone());
+        let written = match &result {
+            Ok(()) => content.len(),
+            Err((_, rest)) => content.len().saturating_sub(rest.len()),
+        };
+        if written > 0 {
+            self.handle
+                .state
+                .lock()
+                .feed(range.start, content.slice(..written));
+        }
+        result
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "sha256")]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+
+    const HELLO_SHA256: &str =
+        "sha256:b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+    #[derive(Default)]
+    struct NullSink {
+        fail_next: bool,
+    }
+    impl Pusher for NullSink {
+        type Error = std::io::Error;
+        fn push(&mut self, _: &ProgressEntry, content: Bytes) -> Result<(), (Self::Error, Bytes)> {
+            if std::mem::take(&mut self.fail_next) {
+                return Err((std::io::Error::other("boom"), content.slice(3..)));
+            }
+            Ok(())
+        }
+    }
+
+    fn push(p: &mut HashingPusher<NullSink>, range: ProgressEntry, data: &[u8]) {
+        p.push(&range, Bytes::copy_from_slice(data)).unwrap();
+    }
+
+    #[test]
+    fn piece_hashes_report_bad_and_missing_pieces() {
+        let piece = |data: &[u8]| {
+            let mut hasher = Hasher::new(HashAlgorithm::Sha256);
+            hasher.update(data);
+            Digest {
+                algorithm: HashAlgorithm::Sha256,
+                bytes: hasher.finalize(),
+            }
+        };
+        let pieces = PieceHashes {
+            length: 4,
+            hashes: vec![piece(b"abcd"), piece(b"efgh"), piece(b"ij"), piece(b"kl")],
+        };
+        assert_eq!(pieces.verify(&b"abcdefghij"[..]).unwrap(), [3]);
+        assert_eq!(pieces.verify(&b"abcdXfghij"[..]).unwrap(), [1, 3]);
+    }
+
+    #[test]
+    fn digest_round_trips_through_text() {
+        let digest: Digest = HELLO_SHA256.parse().unwrap();
+        assert_eq!(digest.algorithm, HashAlgorithm::Sha256);
+        assert_eq!(digest.to_string(), HELLO_SHA256);
+        assert_eq!(
+            "SHA-256:B94D27B9934D3E08A52E52D7DA7DABFAC484EFE37A5380EE9088F7ACE2EFCDE9"
+                .parse::<Digest>()
+                .unwrap(),
+            digest
+        );
+        assert!(matches!(
+            "b94d27".parse::<Digest>(),
+            Err(ParseDigestError::MissingAlgorithm(_))
+        ));
+        assert!(matches!(
+            "crc32:00000000".parse::<Digest>(),
+            Err(ParseDigestError::UnknownAlgorithm(_))
+        ));
+        assert!(matches!(
+            "sha256:b94d".parse::<Digest>(),
+            Err(ParseDigestError::InvalidHex(_))
+        ));
+    }
+
+    #[test]
+    fn out_of_order_ranges_hash_in_offset_order() {
+        let mut p = HashingPusher::new(NullSink::default(), HashAlgorithm::Sha256, 1024);
+        let handle = p.digest_handle();
+        push(&mut p, 6..11, b"world");
+        push(&mut p, 3..6, b"lo ");
+        assert_eq!(handle.hashed_len(), 0);
+        push(&mut p, 0..3, b"hel");
+        assert_eq!(handle.hashed_len(), 11);
+        assert_eq!(handle.digest().to_string(), HELLO_SHA256);
+    }
+
+    #[test]
+    fn overlapping_ranges_are_hashed_once() {
+        let mut p = HashingPusher::new(NullSink::default(), HashAlgorithm::Sha256, 1024);
+        let handle = p.digest_handle();
+        push(&mut p, 0..5, b"hello");
+        push(&mut p, 3..8, b"lo wo");
+        push(&mut p, 9..11, b"ld");
+        push(&mut p, 8..11, b"rld");
+        push(&mut p, 0..11, b"hello world");
+        assert_eq!(handle.digest().to_string(), HELLO_SHA256);
+    }
+
+    #[test]
+    fn failed_push_hashes_only_the_written_part() {
+        let mut p = HashingPusher::new(NullSink { fail_next: true }, HashAlgorithm::Sha256, 1024);
+        let handle = p.digest_handle();
+        let (_, rest) = p
+            .push(&(0..11), Bytes::from_static(b"hello world"))
+            .unwrap_err();
+        assert_eq!(handle.hashed_len(), 3);
+        p.push(&(3..11), rest).unwrap();
+        assert_eq!(handle.digest().to_string(), HELLO_SHA256);
+    }
+
+    #[test]
+    fn overflow_defers_the_tail_to_a_read_back() {
+        let mut p = HashingPusher::new(NullSink::default(), HashAlgorithm::Sha256, 4);
+        let handle = p.digest_handle();
+        push(&mut p, 3..6, b"lo ");
+        // Exceeds the buffer: the furthest range is dropped and deferred.
+        push(&mut p, 6..11, b"world");
+        push(&mut p, 0..3, b"hel");
+        assert_eq!(handle.hashed_len(), 6);
+        handle.update_from(&b"world"[..]).unwrap();
+        assert_eq!(handle.hashed_len(), 11);
+        assert_eq!(handle.digest().to_string(), HELLO_SHA256);
+    }
+
+    #[test]
+    fn resumed_prefix_is_read_back() {
+        let mut p = HashingPusher::new(NullSink::default(), HashAlgorithm::Sha256, 1024);
+        let handle = p.digest_handle();
+        push(&mut p, 6..11, b"world");
+        handle.update_from(&b"hello "[..]).unwrap();
+        assert_eq!(handle.hashed_len(), 11);
+        assert_eq!(handle.digest().to_string(), HELLO_SHA256);
+    }
+}
@@ -158,7 +158,7 @@ impl<P: Pusher> Pusher for CacheMergePusher<P> {
 
 #[cfg(test)]
 mod tests {
-    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unwrap_used, clippy::significant_drop_tightening)]
     use super::*;
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::{Arc, Mutex};
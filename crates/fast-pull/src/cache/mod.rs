@@ -7,13 +7,29 @@
 //! [`CacheMergePusher`] coalesces each run into one contiguous buffer, and
 //! [`CacheSeqPusher`] reorders runs into sequential order. [`BufWriterPusher`]
 //! instead mimics `std::io::BufWriter` with a fixed-size contiguous buffer.
+//! [`HashingPusher`] buffers the same way, but to hash the written bytes in
+//! offset order.
 
 mod buf_writer;
 mod direct;
+#[cfg(any(
+    feature = "sha256",
+    feature = "sha1",
+    feature = "md5",
+    feature = "blake3"
+))]
+mod hashing;
 mod merge;
 mod seq;
 
 pub use buf_writer::*;
 pub use direct::*;
+#[cfg(any(
+    feature = "sha256",
+    feature = "sha1",
+    feature = "md5",
+    feature = "blake3"
+))]
+pub use hashing::*;
 pub use merge::*;
 pub use seq::*;
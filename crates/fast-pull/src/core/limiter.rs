@@ -0,0 +1,201 @@
+//! Shared token-bucket bandwidth limiter.
+
+use parking_lot::Mutex;
+use std::sync::Arc;
+use tokio::{
+    sync::Notify,
+    time::{Duration, Instant},
+};
+
+/// A cheaply cloneable token-bucket bandwidth limiter.
+///
+/// All clones share one bucket, so a single limiter handed to several sessions
+/// (or to every worker of one session) caps their **combined** throughput. The
+/// bucket refills at `limit` bytes per second and holds at most one second worth
+/// of tokens, so an idle limiter allows a burst of at most `limit` bytes.
+///
+/// A `limit` of `0` means unlimited: [`acquire`](Self::acquire) returns
+/// immediately. The limit can be changed at any time with
+/// [`set_limit`](Self::set_limit); callers already waiting re-evaluate their
+/// wait against the new rate instead of sleeping out the old one.
+#[derive(Debug, Clone, Default)]
+pub struct SpeedLimiter {
+    inner: Arc<SpeedLimiterInner>,
+}
+
+#[derive(Debug, Default)]
+struct SpeedLimiterInner {
+    bucket: Mutex<Bucket>,
+    changed: Notify,
+}
+
+#[derive(Debug, Default)]
+struct Bucket {
+    /// Refill rate in bytes per second, `0` for unlimited.
+    limit: u64,
+    /// Available tokens. Negative while callers are paying off a debt.
+    tokens: f64,
+    /// Last time `tokens` was brought up to date, `None` before the first use.
+    last: Option<Instant>,
+}
+
+impl Bucket {
+    #[allow(clippy::cast_precision_loss)]
+    fn refill(&mut self, now: Instant) {
+        if let Some(last) = self.last {
+            let elapsed = now.saturating_duration_since(last).as_secs_f64();
+            self.tokens = elapsed
+                .mul_add(self.limit as f64, self.tokens)
+                .min(self.limit as f64);
+        }
+        self.last = Some(now);
+    }
+
+    /// How long a caller has to wait until the debt is paid off, if at all.
+    #[allow(clippy::cast_precision_loss)]
+    fn wait(&self) -> Option<Duration> {
+        (self.limit != 0 && self.tokens < 0.0)
+            .then(|| Duration::from_secs_f64(-self.tokens / self.limit as f64))
+    }
+}
+
+impl SpeedLimiter {
+    /// Create a limiter capped at `limit` bytes per second (`0` = unlimited).
+    #[must_use]
+    pub fn new(limit: u64) -> Self {
+        let limiter = Self::default();
+        limiter.inner.bucket.lock().limit = limit;
+        limiter
+    }
+
+    /// The current limit in bytes per second, `0` when unlimited.
+    #[must_use]
+    pub fn limit(&self) -> u64 {
+        self.inner.bucket.lock().limit
+    }
+
+    /// Change the limit to `limit` bytes per second (`0` = unlimited).
+    ///
+    /// Takes effect for every clone of this limiter, including callers that are
+    /// currently waiting in [`acquire`](Self::acquire).
+    #[allow(clippy::cast_precision_loss)]
+    pub fn set_limit(&self, limit: u64) {
+        {
+            let mut bucket = self.inner.bucket.lock();
+            bucket.refill(Instant::now());
+            bucket.limit = limit;
+            if limit == 0 {
+                bucket.tokens = 0.0;
+            } else {
+                bucket.tokens = bucket.tokens.min(limit as f64);
+            }
+        }
+        self.inner.changed.notify_waiters();
+    }
+
+    /// Take `bytes` tokens from the bucket, waiting until the bucket has refilled
+    /// enough to cover them.
+    ///
+    /// The tokens are charged up front, so a chunk larger than the bucket is never
+    /// starved: the caller simply waits out the resulting debt.
+    #[allow(clippy::cast_precision_loss)]
+    pub async fn acquire(&self, bytes: u64) {
+        let mut charged = false;
+        loop {
+            let notified = self.inner.changed.notified();
+            let wait = {
+                let mut bucket = self.inner.bucket.lock();
+                if bucket.limit == 0 {
+                    return;
+                }
+                bucket.refill(Instant::now());
+                if !charged {
+                    bucket.tokens -= bytes as f64;
+                    charged = true;
+                }
+                bucket.wait()
+            };
+            let Some(wait) = wait else { return };
+            tokio::select! {
+                () = tokio::time::sleep(wait) => {}
+                () = notified => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn unlimited_never_waits() {
+        let limiter = SpeedLimiter::default();
+        let start = Instant::now();
+        limiter.acquire(u64::MAX).await;
+        limiter.acquire(u64::MAX).await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn throughput_matches_limit() {
+        let limiter = SpeedLimiter::new(10_000);
+        let start = Instant::now();
+        for _ in 0..10 {
+            limiter.acquire(300).await;
+        }
+        // 3000 bytes at 10000 B/s, starting from an empty bucket.
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(290), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn clones_share_one_bucket() {
+        let limiter = SpeedLimiter::new(10_000);
+        let start = Instant::now();
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move { limiter.acquire(1000).await })
+            })
+            .collect();
+        for t in tasks {
+            t.await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(390));
+    }
+
+    #[tokio::test]
+    async fn lifting_the_limit_wakes_waiters() {
+        let limiter = SpeedLimiter::new(1);
+        let waiter = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire(1_000_000).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+        limiter.set_limit(0);
+        assert_eq!(limiter.limit(), 0);
+        tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .expect("waiter must observe the new limit")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn raising_the_limit_shortens_the_wait() {
+        let limiter = SpeedLimiter::new(10);
+        let waiter = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire(1000).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        limiter.set_limit(10_000);
+        // The remaining debt is repaid at the new rate: ~100ms instead of ~100s.
+        tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .expect("waiter must observe the new limit")
+            .unwrap();
+    }
+}
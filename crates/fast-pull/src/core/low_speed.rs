@@ -0,0 +1,98 @@
+//! Minimum-throughput rules for workers and whole sessions.
+
+use super::throttle::Throttle;
+use crate::{PauseToken, SessionStats};
+use core::time::Duration;
+use std::sync::Arc;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// A throughput floor: at least `bps` bytes per second, judged over `window`.
+///
+/// Used per worker as
+/// [`multi::DownloadOptions::slow_worker`](crate::multi::DownloadOptions::slow_worker),
+/// and per session as `low_speed_abort`, like curl's `--speed-limit` and
+/// `--speed-time`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpeedFloor {
+    pub bps: u64,
+    pub window: Duration,
+}
+
+impl SpeedFloor {
+    #[must_use]
+    pub const fn new(bps: u64, window: Duration) -> Self {
+        Self { bps, window }
+    }
+
+    /// Whether pulling `bytes` over one `window` stays below the floor.
+    #[must_use]
+    pub fn is_below(&self, bytes: u64) -> bool {
+        u128::from(bytes) * 1000 < u128::from(self.bps) * self.window.as_millis()
+    }
+}
+
+/// Fail the session once it pulls less than `floor` over a full window.
+///
+/// A window is only judged if the session was pulling, not paused and not
+/// held back by a `throttle` cooldown for all of it, so neither a pause, a
+/// server's `Retry-After` nor the final flush ends the session. The task
+/// exits once the session does.
+pub fn spawn_low_speed_abort(
+    floor: SpeedFloor,
+    stats: Arc<SessionStats>,
+    token: CancellationToken,
+    pause_token: PauseToken,
+    throttle: Arc<Throttle>,
+) {
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                () = token.cancelled() => return,
+                () = pause_token.unpaused() => {}
+            }
+            if let Some(end) = throttle.cooldown_end() {
+                tokio::select! {
+                    () = token.cancelled() => return,
+                    () = tokio::time::sleep_until(end) => {}
+                }
+            }
+            let start = Instant::now();
+            let pulled = stats.bytes_pulled();
+            tokio::select! {
+                () = token.cancelled() => return,
+                _ = stats.join() => return,
+                () = pause_token.paused() => continue,
+                () = tokio::time::sleep(floor.window) => {}
+            }
+            if !stats.is_pulling() {
+                return;
+            }
+            if throttle.cooldown_end().is_some_and(|end| end > start) {
+                continue;
+            }
+            if floor.is_below(stats.bytes_pulled() - pulled) {
+                stats.fail(&format!(
+                    "download speed stayed below {} B/s for {:?}",
+                    floor.bps, floor.window
+                ));
+                token.cancel();
+                return;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speed_floor_compares_the_window_total() {
+        let floor = SpeedFloor::new(1000, Duration::from_secs(5));
+        assert!(floor.is_below(4999));
+        assert!(!floor.is_below(5000));
+        assert!(!SpeedFloor::new(0, Duration::from_secs(5)).is_below(0));
+        assert!(SpeedFloor::new(10, Duration::from_millis(500)).is_below(4));
+    }
+}
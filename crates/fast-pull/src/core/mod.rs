@@ -7,16 +7,30 @@
 //! cheaply cloneable handle that keeps the session alive until the last clone is
 //! dropped (or [`DownloadResult::abort`] is called).
 
-use crate::Event;
+use crate::{Event, Puller, multi::TokioExecutor};
 use crossfire::{MAsyncRx, mpmc};
 use fast_steal::{Executor, TaskQueue};
+use schedule::Schedule;
 use std::fmt;
 use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 
+mod limiter;
+mod low_speed;
 pub mod mock;
 pub mod multi;
+mod outcome;
+mod pause;
+mod retry;
+mod schedule;
 pub mod single;
+mod throttle;
+
+pub use limiter::*;
+pub use low_speed::SpeedFloor;
+pub use outcome::*;
+pub use pause::PauseToken;
+pub use retry::{CappedRetry, ExponentialRetry, FixedRetry, RetryContext, RetryKind, RetryPolicy};
 
 /// Shared state of an active download session.
 ///
@@ -49,6 +63,19 @@ where
     /// notify per handle. Cancellation is terminal: once cancelled it never
     /// clears, so `is_aborted` stays `true` for the rest of the session.
     abort_token: CancellationToken,
+    /// The bandwidth limiter every worker of the session consults before
+    /// forwarding a pulled chunk. Possibly shared with other sessions.
+    speed_limiter: SpeedLimiter,
+    /// The pause switch every worker and the push driver of the session wait
+    /// on. Possibly shared with other sessions.
+    pause_token: PauseToken,
+    /// Minimum chunk size and speculation limit the workers of a
+    /// multi-threaded session read whenever they steal work. Unused by a
+    /// single-threaded one.
+    schedule: Arc<Schedule>,
+    /// Counters and final status of the session, sealed by the engine once its
+    /// push driver exits.
+    stats: Arc<SessionStats>,
 }
 
 impl<E, PullError, PushError> fmt::Debug for DownloadResultInner<E, PullError, PushError>
@@ -61,6 +88,8 @@ where
         f.debug_struct("DownloadResultInner")
             .field("event_chain", &self.event_chain)
             .field("is_aborted", &self.abort_token.is_cancelled())
+            .field("speed_limit", &self.speed_limiter.limit())
+            .field("is_paused", &self.pause_token.is_paused())
             .finish_non_exhaustive()
     }
 }
@@ -109,8 +138,8 @@ where
 /// dropped. An explicit [`abort`](Self::abort) cancels immediately.
 ///
 /// `DownloadResult` wraps `Arc<DownloadResultInner>` and exposes the session
-/// methods (`abort`, `set_threads`, `is_aborted`) and
-/// [`event_chain`](Self::event_chain) directly; each delegates to the inner
+/// methods (`abort`, `pause`, `unpause`, `set_threads`, `set_min_chunk_size`,
+/// `set_max_speculative`, `set_speed_limit`, `is_aborted`, `join`) and [`event_chain`](Self::event_chain) directly; each delegates to the inner
 /// value. There is intentionally **no** `Deref` impl — `DownloadResultInner`
 /// is private, so callers reach session state only through these methods.
 ///
@@ -118,6 +147,8 @@ where
 /// the last sender is dropped (the download finished or was aborted) the
 /// receiver disconnects, so `while result.event_chain().recv().await.is_ok() {}`
 /// awaits the session end.
+/// [`join`](Self::join) awaits the same point without consuming events and
+/// also tells a completed session from an aborted or failed one.
 pub struct DownloadResult<E, PullError, PushError>
 where
     E: Executor + Send + Sync,
@@ -169,12 +200,20 @@ where
         event_chain: MAsyncRx<mpmc::List<Event<PullError, PushError>>>,
         task_queue: Option<(E, TaskQueue<E::Handle>)>,
         abort_token: CancellationToken,
+        speed_limiter: SpeedLimiter,
+        pause_token: PauseToken,
+        schedule: Arc<Schedule>,
+        stats: Arc<SessionStats>,
     ) -> Self {
         Self {
             inner: Arc::new(DownloadResultInner {
                 event_chain,
                 task_queue,
                 abort_token,
+                speed_limiter,
+                pause_token,
+                schedule,
+                stats,
             }),
         }
     }
@@ -221,11 +260,124 @@ where
         self.inner.set_threads(threads, min_chunk_size);
     }
 
+    /// Change the smallest range a multi-threaded session splits off for an
+    /// idle or newly spawned worker.
+    ///
+    /// Applies from the next split on; ranges already handed out are not
+    /// re-cut. No-op for single-threaded sessions.
+    pub fn set_min_chunk_size(&self, min_chunk_size: u64) {
+        self.inner.schedule.set_min_chunk_size(min_chunk_size);
+    }
+
+    /// Change how many workers of a multi-threaded session may race on the
+    /// same range once nothing is left to split. Applies from the next steal
+    /// on. No-op for single-threaded sessions.
+    pub fn set_max_speculative(&self, max_speculative: usize) {
+        self.inner.schedule.set_max_speculative(max_speculative);
+    }
+
+    /// The minimum chunk size workers currently split with.
+    #[must_use]
+    pub fn min_chunk_size(&self) -> u64 {
+        self.inner.schedule.min_chunk_size()
+    }
+
+    /// The current speculation limit.
+    #[must_use]
+    pub fn max_speculative(&self) -> usize {
+        self.inner.schedule.max_speculative()
+    }
+
     /// Whether the session has been (or is being) cancelled.
     #[must_use]
     pub fn is_aborted(&self) -> bool {
         self.inner.is_aborted()
     }
+
+    /// Change the bandwidth limit of a running session, in bytes per second
+    /// (`0` = unlimited).
+    ///
+    /// Works for both single- and multi-threaded sessions and takes effect
+    /// immediately, including for workers currently waiting on the limiter. The
+    /// limiter is the one passed in the session's `DownloadOptions`, so if it is
+    /// shared with other sessions the new limit applies to all of them.
+    pub fn set_speed_limit(&self, bytes_per_sec: u64) {
+        self.inner.speed_limiter.set_limit(bytes_per_sec);
+    }
+
+    /// The bandwidth limiter of this session.
+    #[must_use]
+    pub fn speed_limiter(&self) -> &SpeedLimiter {
+        &self.inner.speed_limiter
+    }
+
+    /// Suspend the session without ending it.
+    ///
+    /// Workers stop pulling, dropping their connections where the puller can
+    /// resume a range, and the push driver stops writing before its next push.
+    /// The task queue, progress and sink are kept, so
+    /// [`unpause`](Self::unpause) continues from where the session stopped.
+    /// Pull timeouts do not elapse while paused. [`abort`](Self::abort) still
+    /// ends a paused session immediately.
+    ///
+    /// The switch is the `pause_token` passed in the session's
+    /// `DownloadOptions`, so if it is shared with other sessions they are
+    /// paused as well.
+    pub fn pause(&self) {
+        self.inner.pause_token.pause();
+    }
+
+    /// Resume a session suspended by [`pause`](Self::pause). No-op if it is not
+    /// paused.
+    pub fn unpause(&self) {
+        self.inner.pause_token.unpause();
+    }
+
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.inner.pause_token.is_paused()
+    }
+
+    /// Wait for the session to end and report how it ended.
+    ///
+    /// Resolves once the push driver has exited, whether it flushed the sink
+    /// ([`DownloadStatus::Completed`]), was cancelled
+    /// ([`DownloadStatus::Aborted`]) or was stopped by a terminal error or a
+    /// panic ([`DownloadStatus::Failed`]). Unlike draining
+    /// [`event_chain`](Self::event_chain) this consumes no events, so it can be
+    /// awaited alongside an event loop, and any number of clones may join.
+    pub async fn join(&self) -> DownloadOutcome {
+        self.inner.stats.join().await
+    }
+}
+
+impl<R, PushError> DownloadResult<TokioExecutor<R, PushError>, R::Error, PushError>
+where
+    R: Puller,
+    PushError: Send + Unpin + 'static,
+{
+    /// A snapshot of the workers currently running, for a "connections" view.
+    ///
+    /// A multi-threaded session lists the workers registered in its task
+    /// queue, with what is left of their ranges; workers that finished, or were
+    /// aborted as speculative twins or by [`set_threads`](Self::set_threads),
+    /// are gone. A single-threaded session lists its one worker until the
+    /// session ends. The counters are the ones [`join`](Self::join) reports.
+    #[must_use]
+    pub fn workers(&self) -> Vec<WorkerSnapshot> {
+        let stats = &self.inner.stats;
+        match &self.inner.task_queue {
+            Some((_, task_queue)) => task_queue
+                .running()
+                .into_iter()
+                .map(|(handle, task)| {
+                    stats.snapshot(handle.id(), Some(task.get()), task.is_speculative())
+                })
+                .collect(),
+            None if stats.is_finished() => Vec::new(),
+            None => vec![stats.snapshot(single::WORKER_ID, None, false)],
+        }
+    }
 }
 
 #[cfg(test)]
@@ -236,7 +388,9 @@ mod tests {
     use crate::mem::MemPusher;
     use crate::mock::{MockPuller, build_mock_data};
     use crate::multi::{DownloadOptions, download_multi};
-    use crate::{Event, ProgressEntry, PullResult, PullStream, Puller};
+    use crate::{
+        Event, PauseToken, ProgressEntry, PullResult, PullStream, Puller, SpeedLimiter, Verbosity,
+    };
     use bytes::Bytes;
     use futures::{StreamExt, stream};
     use std::collections::BTreeSet;
@@ -348,6 +502,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
         // `Debug` of `DownloadResultInner` is reached through `DownloadResult`'s
@@ -376,6 +536,11 @@ mod tests {
             crate::single::DownloadOptions {
                 retry_gap: Duration::from_secs(1),
                 push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
             },
         );
         // Lines 167-171: `DownloadResult` is `Clone`.
@@ -406,6 +571,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
         // Live session: flag starts false and must stay false after a resize.
@@ -444,6 +615,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 1,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
 
@@ -491,6 +668,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 1,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
 
@@ -538,6 +721,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
 
@@ -596,4 +785,202 @@ mod tests {
             "repeated resizing corrupted the downloaded bytes"
         );
     }
+
+    // Pausing a running pool mid-range must stop pulls and writes, and
+    // unpausing must pick every range up from its cursor: no byte may be lost or
+    // written twice.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pause_mid_flight_preserves_all_bytes() {
+        let mock_data = build_mock_data(64 * 1024);
+        let download_chunks = eight_chunks(mock_data.len() as u64);
+        let puller = ChunkedPuller {
+            data: Arc::from(mock_data.as_slice()),
+            piece: 512,
+            delay: Duration::from_millis(2),
+        };
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        let result = download_multi(
+            puller,
+            pusher,
+            DownloadOptions {
+                concurrent: 4,
+                retry_gap: Duration::from_millis(10),
+                push_queue_cap: 1024,
+                download_chunks: download_chunks.iter().cloned(),
+                pull_timeout: Duration::from_millis(50),
+                min_chunk_size: 1,
+                max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
+            },
+        );
+
+        while let Ok(e) = result.event_chain().recv().await {
+            if matches!(e, Event::PushProgress(_)) {
+                break;
+            }
+        }
+        result.pause();
+        // Let the pieces already in flight settle.
+        sleep(Duration::from_millis(50)).await;
+        while result.event_chain().try_recv().is_ok() {}
+        let written = receive.lock().len();
+        let mut progressed = false;
+        let _ = timeout(Duration::from_millis(300), async {
+            while let Ok(e) = result.event_chain().recv().await {
+                progressed |= matches!(
+                    e,
+                    Event::PullProgress(..) | Event::PushProgress(_) | Event::PullTimeout(_)
+                );
+            }
+        })
+        .await;
+        assert!(!progressed, "a paused session kept pulling or timed out");
+        assert_eq!(receive.lock().len(), written);
+        assert!(written < mock_data.len(), "pause landed after the download");
+
+        result.unpause();
+        timeout(Duration::from_secs(10), async {
+            while result.event_chain().recv().await.is_ok() {}
+        })
+        .await
+        .expect("unpausing must let the session finish");
+        assert!(result.join().await.is_completed());
+        assert_eq!(&**receive.lock(), mock_data);
+    }
+
+    // Retuning the split parameters of a running pool must reach the workers'
+    // next steals without disturbing the bytes already in flight.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retuning_schedule_mid_flight_preserves_all_bytes() {
+        let mock_data = build_mock_data(64 * 1024);
+        let puller = ChunkedPuller {
+            data: Arc::from(mock_data.as_slice()),
+            piece: 512,
+            delay: Duration::from_millis(2),
+        };
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        let result = download_multi(
+            puller,
+            pusher,
+            DownloadOptions {
+                concurrent: 2,
+                retry_gap: Duration::from_millis(10),
+                push_queue_cap: 1024,
+                download_chunks: std::iter::once(0..mock_data.len() as u64),
+                pull_timeout: Duration::from_secs(5),
+                min_chunk_size: 32 * 1024,
+                max_speculative: 1,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
+            },
+        );
+        assert_eq!(
+            (result.min_chunk_size(), result.max_speculative()),
+            (32 * 1024, 1)
+        );
+        result.set_min_chunk_size(1024);
+        result.set_max_speculative(3);
+        assert_eq!(
+            (result.min_chunk_size(), result.max_speculative()),
+            (1024, 3)
+        );
+        result.set_threads(8, 1024);
+
+        let mut pulling_ids = BTreeSet::new();
+        while let Ok(e) = result.event_chain().recv().await {
+            if let Event::Pulling(id) = e {
+                pulling_ids.insert(id);
+            }
+        }
+        assert!(
+            pulling_ids.len() > 2,
+            "the smaller chunk size let no extra worker split off work: {pulling_ids:?}"
+        );
+        assert_eq!(&**receive.lock(), mock_data);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn workers_snapshot_lists_running_workers() {
+        let mock_data = build_mock_data(8 * 1024);
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        let pause_token = PauseToken::new();
+        pause_token.pause();
+        let result = download_multi(
+            MockPuller::new(&mock_data),
+            pusher,
+            DownloadOptions {
+                download_chunks: eight_chunks(mock_data.len() as u64).into_iter(),
+                concurrent: 4,
+                retry_gap: Duration::from_secs(1),
+                pull_timeout: Duration::from_secs(5),
+                push_queue_cap: 1024,
+                min_chunk_size: 1,
+                max_speculative: 1,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token,
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
+            },
+        );
+        // Paused before pulling anything: every worker holds a whole chunk.
+        let workers = result.workers();
+        let ids: Vec<_> = workers.iter().map(|w| w.id).collect();
+        assert_eq!(ids, [0, 1, 2, 3]);
+        let ranges: Vec<_> = workers.iter().filter_map(|w| w.range.clone()).collect();
+        assert_eq!(ranges, eight_chunks(mock_data.len() as u64)[..4]);
+        assert!(
+            workers
+                .iter()
+                .all(|w| !w.speculative && w.counts.pulled == 0)
+        );
+
+        result.unpause();
+        let outcome = result.join().await;
+        assert!(outcome.is_completed());
+        assert!(result.workers().is_empty());
+        assert_eq!(&**receive.lock(), mock_data);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn workers_snapshot_of_a_single_session() {
+        use crate::single::{WORKER_ID, download_single};
+        let mock_data = build_mock_data(1024);
+        let pause_token = PauseToken::new();
+        pause_token.pause();
+        let result = download_single(
+            MockPuller::new(&mock_data),
+            MemPusher::with_capacity(mock_data.len()),
+            crate::single::DownloadOptions {
+                retry_gap: Duration::from_secs(1),
+                push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token,
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
+            },
+        );
+        let workers = result.workers();
+        assert_eq!(workers.len(), 1);
+        assert_eq!((workers[0].id, workers[0].range.clone()), (WORKER_ID, None));
+
+        result.unpause();
+        let outcome = result.join().await;
+        assert_eq!(outcome.workers[&WORKER_ID].pulled, mock_data.len() as u64);
+        assert!(result.workers().is_empty());
+    }
 }
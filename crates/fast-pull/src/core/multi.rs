@@ -1,6 +1,13 @@
 //! Multi-threaded concurrent download with work-stealing.
 
-use crate::{DownloadResult, Event, ProgressEntry, Puller, PullerError, Pusher, WorkerId};
+use super::{
+    low_speed::spawn_low_speed_abort, pause::block_while_paused, retry::RetryState,
+    schedule::Schedule, throttle::Throttle,
+};
+use crate::{
+    DownloadResult, Event, PauseToken, ProgressEntry, Puller, PullerError, Pusher, RetryKind,
+    RetryPolicy, SessionStats, SpeedFloor, SpeedLimiter, Verbosity, WorkerId,
+};
 use bytes::Bytes;
 use core::{
     sync::atomic::{AtomicUsize, Ordering},
@@ -10,11 +17,13 @@ use crossfire::{MAsyncTx, MTx, WeakTx, mpmc, mpsc};
 use fast_steal::{Executor, Handle, Task, TaskQueue};
 use futures::TryStreamExt;
 use std::sync::{Arc, OnceLock};
+use tokio::time::Instant;
 use tokio_util::sync::CancellationToken;
 
 /// Options for a multi-threaded concurrent download.
 ///
-/// Controls chunk splitting, speculation, pull timeouts, and write queue capacity.
+/// Controls chunk splitting, speculation, pull timeouts, write queue capacity,
+/// bandwidth and pausing.
 #[derive(Debug, Clone)]
 pub struct DownloadOptions<I: Iterator<Item = ProgressEntry>> {
     pub download_chunks: I,
@@ -24,51 +33,99 @@ pub struct DownloadOptions<I: Iterator<Item = ProgressEntry>> {
     pub push_queue_cap: usize,
     pub min_chunk_size: u64,
     pub max_speculative: usize,
+    /// Bandwidth limiter every worker consults before forwarding a pulled chunk.
+    /// Pass a clone of one limiter to several sessions to cap them together.
+    pub speed_limiter: SpeedLimiter,
+    /// Consulted on every pull error, pull timeout and push error. `None` retries
+    /// forever every `retry_gap`, as [`FixedRetry`](crate::FixedRetry) does.
+    pub retry_policy: Option<Arc<dyn RetryPolicy>>,
+    /// Pause switch the workers and the push driver wait on. A token that is
+    /// already paused starts the session paused.
+    pub pause_token: PauseToken,
+    /// Which events the session emits; see [`Verbosity`].
+    pub verbosity: Verbosity,
+    /// A worker pulling slower than this over a window drops its connection
+    /// and reconnects, emitting [`Event::PullSlow`]; meanwhile idle workers
+    /// steal from its range as usual. Time spent waiting on the
+    /// `speed_limiter`, the write queue or a pause does not count.
+    pub slow_worker: Option<SpeedFloor>,
+    /// Fail the session once all workers together pull slower than this over
+    /// a window.
+    pub low_speed_abort: Option<SpeedFloor>,
 }
 
+#[allow(clippy::too_many_lines)]
 pub fn download_multi<R: Puller, W: Pusher, I: Iterator<Item = ProgressEntry>>(
     puller: R,
     mut pusher: W,
     options: DownloadOptions<I>,
 ) -> DownloadResult<TokioExecutor<R, W::Error>, R::Error, W::Error> {
     let token = CancellationToken::new();
+    let stats = Arc::new(SessionStats::default());
+    let verbosity = options.verbosity;
     let (tx, event_chain) = mpmc::unbounded_async();
     pusher.set_listener({
         let tx = tx.clone();
+        let stats = stats.clone();
         Box::new(move |p| {
-            let _ = tx.send(Event::PushProgress(p));
+            stats.add_pushed(p.end - p.start);
+            if verbosity.includes(Verbosity::Progress) {
+                let _ = tx.send(Event::PushProgress(p));
+            }
         })
     });
     let (tx_push, rx_push) =
         mpsc::bounded_async_blocking::<(WorkerId, ProgressEntry, Bytes)>(options.push_queue_cap);
 
+    let retry = RetryState::new(options.retry_policy, options.retry_gap);
     let push_thread = Arc::new(OnceLock::new());
     let push_handle = tokio::task::spawn_blocking({
         let push_thread = push_thread.clone();
         let token = token.clone();
         let tx = tx.clone();
+        let retry = retry.clone();
+        let stats = stats.clone();
+        let pause_token = options.pause_token.clone();
         move || {
             let _ = push_thread.set(std::thread::current());
+            let mut attempt = 0;
             while let Ok((id, mut spin, mut data)) = rx_push.recv() {
                 loop {
+                    block_while_paused(&pause_token, &token);
                     if token.is_cancelled() {
                         return;
                     }
-                    let _ = tx.send(Event::Pushing(id, spin.clone()));
+                    if verbosity.includes(Verbosity::Trace) {
+                        let _ = tx.send(Event::Pushing(id, spin.clone()));
+                    }
                     let len_before_push = data.len();
                     match pusher.push(&spin, data) {
-                        Ok(()) => break,
+                        Ok(()) => {
+                            retry.on_progress(&mut attempt);
+                            break;
+                        }
                         Err((err, bytes)) => {
-                            let _ = tx.send(Event::PushError(id, spin.clone(), err));
+                            stats.worker(id).add_push_error();
+                            let Some(delay) = retry.on_error(RetryKind::Push, &mut attempt, None)
+                            else {
+                                stats.fail(&err);
+                                let _ = tx.send(Event::PushFailed(id, spin, err));
+                                token.cancel();
+                                return;
+                            };
+                            if verbosity.includes(Verbosity::Errors) {
+                                let _ = tx.send(Event::PushError(id, spin.clone(), err));
+                            }
                             let written = len_before_push.saturating_sub(bytes.len());
                             data = bytes;
                             spin.start += written as u64;
+                            std::thread::park_timeout(delay);
                         }
                     }
-                    std::thread::park_timeout(options.retry_gap);
                 }
             }
             loop {
+                block_while_paused(&pause_token, &token);
                 if token.is_cancelled() {
                     return;
                 }
@@ -76,7 +133,9 @@ pub fn download_multi<R: Puller, W: Pusher, I: Iterator<Item = ProgressEntry>>(
                 match pusher.flush() {
                     Ok(()) => break,
                     Err(err) => {
-                        let _ = tx.send(Event::FlushError(err));
+                        if verbosity.includes(Verbosity::Errors) {
+                            let _ = tx.send(Event::FlushError(err));
+                        }
                     }
                 }
                 std::thread::park_timeout(options.retry_gap);
@@ -85,33 +144,67 @@ pub fn download_multi<R: Puller, W: Pusher, I: Iterator<Item = ProgressEntry>>(
     });
     tokio::spawn({
         let token = token.clone();
+        let stats = stats.clone();
         async move {
-            tokio::select! {
-                _ = push_handle => {},
+            let mut push_handle = push_handle;
+            let res = tokio::select! {
+                res = &mut push_handle => res,
                 () = token.cancelled() => {
                     if let Some(t) = push_thread.get() {
                         t.unpark();
                     }
+                    push_handle.await
                 }
-            }
+            };
+            stats.finish(
+                res.err().and_then(|e| e.try_into_panic().ok()),
+                token.is_cancelled(),
+            );
         }
     });
 
+    let schedule = Arc::new(Schedule::new(
+        options.min_chunk_size,
+        options.max_speculative,
+    ));
+    let throttle = Arc::<Throttle>::default();
     let executor: TokioExecutor<R, W::Error> = TokioExecutor {
         token: token.clone(),
         tx: tx.downgrade(),
         tx_push: tx_push.downgrade(),
         puller,
         id: AtomicUsize::new(0),
-        retry_gap: options.retry_gap,
+        retry,
         pull_timeout: options.pull_timeout,
-        min_chunk_size: options.min_chunk_size,
-        max_speculative: options.max_speculative,
+        slow_worker: options.slow_worker,
+        throttle: throttle.clone(),
+        schedule: schedule.clone(),
+        speed_limiter: options.speed_limiter.clone(),
+        pause_token: options.pause_token.clone(),
+        verbosity,
+        stats: stats.clone(),
     };
     let task_queue = TaskQueue::new(options.download_chunks);
     let _ = task_queue.set_threads(options.concurrent, options.min_chunk_size, Some(&executor));
+    if let Some(floor) = options.low_speed_abort {
+        spawn_low_speed_abort(
+            floor,
+            stats.clone(),
+            token.clone(),
+            options.pause_token.clone(),
+            throttle,
+        );
+    }
 
-    DownloadResult::new(event_chain, Some((executor, task_queue)), token)
+    DownloadResult::new(
+        event_chain,
+        Some((executor, task_queue)),
+        token,
+        options.speed_limiter,
+        options.pause_token,
+        schedule,
+        stats,
+    )
 }
 
 /// A [`Handle`] implementation whose cancellation is a worker-local
@@ -121,6 +214,13 @@ pub struct TokioHandle {
     id: usize,
     token: CancellationToken,
 }
+impl TokioHandle {
+    /// The id of the worker, as carried by its events.
+    #[must_use]
+    pub const fn id(&self) -> WorkerId {
+        self.id
+    }
+}
 impl Handle for TokioHandle {
     type Id = usize;
     fn abort(&mut self) {
@@ -153,11 +253,17 @@ where
     /// only has to observe the cancel once to know the session is over.
     token: CancellationToken,
     puller: R,
-    retry_gap: Duration,
+    retry: RetryState,
     pull_timeout: Duration,
+    slow_worker: Option<SpeedFloor>,
+    /// Holds the workers back while the server rate limits.
+    throttle: Arc<Throttle>,
     id: AtomicUsize,
-    min_chunk_size: u64,
-    max_speculative: usize,
+    schedule: Arc<Schedule>,
+    speed_limiter: SpeedLimiter,
+    pause_token: PauseToken,
+    verbosity: Verbosity,
+    stats: Arc<SessionStats>,
 }
 impl<R, WE> Executor for TokioExecutor<R, WE>
 where
@@ -177,47 +283,140 @@ where
         };
 
         let mut puller = self.puller.clone();
-        let min_chunk_size = self.min_chunk_size;
         let pull_timeout = self.pull_timeout;
-        let cfg_retry_gap = self.retry_gap;
-        let max_speculative = self.max_speculative;
+        let slow_worker = self.slow_worker;
+        let throttle = self.throttle.clone();
+        let retry = self.retry.clone();
+        let schedule = self.schedule.clone();
+        let speed_limiter = self.speed_limiter.clone();
+        let pause_token = self.pause_token.clone();
+        let verbosity = self.verbosity;
+        let session_token = self.token.clone();
         let worker_token = token.clone();
-        tokio::spawn(async move {
+        let stats = self.stats.clone();
+        let counter = stats.worker(id);
+        stats.worker_started();
+        let watch = (
+            tx.clone(),
+            tx_push.clone(),
+            stats.clone(),
+            self.token.clone(),
+        );
+        let worker = tokio::spawn(async move {
+            let mut attempt = 0;
             'task: loop {
                 if worker_token.is_cancelled() {
                     break 'task;
                 }
                 let mut start = task.start();
                 if start >= task.end() {
-                    if task_queue.steal(&id, &mut task, min_chunk_size, max_speculative) {
+                    if task_queue.steal(
+                        &id,
+                        &mut task,
+                        schedule.min_chunk_size(),
+                        schedule.max_speculative(),
+                    ) {
                         continue 'task;
                     }
                     break 'task;
                 }
+                tokio::select! {
+                    () = worker_token.cancelled() => break 'task,
+                    () = pause_token.unpaused() => {}
+                };
                 let _ = tx.send(Event::Pulling(id));
                 let download_range = start..task.end();
-                let mut stream = loop {
+                // The slot is held for as long as the connection is open.
+                let (mut stream, _slot) = loop {
+                    let slot = tokio::select! {
+                        () = worker_token.cancelled() => break 'task,
+                        () = pause_token.paused() => continue 'task,
+                        slot = throttle.admit() => slot,
+                    };
                     let t = tokio::select! {
                         () = worker_token.cancelled() => break 'task,
+                        // Pausing drops the connection; the range is pulled
+                        // again from its cursor once unpaused.
+                        () = pause_token.paused() => continue 'task,
                         t = puller.pull(Some(&download_range)) => t
                     };
                     match t {
-                        Ok(t) => break t,
-                        Err((e, retry_gap)) => {
-                            let _ = tx.send(Event::PullError(id, e));
+                        Ok(t) => break (t, slot),
+                        Err((e, retry_after)) => {
+                            drop(slot);
+                            let delay = if e.is_permanent() {
+                                None
+                            } else {
+                                retry.on_error(RetryKind::Pull, &mut attempt, retry_after)
+                            };
+                            let Some(delay) = delay else {
+                                stats.fail(&e);
+                                let _ = tx.send(Event::PullFailed(id, e));
+                                session_token.cancel();
+                                break 'task;
+                            };
+                            counter.add_pull_error();
+                            if e.is_rate_limited()
+                                && throttle.throttle(delay)
+                                && verbosity.includes(Verbosity::Errors)
+                            {
+                                let _ = tx.send(Event::Throttled(delay));
+                            }
+                            if verbosity.includes(Verbosity::Errors) {
+                                let _ = tx.send(Event::PullError(id, e));
+                            }
                             tokio::select! {
                                 () = worker_token.cancelled() => break 'task,
-                                () = tokio::time::sleep(retry_gap.unwrap_or(cfg_retry_gap)) => {}
+                                () = tokio::time::sleep(delay) => {}
                             };
                         }
                     }
                 };
+                // The end of the current speed window and the bytes pulled in it.
+                let mut window = slow_worker.map(|floor| (Instant::now() + floor.window, 0));
                 loop {
                     let t = tokio::select! {
                         () = worker_token.cancelled() => break 'task,
+                        () = pause_token.paused() => continue 'task,
+                        () = async {
+                            match window {
+                                Some((end, _)) => tokio::time::sleep_until(end).await,
+                                None => core::future::pending().await,
+                            }
+                        } => {
+                            let (Some(floor), Some((_, bytes))) = (slow_worker, window) else {
+                                continue;
+                            };
+                            if !floor.is_below(bytes) {
+                                window = Some((Instant::now() + floor.window, 0));
+                                continue;
+                            }
+                            drop(stream);
+                            counter.add_slow_reconnect();
+                            if verbosity.includes(Verbosity::Errors) {
+                                let _ = tx.send(Event::PullSlow(id));
+                            }
+                            puller = puller.clone();
+                            continue 'task;
+                        },
                         () = tokio::time::sleep(pull_timeout) => {
-                            let _ = tx.send(Event::PullTimeout(id));
                             drop(stream);
+                            let Some(delay) =
+                                retry.on_error(RetryKind::PullTimeout, &mut attempt, None)
+                            else {
+                                stats.fail(&"pull timed out");
+                                let _ = tx.send(Event::PullTimeoutFailed(id));
+                                session_token.cancel();
+                                break 'task;
+                            };
+                            counter.add_pull_timeout();
+                            if verbosity.includes(Verbosity::Errors) {
+                                let _ = tx.send(Event::PullTimeout(id));
+                            }
+                            tokio::select! {
+                                () = worker_token.cancelled() => break 'task,
+                                () = tokio::time::sleep(delay) => {}
+                            };
                             puller = puller.clone();
                             continue 'task;
                         },
@@ -228,7 +427,15 @@ where
                             if chunk.is_empty() {
                                 continue;
                             }
+                            retry.on_progress(&mut attempt);
                             let len = chunk.len() as u64;
+                            let waiting = Instant::now();
+                            // Throttle before claiming the span, so a worker aborted
+                            // while waiting leaves its range intact for a stealer.
+                            tokio::select! {
+                                () = worker_token.cancelled() => break 'task,
+                                () = speed_limiter.acquire(len) => {}
+                            };
                             let Ok(span) = task.safe_add_start(start, len) else {
                                 start += len;
                                 continue;
@@ -241,19 +448,48 @@ where
                                 (span.start - start) as usize..(span.end - start) as usize;
                             chunk = chunk.slice(slice_span);
                             start = span.end;
-                            let _ = tx.send(Event::PullProgress(id, span.clone()));
+                            counter.add_pulled(span.end - span.start);
+                            stats.add_pulled(span.end - span.start);
+                            if verbosity.includes(Verbosity::Trace) {
+                                let _ = tx.send(Event::PullProgress(id, span.clone()));
+                            }
+                            let claimed = span.end - span.start;
                             let _ = tx_push.send((id, span, chunk)).await;
+                            if let Some((end, bytes)) = &mut window {
+                                *end += waiting.elapsed();
+                                *bytes += claimed;
+                            }
                             if start >= task.end() {
                                 continue 'task;
                             }
                         }
                         Ok(None) => continue 'task,
-                        Err((e, retry_gap)) => {
+                        Err((e, retry_after)) => {
+                            let delay = if e.is_permanent() {
+                                None
+                            } else {
+                                retry.on_error(RetryKind::Pull, &mut attempt, retry_after)
+                            };
+                            let Some(delay) = delay else {
+                                stats.fail(&e);
+                                let _ = tx.send(Event::PullFailed(id, e));
+                                session_token.cancel();
+                                break 'task;
+                            };
                             let is_irrecoverable = e.is_irrecoverable();
-                            let _ = tx.send(Event::PullError(id, e));
+                            counter.add_pull_error();
+                            if e.is_rate_limited()
+                                && throttle.throttle(delay)
+                                && verbosity.includes(Verbosity::Errors)
+                            {
+                                let _ = tx.send(Event::Throttled(delay));
+                            }
+                            if verbosity.includes(Verbosity::Errors) {
+                                let _ = tx.send(Event::PullError(id, e));
+                            }
                             tokio::select! {
                                 () = worker_token.cancelled() => break 'task,
-                                () = tokio::time::sleep(retry_gap.unwrap_or(cfg_retry_gap)) => {}
+                                () = tokio::time::sleep(delay) => {}
                             };
                             if is_irrecoverable {
                                 continue 'task;
@@ -262,8 +498,24 @@ where
                     }
                 }
             }
+            stats.worker_finished();
             let _ = tx.send(Event::Finished(id));
         });
+        // A panicked worker leaves its range unpulled, so it fails the session.
+        // `tx_push` is held until then, or the push driver could seal the
+        // session as completed first.
+        tokio::spawn(async move {
+            let (tx, tx_push, stats, session_token) = watch;
+            if let Err(e) = worker.await
+                && let Ok(panic) = e.try_into_panic()
+            {
+                stats.fail_panicked("worker", &*panic);
+                stats.worker_finished();
+                let _ = tx.send(Event::Finished(id));
+                session_token.cancel();
+            }
+            drop(tx_push);
+        });
         TokioHandle { id, token }
     }
 }
@@ -308,6 +560,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
 
@@ -333,6 +591,45 @@ mod tests {
         assert_eq!(&**receive.lock(), mock_data);
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_verbosity_drops_events_above_level() {
+        let mock_data = build_mock_data(3 * 1024);
+        let puller = MockPuller::new(&mock_data);
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        let result = download_multi(
+            puller,
+            pusher,
+            DownloadOptions {
+                concurrent: 8,
+                retry_gap: Duration::from_secs(1),
+                push_queue_cap: 1024,
+                download_chunks: std::iter::once(0..mock_data.len() as u64),
+                pull_timeout: Duration::from_secs(5),
+                min_chunk_size: 1,
+                max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::Lifecycle,
+                slow_worker: None,
+                low_speed_abort: None,
+            },
+        );
+
+        let mut finished = 0;
+        while let Ok(e) = result.event_chain().recv().await {
+            assert_eq!(e.verbosity(), Verbosity::Lifecycle, "{e:?}");
+            if matches!(e, Event::Finished(_)) {
+                finished += 1;
+            }
+        }
+        assert!(finished > 0);
+        let outcome = result.join().await;
+        assert_eq!(outcome.bytes_pushed, mock_data.len() as u64);
+        assert_eq!(&**receive.lock(), mock_data);
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_concurrent_download_abort_discards() {
         let mock_data = build_mock_data(3 * 1024);
@@ -352,6 +649,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
 
@@ -451,6 +754,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
 
@@ -716,6 +1025,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
         drain(&result).await;
@@ -743,6 +1058,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
         drain(&result).await;
@@ -772,6 +1093,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
         drain(&result).await;
@@ -800,6 +1127,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
         drain(&result).await;
@@ -830,6 +1163,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
         drain(&result).await;
@@ -861,6 +1200,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
         drain(&result).await;
@@ -994,6 +1339,12 @@ mod tests {
                 pull_timeout: Duration::from_millis(50),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
         drain(&result).await;
@@ -1020,6 +1371,12 @@ mod tests {
                 pull_timeout: Duration::from_secs(5),
                 min_chunk_size: 1,
                 max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
             },
         );
         timeout(Duration::from_secs(10), drain(&result))
@@ -1027,4 +1384,754 @@ mod tests {
             .expect("event loop hung on empty chunks");
         assert_eq!(receive.lock().len(), 0);
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_download_respects_speed_limit() {
+        // 3 KiB at 10 KiB/s shared by all 8 workers must take ~300ms in total,
+        // not 300ms / 8.
+        let mock_data = build_mock_data(3 * 1024);
+        let puller = MockPuller::new(&mock_data);
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        #[allow(clippy::single_range_in_vec_init)]
+        let download_chunks = [0..mock_data.len() as u64];
+        let start = std::time::Instant::now();
+        let result = download_multi(
+            puller,
+            pusher,
+            DownloadOptions {
+                concurrent: 8,
+                retry_gap: Duration::from_secs(1),
+                push_queue_cap: 1024,
+                download_chunks: download_chunks.iter().cloned(),
+                pull_timeout: Duration::from_secs(5),
+                min_chunk_size: 1,
+                max_speculative: 3,
+                speed_limiter: SpeedLimiter::new(10 * 1024),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
+            },
+        );
+        drain(&result).await;
+        assert!(start.elapsed() >= Duration::from_millis(250));
+        assert_eq!(&**receive.lock(), mock_data);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_download_set_speed_limit_lifts_throttle() {
+        // At 1 B/s the download would take almost an hour; lifting the limit
+        // through the handle must let it finish promptly.
+        let mock_data = build_mock_data(3 * 1024);
+        let puller = MockPuller::new(&mock_data);
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        #[allow(clippy::single_range_in_vec_init)]
+        let download_chunks = [0..mock_data.len() as u64];
+        let result = download_multi(
+            puller,
+            pusher,
+            DownloadOptions {
+                concurrent: 4,
+                retry_gap: Duration::from_secs(1),
+                push_queue_cap: 1024,
+                download_chunks: download_chunks.iter().cloned(),
+                pull_timeout: Duration::from_secs(5),
+                min_chunk_size: 1,
+                max_speculative: 3,
+                speed_limiter: SpeedLimiter::new(1),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
+            },
+        );
+        sleep(Duration::from_millis(100)).await;
+        result.set_speed_limit(0);
+        assert_eq!(result.speed_limiter().limit(), 0);
+        timeout(Duration::from_secs(10), drain(&result))
+            .await
+            .expect("lifting the speed limit must unblock the workers");
+        assert_eq!(&**receive.lock(), mock_data);
+    }
+
+    #[derive(Debug)]
+    struct PermanentErr;
+    impl std::fmt::Display for PermanentErr {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("permanent")
+        }
+    }
+    impl std::error::Error for PermanentErr {}
+    impl crate::PullerError for PermanentErr {
+        fn is_permanent(&self) -> bool {
+            true
+        }
+    }
+
+    /// Fails every pull with a permanent error, either from `pull` itself or
+    /// from the stream after one good chunk.
+    #[derive(Debug, Clone)]
+    struct PermanentErrPuller {
+        data: Arc<[u8]>,
+        in_stream: bool,
+    }
+    impl crate::Puller for PermanentErrPuller {
+        type Error = PermanentErr;
+        fn pull(
+            &mut self,
+            range: Option<&crate::ProgressEntry>,
+        ) -> impl Future<
+            Output = crate::PullResult<impl crate::PullStream<Self::Error>, Self::Error>,
+        > + Send {
+            if !self.in_stream {
+                return std::future::ready(Err((PermanentErr, Some(Duration::ZERO))));
+            }
+            let start = range.map_or(0, |r| r.start as usize);
+            let items: Vec<Result<Bytes, (PermanentErr, Option<Duration>)>> = vec![
+                Ok(Bytes::copy_from_slice(&self.data[start..start + 2])),
+                Err((PermanentErr, Some(Duration::ZERO))),
+            ];
+            std::future::ready(Ok(stream::iter(items)))
+        }
+    }
+
+    async fn assert_permanent_error_ends_session(in_stream: bool) {
+        let mock_data = build_mock_data(3 * 1024);
+        let puller = PermanentErrPuller {
+            data: Arc::from(mock_data.as_slice()),
+            in_stream,
+        };
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        #[allow(clippy::single_range_in_vec_init)]
+        let download_chunks = [0..mock_data.len() as u64];
+        let result = download_multi(
+            puller,
+            pusher,
+            DownloadOptions {
+                concurrent: 4,
+                retry_gap: Duration::from_secs(1),
+                push_queue_cap: 1024,
+                download_chunks: download_chunks.iter().cloned(),
+                pull_timeout: Duration::from_secs(5),
+                min_chunk_size: 1,
+                max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
+            },
+        );
+        let mut failed = 0;
+        let mut retried = 0;
+        timeout(Duration::from_secs(10), async {
+            while let Ok(e) = result.event_chain().recv().await {
+                match e {
+                    Event::PullFailed(_, PermanentErr) => failed += 1,
+                    Event::PullError(_, _) => retried += 1,
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .expect("a permanent error must end the session instead of retrying");
+        assert!(failed >= 1, "expected a terminal PullFailed event");
+        assert_eq!(
+            retried, 0,
+            "permanent errors must not be reported as retried"
+        );
+        assert!(result.is_aborted());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_multi_permanent_pull_error_ends_session() {
+        assert_permanent_error_ends_session(false).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_multi_permanent_stream_error_ends_session() {
+        assert_permanent_error_ends_session(true).await;
+    }
+
+    /// Every pull fails (`stall == false`) or never yields a byte (`stall == true`).
+    #[derive(Debug, Clone)]
+    struct DeadPuller {
+        stall: bool,
+    }
+    impl crate::Puller for DeadPuller {
+        type Error = RecoverableErr;
+        fn pull(
+            &mut self,
+            _range: Option<&crate::ProgressEntry>,
+        ) -> impl Future<
+            Output = crate::PullResult<impl crate::PullStream<Self::Error>, Self::Error>,
+        > + Send {
+            let stall = self.stall;
+            async move {
+                if stall {
+                    Ok(stream::pending())
+                } else {
+                    Err((RecoverableErr, None))
+                }
+            }
+        }
+    }
+
+    /// Sink whose every `push` fails.
+    struct DeadSink;
+    impl crate::Pusher for DeadSink {
+        type Error = std::io::Error;
+        fn push(
+            &mut self,
+            _range: &crate::ProgressEntry,
+            bytes: Bytes,
+        ) -> Result<(), (Self::Error, Bytes)> {
+            Err((std::io::Error::other("push"), bytes))
+        }
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    fn capped_options(
+        len: u64,
+        pull_timeout: Duration,
+        max_attempts: usize,
+    ) -> DownloadOptions<core::iter::Once<ProgressEntry>> {
+        DownloadOptions {
+            concurrent: 1,
+            retry_gap: Duration::from_mins(1),
+            push_queue_cap: 1024,
+            download_chunks: core::iter::once(0..len),
+            pull_timeout,
+            min_chunk_size: 1,
+            max_speculative: 3,
+            speed_limiter: SpeedLimiter::default(),
+            retry_policy: Some(Arc::new(crate::CappedRetry::new(
+                crate::FixedRetry::new(Duration::ZERO),
+                max_attempts,
+            ))),
+            pause_token: PauseToken::default(),
+            verbosity: Verbosity::default(),
+            slow_worker: None,
+            low_speed_abort: None,
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_multi_retry_policy_gives_up_on_pull_errors() {
+        let result = download_multi(
+            DeadPuller { stall: false },
+            MemPusher::with_capacity(1024),
+            capped_options(1024, Duration::from_secs(5), 3),
+        );
+        let (mut errors, mut failed) = (0, 0);
+        timeout(Duration::from_secs(10), async {
+            while let Ok(e) = result.event_chain().recv().await {
+                match e {
+                    Event::PullError(_, RecoverableErr) => errors += 1,
+                    Event::PullFailed(_, RecoverableErr) => failed += 1,
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .expect("the retry policy must end the session");
+        assert_eq!((errors, failed), (3, 1));
+        assert!(result.is_aborted());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_multi_retry_policy_gives_up_on_pull_timeouts() {
+        let result = download_multi(
+            DeadPuller { stall: true },
+            MemPusher::with_capacity(1024),
+            capped_options(1024, Duration::from_millis(20), 2),
+        );
+        let (mut timeouts, mut failed) = (0, 0);
+        timeout(Duration::from_secs(10), async {
+            while let Ok(e) = result.event_chain().recv().await {
+                match e {
+                    Event::PullTimeout(_) => timeouts += 1,
+                    Event::PullTimeoutFailed(_) => failed += 1,
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .expect("the retry policy must end the session");
+        assert_eq!((timeouts, failed), (2, 1));
+        assert!(result.is_aborted());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_multi_retry_policy_gives_up_on_push_errors() {
+        let mock_data = build_mock_data(1024);
+        let result = download_multi(
+            MockPuller::new(&mock_data),
+            DeadSink,
+            capped_options(mock_data.len() as u64, Duration::from_secs(5), 2),
+        );
+        let (mut errors, mut failed) = (0, 0);
+        timeout(Duration::from_secs(10), async {
+            while let Ok(e) = result.event_chain().recv().await {
+                match e {
+                    Event::PushError(..) => errors += 1,
+                    Event::PushFailed(..) => failed += 1,
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .expect("the retry policy must end the session");
+        assert_eq!((errors, failed), (2, 1));
+        assert!(result.is_aborted());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_join_reports_completed_with_counts() {
+        let mock_data = build_mock_data(3 * 1024);
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        #[allow(clippy::single_range_in_vec_init)]
+        let download_chunks = [0..mock_data.len() as u64];
+        let result = download_multi(
+            MockPuller::new(&mock_data),
+            pusher,
+            DownloadOptions {
+                concurrent: 8,
+                retry_gap: Duration::from_secs(1),
+                push_queue_cap: 1024,
+                download_chunks: download_chunks.iter().cloned(),
+                pull_timeout: Duration::from_secs(5),
+                min_chunk_size: 1,
+                max_speculative: 3,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                slow_worker: None,
+                low_speed_abort: None,
+            },
+        );
+        // `join` must not depend on anyone draining the events.
+        let outcome = timeout(Duration::from_secs(10), result.join())
+            .await
+            .expect("join must resolve once the session ends");
+        assert_eq!(outcome.status, crate::DownloadStatus::Completed);
+        assert_eq!(outcome.bytes_pulled, mock_data.len() as u64);
+        assert_eq!(outcome.bytes_pushed, mock_data.len() as u64);
+        assert_eq!(
+            outcome.workers.values().map(|w| w.pulled).sum::<u64>(),
+            mock_data.len() as u64
+        );
+        assert_eq!(&**receive.lock(), mock_data);
+        // Joining again returns the same status.
+        assert!(result.join().await.is_completed());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_join_reports_aborted() {
+        let mock_data = build_mock_data(1024);
+        let result = download_multi(
+            DeadPuller { stall: true },
+            MemPusher::with_capacity(mock_data.len()),
+            capped_options(mock_data.len() as u64, Duration::from_mins(1), 0),
+        );
+        sleep(Duration::from_millis(50)).await;
+        result.abort();
+        let outcome = timeout(Duration::from_secs(10), result.join())
+            .await
+            .expect("join must resolve after abort");
+        assert_eq!(outcome.status, crate::DownloadStatus::Aborted);
+        assert_eq!(outcome.bytes_pushed, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_join_reports_failure_with_last_error() {
+        let result = download_multi(
+            DeadPuller { stall: false },
+            MemPusher::with_capacity(1024),
+            capped_options(1024, Duration::from_secs(5), 2),
+        );
+        let outcome = timeout(Duration::from_secs(10), result.join())
+            .await
+            .expect("join must resolve after a terminal error");
+        assert_eq!(
+            outcome.status,
+            crate::DownloadStatus::Failed("recoverable".into())
+        );
+        assert_eq!(outcome.workers[&0].pull_errors, 2);
+    }
+
+    /// Sink whose first `push` panics.
+    struct PanicSink;
+    impl crate::Pusher for PanicSink {
+        type Error = std::io::Error;
+        fn push(
+            &mut self,
+            _range: &crate::ProgressEntry,
+            _bytes: Bytes,
+        ) -> Result<(), (Self::Error, Bytes)> {
+            panic!("sink exploded")
+        }
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_join_reports_push_driver_panic() {
+        let mock_data = build_mock_data(1024);
+        let result = download_multi(
+            MockPuller::new(&mock_data),
+            PanicSink,
+            capped_options(mock_data.len() as u64, Duration::from_secs(5), 0),
+        );
+        let outcome = timeout(Duration::from_secs(10), result.join())
+            .await
+            .expect("join must resolve after the push driver panicked");
+        assert_eq!(
+            outcome.status,
+            crate::DownloadStatus::Failed("push driver panicked: sink exploded".into())
+        );
+    }
+
+    /// Yields the first byte of its range, then panics.
+    #[derive(Debug, Clone)]
+    struct PanicPuller;
+    impl crate::Puller for PanicPuller {
+        type Error = RecoverableErr;
+        fn pull(
+            &mut self,
+            _range: Option<&crate::ProgressEntry>,
+        ) -> impl Future<
+            Output = crate::PullResult<impl crate::PullStream<Self::Error>, Self::Error>,
+        > + Send {
+            let first = stream::iter([Ok(Bytes::from_static(b"a"))]);
+            std::future::ready(Ok(
+                first.chain(stream::repeat_with(|| panic!("puller exploded")))
+            ))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_join_reports_worker_panic() {
+        let result = download_multi(
+            PanicPuller,
+            MemPusher::with_capacity(1024),
+            capped_options(1024, Duration::from_secs(5), 0),
+        );
+        let outcome = timeout(Duration::from_secs(10), result.join())
+            .await
+            .expect("join must resolve after a worker panicked");
+        assert_eq!(
+            outcome.status,
+            crate::DownloadStatus::Failed("worker panicked: puller exploded".into())
+        );
+        assert_eq!(outcome.bytes_pulled, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_multi_starts_paused_until_unpaused() {
+        let mock_data = build_mock_data(3 * 1024);
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        let pause_token = PauseToken::new();
+        pause_token.pause();
+        let mut options = capped_options(mock_data.len() as u64, Duration::from_millis(20), 0);
+        options.concurrent = 4;
+        options.pause_token = pause_token;
+        let result = download_multi(MockPuller::new(&mock_data), pusher, options);
+        assert!(result.is_paused());
+        // Far beyond `pull_timeout`: a paused worker must neither pull nor time out.
+        let idle = timeout(Duration::from_millis(200), result.event_chain().recv()).await;
+        assert!(idle.is_err(), "a paused session emitted {idle:?}");
+        assert!(receive.lock().is_empty());
+
+        result.unpause();
+        let mut timeouts = 0;
+        timeout(Duration::from_secs(10), async {
+            while let Ok(e) = result.event_chain().recv().await {
+                if matches!(e, Event::PullTimeout(_)) {
+                    timeouts += 1;
+                }
+            }
+        })
+        .await
+        .expect("unpausing must let the session finish");
+        assert_eq!(timeouts, 0);
+        assert_eq!(&**receive.lock(), mock_data);
+    }
+
+    /// A [`Puller`] whose first `trickles` pulls yield two bytes every 20ms,
+    /// about 100 B/s; later pulls yield the whole range at once.
+    #[derive(Debug, Clone)]
+    struct TricklePuller {
+        data: Arc<[u8]>,
+        trickles: Arc<AtomicUsize>,
+    }
+    impl Puller for TricklePuller {
+        type Error = std::convert::Infallible;
+        fn pull(
+            &mut self,
+            range: Option<&ProgressEntry>,
+        ) -> impl Future<
+            Output = crate::PullResult<impl crate::PullStream<Self::Error>, Self::Error>,
+        > + Send {
+            let trickle = self
+                .trickles
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            let data: Vec<Bytes> = match range {
+                Some(r) => &self.data[r.start as usize..r.end as usize],
+                None => &self.data[..],
+            }
+            .chunks(if trickle { 2 } else { usize::MAX })
+            .map(Bytes::copy_from_slice)
+            .collect();
+            let gap = if trickle {
+                Duration::from_millis(20)
+            } else {
+                Duration::ZERO
+            };
+            async move {
+                Ok(Box::pin(stream::unfold(
+                    data.into_iter(),
+                    move |mut chunks| async move {
+                        sleep(gap).await;
+                        chunks
+                            .next()
+                            .map(|c| (Ok::<_, (Self::Error, Option<Duration>)>(c), chunks))
+                    },
+                )))
+            }
+        }
+    }
+
+    fn trickle_options(
+        len: u64,
+        slow_worker: Option<SpeedFloor>,
+        low_speed_abort: Option<SpeedFloor>,
+    ) -> DownloadOptions<core::iter::Once<ProgressEntry>> {
+        DownloadOptions {
+            concurrent: 1,
+            retry_gap: Duration::ZERO,
+            push_queue_cap: 1024,
+            download_chunks: core::iter::once(0..len),
+            pull_timeout: Duration::from_secs(5),
+            min_chunk_size: 1,
+            max_speculative: 3,
+            speed_limiter: SpeedLimiter::default(),
+            retry_policy: None,
+            pause_token: PauseToken::default(),
+            verbosity: Verbosity::default(),
+            slow_worker,
+            low_speed_abort,
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_multi_slow_worker_reconnects() {
+        let mock_data = build_mock_data(3 * 1024);
+        let puller = TricklePuller {
+            data: Arc::from(mock_data.as_slice()),
+            trickles: Arc::new(AtomicUsize::new(1)),
+        };
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        let result = download_multi(
+            puller,
+            pusher,
+            trickle_options(
+                mock_data.len() as u64,
+                Some(SpeedFloor::new(1000, Duration::from_millis(100))),
+                None,
+            ),
+        );
+        let mut slow = 0;
+        timeout(Duration::from_secs(10), async {
+            while let Ok(e) = result.event_chain().recv().await {
+                if matches!(e, Event::PullSlow(0)) {
+                    slow += 1;
+                }
+            }
+        })
+        .await
+        .expect("the reconnected worker must finish the download");
+        assert_eq!(slow, 1);
+        assert_eq!(&**receive.lock(), mock_data);
+        let outcome = result.join().await;
+        assert!(outcome.is_completed());
+        assert_eq!(outcome.workers[&0].slow_reconnects, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_multi_low_speed_abort_fails_session() {
+        let mock_data = build_mock_data(3 * 1024);
+        let puller = TricklePuller {
+            data: Arc::from(mock_data.as_slice()),
+            trickles: Arc::new(AtomicUsize::new(usize::MAX)),
+        };
+        let result = download_multi(
+            puller,
+            MemPusher::with_capacity(mock_data.len()),
+            trickle_options(
+                mock_data.len() as u64,
+                None,
+                Some(SpeedFloor::new(1000, Duration::from_millis(100))),
+            ),
+        );
+        let outcome = timeout(Duration::from_secs(10), result.join())
+            .await
+            .expect("a session below the floor must fail instead of trickling on");
+        assert_eq!(
+            outcome.status,
+            crate::DownloadStatus::Failed("download speed stayed below 1000 B/s for 100ms".into())
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_multi_low_speed_abort_spares_fast_session() {
+        let mock_data = build_mock_data(3 * 1024);
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        let result = download_multi(
+            MockPuller::new(&mock_data),
+            pusher,
+            trickle_options(
+                mock_data.len() as u64,
+                None,
+                Some(SpeedFloor::new(1000, Duration::from_millis(20))),
+            ),
+        );
+        let outcome = timeout(Duration::from_secs(10), result.join())
+            .await
+            .expect("join must resolve");
+        assert!(outcome.is_completed());
+        assert_eq!(&**receive.lock(), mock_data);
+    }
+
+    #[derive(Debug)]
+    struct RateLimitedErr;
+    impl std::fmt::Display for RateLimitedErr {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("429 Too Many Requests")
+        }
+    }
+    impl std::error::Error for RateLimitedErr {}
+    impl crate::PullerError for RateLimitedErr {
+        fn is_rate_limited(&self) -> bool {
+            true
+        }
+    }
+
+    /// Answers the first `limits` pulls with a rate limit asking for 300ms,
+    /// recording when each pull was made and whether it was limited.
+    #[derive(Debug, Clone)]
+    struct RateLimitedPuller {
+        data: Arc<[u8]>,
+        limits: Arc<AtomicUsize>,
+        pulls: Arc<Mutex<Vec<(Instant, bool)>>>,
+    }
+    impl Puller for RateLimitedPuller {
+        type Error = RateLimitedErr;
+        fn pull(
+            &mut self,
+            range: Option<&ProgressEntry>,
+        ) -> impl Future<
+            Output = crate::PullResult<impl crate::PullStream<Self::Error>, Self::Error>,
+        > + Send {
+            let limited = self
+                .limits
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            self.pulls.lock().push((Instant::now(), limited));
+            let chunk = Bytes::copy_from_slice(match range {
+                Some(r) => &self.data[r.start as usize..r.end as usize],
+                None => &self.data[..],
+            });
+            async move {
+                if limited {
+                    return Err((RateLimitedErr, Some(Duration::from_millis(300))));
+                }
+                Ok(stream::iter([Ok(chunk)]))
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_multi_rate_limit_holds_every_worker() {
+        let mock_data = build_mock_data(3 * 1024);
+        let pulls = Arc::new(Mutex::new(Vec::new()));
+        let puller = RateLimitedPuller {
+            data: Arc::from(mock_data.as_slice()),
+            limits: Arc::new(AtomicUsize::new(1)),
+            pulls: pulls.clone(),
+        };
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        let result = download_multi(
+            puller,
+            pusher,
+            trickle_options(mock_data.len() as u64, None, None),
+        );
+        let mut throttled = Vec::new();
+        timeout(Duration::from_secs(10), async {
+            while let Ok(e) = result.event_chain().recv().await {
+                if let Event::Throttled(cooldown) = e {
+                    throttled.push(cooldown);
+                    // Workers started during the cooldown wait for it too.
+                    result.set_threads(4, 1);
+                }
+            }
+        })
+        .await
+        .expect("the session must finish after the cooldown");
+        assert_eq!(throttled, [Duration::from_millis(300)]);
+        assert_eq!(&**receive.lock(), mock_data);
+
+        let pulls = pulls.lock().clone();
+        let (limited, later) = pulls.split_first().expect("the first pull was made");
+        assert!(limited.1, "{pulls:?}");
+        assert!(later.len() > 1, "{pulls:?}");
+        assert!(
+            later
+                .iter()
+                .all(|&(at, _)| at >= limited.0 + Duration::from_millis(290)),
+            "{pulls:?}"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_multi_low_speed_abort_waits_out_retry_after() {
+        let mock_data = build_mock_data(3 * 1024);
+        let puller = RateLimitedPuller {
+            data: Arc::from(mock_data.as_slice()),
+            limits: Arc::new(AtomicUsize::new(1)),
+            pulls: Arc::default(),
+        };
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        // A 100 ms window passes inside the 300 ms `Retry-After` cooldown.
+        let floor = SpeedFloor::new(1, Duration::from_millis(100));
+        let result = download_multi(
+            puller,
+            pusher,
+            trickle_options(mock_data.len() as u64, None, Some(floor)),
+        );
+        timeout(Duration::from_secs(10), async {
+            while result.event_chain().recv().await.is_ok() {}
+        })
+        .await
+        .expect("the session must finish after the cooldown");
+        let outcome = result.join().await;
+        assert!(outcome.is_completed(), "{:?}", outcome.status);
+        assert_eq!(&**receive.lock(), mock_data);
+    }
 }
@@ -0,0 +1,393 @@
+//! Session bookkeeping behind [`DownloadResult::join`](crate::DownloadResult::join).
+
+use crate::{ProgressEntry, WorkerId};
+use core::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+use parking_lot::Mutex;
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    sync::{Arc, OnceLock},
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+
+/// How a download session ended.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DownloadStatus {
+    /// Every byte was pulled, pushed and flushed.
+    Completed,
+    /// The session was cancelled through
+    /// [`DownloadResult::abort`](crate::DownloadResult::abort) or by dropping
+    /// the last handle. The sink was not flushed.
+    Aborted,
+    /// The session was cancelled by a terminal event
+    /// ([`Event::PullFailed`](crate::Event::PullFailed),
+    /// [`Event::PullTimeoutFailed`](crate::Event::PullTimeoutFailed),
+    /// [`Event::PushFailed`](crate::Event::PushFailed)) or the push driver or
+    /// a worker panicked. Carries the message of the error that ended it.
+    Failed(String),
+}
+
+/// Span over which [`WorkerSnapshot::bps`] is measured.
+const SPEED_WINDOW: Duration = Duration::from_secs(1);
+
+/// Per-worker counters of a session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorkerCounts {
+    /// Bytes this worker pulled, as reported by
+    /// [`Event::PullProgress`](crate::Event::PullProgress).
+    pub pulled: u64,
+    pub pull_errors: usize,
+    pub pull_timeouts: usize,
+    /// Connections dropped for pulling slower than
+    /// [`multi::DownloadOptions::slow_worker`](crate::multi::DownloadOptions::slow_worker).
+    pub slow_reconnects: usize,
+    /// Failed pushes of ranges pulled by this worker.
+    pub push_errors: usize,
+}
+
+/// A live view of one worker, returned by
+/// [`DownloadResult::workers`](crate::DownloadResult::workers).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorkerSnapshot {
+    pub id: WorkerId,
+    /// What is left of the range the worker is pulling, end exclusive. `None`
+    /// for the worker of a single-threaded session, which pulls the whole body
+    /// without a planned range.
+    pub range: Option<ProgressEntry>,
+    /// Pull rate over about the last second, in bytes per second.
+    pub bps: u64,
+    /// Whether another worker races this one on the same range, after
+    /// nothing was left to split.
+    pub speculative: bool,
+    /// Counters since the worker was spawned. Every pull error and timeout is
+    /// followed by a retry.
+    pub counts: WorkerCounts,
+}
+
+/// The final result of a download session, returned by
+/// [`DownloadResult::join`](crate::DownloadResult::join).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadOutcome {
+    pub status: DownloadStatus,
+    /// Bytes pulled by all workers. Ranges pulled by several speculative
+    /// workers are only counted once.
+    pub bytes_pulled: u64,
+    /// Bytes the sink reported as written through
+    /// [`Event::PushProgress`](crate::Event::PushProgress).
+    pub bytes_pushed: u64,
+    /// Time from the start of the session until the push driver exited.
+    pub elapsed: Duration,
+    /// Counters of every worker the session ever spawned.
+    pub workers: BTreeMap<WorkerId, WorkerCounts>,
+}
+
+impl DownloadOutcome {
+    #[must_use]
+    pub fn is_completed(&self) -> bool {
+        self.status == DownloadStatus::Completed
+    }
+}
+
+/// Live counters of one worker. Only the speed window takes a lock, which
+/// nobody but the worker and a snapshot contend for.
+#[derive(Debug, Default)]
+pub struct WorkerCounter {
+    pulled: AtomicU64,
+    pull_errors: AtomicUsize,
+    pull_timeouts: AtomicUsize,
+    slow_reconnects: AtomicUsize,
+    push_errors: AtomicUsize,
+    window: Mutex<SpeedWindow>,
+}
+
+/// The current speed window of a worker: when it opened, the bytes pulled by
+/// then, and the rate measured over the previous window.
+#[derive(Debug)]
+struct SpeedWindow {
+    start: Instant,
+    pulled: u64,
+    bps: u64,
+}
+
+impl Default for SpeedWindow {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            pulled: 0,
+            bps: 0,
+        }
+    }
+}
+
+/// Bytes per second of `bytes` over `elapsed`.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+fn rate(bytes: u64, elapsed: Duration) -> u64 {
+    (bytes as f64 / elapsed.as_secs_f64()) as u64
+}
+
+impl WorkerCounter {
+    pub fn add_pulled(&self, bytes: u64) {
+        let pulled = self.pulled.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        let mut window = self.window.lock();
+        let elapsed = window.start.elapsed();
+        if elapsed >= SPEED_WINDOW {
+            window.bps = rate(pulled - window.pulled, elapsed);
+            window.start = Instant::now();
+            window.pulled = pulled;
+        }
+    }
+
+    /// The pull rate of the last full window, or of the current one once it
+    /// runs over, so a stalled worker drops towards zero.
+    #[must_use]
+    pub fn bps(&self) -> u64 {
+        let pulled = self.pulled.load(Ordering::Relaxed);
+        let window = self.window.lock();
+        let elapsed = window.start.elapsed();
+        if elapsed > SPEED_WINDOW {
+            rate(pulled.saturating_sub(window.pulled), elapsed)
+        } else {
+            window.bps
+        }
+    }
+
+    pub fn add_pull_error(&self) {
+        self.pull_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_pull_timeout(&self) {
+        self.pull_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_slow_reconnect(&self) {
+        self.slow_reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_push_error(&self) {
+        self.push_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn snapshot(&self) -> WorkerCounts {
+        WorkerCounts {
+            pulled: self.pulled.load(Ordering::Relaxed),
+            pull_errors: self.pull_errors.load(Ordering::Relaxed),
+            pull_timeouts: self.pull_timeouts.load(Ordering::Relaxed),
+            slow_reconnects: self.slow_reconnects.load(Ordering::Relaxed),
+            push_errors: self.push_errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Live statistics of a session, shared by its workers, its push driver and
+/// every clone of its [`DownloadResult`](crate::DownloadResult).
+#[derive(Debug)]
+pub struct SessionStats {
+    start: Instant,
+    bytes_pulled: AtomicU64,
+    bytes_pushed: AtomicU64,
+    /// Workers that have not finished pulling yet.
+    pulling: AtomicUsize,
+    workers: Mutex<BTreeMap<WorkerId, Arc<WorkerCounter>>>,
+    /// Message of the first terminal error.
+    failure: OnceLock<String>,
+    /// Final status and elapsed time, set once the push driver has exited.
+    finished: OnceLock<(DownloadStatus, Duration)>,
+    done: CancellationToken,
+}
+
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            bytes_pulled: AtomicU64::new(0),
+            bytes_pushed: AtomicU64::new(0),
+            pulling: AtomicUsize::new(0),
+            workers: Mutex::default(),
+            failure: OnceLock::new(),
+            finished: OnceLock::new(),
+            done: CancellationToken::new(),
+        }
+    }
+}
+
+impl SessionStats {
+    /// The counter of worker `id`, created on first use.
+    pub fn worker(&self, id: WorkerId) -> Arc<WorkerCounter> {
+        self.workers.lock().entry(id).or_default().clone()
+    }
+
+    pub fn add_pulled(&self, bytes: u64) {
+        self.bytes_pulled.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn bytes_pulled(&self) -> u64 {
+        self.bytes_pulled.load(Ordering::Relaxed)
+    }
+
+    /// Count a worker in until [`worker_finished`](Self::worker_finished).
+    /// Called before the worker is spawned, so the count never dips to zero
+    /// between two workers.
+    pub fn worker_started(&self) {
+        self.pulling.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn worker_finished(&self) {
+        self.pulling.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Whether any worker is still pulling.
+    pub fn is_pulling(&self) -> bool {
+        self.pulling.load(Ordering::Relaxed) > 0
+    }
+
+    /// A [`WorkerSnapshot`] of worker `id`, working on `range`.
+    pub fn snapshot(
+        &self,
+        id: WorkerId,
+        range: Option<ProgressEntry>,
+        speculative: bool,
+    ) -> WorkerSnapshot {
+        let counter = self.worker(id);
+        WorkerSnapshot {
+            id,
+            range,
+            bps: counter.bps(),
+            speculative,
+            counts: counter.snapshot(),
+        }
+    }
+
+    /// Whether [`finish`](Self::finish) has been called.
+    pub fn is_finished(&self) -> bool {
+        self.done.is_cancelled()
+    }
+
+    pub fn add_pushed(&self, bytes: u64) {
+        self.bytes_pushed.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Record the error that is about to end the session. Only the first call
+    /// has an effect.
+    pub fn fail(&self, error: &impl ToString) {
+        let _ = self.failure.set(error.to_string());
+    }
+
+    /// Record the panic of `what` (a worker, the push driver) as the error
+    /// that is about to end the session.
+    pub fn fail_panicked(&self, what: &str, panic: &(dyn Any + Send)) {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(ToString::to_string)
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        self.fail(&format!("{what} panicked: {message}"));
+    }
+
+    /// Seal the session once the push driver has exited, waking every
+    /// [`join`](Self::join)er.
+    ///
+    /// `panic` is the payload of the push driver if it panicked; `aborted`
+    /// whether the session token was cancelled.
+    pub fn finish(&self, panic: Option<Box<dyn Any + Send>>, aborted: bool) {
+        if let Some(panic) = panic {
+            self.fail_panicked("push driver", &*panic);
+        }
+        let status = match self.failure.get() {
+            Some(error) => DownloadStatus::Failed(error.clone()),
+            None if aborted => DownloadStatus::Aborted,
+            None => DownloadStatus::Completed,
+        };
+        let _ = self.finished.set((status, self.start.elapsed()));
+        self.done.cancel();
+    }
+
+    /// Wait until [`finish`](Self::finish) has been called, then return the
+    /// outcome.
+    pub async fn join(&self) -> DownloadOutcome {
+        self.done.cancelled().await;
+        let (status, elapsed) = self
+            .finished
+            .get()
+            .cloned()
+            .unwrap_or_else(|| (DownloadStatus::Aborted, self.start.elapsed()));
+        DownloadOutcome {
+            status,
+            bytes_pulled: self.bytes_pulled.load(Ordering::Relaxed),
+            bytes_pushed: self.bytes_pushed.load(Ordering::Relaxed),
+            elapsed,
+            workers: self
+                .workers
+                .lock()
+                .iter()
+                .map(|(id, counter)| (*id, counter.snapshot()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn failure_wins_over_abort() {
+        let stats = SessionStats::default();
+        stats.worker(1).add_pull_error();
+        stats.fail(&"boom");
+        stats.fail(&"later");
+        stats.finish(None, true);
+        let outcome = stats.join().await;
+        assert_eq!(outcome.status, DownloadStatus::Failed("boom".into()));
+        assert_eq!(outcome.workers[&1].pull_errors, 1);
+    }
+
+    #[tokio::test]
+    async fn panic_payload_becomes_failure() {
+        let stats = SessionStats::default();
+        stats.finish(Some(Box::new("oops")), false);
+        assert_eq!(
+            stats.join().await.status,
+            DownloadStatus::Failed("push driver panicked: oops".into())
+        );
+    }
+
+    #[test]
+    fn worker_speed_follows_the_window() {
+        let counter = WorkerCounter::default();
+        assert_eq!(counter.bps(), 0);
+        counter.window.lock().start -= SPEED_WINDOW;
+        counter.add_pulled(1000);
+        let bps = counter.bps();
+        assert!((500..=1000).contains(&bps), "{bps}");
+        // Nothing pulled for two windows: the rate decays.
+        counter.window.lock().start -= SPEED_WINDOW * 2;
+        assert_eq!(counter.bps(), 0);
+        assert_eq!(counter.snapshot().pulled, 1000);
+    }
+
+    #[tokio::test]
+    async fn status_follows_abort_flag() {
+        let stats = SessionStats::default();
+        stats.add_pulled(3);
+        stats.add_pushed(2);
+        stats.finish(None, false);
+        let outcome = stats.join().await;
+        assert!(outcome.is_completed());
+        assert_eq!((outcome.bytes_pulled, outcome.bytes_pushed), (3, 2));
+
+        let stats = SessionStats::default();
+        stats.finish(None, true);
+        assert_eq!(stats.join().await.status, DownloadStatus::Aborted);
+    }
+}
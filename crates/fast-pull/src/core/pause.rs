@@ -0,0 +1,109 @@
+//! Shared pause switch for download sessions.
+
+use std::sync::Arc;
+use tokio::{runtime::Handle, sync::watch};
+use tokio_util::sync::CancellationToken;
+
+/// A cheaply cloneable pause switch.
+///
+/// All clones share one flag. While it is set, every worker of a session that
+/// was given this token stops pulling and the push driver stops writing; the
+/// session's task queue, progress and connections to the sink are kept, so
+/// [`unpause`](Self::unpause) picks up exactly where the session stopped. Pass
+/// one token to several sessions to pause them together.
+///
+/// Unlike cancellation a pause can be lifted, and it may be set before the
+/// session starts, in which case the session starts paused.
+#[derive(Debug, Clone)]
+pub struct PauseToken {
+    state: Arc<watch::Sender<bool>>,
+}
+
+impl Default for PauseToken {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(watch::Sender::new(false)),
+        }
+    }
+}
+
+impl PauseToken {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pause(&self) {
+        self.state.send_replace(true);
+    }
+
+    pub fn unpause(&self) {
+        self.state.send_replace(false);
+    }
+
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        *self.state.borrow()
+    }
+
+    /// Resolves once the token is paused, immediately if it already is.
+    pub async fn paused(&self) {
+        let _ = self.state.subscribe().wait_for(|paused| *paused).await;
+    }
+
+    /// Resolves once the token is not paused, immediately if it already is not.
+    pub async fn unpaused(&self) {
+        let _ = self.state.subscribe().wait_for(|paused| !*paused).await;
+    }
+}
+
+/// Park the calling push driver while `pause` is set, until it is lifted or
+/// `token` is cancelled.
+///
+/// Must run on a `spawn_blocking` thread: it blocks on the runtime that thread
+/// belongs to.
+pub fn block_while_paused(pause: &PauseToken, token: &CancellationToken) {
+    if pause.is_paused() {
+        Handle::current().block_on(async {
+            tokio::select! {
+                () = token.cancelled() => {}
+                () = pause.unpaused() => {}
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::{Duration, timeout};
+
+    #[tokio::test]
+    async fn unpaused_resolves_immediately_when_running() {
+        let token = PauseToken::new();
+        assert!(!token.is_paused());
+        timeout(Duration::from_secs(1), token.unpaused())
+            .await
+            .expect("a running token must not block");
+    }
+
+    #[tokio::test]
+    async fn unpause_wakes_waiters() {
+        let token = PauseToken::new();
+        token.pause();
+        timeout(Duration::from_secs(1), token.paused())
+            .await
+            .expect("paused() must resolve on a paused token");
+        let waiter = tokio::spawn({
+            let token = token.clone();
+            async move { token.unpaused().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+        token.unpause();
+        timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("unpause must wake waiters")
+            .unwrap();
+    }
+}
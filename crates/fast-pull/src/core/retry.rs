@@ -0,0 +1,334 @@
+//! Pluggable retry policies consulted by the download engines.
+
+use core::{
+    fmt::Debug,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use std::{
+    hash::{BuildHasher, RandomState},
+    sync::Arc,
+};
+
+/// Which kind of failure a [`RetryPolicy`] is asked about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RetryKind {
+    /// A pull failed, reported as [`Event::PullError`](crate::Event::PullError).
+    Pull,
+    /// A pull stalled for longer than `pull_timeout`, reported as
+    /// [`Event::PullTimeout`](crate::Event::PullTimeout).
+    PullTimeout,
+    /// A push failed, reported as [`Event::PushError`](crate::Event::PushError).
+    Push,
+}
+
+/// Everything a [`RetryPolicy`] gets to know about a failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryContext {
+    /// What failed.
+    pub kind: RetryKind,
+    /// Consecutive failures of this worker (or of the push driver for
+    /// [`RetryKind::Push`]), counting the current one, so always `>= 1`.
+    /// Reset whenever that worker makes progress.
+    pub attempt: usize,
+    /// Consecutive failures of the whole session, counting the current one.
+    /// Reset whenever any worker makes progress.
+    pub session_attempt: usize,
+    /// Delay requested by the server, e.g. through `Retry-After`.
+    pub retry_after: Option<Duration>,
+}
+
+/// Decides how long to wait before retrying a failed operation, or whether to
+/// give up.
+///
+/// Returning `None` gives up: the engine emits a terminal event
+/// ([`Event::PullFailed`](crate::Event::PullFailed),
+/// [`Event::PullTimeoutFailed`](crate::Event::PullTimeoutFailed) or
+/// [`Event::PushFailed`](crate::Event::PushFailed)) and cancels the session.
+///
+/// # Example
+///
+/// ```
+/// use fast_pull::{CappedRetry, ExponentialRetry, RetryPolicy};
+/// use std::time::Duration;
+///
+/// // Back off from 500ms up to 30s, fail after 10 errors in a row.
+/// let policy = CappedRetry::new(
+///     ExponentialRetry::new(Duration::from_millis(500), Duration::from_secs(30)),
+///     10,
+/// );
+/// # let _: &dyn RetryPolicy = &policy;
+/// ```
+pub trait RetryPolicy: Debug + Send + Sync {
+    /// The delay before the next attempt, or `None` to give up.
+    fn next_delay(&self, ctx: &RetryContext) -> Option<Duration>;
+}
+
+/// Retry forever with a fixed gap, preferring the server's `Retry-After`.
+///
+/// Timed-out pulls are retried immediately: the engine already waited
+/// `pull_timeout` before reporting them. This is the policy the engines fall
+/// back to when no other policy is configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedRetry {
+    pub gap: Duration,
+}
+
+impl FixedRetry {
+    #[must_use]
+    pub const fn new(gap: Duration) -> Self {
+        Self { gap }
+    }
+}
+
+impl RetryPolicy for FixedRetry {
+    fn next_delay(&self, ctx: &RetryContext) -> Option<Duration> {
+        Some(match ctx.kind {
+            RetryKind::PullTimeout => Duration::ZERO,
+            RetryKind::Pull | RetryKind::Push => ctx.retry_after.unwrap_or(self.gap),
+        })
+    }
+}
+
+/// Retry forever, doubling the gap after every consecutive failure of a worker.
+///
+/// The `n`-th consecutive failure waits `base * 2^(n-1)`, capped at `max`, then
+/// shortened by a random amount of up to `jitter` of itself so that workers
+/// failing together do not retry in lockstep. A `Retry-After` from the server
+/// takes precedence over the computed delay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExponentialRetry {
+    pub base: Duration,
+    pub max: Duration,
+    /// Fraction of the delay that is randomized, clamped to `0.0..=1.0`.
+    pub jitter: f64,
+}
+
+impl ExponentialRetry {
+    /// Exponential backoff from `base` up to `max`, with a jitter of `0.5`.
+    #[must_use]
+    pub const fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            jitter: 0.5,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter;
+        self
+    }
+}
+
+impl RetryPolicy for ExponentialRetry {
+    #[allow(clippy::cast_precision_loss)]
+    fn next_delay(&self, ctx: &RetryContext) -> Option<Duration> {
+        if let Some(retry_after) = ctx.retry_after {
+            return Some(retry_after);
+        }
+        let exp = u32::try_from(ctx.attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        let delay = 2u32
+            .checked_pow(exp)
+            .and_then(|factor| self.base.checked_mul(factor))
+            .map_or(self.max, |d| d.min(self.max));
+        let random = RandomState::new().hash_one(ctx.attempt) as f64 / u64::MAX as f64;
+        let jitter = self.jitter.clamp(0.0, 1.0) * random;
+        Some(delay.mul_f64(1.0 - jitter))
+    }
+}
+
+/// Wraps another policy and gives up after too many consecutive failures.
+///
+/// `max_attempts` bounds [`RetryContext::attempt`], so a single worker that
+/// keeps failing stops the session; `max_session_attempts` bounds
+/// [`RetryContext::session_attempt`], so the session stops once no worker has
+/// made progress for that many failures in a row. `0` disables a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CappedRetry<P> {
+    pub inner: P,
+    pub max_attempts: usize,
+    pub max_session_attempts: usize,
+}
+
+impl<P> CappedRetry<P> {
+    /// Give up once a worker fails more than `max_attempts` times in a row.
+    #[must_use]
+    pub const fn new(inner: P, max_attempts: usize) -> Self {
+        Self {
+            inner,
+            max_attempts,
+            max_session_attempts: 0,
+        }
+    }
+
+    /// Also give up once the session fails more than `max_session_attempts`
+    /// times in a row.
+    #[must_use]
+    pub const fn with_session_limit(mut self, max_session_attempts: usize) -> Self {
+        self.max_session_attempts = max_session_attempts;
+        self
+    }
+}
+
+impl<P: RetryPolicy> RetryPolicy for CappedRetry<P> {
+    fn next_delay(&self, ctx: &RetryContext) -> Option<Duration> {
+        let exceeded = |limit: usize, attempt: usize| limit != 0 && attempt > limit;
+        if exceeded(self.max_attempts, ctx.attempt)
+            || exceeded(self.max_session_attempts, ctx.session_attempt)
+        {
+            return None;
+        }
+        self.inner.next_delay(ctx)
+    }
+}
+
+/// The retry bookkeeping of one session: the policy plus the session-wide
+/// consecutive failure counter. Each worker keeps its own `attempt` counter and
+/// passes it in.
+#[derive(Debug, Clone)]
+pub struct RetryState {
+    policy: Arc<dyn RetryPolicy>,
+    session_attempt: Arc<AtomicUsize>,
+}
+
+impl RetryState {
+    pub fn new(policy: Option<Arc<dyn RetryPolicy>>, retry_gap: Duration) -> Self {
+        Self {
+            policy: policy.unwrap_or_else(|| Arc::new(FixedRetry::new(retry_gap))),
+            session_attempt: Arc::default(),
+        }
+    }
+
+    /// Record a failure and ask the policy how to proceed.
+    pub fn on_error(
+        &self,
+        kind: RetryKind,
+        attempt: &mut usize,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        *attempt = attempt.saturating_add(1);
+        let session_attempt = self
+            .session_attempt
+            .fetch_add(1, Ordering::Relaxed)
+            .saturating_add(1);
+        self.policy.next_delay(&RetryContext {
+            kind,
+            attempt: *attempt,
+            session_attempt,
+            retry_after,
+        })
+    }
+
+    /// Record progress, resetting both consecutive failure counters.
+    pub fn on_progress(&self, attempt: &mut usize) {
+        *attempt = 0;
+        if self.session_attempt.load(Ordering::Relaxed) != 0 {
+            self.session_attempt.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn ctx(kind: RetryKind, attempt: usize, session_attempt: usize) -> RetryContext {
+        RetryContext {
+            kind,
+            attempt,
+            session_attempt,
+            retry_after: None,
+        }
+    }
+
+    #[test]
+    fn fixed_prefers_retry_after() {
+        let policy = FixedRetry::new(Duration::from_secs(1));
+        assert_eq!(
+            policy.next_delay(&ctx(RetryKind::Pull, 7, 7)),
+            Some(Duration::from_secs(1))
+        );
+        let mut c = ctx(RetryKind::Pull, 1, 1);
+        c.retry_after = Some(Duration::from_secs(3));
+        assert_eq!(policy.next_delay(&c), Some(Duration::from_secs(3)));
+        assert_eq!(
+            policy.next_delay(&ctx(RetryKind::PullTimeout, 1, 1)),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn exponential_doubles_up_to_max() {
+        let policy = ExponentialRetry::new(Duration::from_millis(100), Duration::from_secs(1))
+            .with_jitter(0.0);
+        let delays: Vec<_> = (1..=6)
+            .map(|n| policy.next_delay(&ctx(RetryKind::Pull, n, n)).unwrap())
+            .collect();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 800, 1000, 1000].map(Duration::from_millis)
+        );
+        assert_eq!(
+            policy.next_delay(&ctx(RetryKind::Pull, usize::MAX, 1)),
+            Some(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn exponential_jitter_stays_in_range() {
+        let policy = ExponentialRetry::new(Duration::from_millis(100), Duration::from_secs(10));
+        for n in 1..=5 {
+            let full = Duration::from_millis(100) * 2u32.pow(n - 1);
+            let delay = policy
+                .next_delay(&ctx(RetryKind::Push, n as usize, 1))
+                .unwrap();
+            assert!(delay <= full && delay >= full / 2, "{delay:?} vs {full:?}");
+        }
+    }
+
+    #[test]
+    fn capped_gives_up_after_limits() {
+        let policy = CappedRetry::new(FixedRetry::new(Duration::ZERO), 3).with_session_limit(5);
+        assert!(policy.next_delay(&ctx(RetryKind::Pull, 3, 3)).is_some());
+        assert!(policy.next_delay(&ctx(RetryKind::Pull, 4, 4)).is_none());
+        assert!(policy.next_delay(&ctx(RetryKind::Pull, 1, 5)).is_some());
+        assert!(policy.next_delay(&ctx(RetryKind::Push, 1, 6)).is_none());
+        let unlimited = CappedRetry::new(FixedRetry::new(Duration::ZERO), 0);
+        assert!(
+            unlimited
+                .next_delay(&ctx(RetryKind::Pull, usize::MAX, usize::MAX))
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn retry_state_counts_consecutive_failures() {
+        let state = RetryState::new(
+            Some(Arc::new(CappedRetry::new(
+                FixedRetry::new(Duration::ZERO),
+                0,
+            ))),
+            Duration::ZERO,
+        );
+        let (mut a, mut b) = (0, 0);
+        state.on_error(RetryKind::Pull, &mut a, None);
+        state.on_error(RetryKind::Pull, &mut b, None);
+        assert_eq!((a, b), (1, 1));
+        assert_eq!(state.session_attempt.load(Ordering::Relaxed), 2);
+        state.on_progress(&mut a);
+        assert_eq!((a, b), (0, 1));
+        assert_eq!(state.session_attempt.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn retry_state_defaults_to_fixed_gap() {
+        let state = RetryState::new(None, Duration::from_millis(7));
+        let mut attempt = 0;
+        assert_eq!(
+            state.on_error(RetryKind::Pull, &mut attempt, None),
+            Some(Duration::from_millis(7))
+        );
+    }
+}
@@ -0,0 +1,38 @@
+//! Work-splitting parameters a multi-threaded session reads while it runs.
+
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// The chunking knobs shared by the workers of a multi-threaded session and its
+/// [`DownloadResult`](crate::DownloadResult). Workers load them every time they
+/// steal work, so a change applies to the next split.
+#[derive(Debug, Default)]
+pub struct Schedule {
+    min_chunk_size: AtomicU64,
+    max_speculative: AtomicUsize,
+}
+
+impl Schedule {
+    pub const fn new(min_chunk_size: u64, max_speculative: usize) -> Self {
+        Self {
+            min_chunk_size: AtomicU64::new(min_chunk_size),
+            max_speculative: AtomicUsize::new(max_speculative),
+        }
+    }
+
+    pub fn min_chunk_size(&self) -> u64 {
+        self.min_chunk_size.load(Ordering::Relaxed)
+    }
+
+    pub fn max_speculative(&self) -> usize {
+        self.max_speculative.load(Ordering::Relaxed)
+    }
+
+    pub fn set_min_chunk_size(&self, min_chunk_size: u64) {
+        self.min_chunk_size.store(min_chunk_size, Ordering::Relaxed);
+    }
+
+    pub fn set_max_speculative(&self, max_speculative: usize) {
+        self.max_speculative
+            .store(max_speculative, Ordering::Relaxed);
+    }
+}
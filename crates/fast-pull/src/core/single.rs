@@ -1,7 +1,12 @@
 //! Single-threaded sequential download.
 
+use super::{
+    low_speed::spawn_low_speed_abort, pause::block_while_paused, retry::RetryState,
+    throttle::Throttle,
+};
 use crate::{
-    DownloadResult, Event, ProgressEntry, Puller, PullerError, Pusher, multi::TokioExecutor,
+    DownloadResult, Event, PauseToken, ProgressEntry, Puller, PullerError, Pusher, RetryKind,
+    RetryPolicy, SessionStats, SpeedFloor, SpeedLimiter, Verbosity, WorkerId, multi::TokioExecutor,
 };
 use bytes::Bytes;
 use core::time::Duration;
@@ -10,11 +15,26 @@ use futures::TryStreamExt;
 use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 
+/// The id of the one worker of a single-threaded session.
+pub const WORKER_ID: WorkerId = 0;
+
 /// Options for a single-threaded download.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct DownloadOptions {
     pub retry_gap: Duration,
     pub push_queue_cap: usize,
+    /// Bandwidth limiter consulted before forwarding each pulled chunk.
+    pub speed_limiter: SpeedLimiter,
+    /// Consulted on every pull and push error. `None` retries forever every
+    /// `retry_gap`, as [`FixedRetry`](crate::FixedRetry) does.
+    pub retry_policy: Option<Arc<dyn RetryPolicy>>,
+    /// Pause switch the pull task and the push driver wait on. The stream is
+    /// kept open while paused, as a single-threaded pull cannot resume it.
+    pub pause_token: PauseToken,
+    /// Which events the session emits; see [`Verbosity`].
+    pub verbosity: Verbosity,
+    /// Fail the session once it pulls slower than this over a window.
+    pub low_speed_abort: Option<SpeedFloor>,
 }
 
 /// Start a single-threaded sequential download.
@@ -39,45 +59,77 @@ pub fn download_single<R: Puller, W: Pusher>(
     mut pusher: W,
     options: DownloadOptions,
 ) -> DownloadResult<TokioExecutor<R, W::Error>, R::Error, W::Error> {
-    const ID: usize = 0;
+    const ID: usize = WORKER_ID;
     let token = CancellationToken::new();
+    let stats = Arc::new(SessionStats::default());
+    let verbosity = options.verbosity;
     let (tx, event_chain) = mpmc::unbounded_async();
     pusher.set_listener({
         let tx = tx.clone();
+        let stats = stats.clone();
+        // A redownload pushes from 0 again; only bytes past the furthest push
+        // so far are new.
+        let mut pushed_to = 0;
         Box::new(move |p| {
-            let _ = tx.send(Event::PushProgress(p));
+            stats.add_pushed(p.end.saturating_sub(p.start.max(pushed_to)));
+            pushed_to = pushed_to.max(p.end);
+            if verbosity.includes(Verbosity::Progress) {
+                let _ = tx.send(Event::PushProgress(p));
+            }
         })
     });
 
     let (tx_push, rx_push) =
         spsc::bounded_async_blocking::<(ProgressEntry, Bytes)>(options.push_queue_cap);
+    let retry = RetryState::new(options.retry_policy, options.retry_gap);
     let push_thread = Arc::new(std::sync::OnceLock::new());
     let push_handle = tokio::task::spawn_blocking({
         let push_thread = push_thread.clone();
         let token = token.clone();
         let tx = tx.clone();
+        let retry = retry.clone();
+        let stats = stats.clone();
+        let pause_token = options.pause_token.clone();
         move || {
             let _ = push_thread.set(std::thread::current());
+            let mut attempt = 0;
             while let Ok((mut spin, mut data)) = rx_push.recv() {
                 loop {
+                    block_while_paused(&pause_token, &token);
                     if token.is_cancelled() {
                         return;
                     }
-                    let _ = tx.send(Event::Pushing(ID, spin.clone()));
+                    if verbosity.includes(Verbosity::Trace) {
+                        let _ = tx.send(Event::Pushing(ID, spin.clone()));
+                    }
                     let len_before_push = data.len();
                     match pusher.push(&spin, data) {
-                        Ok(()) => break,
+                        Ok(()) => {
+                            retry.on_progress(&mut attempt);
+                            break;
+                        }
                         Err((err, bytes)) => {
-                            let _ = tx.send(Event::PushError(ID, spin.clone(), err));
+                            stats.worker(ID).add_push_error();
+                            let Some(delay) = retry.on_error(RetryKind::Push, &mut attempt, None)
+                            else {
+                                stats.fail(&err);
+                                let _ = tx.send(Event::PushFailed(ID, spin, err));
+                                token.cancel();
+                                return;
+                            };
+                            if verbosity.includes(Verbosity::Errors) {
+                                let _ = tx.send(Event::PushError(ID, spin.clone(), err));
+                            }
                             let written = len_before_push.saturating_sub(bytes.len());
                             data = bytes;
                             spin.start += written as u64;
+                            std::thread::park_timeout(delay);
                         }
                     }
-                    std::thread::park_timeout(options.retry_gap);
                 }
             }
             loop {
+                block_while_paused(&pause_token, &token);
                 if token.is_cancelled() {
                     break;
                 }
@@ -85,7 +137,9 @@ pub fn download_single<R: Puller, W: Pusher>(
                 match pusher.flush() {
                     Ok(()) => break,
                     Err(err) => {
-                        let _ = tx.send(Event::FlushError(err));
+                        if verbosity.includes(Verbosity::Errors) {
+                            let _ = tx.send(Event::FlushError(err));
+                        }
                     }
                 }
                 std::thread::park_timeout(options.retry_gap);
@@ -93,61 +147,159 @@ pub fn download_single<R: Puller, W: Pusher>(
         }
     });
 
-    let pull_handle = tokio::spawn(async move {
-        'redownload: loop {
-            let _ = tx.send(Event::Pulling(ID));
-            let mut downloaded: u64 = 0;
-            let mut stream = loop {
-                match puller.pull(None).await {
-                    Ok(t) => break t,
-                    Err((e, retry_gap)) => {
-                        let _ = tx.send(Event::PullError(ID, e));
-                        tokio::time::sleep(retry_gap.unwrap_or(options.retry_gap)).await;
-                    }
-                }
-            };
-            loop {
-                match stream.try_next().await {
-                    Ok(Some(chunk)) => {
-                        if chunk.is_empty() {
-                            continue;
+    let speed_limiter = options.speed_limiter.clone();
+    let pause_token = options.pause_token.clone();
+    let session_token = token.clone();
+    let counter = stats.worker(ID);
+    // Only records the cooldowns of the one connection, for the low-speed
+    // abort; the retry delay already holds the worker back.
+    let throttle = Arc::<Throttle>::default();
+    stats.worker_started();
+    let pull_handle = tokio::spawn({
+        let stats = stats.clone();
+        let throttle = throttle.clone();
+        async move {
+            let mut attempt = 0;
+            // The furthest offset pulled so far: a redownload starts over from
+            // 0, and only bytes past it count as pulled.
+            let mut pulled_to: u64 = 0;
+            'redownload: loop {
+                pause_token.unpaused().await;
+                let _ = tx.send(Event::Pulling(ID));
+                let mut downloaded: u64 = 0;
+                let mut stream = loop {
+                    match puller.pull(None).await {
+                        Ok(t) => break t,
+                        Err((e, retry_after)) => {
+                            let delay = if e.is_permanent() {
+                                None
+                            } else {
+                                retry.on_error(RetryKind::Pull, &mut attempt, retry_after)
+                            };
+                            let Some(delay) = delay else {
+                                stats.fail(&e);
+                                let _ = tx.send(Event::PullFailed(ID, e));
+                                session_token.cancel();
+                                break 'redownload;
+                            };
+                            counter.add_pull_error();
+                            if e.is_rate_limited()
+                                && throttle.throttle(delay)
+                                && verbosity.includes(Verbosity::Errors)
+                            {
+                                let _ = tx.send(Event::Throttled(delay));
+                            }
+                            if verbosity.includes(Verbosity::Errors) {
+                                let _ = tx.send(Event::PullError(ID, e));
+                            }
+                            tokio::time::sleep(delay).await;
                         }
-                        let len = chunk.len() as u64;
-                        let span = downloaded..(downloaded + len);
-                        let _ = tx.send(Event::PullProgress(ID, span.clone()));
-                        let _ = tx_push.send((span, chunk)).await;
-                        downloaded += len;
                     }
-                    Ok(None) => break 'redownload,
-                    Err((e, retry_gap)) => {
-                        let is_irrecoverable = e.is_irrecoverable();
-                        let _ = tx.send(Event::PullError(ID, e));
-                        tokio::time::sleep(retry_gap.unwrap_or(options.retry_gap)).await;
-                        if is_irrecoverable {
-                            continue 'redownload;
+                };
+                loop {
+                    pause_token.unpaused().await;
+                    match stream.try_next().await {
+                        Ok(Some(chunk)) => {
+                            if chunk.is_empty() {
+                                continue;
+                            }
+                            retry.on_progress(&mut attempt);
+                            let len = chunk.len() as u64;
+                            let span = downloaded..(downloaded + len);
+                            speed_limiter.acquire(len).await;
+                            let fresh = span.end.saturating_sub(pulled_to.max(span.start));
+                            pulled_to = pulled_to.max(span.end);
+                            counter.add_pulled(fresh);
+                            stats.add_pulled(fresh);
+                            if verbosity.includes(Verbosity::Trace) {
+                                let _ = tx.send(Event::PullProgress(ID, span.clone()));
+                            }
+                            let _ = tx_push.send((span, chunk)).await;
+                            downloaded += len;
+                        }
+                        Ok(None) => break 'redownload,
+                        Err((e, retry_after)) => {
+                            let delay = if e.is_permanent() {
+                                None
+                            } else {
+                                retry.on_error(RetryKind::Pull, &mut attempt, retry_after)
+                            };
+                            let Some(delay) = delay else {
+                                stats.fail(&e);
+                                let _ = tx.send(Event::PullFailed(ID, e));
+                                session_token.cancel();
+                                break 'redownload;
+                            };
+                            let is_irrecoverable = e.is_irrecoverable();
+                            counter.add_pull_error();
+                            if e.is_rate_limited()
+                                && throttle.throttle(delay)
+                                && verbosity.includes(Verbosity::Errors)
+                            {
+                                let _ = tx.send(Event::Throttled(delay));
+                            }
+                            if verbosity.includes(Verbosity::Errors) {
+                                let _ = tx.send(Event::PullError(ID, e));
+                            }
+                            tokio::time::sleep(delay).await;
+                            if is_irrecoverable {
+                                continue 'redownload;
+                            }
                         }
                     }
                 }
             }
+            stats.worker_finished();
+            let _ = tx.send(Event::Finished(ID));
         }
-        let _ = tx.send(Event::Finished(ID));
     });
+    if let Some(floor) = options.low_speed_abort {
+        spawn_low_speed_abort(
+            floor,
+            stats.clone(),
+            token.clone(),
+            options.pause_token.clone(),
+            throttle,
+        );
+    }
 
     tokio::spawn({
         let token = token.clone();
+        let stats = stats.clone();
         async move {
-            tokio::select! {
-                _ = push_handle => {},
+            let mut push_handle = push_handle;
+            let res = tokio::select! {
+                res = &mut push_handle => res,
                 () = token.cancelled() => {
                     pull_handle.abort();
                     if let Some(t) = push_thread.get() {
                         t.unpark();
                     }
+                    push_handle.await
                 }
+            };
+            // The push driver has drained `rx_push`, so the pull task is done;
+            // if it panicked, the file is short.
+            if let Err(e) = pull_handle.await
+                && let Ok(panic) = e.try_into_panic()
+            {
+                stats.fail_panicked("worker", &*panic);
             }
+            stats.finish(
+                res.err().and_then(|e| e.try_into_panic().ok()),
+                token.is_cancelled(),
+            );
         }
     });
-    DownloadResult::new(event_chain, None, token)
+    DownloadResult::new(
+        event_chain,
+        None,
+        token,
+        options.speed_limiter,
+        options.pause_token,
+        Arc::default(),
+        stats,
+    )
 }
 
 #[cfg(test)]
@@ -161,7 +313,7 @@ mod tests {
         mem::MemPusher,
         mock::{MockPuller, build_mock_data},
     };
-    use futures::stream;
+    use futures::{StreamExt, stream};
     use std::{dbg, vec};
     use tokio::time::{sleep, timeout};
     use vec::Vec;
@@ -184,6 +336,11 @@ mod tests {
             DownloadOptions {
                 retry_gap: Duration::from_secs(1),
                 push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
             },
         );
 
@@ -219,6 +376,11 @@ mod tests {
             DownloadOptions {
                 retry_gap: Duration::from_secs(1),
                 push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
             },
         );
 
@@ -310,6 +472,11 @@ mod tests {
             DownloadOptions {
                 retry_gap: Duration::from_secs(1),
                 push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
             },
         );
 
@@ -369,6 +536,11 @@ mod tests {
             DownloadOptions {
                 retry_gap: Duration::from_secs(1),
                 push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
             },
         );
 
@@ -553,6 +725,37 @@ mod tests {
         }
     }
 
+    /// Yields the first half of the data, then an irrecoverable stream error;
+    /// later pulls serve the whole file.
+    #[derive(Debug, Clone)]
+    struct StreamErrMidwayPuller {
+        data: Arc<[u8]>,
+        failed: Arc<AtomicBool>,
+    }
+    impl crate::Puller for StreamErrMidwayPuller {
+        type Error = FatalErr;
+        fn pull(
+            &mut self,
+            _: Option<&crate::ProgressEntry>,
+        ) -> impl Future<
+            Output = crate::PullResult<impl crate::PullStream<Self::Error>, Self::Error>,
+        > + Send {
+            let data = if self.failed.swap(true, Ordering::SeqCst) {
+                &self.data[..]
+            } else {
+                &self.data[..self.data.len() / 2]
+            };
+            let mut items: Vec<Result<Bytes, (FatalErr, Option<Duration>)>> = data
+                .chunks(256)
+                .map(|c| Ok(Bytes::copy_from_slice(c)))
+                .collect();
+            if data.len() < self.data.len() {
+                items.push(Err((FatalErr, Some(Duration::ZERO))));
+            }
+            std::future::ready(Ok(stream::iter(items)))
+        }
+    }
+
     /// Like [`StreamErrOncePuller`] but yields a *recoverable* stream error first,
     /// so the `is_irrecoverable == false` fall-through (line 105) is exercised.
     #[derive(Debug, Clone)]
@@ -604,6 +807,11 @@ mod tests {
             DownloadOptions {
                 retry_gap: Duration::ZERO,
                 push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
             },
         );
         while result.event_chain().recv().await.is_ok() {}
@@ -624,6 +832,11 @@ mod tests {
             DownloadOptions {
                 retry_gap: Duration::ZERO,
                 push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
             },
         );
         while result.event_chain().recv().await.is_ok() {}
@@ -646,6 +859,11 @@ mod tests {
             DownloadOptions {
                 retry_gap: Duration::ZERO,
                 push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
             },
         );
         while result.event_chain().recv().await.is_ok() {}
@@ -669,12 +887,50 @@ mod tests {
             DownloadOptions {
                 retry_gap: Duration::ZERO,
                 push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
             },
         );
         while result.event_chain().recv().await.is_ok() {}
         assert_eq!(&**receive.lock(), mock_data);
     }
 
+    #[tokio::test]
+    async fn test_single_redownload_counts_bytes_once() {
+        let mock_data = build_mock_data(3 * 1024);
+        let puller = StreamErrMidwayPuller {
+            data: Arc::from(mock_data.as_slice()),
+            failed: Arc::new(AtomicBool::new(false)),
+        };
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        let result = download_single(
+            puller,
+            pusher,
+            DownloadOptions {
+                retry_gap: Duration::ZERO,
+                push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
+            },
+        );
+        let outcome = timeout(Duration::from_secs(10), result.join())
+            .await
+            .expect("join must resolve once the session ends");
+        assert_eq!(&**receive.lock(), mock_data);
+        assert!(outcome.is_completed());
+        assert_eq!(outcome.bytes_pulled, mock_data.len() as u64);
+        assert_eq!(outcome.bytes_pushed, mock_data.len() as u64);
+        assert_eq!(outcome.workers[&0].pulled, mock_data.len() as u64);
+        assert_eq!(outcome.workers[&0].pull_errors, 1);
+    }
+
     #[tokio::test]
     async fn test_single_stream_error_recoverable_retries() {
         // Lines 99-105: a stream error whose `is_irrecoverable` is false does NOT
@@ -693,6 +949,11 @@ mod tests {
             DownloadOptions {
                 retry_gap: Duration::ZERO,
                 push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
             },
         );
         while result.event_chain().recv().await.is_ok() {}
@@ -766,6 +1027,11 @@ mod tests {
             DownloadOptions {
                 retry_gap: Duration::from_secs(1),
                 push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
             },
         );
         // Drain events so `event_chain` does not pin the task open.
@@ -825,9 +1091,263 @@ mod tests {
             DownloadOptions {
                 retry_gap: Duration::ZERO,
                 push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
+            },
+        );
+        while result.event_chain().recv().await.is_ok() {}
+        assert_eq!(&**receive.lock(), mock_data);
+    }
+
+    #[tokio::test]
+    async fn test_sequential_download_respects_speed_limit() {
+        let mock_data = build_mock_data(3 * 1024);
+        let puller = MockPuller::new(&mock_data);
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        let start = std::time::Instant::now();
+        let result = download_single(
+            puller,
+            pusher,
+            DownloadOptions {
+                retry_gap: Duration::from_secs(1),
+                push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::new(10 * 1024),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
             },
         );
         while result.event_chain().recv().await.is_ok() {}
+        assert!(start.elapsed() >= Duration::from_millis(250));
+        assert_eq!(&**receive.lock(), mock_data);
+    }
+
+    #[derive(Debug)]
+    struct PermanentErr;
+    impl std::fmt::Display for PermanentErr {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("permanent")
+        }
+    }
+    impl std::error::Error for PermanentErr {}
+    impl crate::PullerError for PermanentErr {
+        fn is_permanent(&self) -> bool {
+            true
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    struct PermanentErrPuller;
+    impl crate::Puller for PermanentErrPuller {
+        type Error = PermanentErr;
+        fn pull(
+            &mut self,
+            _range: Option<&crate::ProgressEntry>,
+        ) -> impl Future<
+            Output = crate::PullResult<impl crate::PullStream<Self::Error>, Self::Error>,
+        > + Send {
+            std::future::ready(Err::<stream::Empty<_>, _>((PermanentErr, None)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_single_permanent_pull_error_ends_session() {
+        let pusher = MemPusher::with_capacity(0);
+        let result = download_single(
+            PermanentErrPuller,
+            pusher,
+            DownloadOptions {
+                retry_gap: Duration::from_secs(1),
+                push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
+            },
+        );
+        let mut failed = 0;
+        timeout(Duration::from_secs(10), async {
+            while let Ok(e) = result.event_chain().recv().await {
+                if matches!(e, Event::PullFailed(0, PermanentErr)) {
+                    failed += 1;
+                }
+            }
+        })
+        .await
+        .expect("a permanent error must end the session instead of retrying");
+        assert_eq!(failed, 1);
+        assert!(result.is_aborted());
+    }
+
+    #[derive(Debug, Clone)]
+    struct AlwaysErrPuller;
+    impl crate::Puller for AlwaysErrPuller {
+        type Error = RecoverableErr;
+        fn pull(
+            &mut self,
+            _range: Option<&crate::ProgressEntry>,
+        ) -> impl Future<
+            Output = crate::PullResult<impl crate::PullStream<Self::Error>, Self::Error>,
+        > + Send {
+            std::future::ready(Err::<stream::Empty<_>, _>((RecoverableErr, None)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_single_retry_policy_gives_up_on_pull_errors() {
+        let result = download_single(
+            AlwaysErrPuller,
+            MemPusher::with_capacity(0),
+            DownloadOptions {
+                retry_gap: Duration::from_mins(1),
+                push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: Some(Arc::new(crate::CappedRetry::new(
+                    crate::FixedRetry::new(Duration::ZERO),
+                    3,
+                ))),
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
+            },
+        );
+        let (mut errors, mut failed) = (0, 0);
+        timeout(Duration::from_secs(10), async {
+            while let Ok(e) = result.event_chain().recv().await {
+                match e {
+                    Event::PullError(0, RecoverableErr) => errors += 1,
+                    Event::PullFailed(0, RecoverableErr) => failed += 1,
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .expect("the retry policy must end the session");
+        assert_eq!((errors, failed), (3, 1));
+        assert!(result.is_aborted());
+    }
+
+    /// Yields one byte, then panics.
+    #[derive(Debug, Clone)]
+    struct PanicPuller;
+    impl crate::Puller for PanicPuller {
+        type Error = RecoverableErr;
+        fn pull(
+            &mut self,
+            _range: Option<&crate::ProgressEntry>,
+        ) -> impl Future<
+            Output = crate::PullResult<impl crate::PullStream<Self::Error>, Self::Error>,
+        > + Send {
+            let first = stream::iter([Ok(Bytes::from_static(b"a"))]);
+            std::future::ready(Ok(
+                first.chain(stream::repeat_with(|| panic!("puller exploded")))
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_single_join_reports_worker_panic() {
+        let result = download_single(
+            PanicPuller,
+            MemPusher::with_capacity(0),
+            DownloadOptions {
+                retry_gap: Duration::from_mins(1),
+                push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
+            },
+        );
+        let outcome = timeout(Duration::from_secs(10), result.join())
+            .await
+            .expect("join must resolve after the worker panicked");
+        assert_eq!(
+            outcome.status,
+            crate::DownloadStatus::Failed("worker panicked: puller exploded".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_single_join_reports_outcome() {
+        let mock_data = build_mock_data(3 * 1024);
+        let result = download_single(
+            MockPuller::new(&mock_data),
+            MemPusher::with_capacity(mock_data.len()),
+            DownloadOptions {
+                retry_gap: Duration::from_secs(1),
+                push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
+            },
+        );
+        let outcome = timeout(Duration::from_secs(10), result.join())
+            .await
+            .expect("join must resolve once the session ends");
+        assert!(outcome.is_completed());
+        assert_eq!(outcome.bytes_pulled, mock_data.len() as u64);
+        assert_eq!(outcome.bytes_pushed, mock_data.len() as u64);
+        assert_eq!(outcome.workers[&0].pulled, mock_data.len() as u64);
+
+        let result = download_single(
+            PermanentErrPuller,
+            MemPusher::with_capacity(0),
+            DownloadOptions {
+                retry_gap: Duration::from_secs(1),
+                push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token: PauseToken::default(),
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
+            },
+        );
+        let outcome = timeout(Duration::from_secs(10), result.join())
+            .await
+            .expect("join must resolve after a terminal error");
+        assert_eq!(
+            outcome.status,
+            crate::DownloadStatus::Failed("permanent".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_single_pause_holds_until_unpaused() {
+        let mock_data = build_mock_data(3 * 1024);
+        let pusher = MemPusher::with_capacity(mock_data.len());
+        let receive = pusher.receive.clone();
+        let pause_token = PauseToken::new();
+        pause_token.pause();
+        let result = download_single(
+            MockPuller::new(&mock_data),
+            pusher,
+            DownloadOptions {
+                retry_gap: Duration::from_secs(1),
+                push_queue_cap: 1024,
+                speed_limiter: SpeedLimiter::default(),
+                retry_policy: None,
+                pause_token,
+                verbosity: Verbosity::default(),
+                low_speed_abort: None,
+            },
+        );
+        let idle = timeout(Duration::from_millis(100), result.event_chain().recv()).await;
+        assert!(idle.is_err(), "a paused session emitted {idle:?}");
+        result.unpause();
+        let outcome = timeout(Duration::from_secs(10), result.join())
+            .await
+            .expect("unpausing must let the session finish");
+        assert!(outcome.is_completed());
         assert_eq!(&**receive.lock(), mock_data);
     }
 }
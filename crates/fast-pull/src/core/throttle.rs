@@ -0,0 +1,159 @@
+//! Session-wide backoff for servers that rate limit.
+
+use core::{pin::pin, time::Duration};
+use parking_lot::Mutex;
+use tokio::{sync::Notify, time::Instant};
+
+/// The connections of a session, shared by its workers.
+///
+/// A [rate-limited](crate::PullerError::is_rate_limited) pull starts a
+/// cooldown: no worker connects until it ends. For as long again afterwards,
+/// at most half of the connections that were open when it started may be
+/// open. Rate limits during a cooldown only extend it, so 32 workers hitting
+/// the same `429` halve the connections once, not 32 times.
+#[derive(Debug, Default)]
+pub struct Throttle {
+    state: Mutex<State>,
+    changed: Notify,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// No worker connects before this.
+    until: Option<Instant>,
+    /// At most this many connections before the instant.
+    cap: Option<(usize, Instant)>,
+    connected: usize,
+}
+
+/// One open connection, released on drop.
+#[derive(Debug)]
+pub struct Slot<'a>(&'a Throttle);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().connected -= 1;
+        self.0.changed.notify_waiters();
+    }
+}
+
+impl Throttle {
+    /// Wait until a worker may connect, and count it as connected until the
+    /// returned [`Slot`] is dropped.
+    pub async fn admit(&self) -> Slot<'_> {
+        loop {
+            let mut changed = pin!(self.changed.notified());
+            changed.as_mut().enable();
+            let wake = match self.try_admit() {
+                Ok(slot) => return slot,
+                Err(wake) => wake,
+            };
+            tokio::select! {
+                () = changed => {}
+                () = tokio::time::sleep_until(wake) => {}
+            }
+        }
+    }
+
+    /// A [`Slot`] if a worker may connect now, else when the cooldown or the
+    /// cap ends; a closing connection may free a slot before that.
+    fn try_admit(&self) -> Result<Slot<'_>, Instant> {
+        let mut state = self.state.lock();
+        let now = Instant::now();
+        if let Some(until) = state.until
+            && until > now
+        {
+            return Err(until);
+        }
+        state.cap = state.cap.filter(|&(_, end)| end > now);
+        if let Some((cap, end)) = state.cap
+            && state.connected >= cap
+        {
+            return Err(end);
+        }
+        state.connected += 1;
+        drop(state);
+        Ok(Slot(self))
+    }
+
+    /// When the current or the last cooldown ends, `None` before the first.
+    pub fn cooldown_end(&self) -> Option<Instant> {
+        self.state.lock().until
+    }
+
+    /// Hold every worker back for `cooldown`. Returns `true` if this started a
+    /// new cooldown rather than extending the current one.
+    pub fn throttle(&self, cooldown: Duration) -> bool {
+        let now = Instant::now();
+        let until = now + cooldown;
+        let mut state = self.state.lock();
+        let started = match state.until {
+            Some(current) if current > now => {
+                state.until = Some(current.max(until));
+                false
+            }
+            _ => {
+                let open = state
+                    .cap
+                    .map_or(state.connected + 1, |(cap, _)| cap.min(state.connected + 1));
+                state.until = Some(until);
+                state.cap = Some(((open / 2).max(1), until + cooldown));
+                true
+            }
+        };
+        drop(state);
+        self.changed.notify_waiters();
+        started
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::join_all;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn cooldown_holds_every_worker() {
+        let throttle = Throttle::default();
+        assert!(throttle.throttle(Duration::from_millis(200)));
+        assert!(
+            !throttle.throttle(Duration::from_millis(50)),
+            "a shorter rate limit does not start another cooldown"
+        );
+        let start = Instant::now();
+        let _slot = throttle.admit().await;
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn recovery_halves_connections() {
+        let throttle = Throttle::default();
+        // The refused worker has already let go of its connection, so 3 are
+        // open and 4 count.
+        let slots = join_all((0..3).map(|_| throttle.admit())).await;
+        assert!(throttle.throttle(Duration::from_millis(100)));
+        drop(slots);
+        let a = throttle.admit().await;
+        let b = throttle.admit().await;
+        assert!(
+            timeout(Duration::from_millis(50), throttle.admit())
+                .await
+                .is_err(),
+            "only half of the 4 connections may reopen"
+        );
+        drop(a);
+        let _c = timeout(Duration::from_millis(50), throttle.admit())
+            .await
+            .expect("a released connection is handed on");
+        drop(b);
+        // Once the recovery ends, the cap is lifted.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let _more = timeout(
+            Duration::from_millis(50),
+            join_all((0..8).map(|_| throttle.admit())),
+        )
+        .await
+        .expect("the cap is lifted after the recovery");
+    }
+}
@@ -303,6 +303,12 @@ impl Task {
     pub(crate) fn sharer_count(&self) -> usize {
         Arc::strong_count(&self.0.state)
     }
+    /// Whether another worker shares this task's progress cursor, racing on the
+    /// same range after a speculative [`steal`](crate::TaskQueue::steal).
+    #[must_use]
+    pub fn is_speculative(&self) -> bool {
+        self.sharer_count() > 1
+    }
     /// Rebinds this task to share `other`'s progress cursor while keeping its own
     /// distinct identity.
     ///
@@ -824,6 +830,7 @@ mod tests {
 
         // A `clone` does NOT touch the cursor, so `sharer_count` is unchanged.
         assert_eq!(task.sharer_count(), 1, "clone does not alias the cursor");
+        assert!(!task.is_speculative());
 
         // `share_state` aliases the cursor (new identity) and bumps `sharer_count`,
         // but leaves `strong_count` (identity) untouched.
@@ -831,6 +838,9 @@ mod tests {
         twin.share_state(&task);
         assert_eq!(task.sharer_count(), 2, "share_state aliases the cursor");
         assert_eq!(task.strong_count(), 2, "share_state keeps its own identity");
+        assert!(task.is_speculative() && twin.is_speculative());
+        drop(twin);
+        assert!(!task.is_speculative(), "the twin is gone");
     }
 
     /// `WeakTask::is_alive` reports whether the worker *identity* is still held,
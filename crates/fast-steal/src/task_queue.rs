@@ -201,6 +201,22 @@ impl<H: Handle> TaskQueue<H> {
         f(&mut iter)
     }
 
+    /// A snapshot of the running workers: a clone of each handle with the task
+    /// it is working on, in the order they were registered. Workers whose task
+    /// is already gone are skipped.
+    #[must_use]
+    pub fn running(&self) -> Vec<(H, Task)>
+    where
+        H: Clone,
+    {
+        let guard = self.inner.lock();
+        guard
+            .running
+            .iter()
+            .filter_map(|(weak, handle)| Some((handle.clone(), weak.upgrade()?)))
+            .collect()
+    }
+
     /// Aborts every running task equal to `task` that does not belong to the
     /// worker `id`.
     ///
@@ -550,6 +566,34 @@ mod tests {
         );
     }
 
+    /// `running` lists every registered worker with the range it holds, and
+    /// drops the ones that finished.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_running_snapshot() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let released = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let executor = StealExecutor {
+            tx,
+            speculative: 1,
+            next_id: Arc::new(Mutex::new(0)),
+            released: released.clone(),
+            steals: Arc::new(Mutex::new(0)),
+        };
+        let task_queue = TaskQueue::new([0..10, 10..30].into_iter());
+        task_queue.set_threads(2, 1, Some(&executor)).unwrap();
+        drop(executor);
+        let running: Vec<_> = task_queue
+            .running()
+            .into_iter()
+            .map(|(handle, task)| (handle.id, task.get(), task.is_speculative()))
+            .collect();
+        assert_eq!(running, [(0, 0..10, false), (1, 10..30, false)]);
+
+        released.store(true, std::sync::atomic::Ordering::Relaxed);
+        while rx.recv().await.is_some() {}
+        assert!(task_queue.running().is_empty());
+    }
+
     /// Genuinely verifies mid-run reclaim: 8 workers split a big task, then the
     /// pool is cut to 2. The 6 aborted workers are truly cancelled (the worker
     /// loop's `.await` point makes `abort` effective) and their remaining ranges